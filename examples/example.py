@@ -7,6 +7,7 @@
 #   block_timestamp, sha256, keccak256
 
 
+@view
 def hello():
     """Return a greeting. Exercises: value_return."""
     value_return("Hello from Monty on NEAR!")
@@ -45,6 +46,7 @@ def counter():
     value_return(str(count))
 
 
+@view
 def get_counter():
     """
     Read the current counter without modifying it.
@@ -81,6 +83,7 @@ def remove_key():
         value_return("removed")
 
 
+@view
 def whoami():
     """
     Return the contract's own account ID and current block height.
@@ -148,6 +151,7 @@ def kv_put():
         value_return("ok")
 
 
+@view
 def kv_get():
     """
     Generic key-value store: read by key.
@@ -0,0 +1,214 @@
+// NEAR ABI generation — describes exported methods in the near-abi JSON
+// format (schema 0.4.0) from their Python type hints.
+
+use ruff_python_ast::{Expr, ModModule, Operator, StmtFunctionDef};
+use serde_json::{json, Map, Value};
+
+use crate::{borsh_io, contract_class, Method, MethodKind};
+
+const ABI_SCHEMA_VERSION: &str = "0.4.0";
+
+/// Build the ABI document for `methods` of the contract `name`.
+pub fn generate_abi(name: &str, module: &ModModule, methods: &[Method]) -> Value {
+    let functions: Vec<Value> = methods
+        .iter()
+        .filter_map(|method| {
+            crate::contract_functions(module)
+                .find(|(func, _)| func.name.as_str() == method.function)
+                .map(|(func, _)| function_abi(func, method))
+        })
+        .collect();
+
+    let mut metadata = json!({
+        "name": name,
+        "build": {
+            "compiler": "monty",
+            "builder": format!("monty-near-cli {}", env!("CARGO_PKG_VERSION")),
+        },
+    });
+    // Not part of near-abi, which allows extra metadata: `upgrade` compares
+    // it with the deployed version's to catch state that needs migrating
+    if let Some(class) = contract_class::find_class(module) {
+        let fields: Vec<Value> = contract_class::layout(class)
+            .into_iter()
+            .map(|(name, ty)| json!({ "name": name, "type": ty }))
+            .collect();
+        metadata["state"] = json!({ "key": contract_class::STATE_KEY, "fields": fields });
+    }
+
+    json!({
+        "schema_version": ABI_SCHEMA_VERSION,
+        "metadata": metadata,
+        "body": {
+            "functions": functions,
+            "root_schema": {
+                "$schema": "http://json-schema.org/draft-07/schema#",
+                "title": "String",
+                "type": "string",
+            },
+        },
+    })
+}
+
+fn function_abi(func: &StmtFunctionDef, method: &Method) -> Value {
+    let mut abi = Map::new();
+    abi.insert("name".into(), json!(method.name));
+    let kind = match method.kind {
+        MethodKind::Call => "call",
+        MethodKind::View => "view",
+    };
+    abi.insert("kind".into(), json!(kind));
+    let modifiers: Vec<&str> = [
+        (method.init, "init"),
+        (method.payable, "payable"),
+        (method.private, "private"),
+    ]
+    .into_iter()
+    .filter_map(|(set, modifier)| set.then_some(modifier))
+    .collect();
+    if !modifiers.is_empty() {
+        abi.insert("modifiers".into(), json!(modifiers));
+    }
+
+    let params = &func.parameters;
+    let mut args: Vec<Value> = params
+        .posonlyargs
+        .iter()
+        .chain(&params.args)
+        .chain(&params.kwonlyargs)
+        // `self` of a `class Contract` method isn't passed by callers
+        .skip(usize::from(method.state))
+        .map(|p| {
+            let type_schema = p
+                .parameter
+                .annotation
+                .as_deref()
+                .map(|a| type_schema(crate::split_promise_error(a).0))
+                .unwrap_or_else(|| json!({}));
+            json!({ "name": p.parameter.name.as_str(), "type_schema": type_schema })
+        })
+        .collect();
+    // A `@migrate` method's first parameter is the stored state, and a
+    // `@callback`'s leading parameters are promise results
+    args.drain(..usize::from(method.migrate));
+    let callbacks: Vec<Value> = args
+        .drain(..method.results.len())
+        .map(|arg| json!({ "serialization_type": "json", "type_schema": arg["type_schema"] }))
+        .collect();
+    if !callbacks.is_empty() {
+        abi.insert("callbacks".into(), json!(callbacks));
+    }
+    if let Some(signature) = &method.borsh {
+        let args: Vec<Value> = method
+            .params
+            .iter()
+            .zip(&signature.params)
+            .map(|(param, ty)| json!({ "name": param.name, "type_schema": borsh_schema(ty) }))
+            .collect();
+        if !args.is_empty() {
+            abi.insert(
+                "params".into(),
+                json!({ "serialization_type": "borsh", "args": args }),
+            );
+        }
+        if let Some(returns) = &signature.returns {
+            abi.insert(
+                "result".into(),
+                json!({ "serialization_type": "borsh", "type_schema": borsh_schema(returns) }),
+            );
+        }
+        return Value::Object(abi);
+    }
+
+    if !args.is_empty() {
+        abi.insert(
+            "params".into(),
+            json!({ "serialization_type": "json", "args": args }),
+        );
+    }
+
+    // `-> None` and a missing annotation both mean nothing is returned, and
+    // what a `@raw_io` method returns isn't JSON
+    if let Some(returns) = func.returns.as_deref().filter(|_| !method.raw_io) {
+        if !matches!(returns, Expr::NoneLiteral(_)) {
+            abi.insert(
+                "result".into(),
+                json!({ "serialization_type": "json", "type_schema": type_schema(returns) }),
+            );
+        }
+    }
+    Value::Object(abi)
+}
+
+/// The Borsh schema of `ty`, as its Rust type declaration.
+fn borsh_schema(ty: &borsh_io::Type) -> Value {
+    json!({ "declaration": ty.declaration(), "definitions": {} })
+}
+
+/// JSON schema for a Python type annotation. Anything unrecognized maps to
+/// the empty schema, which accepts any value.
+fn type_schema(annotation: &Expr) -> Value {
+    match annotation {
+        Expr::NoneLiteral(_) => json!({ "type": "null" }),
+        Expr::Name(name) => match name.id.as_str() {
+            "str" => json!({ "type": "string" }),
+            // A decimal string, as near-sdk's U128
+            "U128" | "Balance" => json!({ "type": "string", "pattern": "^[0-9]+$" }),
+            "int" => json!({ "type": "integer" }),
+            "float" => json!({ "type": "number" }),
+            "bool" => json!({ "type": "boolean" }),
+            "bytes" => json!({ "type": "string", "contentEncoding": "base64" }),
+            "list" | "List" | "tuple" | "Tuple" | "set" | "Set" => json!({ "type": "array" }),
+            "dict" | "Dict" => json!({ "type": "object" }),
+            _ => json!({}),
+        },
+        Expr::BinOp(union) if union.op == Operator::BitOr => {
+            any_of(&[type_schema(&union.left), type_schema(&union.right)])
+        }
+        Expr::Subscript(generic) => {
+            let Expr::Name(base) = generic.value.as_ref() else {
+                return json!({});
+            };
+            let params: Vec<&Expr> = match generic.slice.as_ref() {
+                Expr::Tuple(tuple) => tuple.elts.iter().collect(),
+                single => vec![single],
+            };
+            match (base.id.as_str(), &params[..]) {
+                ("list" | "List" | "set" | "Set", [item]) => {
+                    json!({ "type": "array", "items": type_schema(item) })
+                }
+                ("tuple" | "Tuple", items) => {
+                    let items: Vec<Value> = items.iter().map(|e| type_schema(e)).collect();
+                    // draft-07 tuple form, as near-abi's schemars output uses
+                    json!({
+                        "type": "array",
+                        "items": items,
+                        "minItems": items.len(),
+                        "maxItems": items.len(),
+                    })
+                }
+                ("dict" | "Dict", [_, value]) => {
+                    json!({ "type": "object", "additionalProperties": type_schema(value) })
+                }
+                ("Optional", [inner]) => any_of(&[type_schema(inner), json!({ "type": "null" })]),
+                ("Union", members) => {
+                    any_of(&members.iter().map(|e| type_schema(e)).collect::<Vec<_>>())
+                }
+                _ => json!({}),
+            }
+        }
+        _ => json!({}),
+    }
+}
+
+/// Combine alternatives, flattening nested `anyOf`s from chained `A | B | C`.
+fn any_of(schemas: &[Value]) -> Value {
+    let mut flat = Vec::new();
+    for schema in schemas {
+        match schema.get("anyOf").and_then(Value::as_array) {
+            Some(inner) => flat.extend(inner.iter().cloned()),
+            None => flat.push(schema.clone()),
+        }
+    }
+    json!({ "anyOf": flat })
+}
@@ -0,0 +1,140 @@
+// Release artifacts — `build --out-dir` writes everything a CI pipeline
+// publishes alongside the WASM: ABI, checksums, method list, and a manifest
+// recording how the build was made.
+
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use anyhow::{Context, Result};
+use serde_json::{json, Value};
+use sha2::{Digest, Sha256};
+
+use crate::code_hash;
+
+pub const MANIFEST_FILE: &str = "build-manifest.json";
+
+/// A built WASM file and the target it was built for.
+pub struct Artifact {
+    pub path: PathBuf,
+    /// `standard`, `compat`, or `component`.
+    pub target: &'static str,
+}
+
+/// Everything written to the output directory besides the WASM itself.
+pub struct BuildRecord<'a> {
+    pub contract_name: &'a str,
+    pub source: &'a Path,
+    pub methods: Vec<&'a str>,
+    pub abi: &'a Value,
+    /// Effective build settings, as recorded in the manifest.
+    pub settings: Value,
+    /// The generated project, whose Cargo.lock pins the Monty revision.
+    pub build_dir: &'a Path,
+    pub started: SystemTime,
+}
+
+/// Write the ABI, checksums, method list and manifest for `artifacts` to
+/// `dir`, which already holds the WASM files.
+pub fn write_out_dir(dir: &Path, record: &BuildRecord, artifacts: &[Artifact]) -> Result<()> {
+    let name = record.contract_name;
+    let write = |file: &str, contents: String| -> Result<()> {
+        let path = dir.join(file);
+        fs::write(&path, contents).with_context(|| format!("failed to write {}", path.display()))
+    };
+
+    write(
+        &format!("{name}.abi.json"),
+        serde_json::to_string_pretty(record.abi)? + "\n",
+    )?;
+    write("methods.txt", record.methods.join("\n") + "\n")?;
+
+    let mut checksums = String::new();
+    let mut code_hashes = String::new();
+    let mut entries = Vec::new();
+    for artifact in artifacts {
+        let wasm = fs::read(&artifact.path)
+            .with_context(|| format!("failed to read {}", artifact.path.display()))?;
+        let file = artifact
+            .path
+            .file_name()
+            .unwrap_or_default()
+            .to_string_lossy();
+        let sha256 = hex(&Sha256::digest(&wasm));
+        let hash = code_hash(&wasm);
+        // `sha256sum -c` format
+        checksums.push_str(&format!("{sha256}  {file}\n"));
+        code_hashes.push_str(&format!("{hash}  {file}\n"));
+        entries.push(json!({
+            "file": file,
+            "target": artifact.target,
+            "size": wasm.len(),
+            "sha256": sha256,
+            "code_hash": hash,
+        }));
+    }
+    write(&format!("{name}.sha256"), checksums)?;
+    write(&format!("{name}.code-hash"), code_hashes)?;
+
+    let source = fs::read(record.source)
+        .with_context(|| format!("failed to read {}", record.source.display()))?;
+    let manifest = json!({
+        "version": 1,
+        "tool": {
+            "name": env!("CARGO_PKG_NAME"),
+            "version": env!("CARGO_PKG_VERSION"),
+        },
+        "monty": monty_package(record.build_dir),
+        "contract": {
+            "name": name,
+            "source": record.source.display().to_string(),
+            "source_sha256": hex(&Sha256::digest(&source)),
+            "methods": record.methods,
+        },
+        "settings": record.settings,
+        "artifacts": entries,
+        "started_at": unix_seconds(record.started),
+        "finished_at": unix_seconds(SystemTime::now()),
+    });
+    write(
+        MANIFEST_FILE,
+        serde_json::to_string_pretty(&manifest)? + "\n",
+    )?;
+
+    eprintln!(
+        "  \u{2713} Wrote ABI, checksums, method list and {MANIFEST_FILE} to {}",
+        dir.display()
+    );
+    Ok(())
+}
+
+/// The Monty package the generated project resolved, from its Cargo.lock:
+/// `{"version", "source"}`, where a git source ends in `#<commit>`.
+fn monty_package(build_dir: &Path) -> Value {
+    let lock = fs::read_to_string(build_dir.join("Cargo.lock"))
+        .ok()
+        .and_then(|text| text.parse::<toml::Table>().ok());
+    let package = lock.as_ref().and_then(|lock| {
+        lock.get("package")?
+            .as_array()?
+            .iter()
+            .find(|p| p.get("name").and_then(|n| n.as_str()) == Some("monty"))
+    });
+    match package {
+        Some(package) => json!({
+            "version": package.get("version").and_then(|v| v.as_str()),
+            "source": package.get("source").and_then(|v| v.as_str()),
+        }),
+        None => Value::Null,
+    }
+}
+
+fn unix_seconds(time: SystemTime) -> u64 {
+    time.duration_since(UNIX_EPOCH)
+        .map_or(0, |elapsed| elapsed.as_secs())
+}
+
+/// `bytes` as lowercase hex.
+pub fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{b:02x}")).collect()
+}
@@ -0,0 +1,444 @@
+// Async methods — `price = await cross_call("oracle.near", "get_price", args)`
+// in an `async def` method reads as straight-line code, but calls between
+// contracts are asynchronous: the method has to return a promise, and a
+// callback picks up the result in a later receipt. The compiler splits the
+// method at each such `await`. The code before it returns the call, then a
+// call of a generated private callback, `<method>__await_<n>`, which gets
+// the result and, as JSON arguments, the local variables the rest of the
+// method reads, and runs the rest.
+
+use std::collections::HashSet;
+
+use ruff_python_ast::visitor::{self, Visitor};
+use ruff_python_ast::{Expr, ExprCall, ExprContext, ModModule, Stmt, StmtFunctionDef};
+use ruff_python_parser::parse_module;
+use ruff_text_size::Ranged;
+
+use crate::diagnostics::{Diagnostic, SourceFile};
+use crate::{Method, MethodKind, Param, TypeCheck};
+
+/// The function async methods await; like the host functions, it needs no
+/// import.
+const HELPER: &str = "cross_call";
+
+/// The callback parameter for the result of an `await` that isn't assigned.
+const DISCARDED: &str = "__awaited__";
+
+/// The generated method running the code of `method` after its `n`th await.
+fn continuation(method: &str, n: usize) -> String {
+    format!("{method}__await_{n}")
+}
+
+/// An `await cross_call(...)` statement directly in an async method's body.
+struct Await<'a> {
+    /// Its index in the body.
+    index: usize,
+    call: &'a ExprCall,
+    /// The variable the result is assigned to.
+    target: Option<&'a str>,
+    annotation: Option<&'a Expr>,
+    /// The method's variables the code after it reads, in the order they're
+    /// bound.
+    locals: Vec<String>,
+}
+
+/// The call in `await cross_call(...)`.
+fn awaited_call(expr: &Expr) -> Option<&ExprCall> {
+    let Expr::Await(awaited) = expr else {
+        return None;
+    };
+    match awaited.value.as_ref() {
+        Expr::Call(call) if is_helper(call) => Some(call),
+        _ => None,
+    }
+}
+
+fn is_helper(call: &ExprCall) -> bool {
+    matches!(call.func.as_ref(), Expr::Name(name) if name.id.as_str() == HELPER)
+}
+
+/// Whether `call` passes the account ID and method name as its first two
+/// arguments, which the compiler turns into the promise's.
+fn has_target(call: &ExprCall) -> bool {
+    let args = &call.arguments.args;
+    args.len() >= 2 && !args.iter().any(|arg| matches!(arg, Expr::Starred(_)))
+}
+
+/// Collects the names code binds and reads.
+#[derive(Default)]
+struct Names<'a> {
+    bound: Vec<&'a str>,
+    read: HashSet<&'a str>,
+    global: HashSet<&'a str>,
+}
+
+impl<'a> Visitor<'a> for Names<'a> {
+    fn visit_stmt(&mut self, stmt: &'a Stmt) {
+        if let Stmt::Global(global) = stmt {
+            self.global
+                .extend(global.names.iter().map(|name| name.as_str()));
+        }
+        visitor::walk_stmt(self, stmt);
+    }
+
+    fn visit_expr(&mut self, expr: &'a Expr) {
+        if let Expr::Name(name) = expr {
+            let id = name.id.as_str();
+            match name.ctx {
+                ExprContext::Store if !self.bound.contains(&id) => self.bound.push(id),
+                ExprContext::Load => {
+                    self.read.insert(id);
+                }
+                _ => {}
+            }
+        }
+        visitor::walk_expr(self, expr);
+    }
+}
+
+/// The `await cross_call(...)` statements of `func`, if it's async.
+fn awaits(func: &StmtFunctionDef) -> Vec<Await<'_>> {
+    if !func.is_async {
+        return Vec::new();
+    }
+    let mut found = Vec::new();
+    for (index, stmt) in func.body.iter().enumerate() {
+        let (value, target, annotation) = match stmt {
+            Stmt::Assign(assign) => match &assign.targets[..] {
+                [Expr::Name(target)] => (assign.value.as_ref(), Some(target.id.as_str()), None),
+                _ => continue,
+            },
+            Stmt::AnnAssign(assign) => match (assign.target.as_ref(), &assign.value) {
+                (Expr::Name(target), Some(value)) => (
+                    value.as_ref(),
+                    Some(target.id.as_str()),
+                    Some(assign.annotation.as_ref()),
+                ),
+                _ => continue,
+            },
+            Stmt::Expr(expr) => (expr.value.as_ref(), None, None),
+            _ => continue,
+        };
+        if let Some(call) = awaited_call(value).filter(|call| has_target(call)) {
+            found.push(Await {
+                index,
+                call,
+                target,
+                annotation,
+                locals: Vec::new(),
+            });
+        }
+    }
+
+    let params = &func.parameters;
+    let params: Vec<&str> = params
+        .posonlyargs
+        .iter()
+        .chain(&params.args)
+        .chain(&params.kwonlyargs)
+        .map(|p| p.parameter.name.as_str())
+        .collect();
+    let mut all = Names::default();
+    all.visit_body(&func.body);
+    for awaited in &mut found {
+        let mut before = Names::default();
+        before.visit_body(&func.body[..awaited.index]);
+        let mut after = Names::default();
+        after.visit_body(&func.body[awaited.index + 1..]);
+        let mut seen = HashSet::new();
+        awaited.locals = params
+            .iter()
+            .copied()
+            .chain(before.bound)
+            .filter(|name| {
+                Some(*name) != awaited.target
+                    && after.read.contains(name)
+                    && !all.global.contains(name)
+                    && seen.insert(*name)
+            })
+            .map(String::from)
+            .collect();
+    }
+    found
+}
+
+/// Whether `func` awaits `cross_call`, so the build splits it.
+pub fn splits(func: &StmtFunctionDef) -> bool {
+    !awaits(func).is_empty()
+}
+
+/// Collects `await` expressions, calls of `cross_call`, and the calls
+/// awaited.
+#[derive(Default)]
+struct Uses<'a> {
+    awaits: Vec<&'a Expr>,
+    calls: Vec<&'a ExprCall>,
+    awaited: Vec<&'a ExprCall>,
+}
+
+impl<'a> Visitor<'a> for Uses<'a> {
+    fn visit_expr(&mut self, expr: &'a Expr) {
+        match expr {
+            Expr::Await(_) => {
+                if let Some(call) = awaited_call(expr) {
+                    self.awaits.push(expr);
+                    self.awaited.push(call);
+                }
+            }
+            Expr::Call(call) if is_helper(call) => self.calls.push(call),
+            _ => {}
+        }
+        visitor::walk_expr(self, expr);
+    }
+}
+
+/// Check the calls of `cross_call` in `module`: each must be awaited, as a
+/// statement directly in the body of a top-level `async def`, and pass the
+/// account ID and method name first. Only the contract's own methods
+/// (`methods`) can await, as the callbacks are generated for them. Returns
+/// whether `module` awaits any, so the build adds the promise helpers.
+pub fn check(
+    module: &ModModule,
+    file: SourceFile,
+    methods: bool,
+    diagnostics: &mut Vec<Diagnostic>,
+) -> bool {
+    let error = |offset: usize, message: String| file.error("invalid-await", offset, message);
+    let mut uses = Uses::default();
+    uses.visit_body(&module.body);
+
+    let mut valid: Vec<&ExprCall> = Vec::new();
+    for stmt in &module.body {
+        let Stmt::FunctionDef(func) = stmt else {
+            continue;
+        };
+        let awaits = awaits(func);
+        for n in 1..=awaits.len() {
+            let name = continuation(func.name.as_str(), n);
+            let defined = module.body.iter().any(
+                |stmt| matches!(stmt, Stmt::FunctionDef(other) if other.name.as_str() == name),
+            );
+            if defined {
+                diagnostics.push(error(
+                    func.range.start().to_usize(),
+                    format!("`{name}` is the name of the callback for an await in `{}`, but the contract defines it too", func.name),
+                ));
+            }
+        }
+        if !methods {
+            for awaited in &awaits {
+                diagnostics.push(error(
+                    awaited.call.range.start().to_usize(),
+                    format!("only the contract's methods can await {HELPER}(): functions in imported modules are helpers"),
+                ));
+            }
+        }
+        valid.extend(awaits.iter().map(|awaited| awaited.call));
+    }
+
+    for call in &uses.calls {
+        let offset = call.range.start().to_usize();
+        if !has_target(call) {
+            diagnostics.push(error(
+                offset,
+                format!(
+                    "{HELPER}() takes the account ID and method name as its first two arguments"
+                ),
+            ));
+        } else if !uses
+            .awaited
+            .iter()
+            .any(|awaited| std::ptr::eq(*awaited, *call))
+        {
+            diagnostics.push(
+                error(offset, format!("{HELPER}() must be awaited")).with_note(format!(
+                    "write `result = await {HELPER}(...)` in an async method"
+                )),
+            );
+        }
+    }
+    for expr in &uses.awaits {
+        let Some(call) = awaited_call(expr) else {
+            continue;
+        };
+        if has_target(call) && !valid.iter().any(|valid| std::ptr::eq(*valid, call)) {
+            diagnostics.push(
+                error(
+                    expr.range().start().to_usize(),
+                    format!("`await {HELPER}(...)` only works as a statement directly in the body of a top-level async method"),
+                )
+                .with_note("write `result = await cross_call(...)` on a line of its own, outside loops, conditions, and nested functions"),
+            );
+        }
+    }
+    !valid.is_empty()
+}
+
+/// The generated callbacks continuing `method`, defined by `func`, after
+/// each of its awaits.
+pub fn continuations(method: &Method, func: &StmtFunctionDef) -> Vec<Method> {
+    let awaits = awaits(func);
+    awaits
+        .iter()
+        .enumerate()
+        .map(|(n, awaited)| {
+            let end = awaits.get(n + 1).map_or(func.body.len(), |next| next.index);
+            let rest = &func.body[awaited.index + 1..end];
+            let annotation = awaited.annotation.map(crate::split_promise_error);
+            let result = Param {
+                name: awaited.target.unwrap_or(DISCARDED).to_string(),
+                default: None,
+                keyword_only: false,
+                check: annotation.and_then(|(a, _)| TypeCheck::of(a)),
+                u128: annotation.and_then(|(a, _)| crate::u128_schema(a)),
+                failable: annotation.is_some_and(|(_, failable)| failable),
+            };
+            let params = awaited
+                .locals
+                .iter()
+                .map(|name| Param {
+                    name: name.clone(),
+                    default: None,
+                    keyword_only: false,
+                    check: None,
+                    u128: None,
+                    failable: false,
+                })
+                .collect();
+            Method {
+                name: continuation(&method.function, n + 1),
+                function: continuation(&method.function, n + 1),
+                group: method.group.clone(),
+                kind: MethodKind::Call,
+                payable: false,
+                one_yocto: false,
+                private: true,
+                init: false,
+                requires_init: false,
+                owner_only: false,
+                state: false,
+                migrate: false,
+                params,
+                results: vec![result],
+                returns: n + 1 < awaits.len() || crate::returns_value(rest),
+                returns_u128: method.returns_u128.clone(),
+                raw_io: false,
+                borsh: None,
+            }
+        })
+        .collect()
+}
+
+/// `return` of the promise for `awaited`, in `method`, followed by its
+/// `n`th callback with the locals.
+fn schedule(source: &str, method: &str, awaited: &Await, n: usize) -> String {
+    let arguments = &awaited.call.arguments;
+    let account = &source[arguments.args[0].range()];
+    let rest: Vec<&str> = arguments.args[1..]
+        .iter()
+        .map(|arg| &source[arg.range()])
+        .chain(arguments.keywords.iter().map(|k| &source[k.range()]))
+        .collect();
+    let locals: Vec<String> = awaited
+        .locals
+        .iter()
+        .map(|name| format!("\"{name}\": {name}"))
+        .collect();
+    format!(
+        "return Promise({account}).function_call({}).then(Promise(current_account_id()).function_call(\"{}\", {{{}}}))",
+        rest.join(", "),
+        continuation(method, n),
+        locals.join(", ")
+    )
+}
+
+/// Split the async methods in `source` at their awaits of `cross_call`,
+/// appending the callbacks running the code after each await to the end.
+/// Everything up to a method's first await keeps its lines. Source that
+/// doesn't parse is returned unchanged.
+pub fn lower(source: &str) -> String {
+    let Ok(parsed) = parse_module(source) else {
+        return source.to_string();
+    };
+    let module = parsed.into_syntax();
+
+    let mut edits: Vec<(usize, usize, String)> = Vec::new();
+    let mut callbacks = String::new();
+    for stmt in &module.body {
+        let Stmt::FunctionDef(func) = stmt else {
+            continue;
+        };
+        let awaits = awaits(func);
+        let Some(first) = awaits.first() else {
+            continue;
+        };
+        let name = func.name.as_str();
+
+        // `async def` becomes `def` padded with spaces
+        let header = func
+            .decorator_list
+            .last()
+            .map_or(func.range.start(), |d| d.range.end())
+            .to_usize();
+        if let Some(at) = source[header..].find("async").map(|at| header + at) {
+            if let Some(end) = source[at..].find("def").map(|def| at + def + 3) {
+                edits.push((at, end, format!("{:<width$}", "def", width = end - at)));
+            }
+        }
+
+        // The rest of the body moves to the callbacks; the lines it took stay
+        let start = func.body[first.index].range().start().to_usize();
+        let end = func.range.end().to_usize();
+        let line_start = source[..start].rfind('\n').map_or(0, |i| i + 1);
+        let indent = &source[line_start..start];
+        let indent = if indent.trim().is_empty() {
+            indent
+        } else {
+            "    "
+        };
+        let replacement = schedule(source, name, first, 1);
+        let padding = source[start..end]
+            .matches('\n')
+            .count()
+            .saturating_sub(replacement.matches('\n').count());
+        edits.push((start, end, replacement + &"\n".repeat(padding)));
+
+        for (n, awaited) in awaits.iter().enumerate() {
+            let next = awaits.get(n + 1);
+            let mut params = vec![awaited.target.unwrap_or(DISCARDED).to_string()];
+            params.extend(awaited.locals.iter().cloned());
+            callbacks.push_str(&format!(
+                "\n\ndef {}({}):\n",
+                continuation(name, n + 1),
+                params.join(", ")
+            ));
+            let rest = &func.body[awaited.index + 1..next.map_or(func.body.len(), |a| a.index)];
+            if let (Some(first), Some(last)) = (rest.first(), rest.last()) {
+                let start = first.range().start().to_usize();
+                let line_start = source[..start].rfind('\n').map_or(0, |i| i + 1);
+                callbacks.push_str(&source[line_start..last.range().end().to_usize()]);
+                callbacks.push('\n');
+            }
+            match next {
+                Some(next) => callbacks.push_str(&format!(
+                    "{indent}{}\n",
+                    schedule(source, name, next, n + 2)
+                )),
+                None if rest.is_empty() => callbacks.push_str(&format!("{indent}pass\n")),
+                None => {}
+            }
+        }
+    }
+    if edits.is_empty() {
+        return source.to_string();
+    }
+
+    // From the end, so the earlier offsets stay valid
+    edits.sort_by_key(|&(start, _, _)| start);
+    let mut out = source.to_string();
+    for (start, end, replacement) in edits.into_iter().rev() {
+        out.replace_range(start..end, &replacement);
+    }
+    out.push_str(&callbacks);
+    out
+}
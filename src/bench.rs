@@ -0,0 +1,338 @@
+// Gas measurement against a local sandbox — deploys the built contract and
+// reports the gas actually burnt by real function calls.
+
+use std::time::Instant;
+
+use anyhow::{bail, Context, Result};
+use serde_json::{json, Value};
+
+use crate::rpc::{
+    gas_profile as receipt_gas_profile, outcome_result, receipts_gas_burnt, total_gas_burnt,
+};
+use crate::sandbox::Sandbox;
+
+/// Gas figures for a single call.
+pub struct GasSample {
+    /// Gas burnt executing the contract (all receipts).
+    pub execution: u64,
+    /// Execution plus the transaction-to-receipt conversion cost.
+    pub total: u64,
+}
+
+/// Deploy `wasm` to a fresh sandbox, call `method` `repeat` times, and print
+/// the distribution of burnt gas.
+pub fn gas_bench(
+    wasm: &[u8],
+    method: &str,
+    args: &[u8],
+    deposit: u128,
+    repeat: usize,
+) -> Result<Vec<GasSample>> {
+    if repeat == 0 {
+        bail!("--repeat must be at least 1");
+    }
+
+    eprintln!("  Starting sandbox...");
+    let sandbox = Sandbox::start(&std::env::temp_dir())?;
+    eprintln!("  Deploying to {}...", sandbox.root.account_id);
+    sandbox.deploy(wasm)?;
+
+    eprintln!("  Calling {method} {repeat} time(s)...");
+    let mut samples = Vec::with_capacity(repeat);
+    for _ in 0..repeat {
+        let outcome = sandbox.call(method, args, deposit)?;
+        samples.push(GasSample {
+            execution: receipts_gas_burnt(&outcome),
+            total: total_gas_burnt(&outcome),
+        });
+    }
+
+    eprintln!();
+    eprintln!("  Gas burnt by {method} ({repeat} calls):");
+    print_distribution(
+        "execution",
+        &samples.iter().map(|s| s.execution).collect::<Vec<_>>(),
+    );
+    print_distribution(
+        "total",
+        &samples.iter().map(|s| s.total).collect::<Vec<_>>(),
+    );
+
+    Ok(samples)
+}
+
+/// Where a call's execution gas went, from the receipts' gas profiles.
+#[derive(Default)]
+struct GasBreakdown {
+    /// Executing WASM instructions — the interpreter and Rust runtime.
+    wasm: u64,
+    /// Loading and preparing the contract code.
+    loading: u64,
+    /// Host functions: storage, logs, hashing, register reads, ...
+    host: u64,
+    /// Action fees, e.g. the function call base cost.
+    actions: u64,
+    /// Host function costs by name, largest first.
+    host_costs: Vec<(String, u64)>,
+}
+
+impl GasBreakdown {
+    fn from_outcome(outcome: &Value) -> Self {
+        let mut breakdown = Self::default();
+        for entry in receipt_gas_profile(outcome) {
+            match (entry.category.as_str(), entry.cost.as_str()) {
+                ("WASM_HOST_COST", "WASM_INSTRUCTION") => breakdown.wasm += entry.gas,
+                ("WASM_HOST_COST", cost) if cost.starts_with("CONTRACT_LOADING") => {
+                    breakdown.loading += entry.gas
+                }
+                ("WASM_HOST_COST", cost) => {
+                    breakdown.host += entry.gas;
+                    breakdown.host_costs.push((cost.to_string(), entry.gas));
+                }
+                _ => breakdown.actions += entry.gas,
+            }
+        }
+        breakdown
+            .host_costs
+            .sort_by_key(|(_, gas)| std::cmp::Reverse(*gas));
+        breakdown
+    }
+}
+
+/// Deploy `wasm` to a fresh sandbox, call each of `calls` (method and
+/// input) once, and print the gas each burnt, broken down by where it went.
+/// A failing call is reported and the remaining methods are still profiled.
+pub fn gas_profile(
+    wasm: &[u8],
+    calls: &[(String, Vec<u8>)],
+    deposit: u128,
+    detail: bool,
+) -> Result<()> {
+    eprintln!("  Starting sandbox...");
+    let sandbox = Sandbox::start(&std::env::temp_dir())?;
+    eprintln!("  Deploying to {}...", sandbox.root.account_id);
+    sandbox.deploy(wasm)?;
+
+    eprintln!();
+    eprintln!(
+        "    {:<24} {:>14} {:>14} {:>14} {:>14} {:>14}",
+        "method", "execution", "wasm", "loading", "host", "actions"
+    );
+    let mut details = Vec::new();
+    for (method, args) in calls {
+        let outcome = sandbox.send_call(method, args, deposit)?;
+        let breakdown = GasBreakdown::from_outcome(&outcome);
+        eprintln!(
+            "    {:<24} {:>14} {:>14} {:>14} {:>14} {:>14}",
+            method,
+            tgas(receipts_gas_burnt(&outcome)),
+            tgas(breakdown.wasm),
+            tgas(breakdown.loading),
+            tgas(breakdown.host),
+            tgas(breakdown.actions)
+        );
+        if let Err(e) = outcome_result(&outcome) {
+            eprintln!("      failed: {e:#}");
+        }
+        details.push((method, breakdown));
+    }
+
+    if detail {
+        for (method, breakdown) in details {
+            eprintln!();
+            eprintln!("  Host functions in {method}:");
+            for (cost, gas) in &breakdown.host_costs {
+                eprintln!("    {cost:<40} {:>14}", tgas(*gas));
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Min / median / mean / max of a set of measurements.
+#[derive(Clone, Copy)]
+struct Summary {
+    min: u64,
+    median: u64,
+    mean: u64,
+    max: u64,
+}
+
+impl Summary {
+    fn of(values: &[u64]) -> Option<Self> {
+        let mut sorted = values.to_vec();
+        sorted.sort_unstable();
+        let (&min, &max) = sorted.first().zip(sorted.last())?;
+        let mean = sorted.iter().map(|&v| v as u128).sum::<u128>() / sorted.len() as u128;
+        Some(Self {
+            min,
+            median: sorted[sorted.len() / 2],
+            mean: mean as u64,
+            max,
+        })
+    }
+}
+
+/// Print min / median / mean / max in Tgas.
+pub fn print_distribution(label: &str, values: &[u64]) {
+    let Some(s) = Summary::of(values) else {
+        return;
+    };
+    eprintln!(
+        "    {label:<10} min {}  median {}  mean {}  max {}",
+        tgas(s.min),
+        tgas(s.median),
+        tgas(s.mean),
+        tgas(s.max)
+    );
+}
+
+pub fn tgas(gas: u64) -> String {
+    format!("{:.3} Tgas", gas as f64 / 1e12)
+}
+
+fn millis(micros: u64) -> String {
+    format!("{:.1} ms", micros as f64 / 1e3)
+}
+
+// ---------------------------------------------------------------------------
+// bench — every method, repeated, with regression baselines
+// ---------------------------------------------------------------------------
+
+/// Gas and wall-time measurements for one method.
+pub struct MethodBench {
+    pub method: String,
+    /// Execution gas of each call.
+    pub gas: Vec<u64>,
+    /// Wall time of each call, from submission to execution, in microseconds.
+    pub micros: Vec<u64>,
+}
+
+/// Deploy `wasm` to a fresh sandbox and call each of `calls` (method and
+/// input) `repeat` times, printing gas and wall-time statistics per method.
+pub fn bench(
+    wasm: &[u8],
+    calls: &[(String, Vec<u8>)],
+    deposit: u128,
+    repeat: usize,
+) -> Result<Vec<MethodBench>> {
+    if repeat == 0 {
+        bail!("--repeat must be at least 1");
+    }
+
+    eprintln!("  Starting sandbox...");
+    let sandbox = Sandbox::start(&std::env::temp_dir())?;
+    eprintln!("  Deploying to {}...", sandbox.root.account_id);
+    sandbox.deploy(wasm)?;
+
+    let mut results = Vec::with_capacity(calls.len());
+    for (method, args) in calls {
+        eprintln!("  Calling {method} {repeat} time(s)...");
+        let mut result = MethodBench {
+            method: method.clone(),
+            gas: Vec::with_capacity(repeat),
+            micros: Vec::with_capacity(repeat),
+        };
+        for _ in 0..repeat {
+            let start = Instant::now();
+            let outcome = sandbox
+                .call(method, args, deposit)
+                .with_context(|| format!("{method} failed"))?;
+            result.micros.push(start.elapsed().as_micros() as u64);
+            result.gas.push(receipts_gas_burnt(&outcome));
+        }
+        results.push(result);
+    }
+
+    eprintln!();
+    for result in &results {
+        let Some(time) = Summary::of(&result.micros) else {
+            continue;
+        };
+        eprintln!("  {}:", result.method);
+        print_distribution("gas", &result.gas);
+        eprintln!(
+            "    {:<10} min {}  median {}  mean {}  max {}",
+            "time",
+            millis(time.min),
+            millis(time.median),
+            millis(time.mean),
+            millis(time.max)
+        );
+    }
+    Ok(results)
+}
+
+/// Baseline JSON recording the median and mean gas of each method.
+pub fn baseline(results: &[MethodBench]) -> Value {
+    let methods: serde_json::Map<String, Value> = results
+        .iter()
+        .filter_map(|r| {
+            let gas = Summary::of(&r.gas)?;
+            Some((
+                r.method.clone(),
+                json!({ "median_gas": gas.median, "mean_gas": gas.mean }),
+            ))
+        })
+        .collect();
+    json!({ "version": 1, "methods": methods })
+}
+
+/// Compare median gas against `baseline` and fail if any method got more
+/// than `threshold_percent` more expensive. Methods missing from either side,
+/// or with a zero-gas baseline, are reported but don't fail the check.
+pub fn check_baseline(
+    results: &[MethodBench],
+    baseline: &Value,
+    threshold_percent: f64,
+) -> Result<()> {
+    eprintln!();
+    eprintln!("  Compared with baseline (threshold {threshold_percent}%):");
+    let mut regressions = Vec::new();
+    for result in results {
+        let Some(gas) = Summary::of(&result.gas) else {
+            continue;
+        };
+        let Some(base) = baseline["methods"][&result.method]["median_gas"].as_u64() else {
+            eprintln!("    {:<24} not in baseline", result.method);
+            continue;
+        };
+        // A zero baseline has no percentage change to compare against
+        if base == 0 {
+            eprintln!(
+                "    {:<24} {} -> {} (not comparable: baseline is zero gas)",
+                result.method,
+                tgas(base),
+                tgas(gas.median)
+            );
+            continue;
+        }
+        let change = (gas.median as f64 - base as f64) / base as f64 * 100.0;
+        let regressed = change > threshold_percent;
+        eprintln!(
+            "    {:<24} {} -> {} ({change:+.2}%){}",
+            result.method,
+            tgas(base),
+            tgas(gas.median),
+            if regressed { "  REGRESSED" } else { "" }
+        );
+        if regressed {
+            regressions.push(result.method.as_str());
+        }
+    }
+    if let Some(methods) = baseline["methods"].as_object() {
+        for name in methods.keys() {
+            if !results.iter().any(|r| &r.method == name) {
+                eprintln!("    {name:<24} in baseline but not benchmarked");
+            }
+        }
+    }
+
+    if !regressions.is_empty() {
+        bail!(
+            "gas regressed by more than {threshold_percent}% in: {}",
+            regressions.join(", ")
+        );
+    }
+    Ok(())
+}
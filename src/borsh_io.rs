@@ -0,0 +1,195 @@
+// Borsh method I/O — `@borsh_io` methods take their arguments as the Borsh
+// encoding of their parameters, in order, and return the Borsh encoding of
+// their result, as near-sdk's `#[serializer(borsh)]` and
+// `#[result_serializer(borsh)]` do. Borsh has no self-description, so every
+// parameter and the return value need a type annotation the compiler can
+// map to a layout.
+
+use ruff_python_ast::{Expr, Operator, StmtFunctionDef};
+use ruff_text_size::Ranged;
+
+use crate::diagnostics::{Diagnostic, SourceFile};
+
+/// Fixed-width integer annotations, defined as aliases of `int` by the
+/// Borsh helpers.
+const INTEGERS: &[&str] = &[
+    "u8", "u16", "u32", "u64", "u128", "i8", "i16", "i32", "i64", "i128",
+];
+
+/// A Borsh layout, from an annotation.
+#[derive(Clone)]
+pub enum Type {
+    /// One of [`INTEGERS`], by name.
+    Int(&'static str),
+    Bool,
+    /// `str`: a `u32` byte length, then UTF-8.
+    Str,
+    /// `bytes`: a `u32` length, then the bytes.
+    Bytes,
+    /// `T | None`: a `u8` tag, then the value if it's 1.
+    Option(Box<Type>),
+    /// `list[T]`: a `u32` count, then the items.
+    List(Box<Type>),
+    /// `tuple[A, B]`: the items in order.
+    Tuple(Vec<Type>),
+    /// `dict[K, V]`: a `u32` count, then key-value pairs ordered by key.
+    Dict(Box<Type>, Box<Type>),
+}
+
+impl Type {
+    /// The layout `annotation` describes, or why it has none.
+    fn of(annotation: &Expr) -> Result<Self, String> {
+        match annotation {
+            Expr::Name(name) => match name.id.as_str() {
+                "bool" => Ok(Type::Bool),
+                "str" => Ok(Type::Str),
+                "bytes" => Ok(Type::Bytes),
+                // JSON's U128 strings are plain integers in Borsh
+                "U128" | "Balance" => Ok(Type::Int("u128")),
+                "int" => Err("`int` has no fixed width: use u8 … u128 or i8 … i128".into()),
+                other => INTEGERS
+                    .iter()
+                    .find(|name| **name == other)
+                    .map(|name| Type::Int(name))
+                    .ok_or_else(|| format!("`{other}` has no Borsh layout")),
+            },
+            Expr::BinOp(union) if union.op == Operator::BitOr => {
+                match (union.left.as_ref(), union.right.as_ref()) {
+                    (Expr::NoneLiteral(_), other) | (other, Expr::NoneLiteral(_)) => {
+                        Ok(Type::Option(Box::new(Type::of(other)?)))
+                    }
+                    _ => Err("unions other than `T | None` have no Borsh layout".into()),
+                }
+            }
+            Expr::Subscript(generic) => {
+                let Expr::Name(base) = generic.value.as_ref() else {
+                    return Err("unsupported annotation".into());
+                };
+                let params: Vec<&Expr> = match generic.slice.as_ref() {
+                    Expr::Tuple(tuple) => tuple.elts.iter().collect(),
+                    single => vec![single],
+                };
+                match (base.id.as_str(), &params[..]) {
+                    ("Optional", [item]) => Ok(Type::Option(Box::new(Type::of(item)?))),
+                    ("list" | "List", [item]) => Ok(Type::List(Box::new(Type::of(item)?))),
+                    ("tuple" | "Tuple", items) => Ok(Type::Tuple(
+                        items
+                            .iter()
+                            .map(|item| Type::of(item))
+                            .collect::<Result<_, _>>()?,
+                    )),
+                    ("dict" | "Dict", [key, value]) => Ok(Type::Dict(
+                        Box::new(Type::of(key)?),
+                        Box::new(Type::of(value)?),
+                    )),
+                    (other, _) => Err(format!("`{other}[...]` has no Borsh layout")),
+                }
+            }
+            _ => Err("unsupported annotation".into()),
+        }
+    }
+
+    /// The schema `__borsh_read__` and `__borsh_write__` take, as Python.
+    pub fn schema(&self) -> String {
+        let nested = |kind: &str, items: &[&Type]| {
+            let mut parts = vec![format!("\"{kind}\"")];
+            parts.extend(items.iter().map(|item| item.schema()));
+            format!("[{}]", parts.join(", "))
+        };
+        match self {
+            Type::Int(name) => format!("\"{name}\""),
+            Type::Bool => "\"bool\"".into(),
+            Type::Str => "\"str\"".into(),
+            Type::Bytes => "\"bytes\"".into(),
+            Type::Option(item) => nested("option", &[item]),
+            Type::List(item) => nested("list", &[item]),
+            Type::Tuple(items) => nested("tuple", &items.iter().collect::<Vec<_>>()),
+            Type::Dict(key, value) => nested("dict", &[key, value]),
+        }
+    }
+
+    /// The Rust type with the same Borsh encoding, for the ABI.
+    pub fn declaration(&self) -> String {
+        match self {
+            Type::Int(name) => name.to_string(),
+            Type::Bool => "bool".into(),
+            Type::Str => "String".into(),
+            Type::Bytes => "Vec<u8>".into(),
+            Type::Option(item) => format!("Option<{}>", item.declaration()),
+            Type::List(item) => format!("Vec<{}>", item.declaration()),
+            Type::Tuple(items) => format!(
+                "({})",
+                items
+                    .iter()
+                    .map(Type::declaration)
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            ),
+            Type::Dict(key, value) => {
+                format!("BTreeMap<{}, {}>", key.declaration(), value.declaration())
+            }
+        }
+    }
+}
+
+/// The Borsh layouts of a `@borsh_io` method.
+#[derive(Clone)]
+pub struct Signature {
+    /// One per parameter callers fill, in order.
+    pub params: Vec<Type>,
+    /// `None` if the method returns nothing.
+    pub returns: Option<Type>,
+}
+
+/// Work out the layouts of `func`'s parameters (skipping the `self` of a
+/// `class Contract` method) and of its return value, reporting annotations
+/// that are missing or have no layout.
+pub fn signature(
+    func: &StmtFunctionDef,
+    state: bool,
+    returns_value: bool,
+    file: SourceFile,
+    diagnostics: &mut Vec<Diagnostic>,
+) -> Signature {
+    let name = func.name.as_str();
+    let mut check = |annotation: Option<&Expr>, what: String, offset: usize| {
+        let result = match annotation {
+            Some(annotation) => Type::of(annotation),
+            None => Err("it needs a type annotation".into()),
+        };
+        result
+            .map_err(|reason| {
+                diagnostics.push(file.error(
+                    "invalid-borsh-type",
+                    offset,
+                    format!("{what} of @borsh_io method `{name}` can't be read as Borsh: {reason}"),
+                ));
+            })
+            .ok()
+    };
+
+    let params = &func.parameters;
+    let params = params
+        .posonlyargs
+        .iter()
+        .chain(&params.args)
+        .chain(&params.kwonlyargs)
+        .skip(usize::from(state))
+        .filter_map(|p| {
+            check(
+                p.parameter.annotation.as_deref(),
+                format!("parameter `{}`", p.parameter.name),
+                p.range().start().to_usize(),
+            )
+        })
+        .collect();
+    let returns = match func.returns.as_deref() {
+        None | Some(Expr::NoneLiteral(_)) if !returns_value => None,
+        annotation => check(
+            annotation,
+            "the return value".into(),
+            func.range.start().to_usize(),
+        ),
+    };
+    Signature { params, returns }
+}
@@ -0,0 +1,245 @@
+// Build cache — building a contract nothing changed in still ran the
+// checks, Monty, cargo and wasm-opt, only to produce the same WASM. A build
+// hashes what goes into it: the Python files, the options, the templates,
+// and the versions of the CLI and the tools it runs. When a finished build
+// with the same hash is in the per-user cache (`~/.cache/monty-near/builds`),
+// the build copies its WASM and does nothing else.
+
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+use anyhow::{Context, Result};
+use sha2::{Digest, Sha256};
+
+use crate::{BuildArgs, OutputFormat};
+
+/// A cached build's WASM, in the directory named by its key.
+const WASM_FILE: &str = "contract.wasm";
+
+/// The contract's `__contract_name__`, if it declares one, which names the
+/// output.
+const NAME_FILE: &str = "name";
+
+/// A build found in the cache.
+pub struct Hit {
+    pub wasm: Vec<u8>,
+    pub name: Option<String>,
+}
+
+/// The directory holding the cached builds.
+fn root() -> Option<PathBuf> {
+    Some(crate::target_cache::cache_dir()?.join("builds"))
+}
+
+/// First line of `program`'s version output, or `None` if it can't run.
+fn tool_version(program: &Path, args: &[&str], toolchain: Option<&str>) -> Option<String> {
+    let mut command = Command::new(program);
+    command.args(args);
+    if let Some(toolchain) = toolchain {
+        command.env("RUSTUP_TOOLCHAIN", toolchain);
+    }
+    let output = command.output().ok()?;
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    output
+        .status
+        .success()
+        .then(|| stdout.lines().next().unwrap_or("").trim().to_string())
+}
+
+/// Hash every file under `dir`, by path relative to it.
+fn hash_dir(hasher: &mut Sha256, root: &Path, dir: &Path) -> Result<()> {
+    let mut entries: Vec<PathBuf> = fs::read_dir(dir)
+        .with_context(|| format!("failed to read {}", dir.display()))?
+        .map(|entry| entry.map(|e| e.path()))
+        .collect::<Result<_, _>>()?;
+    entries.sort();
+    for path in entries {
+        if path.is_dir() {
+            hash_dir(hasher, root, &path)?;
+        } else {
+            let relative = path.strip_prefix(root).unwrap_or(&path);
+            hasher.update(relative.to_string_lossy().as_bytes());
+            hasher.update(fs::read(&path)?);
+        }
+    }
+    Ok(())
+}
+
+/// The cache key of the build `args` describe, or `None` if it can't be
+/// cached: with `--no-cache`, `--rebuild` or `--update-lock`, with
+/// `--out-dir`, whose artifacts describe the whole build, with
+/// `--emit-project` or `--wasm-opt-report`, whose output only a real build
+/// produces, with `--monty-path`, a checkout that may change under the same
+/// path, or without a cache directory.
+pub fn key(args: &BuildArgs) -> Result<Option<String>> {
+    if args.no_cache
+        || args.rebuild
+        || args.update_lock
+        || args.out_dir.is_some()
+        || args.emit_project.is_some()
+        || args.wasm_opt_report
+        || args.monty_path.is_some()
+        || crate::reproducible::in_container()
+    {
+        return Ok(None);
+    }
+    if root().is_none() {
+        return Ok(None);
+    }
+
+    let mut hasher = Sha256::new();
+    hasher.update(env!("CARGO_PKG_VERSION").as_bytes());
+
+    // The contract and the modules it imports
+    let input = args.input();
+    let mut files = vec![input.to_path_buf()];
+    files.extend(crate::imports::module_files(input));
+    for file in &files {
+        hasher.update(file.to_string_lossy().as_bytes());
+        hasher
+            .update(fs::read(file).with_context(|| format!("failed to read {}", file.display()))?);
+    }
+
+    // What else changes the output, or whether the build succeeds
+    let options = serde_json::json!({
+        "settings": crate::build_settings(args),
+        "source_version": args.source_version,
+        "source_link": args.source_link,
+        "standards": args.standards,
+        "lints": format!("{:?}", args.lints),
+    });
+    hasher.update(options.to_string().as_bytes());
+
+    // The recorded Cargo.lock the project builds with, and the commit and
+    // version the source metadata names
+    if let Some(path) = &args.lockfile {
+        hasher.update(fs::read(path).unwrap_or_default());
+    }
+    hasher.update(format!("{:?}", crate::metadata::git_state(args)).as_bytes());
+
+    for template in crate::TEMPLATES {
+        hasher.update(template.as_bytes());
+    }
+    if let Some(dir) = &args.template_dir {
+        hash_dir(&mut hasher, dir, dir)?;
+    }
+
+    let channel = if args.compat {
+        "nightly"
+    } else {
+        crate::doctor::pinned_channel()
+    };
+    let mut tools = vec![tool_version(
+        Path::new("rustc"),
+        &["--version"],
+        Some(channel),
+    )];
+    if !args.no_wasm_opt {
+        let wasm_opt = match crate::wasm_opt_program(args.wasm_opt_path.as_deref()) {
+            Some(program) => tool_version(&program, &["--version"], None),
+            None => Some(format!("wasm-opt crate {}", crate::WASM_OPT_CRATE_VERSION)),
+        };
+        tools.push(wasm_opt);
+    }
+    if args.format == OutputFormat::Component {
+        tools.push(tool_version(Path::new("wasm-tools"), &["--version"], None));
+    }
+    hasher.update(format!("{tools:?}").as_bytes());
+
+    Ok(Some(format!("{:x}", hasher.finalize())))
+}
+
+/// The build cached under `key`, if there is one.
+pub fn lookup(key: &str) -> Option<Hit> {
+    let dir = root()?.join(key);
+    let wasm = fs::read(dir.join(WASM_FILE)).ok()?;
+    let name = fs::read_to_string(dir.join(NAME_FILE)).ok();
+    Some(Hit { wasm, name })
+}
+
+/// Cache the finished build at `wasm`, of the contract named `name`, under
+/// `key`.
+pub fn store(key: &str, name: Option<&str>, wasm: &Path) -> Result<()> {
+    let Some(root) = root() else {
+        return Ok(());
+    };
+    // Written next to its place and renamed, so a concurrent build never
+    // finds half an entry
+    let dir = root.join(key);
+    let partial = root.join(format!("{key}.partial-{}", std::process::id()));
+    fs::create_dir_all(&partial)?;
+    fs::copy(wasm, partial.join(WASM_FILE))?;
+    if let Some(name) = name {
+        fs::write(partial.join(NAME_FILE), name)?;
+    }
+    if fs::rename(&partial, &dir).is_err() {
+        // Another build cached the same key first
+        fs::remove_dir_all(&partial)?;
+    }
+    Ok(())
+}
+
+/// `clean --build-cache`: remove every cached build.
+pub fn clean() -> Result<()> {
+    match root().filter(|root| root.exists()) {
+        Some(root) => {
+            fs::remove_dir_all(&root)?;
+            eprintln!("  Removed {}", root.display());
+        }
+        None => eprintln!("  No cached builds"),
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// `build` of `contract.py` in a scratch directory named after `test`.
+    fn build_args(test: &str, options: &[&str]) -> (PathBuf, BuildArgs) {
+        let dir = std::env::temp_dir().join(format!(
+            "monty-near-build-cache-{test}-{}",
+            std::process::id()
+        ));
+        let input = dir.join("contract.py");
+        let mut argv = vec![input.as_os_str()];
+        argv.extend(options.iter().map(std::ffi::OsStr::new));
+        (dir, BuildArgs::from_args(argv).unwrap())
+    }
+
+    #[test]
+    fn builds_with_other_outputs_are_not_cached() {
+        for option in [
+            "--no-cache",
+            "--rebuild",
+            "--update-lock",
+            "--out-dir=dist",
+            "--emit-project=project",
+            "--wasm-opt-report",
+            "--monty-path=monty",
+        ] {
+            let (_, args) = build_args("uncached", &[option]);
+            assert!(key(&args).unwrap().is_none(), "{option} was cached");
+        }
+    }
+
+    #[test]
+    fn key_follows_the_source_and_the_options() {
+        if root().is_none() {
+            return;
+        }
+        let (dir, args) = build_args("key", &[]);
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(args.input(), "def hello():\n    return 'hi'\n").unwrap();
+        let first = key(&args).unwrap().unwrap();
+        assert_eq!(key(&args).unwrap().unwrap(), first);
+
+        let (_, compressed) = build_args("key", &["--compress-bytecode"]);
+        assert_ne!(key(&compressed).unwrap().unwrap(), first);
+
+        fs::write(args.input(), "def hello():\n    return 'hello'\n").unwrap();
+        assert_ne!(key(&args).unwrap().unwrap(), first);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
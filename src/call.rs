@@ -0,0 +1,68 @@
+// Invoking deployed contracts — `call` signs a function-call transaction,
+// `view` runs a read-only query. Both print logs and the return value.
+
+use anyhow::Result;
+use base64::{engine::general_purpose::STANDARD as BASE64, Engine as _};
+
+use crate::bench::tgas;
+use crate::rpc::{network_rpc_url, outcome_logs, outcome_result, total_gas_burnt, RpcClient};
+use crate::tx::{load_credentials, Action};
+
+/// Call `method` on `contract_id` as `signer_id`, print its logs to stderr
+/// and its return value to stdout.
+pub fn call(
+    contract_id: &str,
+    method: &str,
+    args: &[u8],
+    signer_id: &str,
+    network: &str,
+    deposit: u128,
+    gas: u64,
+) -> Result<()> {
+    let rpc = RpcClient::new(&network_rpc_url(network)?);
+    let signer = load_credentials(network, signer_id)?;
+
+    let outcome = signer.send(
+        &rpc,
+        contract_id,
+        &[Action::FunctionCall {
+            method_name: method.to_string(),
+            args: args.to_vec(),
+            gas,
+            deposit,
+        }],
+    )?;
+    print_logs(&outcome_logs(&outcome));
+    let value = outcome_result(&outcome)?;
+
+    let tx_hash = outcome["transaction"]["hash"].as_str().unwrap_or("unknown");
+    eprintln!(
+        "  Transaction {tx_hash} burnt {}",
+        tgas(total_gas_burnt(&outcome))
+    );
+    print_value(&value);
+    Ok(())
+}
+
+/// Run `method` on `contract_id` as a view call and print the result.
+pub fn view(contract_id: &str, method: &str, args: &[u8], network: &str) -> Result<()> {
+    let rpc = RpcClient::new(&network_rpc_url(network)?);
+    let (value, logs) = rpc.view_function(contract_id, method, args)?;
+    print_logs(&logs);
+    print_value(&value);
+    Ok(())
+}
+
+pub fn print_logs(logs: &[String]) {
+    for log in logs {
+        eprintln!("  Log: {log}");
+    }
+}
+
+/// Print a return value as text, or as base64 if it isn't UTF-8.
+pub fn print_value(value: &[u8]) {
+    match std::str::from_utf8(value) {
+        Ok(text) => println!("{text}"),
+        Err(_) => println!("base64:{}", BASE64.encode(value)),
+    }
+}
@@ -0,0 +1,293 @@
+// Persistent collections — `balances = LookupMap()` at the top level of a
+// contract declares a storage-backed collection, like near-sdk's. Monty has
+// no classes, so the compiler gives each collection its variable's name and
+// `:` as key prefix, drops the declaration, and lowers `balances.get(key)` to
+// `__lookup_map_get__("balances:", key)`, one of the functions in
+// template/python/collections.py.
+
+use std::collections::HashMap;
+
+use ruff_python_ast::visitor::{self, Visitor};
+use ruff_python_ast::{Expr, ExprCall, ModModule, Stmt};
+use ruff_python_parser::parse_module;
+use ruff_text_size::Ranged;
+
+use crate::diagnostics::{Diagnostic, SourceFile};
+
+/// A collection type: the name contracts use, the stem of its helper
+/// functions, and its methods.
+type Collection = (&'static str, &'static str, &'static [&'static str]);
+
+const COLLECTIONS: &[Collection] = &[
+    (
+        "LookupMap",
+        "lookup_map",
+        &["get", "contains_key", "insert", "remove"],
+    ),
+    (
+        "Vector",
+        "vector",
+        &[
+            "len",
+            "is_empty",
+            "get",
+            "set",
+            "push",
+            "pop",
+            "swap_remove",
+            "to_list",
+        ],
+    ),
+    (
+        "UnorderedSet",
+        "unordered_set",
+        &["len", "is_empty", "contains", "insert", "remove", "to_list"],
+    ),
+];
+
+/// The collection methods that write storage.
+const WRITES: &[&str] = &["insert", "remove", "set", "push", "pop", "swap_remove"];
+
+/// Whether `method` of a collection writes storage.
+pub fn writes(method: &str) -> bool {
+    WRITES.contains(&method)
+}
+
+/// The names of the collections `module` declares.
+pub fn declared(module: &ModModule) -> Vec<&str> {
+    declarations(module).iter().map(|d| d.name).collect()
+}
+
+/// One of [`COLLECTIONS`], by type name.
+fn collection(name: &str) -> Option<&'static Collection> {
+    COLLECTIONS.iter().find(|(kind, _, _)| *kind == name)
+}
+
+/// A top-level `name = LookupMap()`.
+struct Declaration<'a> {
+    name: &'a str,
+    kind: &'static Collection,
+    call: &'a ExprCall,
+    stmt: &'a Stmt,
+}
+
+/// The collections `module` declares.
+fn declarations(module: &ModModule) -> Vec<Declaration<'_>> {
+    module
+        .body
+        .iter()
+        .filter_map(|stmt| {
+            let Stmt::Assign(assign) = stmt else {
+                return None;
+            };
+            let ([Expr::Name(target)], Expr::Call(call)) =
+                (&assign.targets[..], assign.value.as_ref())
+            else {
+                return None;
+            };
+            let Expr::Name(kind) = call.func.as_ref() else {
+                return None;
+            };
+            Some(Declaration {
+                name: target.id.as_str(),
+                kind: collection(kind.id.as_str())?,
+                call,
+                stmt,
+            })
+        })
+        .collect()
+}
+
+/// Where code uses the declared collections.
+struct Uses<'a> {
+    collections: HashMap<&'a str, &'static Collection>,
+    /// `name.method(...)`: the collection, the method, and the call.
+    calls: Vec<(&'a str, &'a str, &'a ExprCall)>,
+    /// Offsets of any other use of a collection's name.
+    bare: Vec<(&'a str, usize)>,
+    /// Offsets of collection constructors outside a declaration.
+    constructors: Vec<(&'a str, usize)>,
+    /// Offsets of method calls outside functions.
+    top_level: Vec<(&'a str, usize)>,
+    in_function: bool,
+}
+
+impl<'a> Visitor<'a> for Uses<'a> {
+    fn visit_stmt(&mut self, stmt: &'a Stmt) {
+        let in_function = self.in_function;
+        if matches!(stmt, Stmt::FunctionDef(_)) {
+            self.in_function = true;
+        }
+        visitor::walk_stmt(self, stmt);
+        self.in_function = in_function;
+    }
+
+    fn visit_expr(&mut self, expr: &'a Expr) {
+        match expr {
+            Expr::Call(call) => {
+                if let Expr::Attribute(attribute) = call.func.as_ref() {
+                    if let Expr::Name(name) = attribute.value.as_ref() {
+                        if let Some((name, _)) = self.collections.get_key_value(name.id.as_str()) {
+                            self.calls.push((name, attribute.attr.as_str(), call));
+                            if !self.in_function {
+                                self.top_level.push((name, call.range.start().to_usize()));
+                            }
+                            for arg in call.arguments.args.iter() {
+                                self.visit_expr(arg);
+                            }
+                            for keyword in call.arguments.keywords.iter() {
+                                self.visit_expr(&keyword.value);
+                            }
+                            return;
+                        }
+                    }
+                }
+                if let Expr::Name(name) = call.func.as_ref() {
+                    if let Some((kind, _, _)) = collection(name.id.as_str()) {
+                        self.constructors
+                            .push((kind, call.range.start().to_usize()));
+                    }
+                }
+                visitor::walk_expr(self, expr);
+            }
+            Expr::Name(name) => {
+                if let Some((name, _)) = self.collections.get_key_value(name.id.as_str()) {
+                    self.bare.push((name, expr.range().start().to_usize()));
+                }
+            }
+            _ => visitor::walk_expr(self, expr),
+        }
+    }
+}
+
+/// Find the uses of `module`'s declared collections, skipping the
+/// declarations themselves.
+fn uses<'a>(module: &'a ModModule, declarations: &[Declaration<'a>]) -> Uses<'a> {
+    let mut uses = Uses {
+        collections: declarations.iter().map(|d| (d.name, d.kind)).collect(),
+        calls: Vec::new(),
+        bare: Vec::new(),
+        constructors: Vec::new(),
+        top_level: Vec::new(),
+        in_function: false,
+    };
+    for stmt in &module.body {
+        if !declarations.iter().any(|d| std::ptr::eq(d.stmt, stmt)) {
+            uses.visit_stmt(stmt);
+        }
+    }
+    uses
+}
+
+/// Report collections that can't be lowered: declarations with arguments
+/// or declared twice, collections created anywhere but a top-level
+/// declaration, and collections used other than as `name.method(...)`
+/// inside a function. Returns whether `module` declares any collection, so
+/// the build adds the helpers.
+pub fn check(module: &ModModule, file: SourceFile, diagnostics: &mut Vec<Diagnostic>) -> bool {
+    let error = |offset: usize, message: String| file.error("invalid-collection", offset, message);
+    let declarations = declarations(module);
+    let mut seen: HashMap<&str, usize> = HashMap::new();
+    for declaration in &declarations {
+        let (kind, _, _) = declaration.kind;
+        let name = declaration.name;
+        let offset = declaration.stmt.range().start().to_usize();
+        let arguments = &declaration.call.arguments;
+        if !arguments.args.is_empty() || !arguments.keywords.is_empty() {
+            diagnostics.push(
+                error(offset, format!("{kind}() takes no arguments"))
+                    .with_note(format!("the key prefix is the variable's name: `{name}:`")),
+            );
+        }
+        if seen.insert(name, offset).is_some() {
+            diagnostics.push(error(
+                offset,
+                format!("collection `{name}` is declared more than once"),
+            ));
+        }
+    }
+
+    let uses = uses(module, &declarations);
+    for (name, method, call) in &uses.calls {
+        let (kind, _, methods) = uses.collections[name];
+        if !methods.contains(method) {
+            diagnostics.push(
+                error(
+                    call.range.start().to_usize(),
+                    format!("{kind} `{name}` has no method `{method}`"),
+                )
+                .with_note(format!("{kind} methods: {}", methods.join(", "))),
+            );
+        }
+    }
+    for (name, offset) in &uses.top_level {
+        diagnostics.push(error(
+            *offset,
+            format!("collection `{name}` can only be used inside functions"),
+        ));
+    }
+    for (name, offset) in &uses.bare {
+        let (kind, _, _) = uses.collections[name];
+        diagnostics.push(error(
+            *offset,
+            format!("`{name}` is a {kind}: it can only be used as `{name}.method(...)`"),
+        ));
+    }
+    for (kind, offset) in &uses.constructors {
+        diagnostics.push(error(
+            *offset,
+            format!("a {kind} must be declared at the top level, as `name = {kind}()`"),
+        ));
+    }
+    !declarations.is_empty()
+}
+
+/// Lower the collections `source` declares to calls of the helper
+/// functions, keeping every line where it was. Source without collections,
+/// or that doesn't parse, is returned unchanged.
+pub fn lower(source: &str) -> String {
+    let Ok(parsed) = parse_module(source) else {
+        return source.to_string();
+    };
+    let module = parsed.into_syntax();
+    let declarations = declarations(&module);
+    if declarations.is_empty() {
+        return source.to_string();
+    }
+
+    let ranges: Vec<(usize, usize)> = declarations
+        .iter()
+        .map(|d| {
+            (
+                d.stmt.range().start().to_usize(),
+                d.stmt.range().end().to_usize(),
+            )
+        })
+        .collect();
+    let mut out = crate::blank_ranges(source, &ranges);
+
+    // `name.method(` becomes `__stem_method__("name:", `, from the end so the
+    // earlier offsets stay valid
+    let uses = uses(&module, &declarations);
+    let mut calls = uses.calls;
+    calls.sort_by_key(|(_, _, call)| call.range.start());
+    for (name, method, call) in calls.into_iter().rev() {
+        let (_, stem, _) = uses.collections[name];
+        let start = call.func.range().start().to_usize();
+        let func_end = call.func.range().end().to_usize();
+        let Some(paren) = source[func_end..].find('(') else {
+            continue;
+        };
+        let arguments = &call.arguments;
+        let separator = if arguments.args.is_empty() && arguments.keywords.is_empty() {
+            ""
+        } else {
+            ", "
+        };
+        out.replace_range(
+            start..func_end + paren + 1,
+            &format!("__{stem}_{method}__(\"{name}:\"{separator}"),
+        );
+    }
+    out
+}
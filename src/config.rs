@@ -0,0 +1,539 @@
+// Project configuration — `monty-near.toml` supplies defaults for build and
+// deploy options so commands work without flags inside a project. Flags on
+// the command line always win.
+
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use anyhow::{bail, Context, Result};
+use serde::Deserialize;
+
+use crate::diagnostics::Level;
+use crate::BuildArgs;
+
+pub const CONFIG_FILE: &str = "monty-near.toml";
+
+/// Network used when neither the command line nor the config names one.
+const DEFAULT_NETWORK: &str = "testnet";
+
+#[derive(Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Config {
+    #[serde(default)]
+    build: BuildConfig,
+    #[serde(default)]
+    deploy: DeployConfig,
+    #[serde(default)]
+    profile: BTreeMap<String, ProfileConfig>,
+    /// `[lints]`: the level of each lint.
+    #[serde(default)]
+    lints: BTreeMap<String, Level>,
+    /// Directory holding the config file; relative paths are resolved
+    /// against it.
+    #[serde(skip)]
+    dir: PathBuf,
+}
+
+#[derive(Default, Deserialize)]
+#[serde(deny_unknown_fields, rename_all = "kebab-case")]
+struct BuildConfig {
+    input: Option<PathBuf>,
+    output: Option<PathBuf>,
+    out_dir: Option<PathBuf>,
+    compat: Option<bool>,
+    wasm_opt: Option<bool>,
+    wasm_opt_level: Option<crate::WasmOptLevel>,
+    #[serde(default)]
+    wasm_opt_args: Vec<String>,
+    wasm_opt_path: Option<PathBuf>,
+    registry: Option<String>,
+    #[serde(default)]
+    features: Vec<String>,
+    #[serde(default)]
+    cargo_flags: Vec<String>,
+    jobs: Option<u32>,
+    offline: Option<bool>,
+    vendor_dir: Option<PathBuf>,
+    build_dir: Option<PathBuf>,
+    lockfile: Option<PathBuf>,
+    template_dir: Option<PathBuf>,
+    monty_version: Option<String>,
+    monty_path: Option<PathBuf>,
+    profile: Option<String>,
+    allocator: Option<crate::Allocator>,
+    compress_bytecode: Option<bool>,
+    split_methods: Option<bool>,
+    shared_cache: Option<bool>,
+    cache: Option<bool>,
+    stack_size: Option<u32>,
+    initial_memory_pages: Option<u32>,
+    max_memory_pages: Option<u32>,
+    #[serde(default)]
+    define: BTreeMap<String, toml::Value>,
+    source_metadata: Option<bool>,
+    source_version: Option<String>,
+    source_link: Option<String>,
+    #[serde(default)]
+    standards: Vec<String>,
+    #[serde(default)]
+    allowed_host_functions: Vec<String>,
+    deny_warnings: Option<bool>,
+}
+
+/// A `[profile.<name>]` table: overrides on top of a built-in profile.
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields, rename_all = "kebab-case")]
+struct ProfileConfig {
+    inherits: Option<String>,
+    opt_level: Option<OptLevel>,
+    lto: Option<bool>,
+    codegen_units: Option<u32>,
+    wasm_opt: Option<bool>,
+}
+
+/// Cargo `opt-level`: a number or `"s"` / `"z"`.
+#[derive(Clone, Deserialize)]
+#[serde(untagged)]
+pub enum OptLevel {
+    Number(u8),
+    Size(String),
+}
+
+impl OptLevel {
+    /// The value as written in Cargo.toml.
+    pub fn to_toml(&self) -> String {
+        match self {
+            OptLevel::Number(level) => level.to_string(),
+            OptLevel::Size(level) => format!("\"{level}\""),
+        }
+    }
+}
+
+/// Settings of the generated project's cargo profile, and whether the
+/// output goes through wasm-opt.
+#[derive(Clone)]
+pub struct Profile {
+    pub name: String,
+    pub opt_level: OptLevel,
+    pub lto: bool,
+    pub codegen_units: u32,
+    pub wasm_opt: bool,
+}
+
+/// Built-in profiles: (name, opt-level, lto, codegen-units, wasm-opt).
+/// `release` matches the template's `[profile.release]`.
+const BUILTIN_PROFILES: &[(&str, &str, bool, u32, bool)] = &[
+    ("release", "s", true, 1, true),
+    ("dev", "1", false, 16, false),
+];
+
+impl Profile {
+    fn builtin(name: &str) -> Option<Self> {
+        let &(name, opt_level, lto, codegen_units, wasm_opt) =
+            BUILTIN_PROFILES.iter().find(|p| p.0 == name)?;
+        let opt_level = match opt_level.parse() {
+            Ok(level) => OptLevel::Number(level),
+            Err(_) => OptLevel::Size(opt_level.to_string()),
+        };
+        Some(Profile {
+            name: name.to_string(),
+            opt_level,
+            lto,
+            codegen_units,
+            wasm_opt,
+        })
+    }
+
+    pub fn is_release(&self) -> bool {
+        self.name == "release"
+    }
+}
+
+impl Default for Profile {
+    fn default() -> Self {
+        Profile::builtin("release").expect("release is a built-in profile")
+    }
+}
+
+/// `[deploy]` holds the default target; `[deploy.<name>]` tables define
+/// named targets selected with `--target`.
+#[derive(Default, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+struct DeployConfig {
+    account_id: Option<String>,
+    network: Option<String>,
+    #[serde(flatten)]
+    targets: BTreeMap<String, DeployTarget>,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields, rename_all = "kebab-case")]
+struct DeployTarget {
+    account_id: Option<String>,
+    network: Option<String>,
+}
+
+impl Config {
+    /// Load `monty-near.toml` from the current directory or the nearest
+    /// ancestor that has one; an empty config if there is none.
+    pub fn load() -> Result<Self> {
+        let cwd = std::env::current_dir()?;
+        let Some(path) = cwd
+            .ancestors()
+            .map(|dir| dir.join(CONFIG_FILE))
+            .find(|path| path.is_file())
+        else {
+            return Ok(Self::default());
+        };
+
+        let contents = fs::read_to_string(&path)
+            .with_context(|| format!("failed to read {}", path.display()))?;
+        let mut config: Config =
+            toml::from_str(&contents).with_context(|| format!("invalid {}", path.display()))?;
+        config.dir = path.parent().unwrap_or(Path::new(".")).to_path_buf();
+        Ok(config)
+    }
+
+    /// Fill in build options not given on the command line.
+    pub fn apply(&self, args: &mut BuildArgs) -> Result<()> {
+        let build = &self.build;
+        if args.input.is_none() {
+            args.input = build.input.as_ref().map(|p| self.dir.join(p));
+        }
+        if args.input.is_none() {
+            bail!("no input file: pass one or set `input` under [build] in {CONFIG_FILE}");
+        }
+        // A package directory builds its entry file
+        if let Some(input) = args.input.as_mut().filter(|input| input.is_dir()) {
+            *input = crate::imports::package_entry(input)?;
+        }
+        // -o and --out-dir each replace both settings from the file
+        if args.output.is_none() && args.out_dir.is_none() {
+            args.output = build.output.as_ref().map(|p| self.dir.join(p));
+            args.out_dir = build.out_dir.as_ref().map(|p| self.dir.join(p));
+        }
+        if !args.compat && !args.no_compat {
+            args.compat = build.compat.unwrap_or(false);
+        }
+        if !args.no_wasm_opt && !args.wasm_opt_report {
+            args.no_wasm_opt = !build.wasm_opt.unwrap_or(true);
+        }
+        args.wasm_opt_level = args.wasm_opt_level.or(build.wasm_opt_level);
+        // Before the command line's, which may add passes to run after them
+        args.wasm_opt_args
+            .splice(0..0, build.wasm_opt_args.iter().cloned());
+        if args.wasm_opt_path.is_none() {
+            args.wasm_opt_path = self.wasm_opt_path();
+        }
+        if args.registry.is_none() {
+            args.registry = build.registry.clone();
+        }
+        args.features.splice(0..0, build.features.iter().cloned());
+        args.cargo_flags
+            .splice(0..0, build.cargo_flags.iter().cloned());
+        args.jobs = args.jobs.or(build.jobs);
+        args.offline = args.offline || build.offline.unwrap_or(false);
+        if args.vendor_dir.is_none() {
+            args.vendor_dir = build.vendor_dir.as_ref().map(|p| self.dir.join(p));
+        }
+        if args.build_dir.is_none() {
+            args.build_dir = build.build_dir.as_ref().map(|p| self.dir.join(p));
+        }
+        if args.lockfile.is_none() {
+            args.lockfile = Some(match &build.lockfile {
+                Some(path) => self.dir.join(path),
+                None if self.dir.as_os_str().is_empty() => args
+                    .input()
+                    .parent()
+                    .unwrap_or(Path::new(""))
+                    .join(crate::lockfile::LOCK_FILE),
+                None => self.dir.join(crate::lockfile::LOCK_FILE),
+            });
+        }
+        if build.monty_version.is_some() && build.monty_path.is_some() {
+            bail!("set only one of monty-version and monty-path in {CONFIG_FILE}");
+        }
+        // Either flag replaces both settings from the file
+        if args.monty_version.is_none() && args.monty_path.is_none() {
+            args.monty_version = build.monty_version.clone();
+            args.monty_path = build.monty_path.as_ref().map(|p| self.dir.join(p));
+        }
+        if args.template_dir.is_none() {
+            args.template_dir = build.template_dir.as_ref().map(|p| self.dir.join(p));
+        }
+        args.allocator = args.allocator.or(build.allocator);
+        args.compress_bytecode = args.compress_bytecode || build.compress_bytecode.unwrap_or(false);
+        args.split_methods = args.split_methods || build.split_methods.unwrap_or(false);
+        args.no_shared_cache = args.no_shared_cache || !build.shared_cache.unwrap_or(true);
+        args.no_cache = args.no_cache || !build.cache.unwrap_or(true);
+        args.stack_size = args.stack_size.or(build.stack_size);
+        args.initial_memory_pages = args.initial_memory_pages.or(build.initial_memory_pages);
+        args.max_memory_pages = args.max_memory_pages.or(build.max_memory_pages);
+
+        // Before the command line's, so `--define` wins for the same name
+        let mut defines = Vec::new();
+        for (name, value) in &build.define {
+            let literal = match value {
+                toml::Value::String(s) => serde_json::to_string(s)?,
+                toml::Value::Integer(i) => i.to_string(),
+                toml::Value::Float(f) => format!("{f:?}"),
+                toml::Value::Boolean(true) => "True".to_string(),
+                toml::Value::Boolean(false) => "False".to_string(),
+                _ => bail!(
+                    "[build.define] {name} in {CONFIG_FILE} must be a string, number or boolean"
+                ),
+            };
+            defines.push(format!("{name}={literal}"));
+        }
+        defines.append(&mut args.defines);
+        args.defines = defines;
+
+        if !args.no_source_metadata {
+            args.no_source_metadata = !build.source_metadata.unwrap_or(true);
+        }
+        if args.source_version.is_none() {
+            args.source_version = build.source_version.clone();
+        }
+        if args.source_link.is_none() {
+            args.source_link = build.source_link.clone();
+        }
+        args.standards.splice(0..0, build.standards.iter().cloned());
+        args.allowed_host_functions
+            .splice(0..0, build.allowed_host_functions.iter().cloned());
+
+        // The command line's levels after the file's, strongest last
+        for (lint, level) in &self.lints {
+            args.lints
+                .set(lint, *level)
+                .with_context(|| format!("[lints] in {CONFIG_FILE}"))?;
+        }
+        for (lints, level) in [
+            (&args.allow, Level::Allow),
+            (&args.warn, Level::Warn),
+            (&args.deny, Level::Deny),
+        ] {
+            for lint in lints {
+                args.lints.set(lint, level)?;
+            }
+        }
+        args.lints.deny_warnings = args.deny_warnings || build.deny_warnings.unwrap_or(false);
+
+        let profile_name = args
+            .profile
+            .as_deref()
+            .or(build.profile.as_deref())
+            .unwrap_or("release");
+        args.build_profile = self.profile(profile_name)?;
+        if !args.build_profile.wasm_opt && !args.wasm_opt_report {
+            args.no_wasm_opt = true;
+        }
+        Ok(())
+    }
+
+    /// The profile called `name`: a `[profile.<name>]` table applied to the
+    /// built-in profile it inherits from, or a built-in profile.
+    fn profile(&self, name: &str) -> Result<Profile> {
+        let Some(table) = self.profile.get(name) else {
+            return Profile::builtin(name).with_context(|| {
+                let mut names: Vec<&str> = BUILTIN_PROFILES.iter().map(|p| p.0).collect();
+                names.extend(self.profile.keys().map(String::as_str));
+                format!("unknown profile `{name}` (available: {})", names.join(", "))
+            });
+        };
+
+        let base = table.inherits.as_deref().unwrap_or("release");
+        let Some(mut profile) = Profile::builtin(base) else {
+            bail!("profile `{name}` in {CONFIG_FILE} inherits from `{base}`, which is not a built-in profile (release, dev)");
+        };
+        profile.name = name.to_string();
+        if let Some(opt_level) = &table.opt_level {
+            match opt_level {
+                OptLevel::Number(0..=3) => {}
+                OptLevel::Size(level) if level == "s" || level == "z" => {}
+                _ => bail!(
+                    "invalid opt-level {} for profile `{name}` in {CONFIG_FILE} (expected 0-3, \"s\" or \"z\")",
+                    opt_level.to_toml()
+                ),
+            }
+            profile.opt_level = opt_level.clone();
+        }
+        if let Some(lto) = table.lto {
+            profile.lto = lto;
+        }
+        if let Some(codegen_units) = table.codegen_units {
+            profile.codegen_units = codegen_units;
+        }
+        if let Some(wasm_opt) = table.wasm_opt {
+            profile.wasm_opt = wasm_opt;
+        }
+        Ok(profile)
+    }
+
+    /// `wasm-opt-path`, unless `$WASM_OPT` (which beats the file but not
+    /// the flag) is set.
+    pub fn wasm_opt_path(&self) -> Option<PathBuf> {
+        if std::env::var_os("WASM_OPT").is_some() {
+            return None;
+        }
+        // A bare program name is looked up in PATH, like the default
+        self.build.wasm_opt_path.as_ref().map(|p| {
+            if p.components().count() > 1 {
+                self.dir.join(p)
+            } else {
+                p.clone()
+            }
+        })
+    }
+
+    /// The account and network to deploy to: command-line values, then the
+    /// `--target` table (or `[deploy]` without one), then the default network.
+    pub fn deploy_target(
+        &self,
+        account_id: Option<&str>,
+        network: Option<&str>,
+        target: Option<&str>,
+    ) -> Result<(String, String)> {
+        let deploy = &self.deploy;
+        let (target_account, target_network) = match target {
+            Some(name) => {
+                let Some(target) = deploy.targets.get(name) else {
+                    bail!(
+                        "no deploy target `{name}` in {CONFIG_FILE} (defined: {})",
+                        if deploy.targets.is_empty() {
+                            "none".to_string()
+                        } else {
+                            deploy
+                                .targets
+                                .keys()
+                                .cloned()
+                                .collect::<Vec<_>>()
+                                .join(", ")
+                        }
+                    );
+                };
+                (target.account_id.as_deref(), target.network.as_deref())
+            }
+            None => (deploy.account_id.as_deref(), deploy.network.as_deref()),
+        };
+
+        let account_id = account_id.or(target_account).with_context(|| {
+            format!("no account to deploy to: pass --account-id or set `account-id` under [deploy] in {CONFIG_FILE}")
+        })?;
+        let network = network.or(target_network).unwrap_or(DEFAULT_NETWORK);
+        Ok((account_id.to_string(), network.to_string()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn config(toml: &str) -> Config {
+        toml::from_str(toml).unwrap()
+    }
+
+    fn args(options: &[&str]) -> BuildArgs {
+        BuildArgs::from_args(options.iter().map(std::ffi::OsStr::new)).unwrap()
+    }
+
+    #[test]
+    fn build_settings_fill_in_missing_options() {
+        let config = config(
+            r#"
+            [build]
+            input = "contract.py"
+            compat = true
+            features = ["a"]
+            stack-size = 65536
+
+            [build.define]
+            DEBUG = true
+            NAME = "token"
+            "#,
+        );
+        let mut args = args(&["--features=b", "--define=NAME='other'"]);
+        config.apply(&mut args).unwrap();
+        assert_eq!(args.input(), Path::new("contract.py"));
+        assert!(args.compat);
+        assert_eq!(args.features, ["a", "b"]);
+        assert_eq!(args.stack_size, Some(65536));
+        assert_eq!(
+            args.defines,
+            ["DEBUG=True", "NAME=\"token\"", "NAME='other'"]
+        );
+    }
+
+    #[test]
+    fn the_command_line_wins() {
+        let config = config(
+            r#"
+            [build]
+            input = "contract.py"
+            compat = true
+            stack-size = 65536
+            profile = "dev"
+            "#,
+        );
+        let mut args = args(&[
+            "other.py",
+            "--no-compat",
+            "--stack-size=1024",
+            "--profile=release",
+        ]);
+        config.apply(&mut args).unwrap();
+        assert_eq!(args.input(), Path::new("other.py"));
+        assert!(!args.compat);
+        assert_eq!(args.stack_size, Some(1024));
+        assert!(args.build_profile.is_release());
+    }
+
+    #[test]
+    fn unknown_keys_are_rejected() {
+        assert!(toml::from_str::<Config>("[build]\ncompta = true").is_err());
+        assert!(toml::from_str::<Config>("[profile.small]\nlto = 1").is_err());
+    }
+
+    #[test]
+    fn profiles_inherit_from_a_builtin() {
+        let config = config(
+            r#"
+            [profile.small]
+            inherits = "dev"
+            opt-level = "z"
+
+            [profile.broken]
+            inherits = "small"
+            "#,
+        );
+        let profile = config.profile("small").unwrap();
+        assert_eq!(profile.opt_level.to_toml(), "\"z\"");
+        assert!(!profile.lto);
+        assert!(!profile.wasm_opt);
+        assert!(config.profile("broken").is_err());
+        assert!(config.profile("missing").is_err());
+    }
+
+    #[test]
+    fn deploy_targets_fall_back_to_the_deploy_table() {
+        let config = config(
+            r#"
+            [deploy]
+            account-id = "dev.testnet"
+
+            [deploy.prod]
+            account-id = "token.near"
+            network = "mainnet"
+            "#,
+        );
+        assert_eq!(
+            config.deploy_target(None, None, None).unwrap(),
+            ("dev.testnet".to_string(), "testnet".to_string())
+        );
+        assert_eq!(
+            config.deploy_target(None, None, Some("prod")).unwrap(),
+            ("token.near".to_string(), "mainnet".to_string())
+        );
+        assert!(config.deploy_target(None, None, Some("staging")).is_err());
+    }
+}
@@ -0,0 +1,546 @@
+// Class-based contracts — `class Contract:` groups the exported methods with
+// the fields they share. Monty has no classes, so the compiler lowers the
+// class to plain functions: fields become entries of a state dict that is
+// loaded from one storage value before each call and saved after each call
+// method, and `self.field` reads and writes that dict. The fields, in order,
+// are the state's layout; a `@migrate` method rewrites state stored with an
+// earlier one.
+
+use std::collections::{HashMap, HashSet};
+
+use ruff_python_ast::visitor::{self, Visitor};
+use ruff_python_ast::{
+    Expr, ExprCall, ExprContext, ModModule, Number, Stmt, StmtClassDef, StmtFunctionDef,
+};
+use ruff_python_parser::parse_module;
+use ruff_text_size::Ranged;
+
+use crate::diagnostics::{Diagnostic, SourceFile};
+
+/// Name of the class whose methods are exported.
+pub const CLASS_NAME: &str = "Contract";
+
+/// Storage key of the serialized fields, the one near-sdk uses.
+pub const STATE_KEY: &str = "STATE";
+
+/// Names the lowered code defines at module level.
+pub const STATE_VAR: &str = "__state__";
+pub const LOAD_FN: &str = "__load_state__";
+pub const STORED_FN: &str = "__stored_state__";
+pub const SAVE_FN: &str = "__save_state__";
+
+/// The `Contract` class of `module`, if it has one.
+pub fn find_class(module: &ModModule) -> Option<&StmtClassDef> {
+    module.body.iter().find_map(|stmt| match stmt {
+        Stmt::ClassDef(class) if class.name.as_str() == CLASS_NAME => Some(class),
+        _ => None,
+    })
+}
+
+/// The methods of `class`.
+pub fn methods(class: &StmtClassDef) -> impl Iterator<Item = &StmtFunctionDef> {
+    class.body.iter().filter_map(|stmt| match stmt {
+        Stmt::FunctionDef(func) => Some(func),
+        _ => None,
+    })
+}
+
+/// Types a field can have; each is stored as its `str()`.
+#[derive(Clone, Copy)]
+enum FieldType {
+    Str,
+    Int,
+    Float,
+    Bool,
+}
+
+impl FieldType {
+    fn from_annotation(annotation: &Expr) -> Option<Self> {
+        let Expr::Name(name) = annotation else {
+            return None;
+        };
+        match name.id.as_str() {
+            "str" => Some(FieldType::Str),
+            "int" => Some(FieldType::Int),
+            "float" => Some(FieldType::Float),
+            "bool" => Some(FieldType::Bool),
+            _ => None,
+        }
+    }
+
+    fn from_value(value: &Expr) -> Option<Self> {
+        match value {
+            Expr::StringLiteral(_) => Some(FieldType::Str),
+            Expr::NumberLiteral(number) => match number.value {
+                Number::Int(_) => Some(FieldType::Int),
+                Number::Float(_) => Some(FieldType::Float),
+                Number::Complex { .. } => None,
+            },
+            Expr::BooleanLiteral(_) => Some(FieldType::Bool),
+            _ => None,
+        }
+    }
+
+    /// The annotation for the type.
+    fn name(self) -> &'static str {
+        match self {
+            FieldType::Str => "str",
+            FieldType::Int => "int",
+            FieldType::Float => "float",
+            FieldType::Bool => "bool",
+        }
+    }
+
+    /// Python source for the value when the field has no default.
+    fn zero(self) -> &'static str {
+        match self {
+            FieldType::Str => "\"\"",
+            FieldType::Int => "0",
+            FieldType::Float => "0.0",
+            FieldType::Bool => "False",
+        }
+    }
+
+    /// Python source converting the stored string `value` back.
+    fn decode(self, value: &str) -> String {
+        match self {
+            FieldType::Str => value.to_string(),
+            FieldType::Int => format!("int({value})"),
+            FieldType::Float => format!("float({value})"),
+            FieldType::Bool => format!("{value} == \"True\""),
+        }
+    }
+}
+
+/// A field declared in the class body: `count: int = 0`.
+struct Field<'a> {
+    name: &'a str,
+    ty: FieldType,
+    /// The default value.
+    default: Option<&'a Expr>,
+}
+
+/// The class's fields, and the statements that aren't fields or methods.
+fn fields(class: &StmtClassDef) -> (Vec<Field<'_>>, Vec<&Stmt>) {
+    let mut fields = Vec::new();
+    let mut other = Vec::new();
+    for stmt in &class.body {
+        let (target, annotation, value) = match stmt {
+            Stmt::AnnAssign(assign) => (
+                assign.target.as_ref(),
+                Some(assign.annotation.as_ref()),
+                assign.value.as_deref(),
+            ),
+            Stmt::Assign(assign) if assign.targets.len() == 1 => {
+                (&assign.targets[0], None, Some(assign.value.as_ref()))
+            }
+            Stmt::FunctionDef(_) | Stmt::Pass(_) => continue,
+            // A docstring
+            Stmt::Expr(expr) if matches!(expr.value.as_ref(), Expr::StringLiteral(_)) => continue,
+            _ => {
+                other.push(stmt);
+                continue;
+            }
+        };
+        let ty = annotation
+            .and_then(FieldType::from_annotation)
+            .or_else(|| value.and_then(FieldType::from_value));
+        match (target, ty) {
+            (Expr::Name(name), Some(ty)) => fields.push(Field {
+                name: name.id.as_str(),
+                ty,
+                default: value,
+            }),
+            _ => other.push(stmt),
+        }
+    }
+    (fields, other)
+}
+
+/// The name of a method's first parameter, which refers to the contract.
+fn self_name(func: &StmtFunctionDef) -> Option<&str> {
+    let params = &func.parameters;
+    params
+        .posonlyargs
+        .iter()
+        .chain(&params.args)
+        .next()
+        .map(|p| p.parameter.name.as_str())
+}
+
+/// Collects assignments and deletions of `self.field`.
+struct FieldWrites<'a> {
+    receiver: &'a str,
+    found: Vec<(&'a str, usize)>,
+}
+
+impl<'a> Visitor<'a> for FieldWrites<'a> {
+    fn visit_expr(&mut self, expr: &'a Expr) {
+        if let Expr::Attribute(attribute) = expr {
+            let on_self = matches!(attribute.value.as_ref(), Expr::Name(name) if name.id.as_str() == self.receiver);
+            if on_self && attribute.ctx != ExprContext::Load {
+                self.found
+                    .push((attribute.attr.as_str(), attribute.range.start().to_usize()));
+            }
+        }
+        visitor::walk_expr(self, expr);
+    }
+}
+
+/// The fields `method` of the class assigns or deletes, and where; the
+/// state is saved after call methods only.
+pub fn field_writes(method: &StmtFunctionDef) -> Vec<(&str, usize)> {
+    let Some(receiver) = self_name(method) else {
+        return Vec::new();
+    };
+    let mut writes = FieldWrites {
+        receiver,
+        found: Vec::new(),
+    };
+    writes.visit_body(&method.body);
+    writes.found
+}
+
+/// The name of the method `call` calls on the contract, as `self.method()`
+/// inside `method`.
+pub fn self_call<'a>(method: &StmtFunctionDef, call: &'a ExprCall) -> Option<&'a str> {
+    let receiver = self_name(method)?;
+    match call.func.as_ref() {
+        Expr::Attribute(attribute) => match attribute.value.as_ref() {
+            Expr::Name(name) if name.id.as_str() == receiver => Some(attribute.attr.as_str()),
+            _ => None,
+        },
+        _ => None,
+    }
+}
+
+/// Report what in `class` can't be lowered. Fields must be `str`, `int`,
+/// `float` or `bool`, methods take `self` first and use it only as
+/// `self.field` or `self.method`, and method names mustn't clash with
+/// top-level functions.
+pub fn check_class(
+    module: &ModModule,
+    class: &StmtClassDef,
+    file: SourceFile,
+    diagnostics: &mut Vec<Diagnostic>,
+) {
+    let error =
+        |offset: usize, message: String| file.error("invalid-contract-class", offset, message);
+    let start = class.range.start().to_usize();
+    if !class.decorator_list.is_empty() || class.arguments.is_some() || class.type_params.is_some()
+    {
+        diagnostics.push(error(
+            start,
+            format!("class {CLASS_NAME} can't have decorators, base classes or type parameters"),
+        ));
+    }
+    if body_start(class, file.text).is_none() {
+        diagnostics.push(error(
+            start,
+            format!("the body of class {CLASS_NAME} must start on its own line"),
+        ));
+    }
+
+    let (fields, other) = fields(class);
+    for stmt in other {
+        diagnostics.push(error(
+            stmt.range().start().to_usize(),
+            format!(
+                "class {CLASS_NAME} may only contain methods and fields of type str, int, \
+                 float or bool (`name: int = 0`)"
+            ),
+        ));
+    }
+
+    let top_level: HashSet<&str> = module
+        .body
+        .iter()
+        .filter_map(|stmt| match stmt {
+            Stmt::FunctionDef(func) => Some(func.name.as_str()),
+            _ => None,
+        })
+        .collect();
+    let field_names: HashSet<&str> = fields.iter().map(|f| f.name).collect();
+    let method_names: HashSet<&str> = methods(class).map(|m| m.name.as_str()).collect();
+
+    let mut seen: HashMap<&str, usize> = HashMap::new();
+    for method in methods(class) {
+        let name = method.name.as_str();
+        let offset = method.range.start().to_usize();
+        let first = *seen.entry(name).or_insert(offset);
+        if first != offset {
+            diagnostics.push(
+                file.error(
+                    "duplicate-definition",
+                    offset,
+                    format!("`{CLASS_NAME}.{name}` is defined twice; the later definition would silently replace the earlier one"),
+                )
+                .with_note(format!("first defined at {}", file.position(first))),
+            );
+        }
+        if top_level.contains(name) {
+            diagnostics.push(error(
+                offset,
+                format!("`{CLASS_NAME}.{name}` clashes with the top-level function `{name}`"),
+            ));
+        }
+        if field_names.contains(name) {
+            diagnostics.push(error(
+                offset,
+                format!("`{name}` is both a field and a method of {CLASS_NAME}"),
+            ));
+        }
+        let Some(receiver) = self_name(method) else {
+            diagnostics.push(error(
+                offset,
+                format!("`{CLASS_NAME}.{name}` must take `self` as its first parameter"),
+            ));
+            continue;
+        };
+
+        let mut uses = SelfUses::new(receiver);
+        uses.visit_body(&method.body);
+        for (attr, offset, _) in uses.attributes {
+            if !field_names.contains(attr) && !method_names.contains(attr) {
+                diagnostics.push(error(
+                    offset,
+                    format!("{CLASS_NAME} has no field or method `{attr}`"),
+                ));
+            }
+        }
+        for offset in uses.bare {
+            diagnostics.push(error(
+                offset,
+                format!("`{receiver}` can only be used as `{receiver}.field` or `{receiver}.method(...)`"),
+            ));
+        }
+    }
+}
+
+/// Where a method body uses its `self` parameter.
+struct SelfUses<'a> {
+    receiver: &'a str,
+    /// `self.attr`: the attribute and where the expression starts and ends.
+    attributes: Vec<(&'a str, usize, usize)>,
+    /// Offsets of any other use of `self`.
+    bare: Vec<usize>,
+}
+
+impl<'a> SelfUses<'a> {
+    fn new(receiver: &'a str) -> Self {
+        SelfUses {
+            receiver,
+            attributes: Vec::new(),
+            bare: Vec::new(),
+        }
+    }
+}
+
+impl<'a> Visitor<'a> for SelfUses<'a> {
+    fn visit_expr(&mut self, expr: &'a Expr) {
+        match expr {
+            Expr::Attribute(attribute) => match attribute.value.as_ref() {
+                Expr::Name(name) if name.id.as_str() == self.receiver => {
+                    self.attributes.push((
+                        attribute.attr.as_str(),
+                        attribute.range.start().to_usize(),
+                        attribute.range.end().to_usize(),
+                    ));
+                }
+                _ => visitor::walk_expr(self, expr),
+            },
+            Expr::Name(name) if name.id.as_str() == self.receiver => {
+                self.bare.push(name.range.start().to_usize());
+            }
+            _ => visitor::walk_expr(self, expr),
+        }
+    }
+}
+
+/// Lower the `Contract` class in `source` to top-level functions, keeping
+/// every line where it was so errors point at the original lines. Source
+/// without the class is returned unchanged; one that fails
+/// [`check_class`] is lowered as far as possible.
+pub fn lower(source: &str) -> String {
+    let Ok(parsed) = parse_module(source) else {
+        return source.to_string();
+    };
+    let module = parsed.into_syntax();
+    let Some(class) = find_class(&module) else {
+        return source.to_string();
+    };
+    let (Some(first), Some(body_start)) = (class.body.first(), body_start(class, source)) else {
+        return source.to_string();
+    };
+
+    let (fields, _) = fields(class);
+    let field_names: HashSet<&str> = fields.iter().map(|f| f.name).collect();
+
+    // Edits that don't add or remove lines: (start, end, replacement)
+    let mut edits: Vec<(usize, usize, String)> = Vec::new();
+    let class_start = class.range.start().to_usize();
+    edits.push((
+        class_start,
+        body_start,
+        blank(&source[class_start..body_start]),
+    ));
+    for stmt in &class.body {
+        if !matches!(stmt, Stmt::FunctionDef(_)) {
+            let range = stmt.range();
+            edits.push((
+                range.start().to_usize(),
+                range.end().to_usize(),
+                blank(&source[range]),
+            ));
+        }
+    }
+    for method in methods(class) {
+        let Some(receiver) = self_name(method) else {
+            continue;
+        };
+        let params = &method.parameters;
+        let mut all = params
+            .posonlyargs
+            .iter()
+            .chain(&params.args)
+            .map(|p| p.range)
+            .chain(params.vararg.iter().map(|p| p.range))
+            .chain(params.kwonlyargs.iter().map(|p| p.range))
+            .chain(params.kwarg.iter().map(|p| p.range));
+        let Some(first_param) = all.next() else {
+            continue;
+        };
+        let start = first_param.start().to_usize();
+        // Up to the next parameter, or the closing parenthesis
+        let end = match all.next() {
+            Some(next) => next.start().to_usize(),
+            None => source[start..]
+                .find(')')
+                .map_or(first_param.end().to_usize(), |i| start + i),
+        };
+        edits.push((start, end, String::new()));
+
+        let mut uses = SelfUses::new(receiver);
+        uses.visit_body(&method.body);
+        for (attr, start, end) in uses.attributes {
+            let replacement = if field_names.contains(attr) {
+                format!("{STATE_VAR}[\"{attr}\"]")
+            } else {
+                attr.to_string()
+            };
+            edits.push((start, end, replacement));
+        }
+    }
+
+    edits.sort_by_key(|&(start, _, _)| start);
+    let mut out = source.to_string();
+    for (start, end, replacement) in edits.into_iter().rev() {
+        out.replace_range(start..end, &replacement);
+    }
+
+    // Dedent the class body to module level
+    let indent = first.range().start().to_usize() - body_start;
+    let first_line = source[..body_start].matches('\n').count();
+    let last_line = source[..class.range.end().to_usize()].matches('\n').count();
+    let mut lowered: Vec<String> = Vec::new();
+    for (i, line) in out.split('\n').enumerate() {
+        if (first_line..=last_line).contains(&i) {
+            let whitespace = line.len() - line.trim_start_matches([' ', '\t']).len();
+            lowered.push(line[whitespace.min(indent)..].to_string());
+        } else {
+            lowered.push(line.to_string());
+        }
+    }
+    let mut out = lowered.join("\n");
+    out.push_str("\n\n");
+    out.push_str(&state_functions(&fields, source));
+    out
+}
+
+/// The state layout of `class`: each field's name and type, in the order
+/// they're stored.
+pub fn layout(class: &StmtClassDef) -> Vec<(String, String)> {
+    fields(class)
+        .0
+        .iter()
+        .map(|field| (field.name.to_string(), field.ty.name().to_string()))
+        .collect()
+}
+
+/// The field values in a stored `STATE`, as strings, or `None` if it isn't
+/// in the format the lowered code saves.
+pub fn stored_values(data: &str) -> Option<Vec<String>> {
+    let mut values = Vec::new();
+    let mut rest = data;
+    while !rest.is_empty() {
+        let (len, tail) = rest.split_once(':')?;
+        let len: usize = len.parse().ok()?;
+        // Lengths count characters, as Python's `len` does
+        let end = tail.char_indices().nth(len).map_or(tail.len(), |(i, _)| i);
+        if tail[..end].chars().count() != len {
+            return None;
+        }
+        values.push(tail[..end].to_string());
+        rest = &tail[end..];
+    }
+    Some(values)
+}
+
+/// Offset of the start of the line the class body starts on, or `None` if
+/// that's the `class` line itself.
+fn body_start(class: &StmtClassDef, source: &str) -> Option<usize> {
+    let first = class.body.first()?.range().start().to_usize();
+    let line_start = source[..first].rfind('\n').map_or(0, |i| i + 1);
+    (line_start > class.range.start().to_usize()).then_some(line_start)
+}
+
+/// Spaces in place of everything in `text` but its newlines.
+fn blank(text: &str) -> String {
+    text.chars()
+        .map(|c| if c == '\n' { '\n' } else { ' ' })
+        .collect()
+}
+
+/// Python reading the stored field values, building the state dict from
+/// them, and saving it. The stored value is each field's `str()` prefixed
+/// with its length and `:`, in declaration order; fields missing from it
+/// (added since it was saved) take their default. The dispatcher loads the
+/// state, so a `@migrate` method can start from the defaults instead.
+fn state_functions(fields: &[Field], source: &str) -> String {
+    let mut load = format!(
+        "def {STORED_FN}():\n    \
+         data = storage_read(\"{STATE_KEY}\")\n    \
+         values = []\n    \
+         while data:\n        \
+         sep = data.find(\":\")\n        \
+         end = sep + 1 + int(data[:sep])\n        \
+         values.append(data[sep + 1 : end])\n        \
+         data = data[end:]\n    \
+         return values\n\n\n\
+         def {LOAD_FN}(values):\n    \
+         n = len(values)\n    \
+         return {{\n"
+    );
+    let mut save = String::new();
+    for (i, field) in fields.iter().enumerate() {
+        let default = field
+            .default
+            .map_or(field.ty.zero(), |value| &source[value.range()]);
+        load.push_str(&format!(
+            "        \"{}\": {} if n > {i} else {default},\n",
+            field.name,
+            field.ty.decode(&format!("values[{i}]"))
+        ));
+        save.push_str(&format!("str({STATE_VAR}[\"{}\"]), ", field.name));
+    }
+    load.push_str("    }\n");
+    format!(
+        "{load}\n\n\
+         {STATE_VAR} = None\n\n\n\
+         def {SAVE_FN}():\n    \
+         data = \"\"\n    \
+         for value in [{save}]:\n        \
+         data = data + str(len(value)) + \":\" + value\n    \
+         storage_write(\"{STATE_KEY}\", data)\n"
+    )
+}
@@ -0,0 +1,79 @@
+// Dead-code elimination — a contract that imports a utility module shared
+// with code outside the contract bundles all of it, and Monty compiles every
+// function in the program whether anything calls it or not. Before
+// compiling, the build follows the names the program's top-level code uses
+// (the generated dispatcher calls each exported method) through the
+// functions and classes they name, and blanks the top-level definitions it
+// never reaches, keeping every line where it was.
+
+use std::collections::{HashMap, HashSet};
+
+use ruff_python_ast::visitor::Visitor;
+use ruff_python_ast::Stmt;
+use ruff_python_parser::parse_module;
+use ruff_text_size::Ranged;
+
+use crate::NameCollector;
+
+/// The name a top-level `def` or `class` binds.
+fn definition_name(stmt: &Stmt) -> Option<&str> {
+    match stmt {
+        Stmt::FunctionDef(func) => Some(func.name.as_str()),
+        Stmt::ClassDef(class) => Some(class.name.as_str()),
+        _ => None,
+    }
+}
+
+/// `program` without the top-level functions and classes nothing reachable
+/// from its top-level code refers to. Dunder names, the compiler's helpers
+/// among them, are always kept. A program that doesn't parse is returned
+/// unchanged, for Monty to report.
+pub fn eliminate(program: &str) -> String {
+    let Ok(parsed) = parse_module(program) else {
+        return program.to_string();
+    };
+    let module = parsed.into_syntax();
+
+    let mut definitions: HashMap<&str, Vec<&Stmt>> = HashMap::new();
+    let mut roots = NameCollector::default();
+    for stmt in &module.body {
+        match definition_name(stmt) {
+            Some(name) => {
+                definitions.entry(name).or_default().push(stmt);
+                if name.starts_with("__") {
+                    roots.names.insert(name);
+                }
+            }
+            None => roots.visit_stmt(stmt),
+        }
+    }
+
+    let mut reached: HashSet<&str> = HashSet::new();
+    let mut pending: Vec<&str> = roots.names.into_iter().collect();
+    while let Some(name) = pending.pop() {
+        if !reached.insert(name) {
+            continue;
+        }
+        for stmt in definitions.get(name).into_iter().flatten() {
+            let mut collector = NameCollector::default();
+            collector.visit_stmt(stmt);
+            pending.extend(collector.names);
+        }
+    }
+
+    let dropped: Vec<(usize, usize)> = module
+        .body
+        .iter()
+        .filter(|stmt| definition_name(stmt).is_some_and(|name| !reached.contains(name)))
+        .map(|stmt| {
+            (
+                stmt.range().start().to_usize(),
+                stmt.range().end().to_usize(),
+            )
+        })
+        .collect();
+    if dropped.is_empty() {
+        return program.to_string();
+    }
+    crate::blank_ranges(program, &dropped)
+}
@@ -0,0 +1,131 @@
+// Deployment — push a built contract to an account using near-cli's
+// stored credentials, or to a throwaway dev account.
+
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use anyhow::{bail, Context, Result};
+use serde_json::json;
+
+use crate::code_hash;
+use crate::rpc::{network_rpc_url, outcome_result, RpcClient};
+use crate::sandbox::Sandbox;
+use crate::tx::{load_credentials, save_credentials, Action, Signer};
+
+/// Faucet that creates and funds new testnet accounts (the one near-cli-rs uses).
+const TESTNET_FAUCET_URL: &str = "https://helper.nearprotocol.com/account";
+
+/// Balance given to dev accounts created in a sandbox: 10 NEAR.
+const SANDBOX_DEV_BALANCE: u128 = 10 * 10u128.pow(24);
+
+/// Deploy `wasm` to `account_id`, signing with its key from
+/// `~/.near-credentials/<network>/`.
+pub fn deploy(wasm: &[u8], account_id: &str, network: &str) -> Result<()> {
+    let rpc = RpcClient::new(&network_rpc_url(network)?);
+    let signer = load_credentials(network, account_id)?;
+
+    eprintln!("  Deploying to {account_id} on {network}...");
+    let outcome = signer.send(
+        &rpc,
+        account_id,
+        &[Action::DeployContract {
+            code: wasm.to_vec(),
+        }],
+    )?;
+    outcome_result(&outcome).context("deploy transaction failed")?;
+
+    let tx_hash = outcome["transaction"]["hash"].as_str().unwrap_or("unknown");
+    eprintln!("  \u{2713} Deployed {} to {account_id}", code_hash(wasm));
+    eprintln!("    Transaction: {tx_hash}");
+    Ok(())
+}
+
+/// A fresh `dev-<timestamp>-<suffix>` account name under `parent`.
+fn dev_account_id(parent: &str, signer: &Signer) -> String {
+    let secs = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |d| d.as_secs());
+    // The new key is random, so its first bytes make a collision-free suffix
+    let suffix: String = signer.public_key_bytes()[..3]
+        .iter()
+        .map(|b| format!("{b:02x}"))
+        .collect();
+    format!("dev-{secs}-{suffix}.{parent}")
+}
+
+/// Create a testnet account through the faucet, save its key to
+/// `~/.near-credentials/testnet/`, and deploy `wasm` to it.
+pub fn dev_deploy_testnet(wasm: &[u8]) -> Result<()> {
+    let mut signer = Signer::generate("")?;
+    signer.account_id = dev_account_id("testnet", &signer);
+    let account_id = signer.account_id.clone();
+
+    eprintln!("  Creating {account_id}...");
+    let response = ureq::post(TESTNET_FAUCET_URL).send_json(json!({
+        "newAccountId": account_id,
+        "newAccountPublicKey": signer.public_key(),
+    }));
+    match response {
+        Ok(_) => {}
+        Err(ureq::Error::Status(code, response)) => {
+            let body = response.into_string().unwrap_or_default();
+            bail!("faucet refused to create {account_id} (HTTP {code}): {body}");
+        }
+        Err(e) => return Err(e).context("failed to reach the testnet faucet"),
+    }
+    let path = save_credentials("testnet", &signer)?;
+    eprintln!("    Key saved to {}", path.display());
+
+    deploy(wasm, &account_id, "testnet")?;
+    eprintln!("    Explorer: https://testnet.nearblocks.io/address/{account_id}");
+    println!("{account_id}");
+    Ok(())
+}
+
+/// Start a local sandbox, create a funded dev account in it, deploy `wasm`,
+/// and keep the node running until interrupted.
+pub fn dev_deploy_sandbox(wasm: &[u8]) -> Result<()> {
+    eprintln!("  Starting sandbox...");
+    let sandbox = Sandbox::start(&std::env::temp_dir())?;
+    let signer = create_sandbox_dev_account(&sandbox, wasm)?;
+
+    eprintln!();
+    // Ctrl-C reaches near-sandbox too, since it shares our process group
+    eprintln!("  Sandbox running. Press Ctrl-C to stop.");
+    println!("{}", signer.account_id);
+    loop {
+        std::thread::park();
+    }
+}
+
+/// Create a funded dev account in `sandbox` with `wasm` deployed to it, print
+/// its connection details, and return its signer.
+pub fn create_sandbox_dev_account(sandbox: &Sandbox, wasm: &[u8]) -> Result<Signer> {
+    let mut signer = Signer::generate("")?;
+    signer.account_id = dev_account_id(&sandbox.root.account_id, &signer);
+    let account_id = signer.account_id.clone();
+
+    eprintln!("  Creating {account_id} and deploying...");
+    let outcome = sandbox.root.send(
+        &sandbox.rpc,
+        &account_id,
+        &[
+            Action::CreateAccount,
+            Action::Transfer {
+                deposit: SANDBOX_DEV_BALANCE,
+            },
+            Action::AddFullAccessKey {
+                public_key: signer.public_key_bytes(),
+            },
+            Action::DeployContract {
+                code: wasm.to_vec(),
+            },
+        ],
+    )?;
+    outcome_result(&outcome).context("dev account transaction failed")?;
+
+    eprintln!("  \u{2713} Deployed {} to {account_id}", code_hash(wasm));
+    eprintln!("    RPC:        {}", sandbox.rpc.url());
+    eprintln!("    Public key: {}", signer.public_key());
+    eprintln!("    Secret key: {}", signer.secret_key());
+    Ok(signer)
+}
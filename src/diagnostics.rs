@@ -0,0 +1,520 @@
+// Diagnostics — findings from the static checks on a contract, rendered for
+// humans, as JSON lines for editors and CI, or as SARIF for code-scanning
+// platforms.
+
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+
+use anyhow::{bail, Result};
+use clap::ValueEnum;
+use serde::Deserialize;
+use serde_json::{json, Value};
+
+#[derive(Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum DiagnosticsFormat {
+    /// rustc-style messages on stderr
+    Human,
+    /// SARIF 2.1.0 JSON on stdout
+    Sarif,
+    /// One JSON object per line on stdout
+    Json,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum Severity {
+    Error,
+    Warning,
+}
+
+impl Severity {
+    fn as_str(self) -> &'static str {
+        match self {
+            Severity::Error => "error",
+            Severity::Warning => "warning",
+        }
+    }
+}
+
+/// The rules reported as warnings, whose level `-A`, `-W`, `-D` and
+/// `[lints]` in monty-near.toml set. Other rules are always errors.
+const LINTS: &[&str] = &[
+    "unexported-function",
+    "unused-function",
+    "missing-type-hint",
+    "top-level-side-effect",
+    "hand-formatted-event",
+];
+
+/// The level a lint is reported at.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Level {
+    Allow,
+    Warn,
+    Deny,
+}
+
+/// The level of each lint that isn't reported as a warning, and whether
+/// every warning is an error.
+#[derive(Clone, Debug, Default)]
+pub struct Lints {
+    levels: BTreeMap<String, Level>,
+    pub deny_warnings: bool,
+}
+
+impl Lints {
+    /// Report `lint` at `level`, replacing any level set before.
+    pub fn set(&mut self, lint: &str, level: Level) -> Result<()> {
+        if !LINTS.contains(&lint) {
+            bail!("unknown lint `{lint}` (lints: {})", LINTS.join(", "));
+        }
+        self.levels.insert(lint.to_string(), level);
+        Ok(())
+    }
+
+    /// `diagnostic` as reported at its lint's level, or `None` if allowed.
+    fn apply(&self, diagnostic: &Diagnostic) -> Option<Diagnostic> {
+        let severity = match (diagnostic.severity, self.levels.get(diagnostic.rule)) {
+            (Severity::Error, _) => Severity::Error,
+            (_, Some(Level::Allow)) => return None,
+            (_, Some(Level::Deny)) => Severity::Error,
+            _ if self.deny_warnings => Severity::Error,
+            _ => Severity::Warning,
+        };
+        Some(Diagnostic {
+            severity,
+            ..diagnostic.clone()
+        })
+    }
+}
+
+/// Every rule a diagnostic can be reported under, with a short description.
+const RULES: &[(&str, &str)] = &[
+    ("parse-error", "The contract is not valid Python"),
+    ("compile-error", "Monty could not compile the contract"),
+    (
+        "no-exported-methods",
+        "The contract has no exported methods",
+    ),
+    (
+        "invalid-decorator",
+        "A decorator is unknown, malformed, or used on a non-exported function",
+    ),
+    (
+        "invalid-contract-name",
+        "`__contract_name__` is not a string literal usable as a file and crate name",
+    ),
+    (
+        "invalid-contract-class",
+        "`class Contract` uses something the compiler can't lower to functions",
+    ),
+    (
+        "invalid-all",
+        "`__all__` is not a list of string literals naming contract functions",
+    ),
+    (
+        "unexported-function",
+        "A public function isn't exported while the contract chooses its exports explicitly",
+    ),
+    (
+        "unused-function",
+        "A top-level function is neither exported nor called",
+    ),
+    (
+        "missing-type-hint",
+        "An exported method's parameter has no type hint, so the ABI accepts any JSON value for it",
+    ),
+    (
+        "import-error",
+        "An imported module of the contract could not be read",
+    ),
+    (
+        "import-clash",
+        "A function is defined in more than one file of a multi-file contract",
+    ),
+    (
+        "duplicate-definition",
+        "A file defines or imports a top-level function's name twice, or exports two functions as one method, so one would silently replace the other",
+    ),
+    (
+        "invalid-parameter",
+        "An exported method takes `*args` or `**kwargs`, which JSON arguments can't fill",
+    ),
+    (
+        "invalid-borsh-type",
+        "A `@borsh_io` method has a parameter or result without a Borsh layout",
+    ),
+    (
+        "invalid-collection",
+        "A `LookupMap`, `Vector` or `UnorderedSet` is declared or used in a way the compiler can't lower",
+    ),
+    (
+        "invalid-promise",
+        "A `Promise` is created with the wrong arguments or given a method it doesn't have",
+    ),
+    (
+        "invalid-export-name",
+        "An exported method's name is reserved or not a valid NEAR method name",
+    ),
+    (
+        "invalid-await",
+        "`cross_call` isn't awaited as a statement of its own in an exported async method",
+    ),
+    (
+        "invalid-yield",
+        "`yield_create` resumes into a private helper rather than a contract method",
+    ),
+    (
+        "invalid-event",
+        "An `emit_event` call has a malformed standard, version, or event name",
+    ),
+    (
+        "hand-formatted-event",
+        "An `EVENT_JSON:` log is built by hand instead of with `emit_event`",
+    ),
+    (
+        "host-function-not-allowed",
+        "The contract calls a NEAR host function the allow-list in `--allow-host-function` or monty-near.toml doesn't include",
+    ),
+    (
+        "view-changes-state",
+        "A `@view` method writes storage or creates a promise, directly or through the functions it calls",
+    ),
+    (
+        "top-level-side-effect",
+        "Top-level code touches storage, promises, or logs, and would repeat on every method call",
+    ),
+    (
+        "unsupported-syntax",
+        "The code uses a Python construct Monty can't run, such as a class or `try`",
+    ),
+    (
+        "unsupported-import",
+        "The code imports a module that is neither part of the contract, bundled, nor provided by Monty",
+    ),
+    (
+        "unavailable-host-function",
+        "The contract calls a NEAR host function that is not exposed to Python",
+    ),
+    (
+        "invalid-option",
+        "Build options are invalid or incompatible",
+    ),
+    (
+        "missing-tool",
+        "An external tool the build needs is not installed",
+    ),
+    (
+        "build-error",
+        "The build failed after the checks passed, e.g. in cargo or wasm-opt",
+    ),
+];
+
+#[derive(Clone)]
+pub struct Diagnostic {
+    pub severity: Severity,
+    /// Stable rule identifier from `RULES`.
+    pub rule: &'static str,
+    pub message: String,
+    /// Where in the contract source; `None` for findings about the build
+    /// options or environment.
+    pub location: Option<Location>,
+    /// The code at the location, from [`code_frame`].
+    pub frame: Option<String>,
+    pub note: Option<String>,
+}
+
+#[derive(Clone)]
+pub struct Location {
+    pub file: PathBuf,
+    /// 1-based line and column.
+    pub line: usize,
+    pub column: usize,
+}
+
+impl Diagnostic {
+    /// An error not tied to a source location.
+    pub fn error(rule: &'static str, message: impl Into<String>) -> Self {
+        Self {
+            severity: Severity::Error,
+            rule,
+            message: message.into(),
+            location: None,
+            frame: None,
+            note: None,
+        }
+    }
+
+    /// A warning not tied to a source location.
+    pub fn warning(rule: &'static str, message: impl Into<String>) -> Self {
+        Self {
+            severity: Severity::Warning,
+            ..Self::error(rule, message)
+        }
+    }
+
+    pub fn with_note(mut self, note: impl Into<String>) -> Self {
+        self.note = Some(note.into());
+        self
+    }
+
+    pub fn with_frame(mut self, frame: String) -> Self {
+        self.frame = Some(frame);
+        self
+    }
+
+    /// The diagnostic as one line of JSON: its severity, rule (`kind`),
+    /// message, location, and note (`suggestion`), with `null` for what it
+    /// doesn't have.
+    fn json(&self) -> String {
+        let location = self.location.as_ref();
+        json!({
+            "severity": self.severity.as_str(),
+            "kind": self.rule,
+            "message": self.message,
+            "file": location.map(|loc| loc.file.display().to_string()),
+            "line": location.map(|loc| loc.line),
+            "column": location.map(|loc| loc.column),
+            "suggestion": self.note,
+        })
+        .to_string()
+    }
+
+    fn render(&self) -> String {
+        let mut out = format!(
+            "{}[{}]: {}",
+            self.severity.as_str(),
+            self.rule,
+            self.message
+        );
+        if let Some(loc) = &self.location {
+            out.push_str(&format!(
+                "\n  --> {}:{}:{}",
+                loc.file.display(),
+                loc.line,
+                loc.column
+            ));
+        }
+        if let Some(frame) = &self.frame {
+            out.push('\n');
+            out.push_str(frame);
+        }
+        if let Some(note) = &self.note {
+            out.push_str(&format!("\n   = note: {note}"));
+        }
+        out
+    }
+}
+
+/// A contract source file that diagnostics point into.
+#[derive(Clone, Copy)]
+pub struct SourceFile<'a> {
+    pub path: &'a Path,
+    pub text: &'a str,
+}
+
+impl SourceFile<'_> {
+    /// An error located at byte `offset` of this file.
+    pub fn error(
+        &self,
+        rule: &'static str,
+        offset: usize,
+        message: impl Into<String>,
+    ) -> Diagnostic {
+        let (line, column) = line_col(self.text, offset);
+        Diagnostic {
+            location: Some(Location {
+                file: self.path.to_path_buf(),
+                line,
+                column,
+            }),
+            ..Diagnostic::error(rule, message)
+        }
+    }
+
+    /// The code around byte `offset` of this file, from [`code_frame`].
+    pub fn frame(&self, offset: usize) -> String {
+        let (line, column) = line_col(self.text, offset);
+        code_frame(self.text, line, Some(column))
+    }
+
+    /// `path:line:column` of byte `offset` of this file, for notes pointing
+    /// at a second location.
+    pub fn position(&self, offset: usize) -> String {
+        let (line, column) = line_col(self.text, offset);
+        format!("{}:{line}:{column}", self.path.display())
+    }
+
+    /// A warning located at byte `offset` of this file.
+    pub fn warning(
+        &self,
+        rule: &'static str,
+        offset: usize,
+        message: impl Into<String>,
+    ) -> Diagnostic {
+        Diagnostic {
+            severity: Severity::Warning,
+            ..self.error(rule, offset, message)
+        }
+    }
+}
+
+/// 1-based line and column of a byte offset in `source`.
+fn line_col(source: &str, offset: usize) -> (usize, usize) {
+    let before = &source[..offset.min(source.len())];
+    let line = before.matches('\n').count() + 1;
+    let col = before.len() - before.rfind('\n').map_or(0, |i| i + 1) + 1;
+    (line, col)
+}
+
+/// Line `line` (1-based) of `text` and the two before it, numbered, with a
+/// caret under byte `column` (1-based), or under the whole line without
+/// one. Empty if `text` has no such line.
+pub fn code_frame(text: &str, line: usize, column: Option<usize>) -> String {
+    let lines: Vec<&str> = text.lines().collect();
+    let Some(target) = line.checked_sub(1).and_then(|i| lines.get(i)) else {
+        return String::new();
+    };
+    let width = line.to_string().len();
+    let mut out = format!("{:width$} |\n", "");
+    for n in line.saturating_sub(2).max(1)..=line {
+        out.push_str(&format!("{n:>width$} | {}\n", lines[n - 1]));
+    }
+    let chars = |bytes: &str| bytes.chars().count();
+    let (start, len) = match column {
+        Some(column) => {
+            let before = column.saturating_sub(1);
+            let start = target.get(..before).map_or(before, chars);
+            (start, 1)
+        }
+        None => {
+            let indent = target.len() - target.trim_start().len();
+            (indent, chars(target.trim()).max(1))
+        }
+    };
+    out.push_str(&format!(
+        "{:width$} | {}{}",
+        "",
+        " ".repeat(start),
+        "^".repeat(len)
+    ));
+    out
+}
+
+/// Report diagnostics in `format`, at the levels `lints` sets, and fail if
+/// any of them is an error.
+///
+/// SARIF output is always written (even when empty) so CI can upload it
+/// unconditionally.
+pub fn emit(diagnostics: &[Diagnostic], format: DiagnosticsFormat, lints: &Lints) -> Result<()> {
+    let diagnostics: Vec<Diagnostic> = diagnostics.iter().filter_map(|d| lints.apply(d)).collect();
+    let diagnostics = &diagnostics[..];
+    match format {
+        DiagnosticsFormat::Human => {
+            for diagnostic in diagnostics {
+                eprintln!("{}\n", diagnostic.render());
+            }
+        }
+        DiagnosticsFormat::Sarif => {
+            println!("{}", serde_json::to_string_pretty(&sarif(diagnostics))?);
+        }
+        DiagnosticsFormat::Json => {
+            for diagnostic in diagnostics {
+                println!("{}", diagnostic.json());
+            }
+        }
+    }
+
+    let errors = diagnostics
+        .iter()
+        .filter(|d| d.severity == Severity::Error)
+        .count();
+    if errors > 0 {
+        return Err(Reported(errors).into());
+    }
+    Ok(())
+}
+
+/// The error [`emit`] fails with, once it has reported the errors.
+#[derive(Debug)]
+struct Reported(usize);
+
+impl std::fmt::Display for Reported {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let errors = self.0;
+        write!(
+            f,
+            "aborting due to {errors} previous error{}",
+            if errors == 1 { "" } else { "s" }
+        )
+    }
+}
+
+impl std::error::Error for Reported {}
+
+/// Report a command's `error` as a `build-error` JSON line in the JSON
+/// format, unless it is [`emit`]'s, about errors already reported.
+pub fn emit_failure(error: &anyhow::Error, format: DiagnosticsFormat) {
+    if format == DiagnosticsFormat::Json && error.downcast_ref::<Reported>().is_none() {
+        println!(
+            "{}",
+            Diagnostic::error("build-error", format!("{error:#}")).json()
+        );
+    }
+}
+
+fn sarif(diagnostics: &[Diagnostic]) -> Value {
+    let rules: Vec<Value> = RULES
+        .iter()
+        .map(|(id, description)| {
+            json!({
+                "id": id,
+                "shortDescription": { "text": description },
+            })
+        })
+        .collect();
+
+    let results: Vec<Value> = diagnostics
+        .iter()
+        .map(|d| {
+            let mut text = d.message.clone();
+            if let Some(note) = &d.note {
+                text.push_str(&format!(" ({note})"));
+            }
+            let locations: Vec<Value> = d
+                .location
+                .iter()
+                .map(|loc| {
+                    json!({
+                        "physicalLocation": {
+                            "artifactLocation": { "uri": loc.file.display().to_string() },
+                            "region": { "startLine": loc.line, "startColumn": loc.column },
+                        },
+                    })
+                })
+                .collect();
+            json!({
+                "ruleId": d.rule,
+                "level": d.severity.as_str(),
+                "message": { "text": text },
+                "locations": locations,
+            })
+        })
+        .collect();
+
+    json!({
+        "$schema": "https://json.schemastore.org/sarif-2.1.0.json",
+        "version": "2.1.0",
+        "runs": [{
+            "tool": {
+                "driver": {
+                    "name": "monty-near",
+                    "version": env!("CARGO_PKG_VERSION"),
+                    "informationUri": "https://github.com/r-near/monty-near-cli",
+                    "rules": rules,
+                },
+            },
+            "results": results,
+        }],
+    })
+}
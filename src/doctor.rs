@@ -0,0 +1,319 @@
+// Toolchain diagnosis — checks for everything the inner cargo build and
+// post-processing invoke, with the command that installs whatever is missing.
+
+use std::io::{BufRead, IsTerminal};
+use std::path::PathBuf;
+use std::process::Command;
+
+use anyhow::{bail, Context, Result};
+
+use crate::diagnostics::Diagnostic;
+use crate::TEMPLATE_RUST_TOOLCHAIN;
+
+/// Which optional parts of the pipeline the checks should cover.
+pub struct Requirements {
+    pub compat: bool,
+    pub wasm_opt: bool,
+    /// The wasm-opt binary the build would run, or `None` for the one
+    /// built into the CLI.
+    pub wasm_opt_program: Option<PathBuf>,
+    pub component: bool,
+}
+
+/// Outcome of one check.
+enum Finding {
+    /// Present, with a version or detail to show.
+    Found(String),
+    Missing {
+        message: String,
+        fix: String,
+        /// Whether a build fails without it (otherwise it only degrades).
+        required: bool,
+    },
+}
+
+fn missing(message: impl Into<String>, fix: impl Into<String>, required: bool) -> Finding {
+    Finding::Missing {
+        message: message.into(),
+        fix: fix.into(),
+        required,
+    }
+}
+
+/// `doctor`: print every check and fail if a required piece is missing.
+pub fn doctor(req: &Requirements) -> Result<()> {
+    let mut failed = 0;
+    for (name, finding) in run_checks(req) {
+        match finding {
+            Finding::Found(detail) => eprintln!("  \u{2713} {name}: {detail}"),
+            Finding::Missing {
+                message,
+                fix,
+                required,
+            } => {
+                let mark = if required { "\u{2717}" } else { "-" };
+                eprintln!("  {mark} {name}: {message}");
+                eprintln!("      Install with: {fix}");
+                if required {
+                    failed += 1;
+                }
+            }
+        }
+    }
+    if failed > 0 {
+        bail!("{failed} required tool(s) missing");
+    }
+    eprintln!();
+    eprintln!("  Toolchain ready");
+    Ok(())
+}
+
+/// Missing tools as diagnostics: required ones as errors, others as warnings.
+pub fn tool_diagnostics(req: &Requirements) -> Vec<Diagnostic> {
+    run_checks(req)
+        .into_iter()
+        .filter_map(|(name, finding)| match finding {
+            Finding::Found(_) => None,
+            Finding::Missing {
+                message,
+                fix,
+                required,
+            } => {
+                let message = format!("{name}: {message}");
+                let diagnostic = if required {
+                    Diagnostic::error("missing-tool", message)
+                } else {
+                    Diagnostic::warning("missing-tool", message)
+                };
+                Some(diagnostic.with_note(format!("Install with: {fix}")))
+            }
+        })
+        .collect()
+}
+
+/// Before a build: find the Rust toolchain pieces it needs that are missing
+/// and install them with rustup, after asking (or right away with `yes`).
+/// Without a terminal to ask on, fail with the commands to run instead.
+pub fn provision(compat: bool, yes: bool) -> Result<()> {
+    let req = Requirements {
+        compat,
+        wasm_opt: false,
+        wasm_opt_program: None,
+        component: false,
+    };
+    let missing: Vec<(&str, String, String)> = run_checks(&req)
+        .into_iter()
+        .filter_map(|(name, finding)| match finding {
+            Finding::Missing {
+                message,
+                fix,
+                required: true,
+            } => Some((name, message, fix)),
+            _ => None,
+        })
+        .collect();
+    if missing.is_empty() {
+        return Ok(());
+    }
+
+    eprintln!("  Missing Rust toolchain pieces:");
+    for (name, message, _) in &missing {
+        eprintln!("    {name}: {message}");
+    }
+    // Only rustup commands can be run for the user; without rustup (or
+    // cargo) there's nothing to install with
+    if let Some((_, _, fix)) = missing
+        .iter()
+        .find(|(_, _, fix)| !fix.starts_with("rustup "))
+    {
+        bail!("install Rust first: {fix}");
+    }
+    eprintln!("  Install with:");
+    for (_, _, fix) in &missing {
+        eprintln!("    {fix}");
+    }
+
+    if !yes {
+        if !std::io::stdin().is_terminal() {
+            bail!("missing Rust toolchain pieces: run the commands above, or pass --yes to have the build run them");
+        }
+        eprint!("  Run these now? [y/N] ");
+        let mut answer = String::new();
+        std::io::stdin().lock().read_line(&mut answer)?;
+        if !matches!(answer.trim().to_ascii_lowercase().as_str(), "y" | "yes") {
+            bail!("missing Rust toolchain pieces");
+        }
+    }
+    for (_, _, fix) in &missing {
+        eprintln!("  Running {fix}...");
+        let status = Command::new("rustup")
+            .args(fix.split_whitespace().skip(1))
+            .status()
+            .context("failed to run rustup")?;
+        if !status.success() {
+            bail!("`{fix}` failed");
+        }
+    }
+    Ok(())
+}
+
+fn run_checks(req: &Requirements) -> Vec<(&'static str, Finding)> {
+    let mut checks = vec![(
+        "cargo",
+        match version("cargo", &["--version"]) {
+            Some(v) => Finding::Found(v),
+            None => missing("not found", "curl https://sh.rustup.rs -sSf | sh", true),
+        },
+    )];
+
+    let Some(rustup) = version("rustup", &["--version"]) else {
+        // Without rustup the pinned toolchain can't be installed or inspected
+        checks.push((
+            "rustup",
+            missing(
+                "not found (needed to install the pinned toolchain)",
+                "curl https://sh.rustup.rs -sSf | sh",
+                true,
+            ),
+        ));
+        push_tool_checks(&mut checks, req);
+        return checks;
+    };
+    checks.push(("rustup", Finding::Found(rustup)));
+
+    let channel = pinned_channel();
+    let toolchains = output("rustup", &["toolchain", "list"]).unwrap_or_default();
+    let has_toolchain = |name: &str| {
+        toolchains
+            .lines()
+            .any(|l| l.starts_with(&format!("{name}-")))
+    };
+
+    if has_toolchain(channel) {
+        checks.push(("rust toolchain", Finding::Found(channel.to_string())));
+        let targets = output(
+            "rustup",
+            &["target", "list", "--installed", "--toolchain", channel],
+        )
+        .unwrap_or_default();
+        checks.push((
+            "wasm32 target",
+            if targets
+                .lines()
+                .any(|l| l.trim() == "wasm32-unknown-unknown")
+            {
+                Finding::Found(format!("installed for {channel}"))
+            } else {
+                missing(
+                    format!("wasm32-unknown-unknown not installed for {channel}"),
+                    format!("rustup target add wasm32-unknown-unknown --toolchain {channel}"),
+                    true,
+                )
+            },
+        ));
+    } else {
+        checks.push((
+            "rust toolchain",
+            missing(
+                format!("{channel} not installed"),
+                format!("rustup toolchain install {channel} --target wasm32-unknown-unknown"),
+                true,
+            ),
+        ));
+    }
+
+    if req.compat {
+        if has_toolchain("nightly") {
+            checks.push(("nightly toolchain", Finding::Found("installed".into())));
+            let components = output(
+                "rustup",
+                &["component", "list", "--installed", "--toolchain", "nightly"],
+            )
+            .unwrap_or_default();
+            checks.push((
+                "rust-src",
+                if components.lines().any(|l| l.starts_with("rust-src")) {
+                    Finding::Found("installed for nightly".into())
+                } else {
+                    missing(
+                        "not installed for nightly (needed by -Zbuild-std)",
+                        "rustup component add rust-src --toolchain nightly",
+                        true,
+                    )
+                },
+            ));
+        } else {
+            checks.push((
+                "nightly toolchain",
+                missing(
+                    "not installed (needed by --compat)",
+                    "rustup toolchain install nightly --component rust-src",
+                    true,
+                ),
+            ));
+        }
+    }
+
+    push_tool_checks(&mut checks, req);
+    checks
+}
+
+/// Post-processing tools, which are optional unless the build needs them.
+fn push_tool_checks(checks: &mut Vec<(&'static str, Finding)>, req: &Requirements) {
+    if req.wasm_opt {
+        checks.push((
+            "wasm-opt",
+            match &req.wasm_opt_program {
+                None => Finding::Found(format!(
+                    "built in (wasm-opt crate {})",
+                    crate::WASM_OPT_CRATE_VERSION
+                )),
+                Some(program) => match version(&program.to_string_lossy(), &["--version"]) {
+                    Some(v) => Finding::Found(v),
+                    None => missing(
+                        format!("{} not found", program.display()),
+                        "cargo install wasm-opt, or unset it to use the built-in one",
+                        true,
+                    ),
+                },
+            },
+        ));
+    }
+    checks.push((
+        "wasm-tools",
+        match version("wasm-tools", &["--version"]) {
+            Some(v) => Finding::Found(v),
+            None if req.component => missing(
+                "not found (required for --format component)",
+                "cargo install wasm-tools",
+                true,
+            ),
+            None => missing(
+                "not found (needed for --format component and `run`)",
+                "cargo install wasm-tools",
+                false,
+            ),
+        },
+    ));
+}
+
+/// Toolchain channel pinned by the template's rust-toolchain.toml.
+pub fn pinned_channel() -> &'static str {
+    TEMPLATE_RUST_TOOLCHAIN
+        .lines()
+        .find_map(|l| l.trim().strip_prefix("channel = "))
+        .map_or("stable", |c| c.trim_matches('"'))
+}
+
+/// First line of a tool's version output, or `None` if it can't run.
+fn version(program: &str, args: &[&str]) -> Option<String> {
+    output(program, args).map(|o| o.lines().next().unwrap_or("").trim().to_string())
+}
+
+fn output(program: &str, args: &[&str]) -> Option<String> {
+    let output = Command::new(program).args(args).output().ok()?;
+    output
+        .status
+        .success()
+        .then(|| String::from_utf8_lossy(&output.stdout).into_owned())
+}
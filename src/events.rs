@@ -0,0 +1,200 @@
+// NEP-297 events — `emit_event(standard, version, event, data)` writes the
+// `EVENT_JSON:` log line indexers parse, with the fields in the standard's
+// order and no stray whitespace. The helper is added to the program when the
+// contract calls it, and the build checks the standard, version, and event
+// name wherever they're literals.
+
+use ruff_python_ast::visitor::{self, Visitor};
+use ruff_python_ast::{Expr, ExprCall, ModModule, Operator, Stmt};
+use ruff_text_size::Ranged;
+
+use crate::diagnostics::{Diagnostic, SourceFile};
+
+/// The helper's name; like the host functions, it needs no import.
+const HELPER: &str = "emit_event";
+
+/// What an event log line starts with.
+const PREFIX: &str = "EVENT_JSON:";
+
+/// The helper's parameters, in order, and whether each is required.
+const PARAMS: [(&str, bool); 4] = [
+    ("standard", true),
+    ("version", true),
+    ("event", true),
+    ("data", false),
+];
+
+/// Collects calls to `emit_event` and to `log`.
+#[derive(Default)]
+struct EventCalls<'a> {
+    emits: Vec<&'a ExprCall>,
+    logs: Vec<&'a ExprCall>,
+}
+
+impl<'a> Visitor<'a> for EventCalls<'a> {
+    fn visit_expr(&mut self, expr: &'a Expr) {
+        if let Expr::Call(call) = expr {
+            if let Expr::Name(name) = call.func.as_ref() {
+                match name.id.as_str() {
+                    HELPER => self.emits.push(call),
+                    "log" => self.logs.push(call),
+                    _ => {}
+                }
+            }
+        }
+        visitor::walk_expr(self, expr);
+    }
+}
+
+/// Check `module`'s calls to `emit_event`, and point hand-formatted event
+/// logs at it. Returns whether the module calls the helper (rather than a
+/// function of its own by that name), so the build adds it.
+pub fn check(module: &ModModule, file: SourceFile, diagnostics: &mut Vec<Diagnostic>) -> bool {
+    let defined = module.body.iter().any(|stmt| match stmt {
+        Stmt::FunctionDef(func) => func.name.as_str() == HELPER,
+        _ => false,
+    });
+    if defined {
+        return false;
+    }
+
+    let mut calls = EventCalls::default();
+    calls.visit_body(&module.body);
+
+    for call in &calls.emits {
+        check_emit(call, file, diagnostics);
+    }
+    for call in &calls.logs {
+        if call.arguments.args.first().is_some_and(starts_event) {
+            diagnostics.push(
+                file.warning(
+                    "hand-formatted-event",
+                    call.range.start().to_usize(),
+                    "event log formatted by hand",
+                )
+                .with_note(format!(
+                    "use {HELPER}(standard, version, event, data), which writes the \
+                     `{PREFIX}` line exactly as NEP-297 indexers expect"
+                )),
+            );
+        }
+    }
+    !calls.emits.is_empty()
+}
+
+/// Check the arguments of one `emit_event` call.
+fn check_emit(call: &ExprCall, file: SourceFile, diagnostics: &mut Vec<Diagnostic>) {
+    let offset = call.range.start().to_usize();
+    let mut error = |offset: usize, message: String| {
+        diagnostics.push(file.error("invalid-event", offset, message));
+    };
+
+    let args = &call.arguments;
+    if args.args.len() > PARAMS.len() {
+        error(
+            offset,
+            format!("{HELPER}() takes at most {} arguments", PARAMS.len()),
+        );
+        return;
+    }
+    let mut values: [Option<&Expr>; 4] = [None; 4];
+    for (slot, arg) in values.iter_mut().zip(&args.args) {
+        *slot = Some(arg);
+    }
+    for keyword in &args.keywords {
+        let Some(name) = &keyword.arg else {
+            // `**kwargs`: the fields can't be checked
+            return;
+        };
+        match PARAMS.iter().position(|(param, _)| *param == name.as_str()) {
+            Some(i) if values[i].is_none() => values[i] = Some(&keyword.value),
+            Some(_) => error(
+                keyword.range().start().to_usize(),
+                format!("{HELPER}() got `{name}` twice"),
+            ),
+            None => error(
+                keyword.range().start().to_usize(),
+                format!("{HELPER}() has no parameter `{name}`"),
+            ),
+        }
+    }
+    if args.args.iter().any(|arg| matches!(arg, Expr::Starred(_))) {
+        return;
+    }
+    for ((param, required), value) in PARAMS.iter().zip(values) {
+        if *required && value.is_none() {
+            error(offset, format!("{HELPER}() is missing its `{param}`"));
+        }
+    }
+
+    if let Some((standard, offset)) = literal(values[0]) {
+        if !is_standard(standard) {
+            error(
+                offset,
+                format!(
+                    "event standard `{standard}` should be a lowercase name like `nep171`: \
+                     letters, digits, `-` and `_`"
+                ),
+            );
+        }
+    }
+    if let Some((version, offset)) = literal(values[1]) {
+        if !is_version(version) {
+            error(
+                offset,
+                format!("event version `{version}` is not a SemVer version like `1.0.0`"),
+            );
+        }
+    }
+    if let Some((event, offset)) = literal(values[2]) {
+        if event.is_empty() || event.chars().any(char::is_whitespace) {
+            error(
+                offset,
+                format!("event name `{event}` should be a name like `nft_mint`"),
+            );
+        }
+    }
+}
+
+/// The text and offset of `value` if it's a string literal.
+fn literal(value: Option<&Expr>) -> Option<(&str, usize)> {
+    match value {
+        Some(Expr::StringLiteral(lit)) => Some((lit.value.to_str(), lit.range.start().to_usize())),
+        _ => None,
+    }
+}
+
+/// Whether `name` is a standard's name as NEP-297 spells them (`nep171`).
+fn is_standard(name: &str) -> bool {
+    !name.is_empty()
+        && name
+            .chars()
+            .all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '-' || c == '_')
+}
+
+/// Whether `version` is `MAJOR.MINOR.PATCH`, optionally followed by a
+/// `-pre-release` or `+build` suffix.
+fn is_version(version: &str) -> bool {
+    let core = version.split(['-', '+']).next().unwrap_or_default();
+    let suffix = &version[core.len()..];
+    let parts: Vec<&str> = core.split('.').collect();
+    parts.len() == 3
+        && parts.iter().all(|part| {
+            !part.is_empty()
+                && part.chars().all(|c| c.is_ascii_digit())
+                && (part.len() == 1 || !part.starts_with('0'))
+        })
+        && suffix
+            .get(1..)
+            .is_none_or(|rest| !rest.is_empty() && !rest.contains(char::is_whitespace))
+}
+
+/// Whether `expr` is a string starting with `EVENT_JSON:`, or a
+/// concatenation starting with one.
+fn starts_event(expr: &Expr) -> bool {
+    match expr {
+        Expr::StringLiteral(lit) => lit.value.to_str().starts_with(PREFIX),
+        Expr::BinOp(concat) if concat.op == Operator::Add => starts_event(&concat.left),
+        _ => false,
+    }
+}
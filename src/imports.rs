@@ -0,0 +1,575 @@
+// Multi-file contracts — `import utils` and `from utils import helper`
+// resolve to `utils.py` next to the contract, and a package directory builds
+// its `main.py` or `__init__.py`, with `from .sub.mod import f` resolving
+// inside it. Monty compiles one program, so the imported modules are bundled
+// ahead of the contract in dependency order, all sharing one namespace. Only
+// the contract file exports methods. Standard library modules Monty lacks
+// but contracts need, like `json` and `base64`, are bundled from the compiler's own
+// copies unless the contract has a file of that name.
+
+use std::collections::{HashMap, HashSet};
+use std::path::{Path, PathBuf};
+
+use anyhow::{bail, Result};
+use ruff_python_ast::visitor::{self, Visitor};
+use ruff_python_ast::{Expr, ModModule, Stmt};
+use ruff_python_parser::parse_module;
+use ruff_text_size::{Ranged, TextRange};
+
+use crate::diagnostics::{Diagnostic, SourceFile};
+use crate::source_map::Segment;
+
+/// Files a package directory builds, in order of preference: an `__init__.py`
+/// is often just a marker next to the `main.py` holding the contract.
+const PACKAGE_ENTRIES: [&str; 2] = ["main.py", "__init__.py"];
+
+/// Modules bundled from template/python/modules when no file under the root
+/// has their name.
+pub const MODULES: &[(&str, &str)] = &[
+    ("json", crate::TEMPLATE_MODULE_JSON),
+    ("base64", crate::TEMPLATE_MODULE_BASE64),
+    ("binascii", crate::TEMPLATE_MODULE_BINASCII),
+];
+
+/// The bundled source of module `name`, if it's one of [`MODULES`].
+fn builtin_module(name: &str) -> Option<&'static str> {
+    MODULES
+        .iter()
+        .find(|(module, _)| *module == name)
+        .map(|(_, source)| *source)
+}
+
+/// The modules a contract imports, bundled.
+#[derive(Default)]
+pub struct Bundle {
+    /// The directory imports resolve against: the contract's.
+    root: PathBuf,
+    /// Their files, in bundling order.
+    pub files: Vec<PathBuf>,
+    /// The bundled code, to run before the contract: each module after the
+    /// modules it imports, then the assignments the contract's aliased
+    /// imports (`from utils import helper as h`) need.
+    pub prelude: String,
+    /// The helpers the contract or any of the modules uses.
+    pub builtins: crate::Builtins,
+    /// Where in the prelude each module's code is, for compile errors.
+    pub segments: Vec<Segment>,
+}
+
+impl Bundle {
+    /// Rewrite the contract's imports of bundled modules; see [`rewrite`].
+    pub fn rewrite(&self, source: &str, module: &ModModule) -> String {
+        rewrite(&self.root, "", source, module)
+    }
+}
+
+/// The file to build for `dir`, a package directory.
+pub fn package_entry(dir: &Path) -> Result<PathBuf> {
+    match PACKAGE_ENTRIES
+        .iter()
+        .map(|name| dir.join(name))
+        .find(|path| path.is_file())
+    {
+        Some(entry) => Ok(entry),
+        None => bail!(
+            "{} is a directory without a {}",
+            dir.display(),
+            PACKAGE_ENTRIES.join(" or ")
+        ),
+    }
+}
+
+/// The contract's name as far as its path tells: the file stem, or the
+/// directory name for a package entry like `my_contract/main.py`.
+pub fn contract_stem(input: &Path) -> String {
+    let is_entry = input
+        .file_name()
+        .is_some_and(|name| PACKAGE_ENTRIES.iter().any(|entry| name == *entry));
+    let named = if is_entry {
+        input.canonicalize().ok().and_then(|path| {
+            path.parent()
+                .and_then(Path::file_name)
+                .map(|name| name.to_string_lossy().into_owned())
+        })
+    } else {
+        None
+    };
+    named.unwrap_or_else(|| {
+        input
+            .file_stem()
+            .unwrap_or_default()
+            .to_string_lossy()
+            .into()
+    })
+}
+
+/// The directory the imports of the contract at `input` resolve against.
+pub fn root(input: &Path) -> &Path {
+    input
+        .parent()
+        .filter(|p| !p.as_os_str().is_empty())
+        .unwrap_or(Path::new("."))
+}
+
+/// Find and bundle the modules the contract in `file` imports, directly or
+/// through other modules, reporting any that fail to read or parse.
+pub fn resolve(file: SourceFile, module: &ModModule, diagnostics: &mut Vec<Diagnostic>) -> Bundle {
+    let root = root(file.path);
+    // Imports of the contract itself (`from . import x` in a package's
+    // submodule) have nothing to bundle
+    let entry = match file.path.file_name() {
+        Some(name) if name == "__init__.py" => String::new(),
+        _ => file
+            .path
+            .file_stem()
+            .unwrap_or_default()
+            .to_string_lossy()
+            .into(),
+    };
+    let mut resolver = Resolver {
+        bundle: Bundle {
+            root: root.to_path_buf(),
+            ..Bundle::default()
+        },
+        seen: HashSet::from([entry]),
+        defined: HashMap::new(),
+    };
+    let aliases = resolver.import_all(module, "", diagnostics);
+    resolver.check_clashes(file, module, diagnostics);
+    let mut bundle = resolver.bundle;
+    bundle.prelude.push_str(&aliases);
+    bundle
+}
+
+/// The module files the contract at `input` imports, for `build --watch`
+/// and the build cache.
+/// Files that fail to read or parse are left out.
+pub fn module_files(input: &Path) -> Vec<PathBuf> {
+    let Ok(source) = crate::read_source(input) else {
+        return Vec::new();
+    };
+    let Ok(parsed) = parse_module(&source) else {
+        return Vec::new();
+    };
+    let file = SourceFile {
+        path: input,
+        text: &source,
+    };
+    resolve(file, &parsed.into_syntax(), &mut Vec::new()).files
+}
+
+struct Resolver {
+    bundle: Bundle,
+    /// Dotted names of the modules bundled so far, and the contract's.
+    seen: HashSet<String>,
+    /// The file each top-level function is defined in, and where.
+    defined: HashMap<String, (PathBuf, String)>,
+}
+
+impl Resolver {
+    /// Bundle every module `module`, in `package`, imports that isn't bundled
+    /// yet, in the order it imports them. Returns the alias assignments
+    /// `module` needs.
+    fn import_all(
+        &mut self,
+        module: &ModModule,
+        package: &str,
+        diagnostics: &mut Vec<Diagnostic>,
+    ) -> String {
+        let mut assignments = String::new();
+        for stmt in &module.body {
+            let Some(import) = resolve_import(&self.bundle.root, stmt, package) else {
+                continue;
+            };
+            for name in import.modules {
+                if self.seen.insert(name.clone()) {
+                    if let Some(path) = locate(&self.bundle.root, &name) {
+                        self.bundle_module(&name, path, diagnostics);
+                    } else if let Some(source) = builtin_module(&name) {
+                        self.bundle_builtin(&name, source, diagnostics);
+                    }
+                }
+            }
+            for (alias, name) in import.aliases {
+                assignments.push_str(&format!("{alias} = {name}\n"));
+            }
+        }
+        assignments
+    }
+
+    fn bundle_module(&mut self, name: &str, path: PathBuf, diagnostics: &mut Vec<Diagnostic>) {
+        let source = match crate::read_source(&path) {
+            Ok(source) => source,
+            Err(e) => {
+                diagnostics.push(Diagnostic::error("import-error", format!("{e:#}")));
+                return;
+            }
+        };
+        let file = SourceFile {
+            path: &path,
+            text: &source,
+        };
+        let module = match parse_module(&source) {
+            Ok(parsed) => parsed.into_syntax(),
+            Err(e) => {
+                let offset = e.location.start().to_usize();
+                diagnostics.push(
+                    file.error(
+                        "parse-error",
+                        offset,
+                        format!("Python parse error: {}", e.error),
+                    )
+                    .with_frame(file.frame(offset)),
+                );
+                return;
+            }
+        };
+
+        // A package's `__init__.py` is its own package; a module's is its
+        // parent
+        let package = if path.ends_with("__init__.py") {
+            name
+        } else {
+            name.rsplit_once('.').map_or("", |(parent, _)| parent)
+        };
+        // Its own imports go first, so they're defined when it runs
+        let aliases = self.import_all(&module, package, diagnostics);
+        self.check_clashes(file, &module, diagnostics);
+        crate::check_host_calls(&module, file, diagnostics);
+        crate::check_top_level_effects(&module, file, diagnostics);
+        crate::unsupported::check(&module, file, &self.bundle.root, diagnostics);
+        self.bundle.builtins.events |= crate::events::check(&module, file, diagnostics);
+        self.bundle.builtins.collections |= crate::collections::check(&module, file, diagnostics);
+        self.bundle.builtins.promises |= crate::promises::check(&module, file, diagnostics);
+        crate::async_calls::check(&module, file, false, diagnostics);
+        self.bundle.builtins.yields |= crate::yield_resume::check(&module, file, diagnostics);
+        self.bundle.builtins.deposits |= crate::calls_helper(&module, crate::DEPOSIT_HELPERS);
+        let code = rewrite(&self.bundle.root, package, &source, &module);
+        let relative = path.strip_prefix(&self.bundle.root).unwrap_or(&path);
+        self.bundle
+            .prelude
+            .push_str(&format!("# {}\n{aliases}", relative.display()));
+        self.bundle.segments.push(Segment {
+            path: path.clone(),
+            text: source,
+            start: self.bundle.prelude.matches('\n').count(),
+        });
+        self.bundle.prelude.push_str(&format!("{code}\n"));
+        self.bundle.files.push(path);
+    }
+
+    /// Bundle one of [`MODULES`]. It imports nothing, but its functions share
+    /// the namespace like any module's.
+    fn bundle_builtin(&mut self, name: &str, source: &str, diagnostics: &mut Vec<Diagnostic>) {
+        let path = PathBuf::from(format!("<{name}>"));
+        let module = parse_module(source)
+            .expect("bundled modules parse")
+            .into_syntax();
+        let file = SourceFile {
+            path: &path,
+            text: source,
+        };
+        self.check_clashes(file, &module, diagnostics);
+        self.bundle.builtins.json |= name == "json";
+        self.bundle
+            .prelude
+            .push_str(&format!("# {name} (bundled)\n"));
+        self.bundle.segments.push(Segment {
+            path,
+            text: source.to_string(),
+            start: self.bundle.prelude.matches('\n').count(),
+        });
+        self.bundle.prelude.push_str(&format!("{source}\n"));
+    }
+
+    /// Report top-level functions of `file` that another file defines too,
+    /// or that `file` defines twice or imports a name over: with one
+    /// namespace, the later definition would silently win.
+    fn check_clashes(
+        &mut self,
+        file: SourceFile,
+        module: &ModModule,
+        diagnostics: &mut Vec<Diagnostic>,
+    ) {
+        // Each name `file` binds with `def` or `from m import`, where, and
+        // whether by `def`
+        let mut bound: HashMap<&str, (usize, bool)> = HashMap::new();
+        for stmt in &module.body {
+            let bindings: Vec<(&str, usize, bool)> = match stmt {
+                Stmt::FunctionDef(func) => {
+                    vec![(func.name.as_str(), func.range.start().to_usize(), true)]
+                }
+                Stmt::ImportFrom(import) => import
+                    .names
+                    .iter()
+                    .filter(|alias| alias.name.as_str() != "*")
+                    .map(|alias| {
+                        let name = alias.asname.as_ref().unwrap_or(&alias.name);
+                        (name.as_str(), alias.range.start().to_usize(), false)
+                    })
+                    .collect(),
+                _ => continue,
+            };
+            for (name, offset, is_def) in bindings {
+                match bound.get(name) {
+                    Some(&(first, first_is_def)) if is_def || first_is_def => {
+                        let message = if is_def && first_is_def {
+                            format!("`{name}` is defined twice; the later definition would silently replace the earlier one")
+                        } else {
+                            format!("`{name}` is both imported and defined; whichever comes later would silently replace the other")
+                        };
+                        let what = if first_is_def { "defined" } else { "imported" };
+                        diagnostics.push(
+                            file.error("duplicate-definition", offset, message)
+                                .with_note(format!("first {what} at {}", file.position(first))),
+                        );
+                    }
+                    Some(_) => {}
+                    None => {
+                        bound.insert(name, (offset, is_def));
+                    }
+                }
+                if !is_def {
+                    continue;
+                }
+                match self.defined.get(name) {
+                    Some((other, position)) if other != file.path => diagnostics.push(
+                        file.error(
+                            "import-clash",
+                            offset,
+                            format!(
+                                "`{name}` is also defined in {}; imported modules share the contract's namespace",
+                                other.display()
+                            ),
+                        )
+                        .with_note(format!("first defined at {position}")),
+                    ),
+                    Some(_) => {}
+                    None => {
+                        self.defined.insert(
+                            name.to_string(),
+                            (file.path.to_path_buf(), file.position(offset)),
+                        );
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// What an import statement brings in from files under the root, or from
+/// [`MODULES`].
+struct Import {
+    /// Dotted names of the modules to bundle, packages before their modules.
+    modules: Vec<String>,
+    /// Names the statement binds to a module, dotted for `import a.b`.
+    module_names: Vec<String>,
+    /// `(alias, name)` for each `from m import name as alias`.
+    aliases: Vec<(String, String)>,
+    /// Names a plain `import` lists that aren't under the root.
+    kept: Vec<TextRange>,
+}
+
+/// Resolve `stmt`, in `package`, if it's an import of modules under `root`
+/// or of bundled ones. Anything else is left for Monty.
+fn resolve_import(root: &Path, stmt: &Stmt, package: &str) -> Option<Import> {
+    let mut import = Import {
+        modules: Vec::new(),
+        module_names: Vec::new(),
+        aliases: Vec::new(),
+        kept: Vec::new(),
+    };
+    match stmt {
+        Stmt::Import(stmt) => {
+            for alias in &stmt.names {
+                let name = alias.name.as_str();
+                if !exists(root, name) {
+                    import.kept.push(alias.range);
+                    continue;
+                }
+                // `import a.b` runs package `a` first, and binds it too
+                let mut prefix = String::new();
+                for part in name.split('.') {
+                    if !prefix.is_empty() {
+                        prefix.push('.');
+                    }
+                    prefix.push_str(part);
+                    if exists(root, &prefix) {
+                        import.modules.push(prefix.clone());
+                        if alias.asname.is_none() {
+                            import.module_names.push(prefix.clone());
+                        }
+                    }
+                }
+                if let Some(asname) = &alias.asname {
+                    import.module_names.push(asname.to_string());
+                }
+            }
+            if import.modules.is_empty() {
+                return None;
+            }
+        }
+        Stmt::ImportFrom(stmt) => {
+            let base = absolute_name(
+                package,
+                stmt.module.as_ref().map(|m| m.as_str()),
+                stmt.level,
+            )?;
+            let base_found = exists(root, &base);
+            if base_found {
+                import.modules.push(base.clone());
+            }
+            for alias in &stmt.names {
+                let name = alias.name.as_str();
+                if name == "*" {
+                    continue;
+                }
+                // `from pkg import mod` imports a submodule, if there's one
+                let submodule = if base.is_empty() {
+                    name.to_string()
+                } else {
+                    format!("{base}.{name}")
+                };
+                if exists(root, &submodule) {
+                    import.modules.push(submodule);
+                    import
+                        .module_names
+                        .push(alias.asname.as_ref().unwrap_or(&alias.name).to_string());
+                } else if !base_found {
+                    return None;
+                } else if let Some(asname) = &alias.asname {
+                    import.aliases.push((asname.to_string(), name.to_string()));
+                }
+            }
+            if import.modules.is_empty() {
+                return None;
+            }
+        }
+        _ => return None,
+    }
+    Some(import)
+}
+
+/// The dotted name, from the root, of `from <level dots><module> import` in
+/// `package`; `None` if the dots climb above the root.
+fn absolute_name(package: &str, module: Option<&str>, level: u32) -> Option<String> {
+    let mut parts: Vec<&str> = if level == 0 {
+        Vec::new()
+    } else {
+        package.split('.').filter(|p| !p.is_empty()).collect()
+    };
+    for _ in 1..level {
+        parts.pop()?;
+    }
+    parts.extend(module.into_iter().flat_map(|m| m.split('.')));
+    Some(parts.join("."))
+}
+
+/// The file of the module with dotted `name` under `root`: `a/b.py`, or
+/// `a/b/__init__.py` for a package. `""` is the root's own `__init__.py`.
+fn locate(root: &Path, name: &str) -> Option<PathBuf> {
+    let relative = name.replace('.', "/");
+    if !name.is_empty() {
+        let file = root.join(format!("{relative}.py"));
+        if file.is_file() {
+            return Some(file);
+        }
+    }
+    let init = root.join(relative).join("__init__.py");
+    init.is_file().then_some(init)
+}
+
+/// Whether `name` is a module under `root` or a bundled one.
+pub fn exists(root: &Path, name: &str) -> bool {
+    locate(root, name).is_some() || builtin_module(name).is_some()
+}
+
+/// Rewrite the imports in `source`, of modules under `root`, without moving
+/// any code, so offsets into the original stay valid: the import statements
+/// are blanked (keeping any other modules a plain `import` names), and
+/// `utils.helper` becomes `helper` padded with spaces.
+fn rewrite(root: &Path, package: &str, source: &str, module: &ModModule) -> String {
+    let mut edits: Vec<(usize, usize, String)> = Vec::new();
+    let mut module_names: HashSet<String> = HashSet::new();
+    for stmt in &module.body {
+        let Some(import) = resolve_import(root, stmt, package) else {
+            continue;
+        };
+        let replacement = if import.kept.is_empty() {
+            String::new()
+        } else {
+            let kept: Vec<&str> = import.kept.iter().map(|&range| &source[range]).collect();
+            format!("import {}", kept.join(", "))
+        };
+        let range = stmt.range();
+        edits.push((
+            range.start().to_usize(),
+            range.end().to_usize(),
+            replacement,
+        ));
+        module_names.extend(import.module_names);
+    }
+    if edits.is_empty() {
+        return source.to_string();
+    }
+
+    let mut attributes = ModuleAttributes {
+        modules: &module_names,
+        found: Vec::new(),
+    };
+    attributes.visit_body(&module.body);
+    edits.extend(attributes.found);
+
+    edits.sort_by_key(|&(start, _, _)| start);
+    let mut out = source.as_bytes().to_vec();
+    for (start, end, replacement) in edits {
+        let replacement = replacement.as_bytes();
+        for (i, byte) in out[start..end].iter_mut().enumerate() {
+            *byte = match replacement.get(i) {
+                Some(&b) => b,
+                None if *byte == b'\n' => b'\n',
+                None => b' ',
+            };
+        }
+    }
+    // Replacements are shorter than what they replace, and everything past
+    // them became ASCII spaces
+    String::from_utf8(out).expect("rewritten source is valid UTF-8")
+}
+
+/// Finds `module.name` expressions on bundled modules, including dotted ones
+/// like `sub.mod.name`.
+struct ModuleAttributes<'a> {
+    modules: &'a HashSet<String>,
+    /// The expression's range and the bare name replacing it.
+    found: Vec<(usize, usize, String)>,
+}
+
+impl<'a> Visitor<'a> for ModuleAttributes<'_> {
+    fn visit_expr(&mut self, expr: &'a Expr) {
+        if let Expr::Attribute(attribute) = expr {
+            if dotted_name(&attribute.value).is_some_and(|name| self.modules.contains(&name)) {
+                self.found.push((
+                    attribute.range.start().to_usize(),
+                    attribute.range.end().to_usize(),
+                    attribute.attr.as_str().to_string(),
+                ));
+                return;
+            }
+        }
+        visitor::walk_expr(self, expr);
+    }
+}
+
+/// `a.b.c` for a chain of attribute accesses on a name.
+fn dotted_name(expr: &Expr) -> Option<String> {
+    match expr {
+        Expr::Name(name) => Some(name.id.to_string()),
+        Expr::Attribute(attribute) => Some(format!(
+            "{}.{}",
+            dotted_name(&attribute.value)?,
+            attribute.attr
+        )),
+        _ => None,
+    }
+}
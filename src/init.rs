@@ -0,0 +1,89 @@
+// Scaffolding — `init` writes a new contract project laid out the way the
+// other subcommands expect, and `new` writes a starter contract.
+
+use std::fs;
+use std::path::Path;
+
+use anyhow::{bail, Context, Result};
+use clap::ValueEnum;
+
+/// Placeholder in the scaffold files replaced with the project name.
+const MARKER_NAME: &str = "@CONTRACT_NAME";
+
+/// Files written by `init`, as (path in the project, contents).
+const SCAFFOLD: &[(&str, &str)] = &[
+    ("contract.py", include_str!("../template/init/contract.py")),
+    (
+        "monty-near.toml",
+        include_str!("../template/init/monty-near.toml"),
+    ),
+    (".gitignore", include_str!("../template/init/gitignore")),
+    (
+        "tests/package.json",
+        include_str!("../template/init/tests/package.json"),
+    ),
+    (
+        "tests/test_contract.py",
+        include_str!("../template/init/tests/test_contract.py"),
+    ),
+    (
+        "tests/contract.test.ts",
+        include_str!("../template/init/tests/contract.test.ts"),
+    ),
+];
+
+/// Create `dir` and write the scaffold into it, naming the contract `name`.
+pub fn init_project(dir: &Path, name: &str) -> Result<()> {
+    if dir.exists() {
+        bail!("{} already exists", dir.display());
+    }
+
+    for (path, contents) in SCAFFOLD {
+        let path = dir.join(path);
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        fs::write(&path, contents.replace(MARKER_NAME, name))
+            .with_context(|| format!("failed to write {}", path.display()))?;
+        eprintln!("  Created {}", path.display());
+    }
+    Ok(())
+}
+
+/// Starter contracts for `new --template`.
+#[derive(Clone, Copy, ValueEnum)]
+pub enum ContractTemplate {
+    /// Stateful counter with an owner-only reset
+    Counter,
+    /// Fungible token with NEP-141 transfer rules and events
+    Ft,
+    /// Non-fungible token with NEP-171 ownership rules and events
+    Nft,
+    /// Member list, proposals, and majority voting
+    Dao,
+}
+
+impl ContractTemplate {
+    fn source(self) -> &'static str {
+        match self {
+            Self::Counter => include_str!("../template/contracts/counter.py"),
+            Self::Ft => include_str!("../template/contracts/ft.py"),
+            Self::Nft => include_str!("../template/contracts/nft.py"),
+            Self::Dao => include_str!("../template/contracts/dao.py"),
+        }
+    }
+}
+
+/// Write the starter contract for `template` to `path`.
+pub fn new_contract(path: &Path, template: ContractTemplate) -> Result<()> {
+    if path.exists() {
+        bail!("{} already exists", path.display());
+    }
+    if let Some(parent) = path.parent().filter(|p| !p.as_os_str().is_empty()) {
+        fs::create_dir_all(parent)?;
+    }
+    fs::write(path, template.source())
+        .with_context(|| format!("failed to write {}", path.display()))?;
+    eprintln!("  Created {}", path.display());
+    Ok(())
+}
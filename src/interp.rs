@@ -0,0 +1,62 @@
+// Running Python on the host — drives a Monty program to completion,
+// handing each external function call to a Rust callback.
+
+use anyhow::{anyhow, bail, Result};
+use monty::{MontyObject, MontyRun, NoLimitTracker, NoPrint, RunProgress};
+
+/// Compile and run `program`, answering calls to `external_functions` with
+/// `handler`. An error from `handler` stops the program and is returned.
+pub fn run(
+    program: String,
+    script_name: &str,
+    external_functions: &[&str],
+    mut handler: impl FnMut(&str, &[MontyObject]) -> Result<MontyObject>,
+) -> Result<()> {
+    let runner = MontyRun::new(
+        program,
+        script_name,
+        vec![],
+        external_functions.iter().map(|s| s.to_string()).collect(),
+    )
+    .map_err(|e| anyhow!("{e}"))?;
+
+    let mut print = NoPrint;
+    let mut progress = runner
+        .start(vec![], NoLimitTracker, &mut print)
+        .map_err(|e| anyhow!("{e}"))?;
+    loop {
+        match progress {
+            RunProgress::FunctionCall {
+                function_name,
+                args,
+                state,
+                ..
+            } => {
+                let result = handler(&function_name, &args)?;
+                progress = state.run(result, &mut print).map_err(|e| anyhow!("{e}"))?;
+            }
+            RunProgress::Complete(_) => return Ok(()),
+            RunProgress::OsCall { .. } => bail!("OS calls are not permitted"),
+            RunProgress::ResolveFutures(_) => bail!("async futures are not supported"),
+        }
+    }
+}
+
+/// Positional argument `idx` as raw bytes: strings as UTF-8, ints in
+/// decimal, missing or `None` as empty.
+pub fn arg_bytes(args: &[MontyObject], idx: usize) -> Vec<u8> {
+    match args.get(idx) {
+        Some(MontyObject::String(s)) => s.as_bytes().to_vec(),
+        Some(MontyObject::Bytes(b)) => b.clone(),
+        Some(MontyObject::Int(n)) => n.to_string().into_bytes(),
+        _ => Vec::new(),
+    }
+}
+
+/// Positional argument `idx` as a string, if it is one.
+pub fn arg_str(args: &[MontyObject], idx: usize) -> Option<&str> {
+    match args.get(idx) {
+        Some(MontyObject::String(s)) => Some(s),
+        _ => None,
+    }
+}
@@ -0,0 +1,67 @@
+// Protocol limits — the NEAR runtime refuses to compile a contract that
+// exceeds one of its limits, and deploying one fails on-chain with an opaque
+// `CompilationError`. The build checks the final WASM against the same
+// limits and names the one it exceeds.
+
+use anyhow::{bail, Result};
+
+use crate::size;
+
+/// The runtime's limits: nearcore's `LimitConfig` name, the value on
+/// mainnet, and what it counts.
+const LIMITS: &[(&str, u64, &str)] = &[
+    ("max_contract_size", 4 * 1024 * 1024, "bytes of code"),
+    (
+        "max_functions_number_per_contract",
+        10_000,
+        "functions, imported and defined",
+    ),
+    (
+        "max_locals_per_contract",
+        1_000_000,
+        "locals declared across all functions",
+    ),
+    ("max_tables_per_contract", 1, "tables"),
+    (
+        "max_length_method_name",
+        256,
+        "bytes in an exported method's name",
+    ),
+];
+
+/// Check the NEAR contract `wasm` against the runtime's limits, failing
+/// with every limit it exceeds.
+pub fn check(wasm: &[u8]) -> Result<()> {
+    let counts = size::counts(wasm)?;
+    let longest = counts
+        .exports
+        .iter()
+        .max_by_key(|export| export.len())
+        .map_or(0, |export| export.len() as u64);
+    let actual = [
+        ("max_contract_size", wasm.len() as u64),
+        ("max_functions_number_per_contract", counts.functions),
+        ("max_locals_per_contract", counts.locals),
+        ("max_tables_per_contract", counts.tables),
+        ("max_length_method_name", longest),
+    ];
+
+    let mut exceeded = Vec::new();
+    for (name, value, what) in LIMITS {
+        let Some((_, count)) = actual.iter().find(|(limit, _)| limit == name) else {
+            continue;
+        };
+        if count > value {
+            exceeded.push(format!(
+                "  {count} {what}, over the runtime's {name} of {value}"
+            ));
+        }
+    }
+    if !exceeded.is_empty() {
+        bail!(
+            "the contract exceeds NEAR protocol limits, so deploying it would fail:\n{}",
+            exceeded.join("\n")
+        );
+    }
+    Ok(())
+}
@@ -0,0 +1,147 @@
+// Pinned dependencies — `monty-near.lock` records the Cargo.lock of each
+// Rust project the build generates, so rebuilding the same source resolves
+// the same crate versions. A build with a recorded lock runs cargo with
+// `--locked`; one without resolves the dependencies and records the result.
+
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::Path;
+use std::sync::Mutex;
+
+use anyhow::{Context, Result};
+use clap::ValueEnum;
+use serde::{Deserialize, Serialize};
+
+use crate::{Allocator, BuildArgs, OutputFormat};
+
+pub const LOCK_FILE: &str = "monty-near.lock";
+
+/// Serializes [`record`] between the concurrent builds of `--all-targets`.
+static RECORDING: Mutex<()> = Mutex::new(());
+
+const HEADER: &str = "\
+# Cargo.lock of each Rust project monty-near-cli generates for this project.
+# Commit this file; `monty-near-cli build --update-lock` refreshes it.
+
+";
+
+#[derive(Default, Serialize, Deserialize)]
+#[serde(deny_unknown_fields, rename_all = "kebab-case")]
+struct LockFile {
+    /// Cargo.lock contents by [`project_key`].
+    #[serde(default)]
+    cargo_lock: BTreeMap<String, String>,
+}
+
+/// Which generated project a lock belongs to. The crate name is part of the
+/// lock, and the output format, allocator, bytecode compression and Monty
+/// source change the dependencies; `--compat` doesn't, so both targets
+/// share one.
+pub fn project_key(args: &BuildArgs, crate_name: &str) -> String {
+    let mut variants = Vec::new();
+    if args.format == OutputFormat::Component {
+        variants.push("component".to_string());
+    }
+    let allocator = args.allocator.unwrap_or_default();
+    if allocator != Allocator::Dlmalloc {
+        if let Some(value) = allocator.to_possible_value() {
+            variants.push(value.get_name().to_string());
+        }
+    }
+    if args.compress_bytecode {
+        variants.push("compressed bytecode".to_string());
+    }
+    if let Some(version) = &args.monty_version {
+        variants.push(format!("monty {version}"));
+    } else if args.monty_path.is_some() {
+        variants.push("monty path".to_string());
+    }
+    if variants.is_empty() {
+        crate_name.to_string()
+    } else {
+        format!("{crate_name} ({})", variants.join(", "))
+    }
+}
+
+/// The Cargo.lock recorded under `key` in the lockfile at `path`, if any.
+pub fn read(path: &Path, key: &str) -> Result<Option<String>> {
+    Ok(load(path)?.cargo_lock.remove(key))
+}
+
+/// Record `cargo_lock` under `key` in the lockfile at `path`.
+pub fn record(path: &Path, key: &str, cargo_lock: &str) -> Result<()> {
+    let _guard = RECORDING.lock().unwrap_or_else(|e| e.into_inner());
+    let mut lock = load(path)?;
+    if lock.cargo_lock.get(key).map(String::as_str) == Some(cargo_lock) {
+        return Ok(());
+    }
+    lock.cargo_lock
+        .insert(key.to_string(), cargo_lock.to_string());
+    let contents = HEADER.to_string() + &toml::to_string(&lock)?;
+
+    // Write-then-rename, so concurrent builds never see a partial file
+    let tmp = path.with_extension(format!("lock.{}.tmp", std::process::id()));
+    fs::write(&tmp, contents).with_context(|| format!("failed to write {}", tmp.display()))?;
+    fs::rename(&tmp, path).with_context(|| format!("failed to write {}", path.display()))?;
+    eprintln!("  Recorded dependencies of {key} in {}", path.display());
+    Ok(())
+}
+
+fn load(path: &Path) -> Result<LockFile> {
+    match fs::read_to_string(path) {
+        Ok(contents) => {
+            toml::from_str(&contents).with_context(|| format!("invalid {}", path.display()))
+        }
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(LockFile::default()),
+        Err(e) => Err(e).with_context(|| format!("failed to read {}", path.display())),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn args(options: &[&str]) -> BuildArgs {
+        BuildArgs::from_args(options.iter().map(std::ffi::OsStr::new)).unwrap()
+    }
+
+    #[test]
+    fn project_keys_name_what_changes_the_dependencies() {
+        assert_eq!(project_key(&args(&[]), "token"), "token");
+        assert_eq!(project_key(&args(&["--compat"]), "token"), "token");
+        assert_eq!(
+            project_key(
+                &args(&[
+                    "--allocator=bump",
+                    "--compress-bytecode",
+                    "--monty-version=0.1.0"
+                ]),
+                "token"
+            ),
+            "token (bump, compressed bytecode, monty 0.1.0)"
+        );
+    }
+
+    #[test]
+    fn recorded_locks_read_back() {
+        let path =
+            std::env::temp_dir().join(format!("monty-near-lockfile-{}.lock", std::process::id()));
+        assert_eq!(read(&path, "token").unwrap(), None);
+
+        record(&path, "token", "version = 4\n").unwrap();
+        record(&path, "token (bump)", "version = 3\n").unwrap();
+        assert_eq!(
+            read(&path, "token").unwrap().as_deref(),
+            Some("version = 4\n")
+        );
+        assert_eq!(
+            read(&path, "token (bump)").unwrap().as_deref(),
+            Some("version = 3\n")
+        );
+        assert!(fs::read_to_string(&path).unwrap().starts_with(HEADER));
+
+        fs::write(&path, "[cargo-lock]\ntoken = 1\n").unwrap();
+        assert!(read(&path, "token").is_err());
+        fs::remove_file(&path).unwrap();
+    }
+}
@@ -1,12 +1,64 @@
+use std::borrow::Cow;
+use std::collections::{BTreeSet, HashMap, HashSet};
 use std::fs;
 use std::path::{Path, PathBuf};
 use std::process::Command;
 
 use anyhow::{bail, Context, Result};
-use clap::{Parser, Subcommand};
+use clap::{Args, CommandFactory, FromArgMatches, Parser, Subcommand, ValueEnum};
 use monty::MontyRun;
-use ruff_python_ast::Stmt;
+use ruff_python_ast::visitor::{self, Visitor};
+use ruff_python_ast::{
+    Decorator, Expr, ExprName, ModModule, Number, Operator, Stmt, StmtFunctionDef,
+};
 use ruff_python_parser::parse_module;
+use ruff_text_size::Ranged;
+use sha2::{Digest, Sha256};
+
+use diagnostics::{Diagnostic, DiagnosticsFormat, Lints, Severity, SourceFile};
+use source_map::{CompileError, SourceMap};
+
+mod abi;
+mod artifacts;
+mod async_calls;
+mod bench;
+mod borsh_io;
+mod build_cache;
+mod call;
+mod collections;
+mod config;
+mod contract_class;
+mod dead_code;
+mod deploy;
+mod diagnostics;
+mod doctor;
+mod events;
+mod imports;
+mod init;
+mod interp;
+mod limits;
+mod lockfile;
+mod metadata;
+mod mockhost;
+mod patch;
+mod promises;
+mod reproducible;
+mod rpc;
+mod sandbox;
+mod selftest;
+mod size;
+mod source_map;
+mod state;
+mod stubs;
+mod target_cache;
+mod testing;
+mod tx;
+mod unsupported;
+mod upgrade;
+mod vendor;
+mod verify;
+mod watch;
+mod yield_resume;
 
 // ---------------------------------------------------------------------------
 // Template files — embedded at compile time from template/
@@ -16,10 +68,74 @@ const TEMPLATE_CARGO_TOML: &str = include_str!("../template/Cargo.toml");
 const TEMPLATE_RUST_TOOLCHAIN: &str = include_str!("../template/rust-toolchain.toml");
 const TEMPLATE_CARGO_CONFIG: &str = include_str!("../template/.cargo/config.toml");
 const TEMPLATE_LIB_RS: &str = include_str!("../template/src/lib.rs");
+const TEMPLATE_COMPONENT_LIB_RS: &str = include_str!("../template/component/src/lib.rs");
+const TEMPLATE_COMPONENT_WIT: &str = include_str!("../template/component/wit/world.wit");
+const TEMPLATE_ALLOC_BUMP: &str = include_str!("../template/alloc/bump.rs");
+const TEMPLATE_ALLOC_WEE_ALLOC: &str = include_str!("../template/alloc/wee_alloc.rs");
+const TEMPLATE_DOCKERFILE: &str = include_str!("../template/docker/Dockerfile");
+/// Python the dispatcher uses to read JSON method arguments and write JSON
+/// return values.
+const TEMPLATE_JSON: &str = include_str!("../template/python/json.py");
+/// Python `@borsh_io` methods use to decode arguments and encode results.
+const TEMPLATE_BORSH: &str = include_str!("../template/python/borsh.py");
+/// Python defining `emit_event`, for contracts that call it.
+const TEMPLATE_EVENTS: &str = include_str!("../template/python/events.py");
+/// Python behind `LookupMap`, `Vector` and `UnorderedSet`.
+const TEMPLATE_COLLECTIONS: &str = include_str!("../template/python/collections.py");
+/// Python behind `Promise`.
+const TEMPLATE_PROMISE: &str = include_str!("../template/python/promise.py");
+/// Python reading promise results for `@callback` methods.
+const TEMPLATE_CALLBACK: &str = include_str!("../template/python/callback.py");
+/// Python behind `yield_create` and `yield_resume`.
+const TEMPLATE_YIELD: &str = include_str!("../template/python/yield.py");
+/// Python behind `assert_one_yocto` and `assert_min_deposit`.
+const TEMPLATE_DEPOSIT: &str = include_str!("../template/python/deposit.py");
+/// Python bundled for `import json`.
+const TEMPLATE_MODULE_JSON: &str = include_str!("../template/python/modules/json.py");
+/// Python bundled for `import base64`.
+const TEMPLATE_MODULE_BASE64: &str = include_str!("../template/python/modules/base64.py");
+/// Python bundled for `import binascii`.
+const TEMPLATE_MODULE_BINASCII: &str = include_str!("../template/python/modules/binascii.py");
+
+/// Every embedded template, for the build cache's key.
+const TEMPLATES: &[&str] = &[
+    TEMPLATE_CARGO_TOML,
+    TEMPLATE_RUST_TOOLCHAIN,
+    TEMPLATE_CARGO_CONFIG,
+    TEMPLATE_LIB_RS,
+    TEMPLATE_COMPONENT_LIB_RS,
+    TEMPLATE_COMPONENT_WIT,
+    TEMPLATE_ALLOC_BUMP,
+    TEMPLATE_ALLOC_WEE_ALLOC,
+    TEMPLATE_JSON,
+    TEMPLATE_BORSH,
+    TEMPLATE_EVENTS,
+    TEMPLATE_COLLECTIONS,
+    TEMPLATE_PROMISE,
+    TEMPLATE_CALLBACK,
+    TEMPLATE_YIELD,
+    TEMPLATE_DEPOSIT,
+    TEMPLATE_MODULE_JSON,
+    TEMPLATE_MODULE_BASE64,
+    TEMPLATE_MODULE_BINASCII,
+];
+
+/// Package name in the template Cargo.toml, used unless the source sets
+/// `__contract_name__`.
+const DEFAULT_CRATE_NAME: &str = "monty-near-contract";
 
 // Markers in template/src/lib.rs where generated code is spliced in.
 const MARKER_BYTECODE: &str = "// @MONTY_BYTECODE_STATICS";
 const MARKER_EXPORTS: &str = "// @MONTY_EXPORTS";
+const MARKER_ALLOCATOR: &str = "// @MONTY_ALLOCATOR";
+/// Line in template/.cargo/config.toml's rustflags array replaced with the
+/// memory and path-remapping flags.
+const MARKER_RUSTFLAGS: &str = "    # @MONTY_RUSTFLAGS\n";
+
+/// The generated project's dependency for inflating compressed bytecode:
+/// the deflate format of the CLI's own miniz_oxide, without std.
+const INFLATE_DEPENDENCY: &str =
+    "miniz_oxide = { version = \"0.9\", default-features = false, features = [\"with-alloc\"] }\n";
 
 // ---------------------------------------------------------------------------
 // CLI argument parsing
@@ -38,418 +154,5732 @@ struct Cli {
 #[derive(Subcommand)]
 enum Commands {
     /// Build a Python file into a NEAR-deployable WASM contract
-    Build {
-        /// Path to the Python source file
-        input: PathBuf,
-
-        /// Output path for the WASM binary
-        #[arg(short, long, default_value = "contract.wasm")]
-        output: PathBuf,
-
-        /// Build for compatibility with the current production NearVM (Wasmer).
-        ///
-        /// Uses nightly Rust with -Zbuild-std and -Ctarget-cpu=mvp to avoid
-        /// emitting bulk-memory WASM instructions that NearVM rejects. Without
-        /// this flag, the output targets the upcoming Wasmtime-based runtime
-        /// (nearcore 2.12+) which supports bulk-memory natively.
-        #[arg(long)]
-        compat: bool,
-
-        /// Skip wasm-opt post-processing.
-        ///
-        /// By default the build runs `wasm-opt -Oz` on the output to reduce
-        /// WASM size. Pass this flag to skip that step (e.g. for faster
-        /// iteration or if wasm-opt is not installed).
-        #[arg(long)]
-        no_wasm_opt: bool,
-    },
+    Build(BuildCommand),
+
+    /// Check a contract and compile it to Monty bytecode without building WASM, for editors and pre-commit hooks
+    Check(BuildArgs),
+
+    /// Write a NEAR ABI (near-abi JSON) describing the exported methods
+    Abi(AbiArgs),
+
+    /// Write `near.pyi` type stubs for the host functions, for editor completion and type checking
+    Stubs(StubsArgs),
+
+    /// Check that the Rust toolchain and optional tools the build uses are installed
+    Doctor(DoctorArgs),
+
+    /// Show where the bytes of a built contract go: sections, Monty bytecode, functions
+    Size(SizeArgs),
+
+    /// Remove the generated build project and stale WASM outputs
+    Clean(CleanArgs),
+
+    /// Create a new contract project with an example contract and tests
+    Init(InitArgs),
+
+    /// Write a starter contract (counter, fungible token, NFT, or DAO)
+    New(NewArgs),
+
+    /// Build and deploy to a NEAR account using near-cli credentials
+    Deploy(DeployArgs),
+
+    /// Build and deploy over an existing contract, optionally calling a migration method
+    Upgrade(UpgradeArgs),
+
+    /// Build and deploy to a new throwaway account on testnet or a local sandbox
+    DevDeploy(DevDeployArgs),
+
+    /// Call a method on a deployed contract in a signed transaction
+    Call(CallArgs),
+
+    /// Run a read-only view of a method on a deployed contract
+    View(ViewArgs),
+
+    /// Print the storage of a deployed contract, decoded
+    State(StateArgs),
+
+    /// Run a contract method locally against a mock NEAR host, without building WASM
+    Run(RunArgs),
+
+    /// Build, deploy to a local near-sandbox, and run Python `test_*` functions against it
+    Test(TestArgs),
+
+    /// Build, deploy to a local near-sandbox, and measure the gas a method burns
+    GasBench(GasBenchArgs),
+
+    /// Call every exported method repeatedly in a local near-sandbox and compare gas with a baseline
+    Bench(BenchArgs),
+
+    /// Call every exported method once in a local near-sandbox and break down the gas each burnt
+    GasProfile(GasProfileArgs),
+
+    /// Build locally and check the result matches the code deployed on an account
+    #[command(visible_alias = "verify")]
+    VerifyOnchain(VerifyOnchainArgs),
+
+    /// Build the bundled example contract and check its methods, to verify the toolchain
+    SelfTest(SelfTestArgs),
+
+    /// Print a shell completion script
+    Completions(CompletionsArgs),
+}
+
+impl Commands {
+    /// The build options of commands that build the contract.
+    fn build_args_mut(&mut self) -> Option<&mut BuildArgs> {
+        match self {
+            Commands::Build(args) => Some(&mut args.build),
+            Commands::Check(args) => Some(args),
+            Commands::Deploy(args) => Some(&mut args.build),
+            Commands::Upgrade(args) => Some(&mut args.build),
+            Commands::DevDeploy(args) => Some(&mut args.build),
+            Commands::Test(args) => Some(&mut args.build),
+            Commands::GasBench(args) => Some(&mut args.build),
+            Commands::Bench(args) => Some(&mut args.build),
+            Commands::GasProfile(args) => Some(&mut args.build),
+            Commands::VerifyOnchain(args) => Some(&mut args.build),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Args, Clone)]
+struct BuildArgs {
+    /// Path to the Python source file, or a package directory with a main.py
+    /// or __init__.py [default: `input` under [build] in monty-near.toml]
+    input: Option<PathBuf>,
+
+    /// Output path for the WASM binary [default: `<__contract_name__>.wasm`
+    /// if the source declares one, otherwise `contract.wasm`]
+    #[arg(short, long)]
+    output: Option<PathBuf>,
+
+    /// Write the WASM into DIR together with its ABI (`<name>.abi.json`),
+    /// checksums (`<name>.sha256`, `<name>.code-hash`), `methods.txt`, and
+    /// `build-manifest.json` describing the build
+    #[arg(long, value_name = "DIR", conflicts_with = "output")]
+    out_dir: Option<PathBuf>,
+
+    /// Also write the NEAR code hash (base58 sha256, as RPC reports it) of
+    /// the output to FILE
+    #[arg(long, value_name = "FILE")]
+    code_hash_file: Option<PathBuf>,
+
+    /// Build for compatibility with the current production NearVM (Wasmer).
+    ///
+    /// Uses nightly Rust with -Zbuild-std and -Ctarget-cpu=mvp to avoid
+    /// emitting bulk-memory WASM instructions that NearVM rejects. Without
+    /// this flag, the output targets the upcoming Wasmtime-based runtime
+    /// (nearcore 2.12+) which supports bulk-memory natively.
+    #[arg(long)]
+    compat: bool,
+
+    /// Target the Wasmtime-based runtime even if monty-near.toml sets
+    /// `compat = true`
+    #[arg(long, conflicts_with = "compat")]
+    no_compat: bool,
+
+    /// Skip wasm-opt post-processing.
+    ///
+    /// By default the build runs `wasm-opt -Oz` on the output to reduce
+    /// WASM size. Pass this flag to skip that step (e.g. for faster
+    /// iteration).
+    #[arg(long)]
+    no_wasm_opt: bool,
+
+    /// Print which wasm-opt passes contributed to the size reduction.
+    ///
+    /// Before the real `-Oz` run, applies the main size passes one at a time
+    /// to a scratch copy of the binary and reports the bytes each one saved.
+    /// This is slower than a plain build and only meant for tuning.
+    #[arg(long, conflicts_with = "no_wasm_opt")]
+    wasm_opt_report: bool,
+
+    /// wasm-opt optimization level [default: Oz].
+    ///
+    /// `Oz` and `Os` optimize for size; `O2`-`O4` optimize for speed, which
+    /// can lower gas at the cost of a larger binary.
+    #[arg(long, value_enum, value_name = "LEVEL", conflicts_with = "no_wasm_opt")]
+    wasm_opt_level: Option<WasmOptLevel>,
+
+    /// Extra argument for wasm-opt, e.g. `--wasm-opt-arg=--converge`
+    /// (repeatable). Added after the level, so passes run after it.
+    #[arg(
+        long = "wasm-opt-arg",
+        value_name = "ARG",
+        allow_hyphen_values = true,
+        conflicts_with = "no_wasm_opt"
+    )]
+    wasm_opt_args: Vec<String>,
+
+    /// wasm-opt binary to run instead of the built-in one [default: $WASM_OPT]
+    #[arg(long, value_name = "PATH")]
+    wasm_opt_path: Option<PathBuf>,
+
+    /// Resolve crates.io dependencies of the generated project from a mirror.
+    ///
+    /// Writes a `[source.crates-io]` replacement into the generated
+    /// `.cargo/config.toml`. Accepts a git index URL (`https://...`) or a
+    /// sparse index (`sparse+https://...`). `CARGO_REGISTRIES_*` and other
+    /// cargo environment variables are passed through to the inner build.
+    #[arg(long, value_name = "URL")]
+    registry: Option<String>,
+
+    /// Enable features of the generated project's dependencies, as
+    /// `DEP/FEATURE` (e.g. `monty/<feature>`); comma-separated or repeated
+    #[arg(long, value_name = "FEATURES", value_delimiter = ',')]
+    features: Vec<String>,
+
+    /// Extra flags for the inner `cargo build`, split on whitespace
+    /// (repeatable), e.g. `--cargo-flags="--config profile.release.codegen-units=4"`
+    #[arg(long, value_name = "FLAGS", allow_hyphen_values = true)]
+    cargo_flags: Vec<String>,
+
+    /// Install missing Rust toolchains, targets and components with rustup
+    /// without asking first
+    #[arg(short, long)]
+    yes: bool,
+
+    /// Number of parallel jobs for the inner `cargo build`, and threads for
+    /// wasm-opt [default: one per CPU]
+    #[arg(
+        short,
+        long,
+        value_name = "N",
+        value_parser = clap::value_parser!(u32).range(1..)
+    )]
+    jobs: Option<u32>,
+
+    /// Run the inner cargo build with `--offline`: dependencies must be in
+    /// the cargo cache from an earlier build, or in --vendor-dir
+    #[arg(long)]
+    offline: bool,
+
+    /// Build the generated project from dependencies vendored into DIR.
+    ///
+    /// A build without --offline runs `cargo vendor` to fill DIR with
+    /// whatever it's missing (through the --registry mirror, if one is set);
+    /// builds after that read all crates and the Monty sources from DIR.
+    #[arg(long, value_name = "DIR")]
+    vendor_dir: Option<PathBuf>,
+
+    /// Directory for the generated Rust project and its cargo cache
+    /// [default: a directory under target/monty-near-build/ per source file
+    /// and build target].
+    ///
+    /// A build holds a lock on the directory, so concurrent builds sharing
+    /// one wait for each other instead of overwriting its files.
+    #[arg(long, value_name = "DIR")]
+    build_dir: Option<PathBuf>,
+
+    /// Keep the compiled dependencies in the build directory's own target/
+    /// instead of the per-user cache shared by every project
+    /// (~/.cache/monty-near/target)
+    #[arg(long)]
+    no_shared_cache: bool,
+
+    /// Lockfile pinning the generated project's dependencies [default:
+    /// monty-near.lock next to monty-near.toml, or next to the source].
+    ///
+    /// A build with an entry for its project runs cargo with `--locked`; a
+    /// build without one resolves the dependencies and records them.
+    #[arg(long, value_name = "FILE")]
+    lockfile: Option<PathBuf>,
+
+    /// Resolve the generated project's dependencies to their latest
+    /// compatible versions and record them in the lockfile
+    #[arg(long)]
+    update_lock: bool,
+
+    /// Build the contract runtime against Monty VERSION from crates.io
+    /// (a Cargo version requirement, e.g. `=0.0.3`) instead of the
+    /// template's git dependency
+    #[arg(long, value_name = "VERSION")]
+    monty_version: Option<String>,
+
+    /// Build the contract runtime against the Monty crate in DIR (the
+    /// directory holding its Cargo.toml), e.g. an unreleased checkout
+    #[arg(long, value_name = "DIR", conflicts_with = "monty_version")]
+    monty_path: Option<PathBuf>,
+
+    /// Directory whose files replace the embedded templates of the same
+    /// name: `Cargo.toml`, `rust-toolchain.toml`, `.cargo/config.toml`,
+    /// `src/lib.rs`, `alloc/*.rs`, `component/...`, laid out as in the CLI's
+    /// template/ directory. Files it doesn't have come from the built-ins.
+    #[arg(long, value_name = "DIR")]
+    template_dir: Option<PathBuf>,
+
+    /// Also copy the generated Rust project (Cargo.toml, src/lib.rs, the
+    /// bytecode, cargo and toolchain config) to DIR, to inspect or rebuild
+    /// by hand. Written before cargo runs, so it's there even if the build
+    /// fails.
+    #[arg(long, value_name = "DIR")]
+    emit_project: Option<PathBuf>,
+
+    /// Always run cargo, even when only the bytecode changed since the last
+    /// build, which otherwise patches the new bytecode into the last binary
+    #[arg(long)]
+    rebuild: bool,
+
+    /// Build even if an identical build is in the build cache
+    /// (~/.cache/monty-near/builds), instead of copying its WASM
+    #[arg(long)]
+    no_cache: bool,
+
+    /// Build profile: `release` (default) or `dev`, or one defined under
+    /// [profile.<name>] in monty-near.toml.
+    ///
+    /// `dev` compiles the generated project with opt-level 1, no LTO and
+    /// parallel codegen, and skips wasm-opt: much faster builds of a larger,
+    /// more gas-hungry contract. Use it for iteration, not for deployment.
+    #[arg(long, value_name = "NAME")]
+    profile: Option<String>,
+
+    /// Set a module-level constant, as NAME=VALUE (repeatable).
+    ///
+    /// Integers, floats, `True`, `False`, `None` and quoted strings are used
+    /// as Python literals; any other value is a string. A module-level
+    /// assignment to NAME in the source is overridden, so it can hold the
+    /// default; otherwise NAME is defined before the first line runs.
+    #[arg(long = "define", value_name = "NAME=VALUE")]
+    defines: Vec<String>,
+
+    /// Memory allocator compiled into the contract [default: dlmalloc]
+    #[arg(long, value_enum)]
+    allocator: Option<Allocator>,
+
+    /// Deflate the Monty bytecode, and inflate it in the contract on each
+    /// call: a smaller WASM and storage stake for a little more gas.
+    #[arg(long)]
+    compress_bytecode: bool,
+
+    /// Compile each exported method, with the shared code, into a bytecode
+    /// blob of its own: less to load per call, for a larger WASM
+    #[arg(long)]
+    split_methods: bool,
+
+    /// Size of the contract's shadow stack in bytes [default: 1 MiB, Rust's
+    /// default for wasm32].
+    ///
+    /// The stack is carved out of linear memory before the heap, so a
+    /// smaller stack leaves more room for contract state, and deeply
+    /// recursive contracts may need a larger one.
+    #[arg(long, value_name = "BYTES")]
+    stack_size: Option<u32>,
+
+    /// Initial linear memory, in 64 KiB pages, declared by the module.
+    ///
+    /// NEAR replaces the contract's memory declaration with the protocol's
+    /// limits at deploy time, so this only affects other hosts (e.g.
+    /// `--format component`).
+    #[arg(long, value_name = "PAGES")]
+    initial_memory_pages: Option<u32>,
+
+    /// Maximum linear memory, in 64 KiB pages, declared by the module. Like
+    /// --initial-memory-pages, ignored by NEAR.
+    #[arg(long, value_name = "PAGES")]
+    max_memory_pages: Option<u32>,
+
+    /// Don't export `contract_source_metadata` (NEP-330).
+    ///
+    /// By default NEAR builds export this view method, returning the
+    /// contract's version, a link to its source, the standards it
+    /// implements, and, in a git checkout with an `origin` remote, the
+    /// commit and options it was built from. A contract that defines its own
+    /// `contract_source_metadata` keeps it.
+    #[arg(long)]
+    no_source_metadata: bool,
+
+    /// Version in the source metadata [default: `git describe --tags
+    /// --always` of the contract's checkout]
+    #[arg(long, value_name = "VERSION", conflicts_with = "no_source_metadata")]
+    source_version: Option<String>,
+
+    /// Link to the source in the source metadata [default: the `origin`
+    /// remote, as an https URL]
+    #[arg(long, value_name = "URL", conflicts_with = "no_source_metadata")]
+    source_link: Option<String>,
+
+    /// A standard the contract implements, listed in the source metadata as
+    /// NAME:VERSION, e.g. `nep141:1.0.0` (repeatable). `nep330` is always
+    /// listed.
+    #[arg(
+        long = "standard",
+        value_name = "NAME:VERSION",
+        conflicts_with = "no_source_metadata"
+    )]
+    standards: Vec<String>,
+
+    /// Fail the build if the contract calls a NEAR host function not listed
+    /// (repeatable), to limit what it can do. A name ending in `*` allows
+    /// every function it prefixes, e.g. `storage_*`. [default: any]
+    #[arg(long = "allow-host-function", value_name = "NAME")]
+    allowed_host_functions: Vec<String>,
+
+    /// Report LINT as an error (repeatable). Overrides `[lints]` in
+    /// monty-near.toml, as do -W and -A; naming a lint more than once, -D
+    /// wins over -W, and -W over -A.
+    #[arg(short = 'D', long = "deny", value_name = "LINT")]
+    deny: Vec<String>,
+
+    /// Report LINT as a warning (repeatable)
+    #[arg(short = 'W', long = "warn", value_name = "LINT")]
+    warn: Vec<String>,
+
+    /// Don't report LINT (repeatable)
+    #[arg(short = 'A', long = "allow", value_name = "LINT")]
+    allow: Vec<String>,
+
+    /// Report every warning as an error, e.g. in CI
+    #[arg(long)]
+    deny_warnings: bool,
+
+    /// The resolved lint levels, filled in from monty-near.toml.
+    #[arg(skip)]
+    lints: Lints,
+
+    /// The resolved `--profile`, filled in from monty-near.toml.
+    #[arg(skip)]
+    build_profile: config::Profile,
+
+    /// Output format.
+    ///
+    /// `component` (experimental) builds a WebAssembly component exporting
+    /// `run(method, input) -> string` for use outside NEAR. Only `input`,
+    /// `value_return`, and `log` work there; other host functions trap.
+    /// Requires wasm-tools.
+    #[arg(long, value_enum, default_value_t = OutputFormat::Near)]
+    format: OutputFormat,
+
+    /// How to report findings from the static checks.
+    ///
+    /// `sarif` writes a SARIF 2.1.0 log to stdout (even when there are no
+    /// findings) for GitHub code scanning and similar tools. `json` writes
+    /// one JSON object per finding to stdout, and one for a build that
+    /// fails later, for editor plugins and CI annotations.
+    #[arg(
+        long,
+        alias = "error-format",
+        value_enum,
+        default_value_t = DiagnosticsFormat::Human
+    )]
+    diagnostics_format: DiagnosticsFormat,
+}
+
+#[derive(Args)]
+struct BuildCommand {
+    #[command(flatten)]
+    build: BuildArgs,
+
+    /// Run every check a build would (options, installed tools, parsing,
+    /// exports, host functions, Monty compilation) and report all problems
+    /// without invoking cargo or writing any files.
+    #[arg(long, conflicts_with = "wasm_opt_report")]
+    preflight_only: bool,
+
+    /// Keep running and rebuild whenever the contract source changes
+    #[arg(long, conflicts_with = "preflight_only")]
+    watch: bool,
+
+    /// Build both a standard and a `--compat` artifact (`<output>.wasm` and
+    /// `<output>-compat.wasm`), compiling the Python once and running the two
+    /// cargo builds concurrently
+    #[arg(long, conflicts_with_all = ["preflight_only", "watch", "compat", "no_compat"])]
+    all_targets: bool,
+
+    /// Run the build inside a pinned container image, so the same source
+    /// and options give a bit-identical WASM on any machine. Requires Docker.
+    ///
+    /// The image fixes the Rust toolchains and wasm-opt, and the generated
+    /// project uses the Monty revision this CLI was built with. It's built
+    /// on first use; the project (the git checkout, or the current
+    /// directory) is mounted at the same path, so paths work unchanged but
+    /// must stay inside it.
+    #[arg(long, conflicts_with_all = ["preflight_only", "watch", "wasm_opt_path"])]
+    reproducible: bool,
+
+    /// With --reproducible, build in IMAGE instead of the image built from
+    /// the CLI's pinned Dockerfile, e.g. one published by your team
+    #[arg(long, value_name = "IMAGE", requires = "reproducible")]
+    image: Option<String>,
+
+    /// With --watch, start a local sandbox and redeploy each successful
+    /// build to a dev account in it
+    #[arg(long, requires = "watch")]
+    deploy: bool,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, ValueEnum)]
+enum OutputFormat {
+    /// NEAR contract module with one export per method
+    Near,
+    /// WebAssembly component without NEAR host imports (experimental)
+    Component,
+}
+
+#[derive(Clone, Copy, Default, PartialEq, Eq, ValueEnum, serde::Deserialize)]
+enum WasmOptLevel {
+    #[value(name = "O1")]
+    O1,
+    #[value(name = "O2")]
+    O2,
+    #[value(name = "O3")]
+    O3,
+    #[value(name = "O4")]
+    O4,
+    #[value(name = "Os")]
+    Os,
+    #[default]
+    #[value(name = "Oz")]
+    Oz,
+}
+
+impl WasmOptLevel {
+    fn flag(self) -> &'static str {
+        match self {
+            WasmOptLevel::O1 => "-O1",
+            WasmOptLevel::O2 => "-O2",
+            WasmOptLevel::O3 => "-O3",
+            WasmOptLevel::O4 => "-O4",
+            WasmOptLevel::Os => "-Os",
+            WasmOptLevel::Oz => "-Oz",
+        }
+    }
+}
+
+#[derive(Clone, Copy, Default, PartialEq, Eq, ValueEnum, serde::Deserialize)]
+#[serde(rename_all = "kebab-case")]
+enum Allocator {
+    /// Rust's default allocator for wasm32
+    #[default]
+    Dlmalloc,
+    /// Smaller code, slower allocation (the crate is no longer maintained)
+    WeeAlloc,
+    /// Never frees memory; smallest and cheapest, for contracts whose calls
+    /// don't allocate more than the memory NEAR gives one call
+    Bump,
+}
+
+impl Allocator {
+    /// Code spliced into lib.rs, as its path under template/ and the
+    /// built-in, and the dependency it needs in Cargo.toml.
+    fn template(self) -> (Option<(&'static str, &'static str)>, Option<&'static str>) {
+        match self {
+            Allocator::Dlmalloc => (None, None),
+            Allocator::WeeAlloc => (
+                Some(("alloc/wee_alloc.rs", TEMPLATE_ALLOC_WEE_ALLOC)),
+                Some("wee_alloc = \"0.4\"\n"),
+            ),
+            Allocator::Bump => (Some(("alloc/bump.rs", TEMPLATE_ALLOC_BUMP)), None),
+        }
+    }
+}
+
+#[derive(Args)]
+struct AbiArgs {
+    /// Path to the Python source file
+    input: PathBuf,
+
+    /// Where to write the ABI [default: stdout]
+    #[arg(short, long)]
+    output: Option<PathBuf>,
+}
+
+#[derive(Args)]
+struct StubsArgs {
+    /// Directory to write `near.pyi` and `__builtins__.pyi` to
+    #[arg(long, default_value = ".")]
+    out_dir: PathBuf,
+}
+
+#[derive(Args)]
+struct DoctorArgs {
+    /// Also check what --compat builds need (nightly + rust-src)
+    #[arg(long)]
+    compat: bool,
+}
+
+#[derive(Args)]
+struct SizeArgs {
+    /// Path to the built WASM contract
+    wasm: PathBuf,
+
+    /// Number of largest functions to list
+    #[arg(long, default_value_t = 20)]
+    top: usize,
+
+    /// Build directory to look for bytecode blobs in, if the contract was
+    /// built with --build-dir
+    #[arg(long, value_name = "DIR")]
+    build_dir: Option<PathBuf>,
+}
+
+#[derive(Args)]
+struct CleanArgs {
+    /// Also remove the compiled dependency cache (the next build starts
+    /// from scratch)
+    #[arg(long)]
+    all: bool,
+
+    /// Clean this build directory instead of every one under
+    /// target/monty-near-build/
+    #[arg(long, value_name = "DIR")]
+    build_dir: Option<PathBuf>,
+
+    /// Also remove the per-user target cache every project shares
+    /// (~/.cache/monty-near/target)
+    #[arg(long)]
+    shared_cache: bool,
+
+    /// Also remove the cached builds (~/.cache/monty-near/builds)
+    #[arg(long)]
+    build_cache: bool,
+}
+
+#[derive(Args)]
+struct InitArgs {
+    /// Project directory to create; its name becomes the contract name
+    path: PathBuf,
+
+    /// Contract name, if it should differ from the directory name
+    #[arg(long)]
+    name: Option<String>,
+}
+
+#[derive(Args)]
+struct NewArgs {
+    /// Path of the Python file to create
+    #[arg(default_value = "contract.py")]
+    path: PathBuf,
+
+    /// Which starter contract to write
+    #[arg(long, value_enum)]
+    template: init::ContractTemplate,
+}
+
+impl BuildArgs {
+    /// The options `build` would take from `args`, for commands that build
+    /// a contract of their own, so every other flag keeps its default.
+    fn from_args<'a>(args: impl IntoIterator<Item = &'a std::ffi::OsStr>) -> Result<BuildArgs> {
+        let command = BuildArgs::augment_args(clap::Command::new("build"));
+        let matches = command
+            .try_get_matches_from(std::iter::once(std::ffi::OsStr::new("build")).chain(args))?;
+        Ok(BuildArgs::from_arg_matches(&matches)?)
+    }
+
+    /// The contract source. Always set once `Config::apply` has run.
+    fn input(&self) -> &Path {
+        self.input
+            .as_deref()
+            .expect("input is resolved from monty-near.toml before commands run")
+    }
+}
+
+/// Where to deploy, for commands that deploy to an existing account.
+#[derive(Args)]
+struct DeployTargetArgs {
+    /// Account to deploy to; its key is read from ~/.near-credentials
+    /// [default: from monty-near.toml]
+    #[arg(long, alias = "account")]
+    account_id: Option<String>,
+
+    /// Network name (mainnet, testnet) [default: from monty-near.toml, or
+    /// testnet]
+    #[arg(long)]
+    network: Option<String>,
+
+    /// Named deploy target from a `[deploy.<name>]` table in monty-near.toml
+    #[arg(long)]
+    target: Option<String>,
+}
+
+#[derive(Args)]
+struct DeployArgs {
+    #[command(flatten)]
+    build: BuildArgs,
+
+    #[command(flatten)]
+    target: DeployTargetArgs,
+}
+
+#[derive(Args)]
+struct UpgradeArgs {
+    #[command(flatten)]
+    build: BuildArgs,
+
+    #[command(flatten)]
+    target: DeployTargetArgs,
+
+    /// Method of the new build to call in the same transaction as the deploy
+    #[arg(long, value_name = "METHOD")]
+    migrate: Option<String>,
+
+    /// Raw input passed to the migration method (read with `input()`)
+    #[arg(long, default_value = "", requires = "migrate")]
+    migrate_args: String,
+
+    /// Gas attached to the migration call
+    #[arg(long, default_value_t = tx::DEFAULT_GAS, requires = "migrate")]
+    gas: u64,
+
+    /// ABI of the deployed version (from `monty-near abi`), to also detect
+    /// changed parameter and result types
+    #[arg(long, value_name = "FILE")]
+    previous_abi: Option<PathBuf>,
+
+    /// Upgrade even if methods were removed or their signatures changed
+    #[arg(long)]
+    force: bool,
+}
+
+#[derive(Args)]
+struct DevDeployArgs {
+    #[command(flatten)]
+    build: BuildArgs,
+
+    /// Where to create the dev account.
+    ///
+    /// `testnet` creates the account through the faucet and saves its key to
+    /// ~/.near-credentials/testnet/. `sandbox` starts a local near-sandbox
+    /// and keeps it running until interrupted.
+    #[arg(long, value_enum, default_value_t = DevNetwork::Testnet)]
+    network: DevNetwork,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, ValueEnum)]
+enum DevNetwork {
+    Testnet,
+    Sandbox,
+}
+
+#[derive(Args)]
+struct CallArgs {
+    /// Account the contract is deployed on
+    contract_id: String,
+
+    /// Contract method to call
+    method: String,
+
+    /// Raw input passed to the method (read with `input()`)
+    #[arg(long, default_value = "")]
+    args: String,
+
+    /// Account that signs the call [default: the contract account]; its key
+    /// is read from ~/.near-credentials
+    #[arg(long)]
+    account_id: Option<String>,
+
+    /// Attached deposit in yoctoNEAR
+    #[arg(long, default_value_t = 0)]
+    deposit: u128,
+
+    /// Gas to attach
+    #[arg(long, default_value_t = tx::DEFAULT_GAS)]
+    gas: u64,
+
+    /// Network name (mainnet, testnet)
+    #[arg(long, default_value = "testnet")]
+    network: String,
+}
+
+#[derive(Args)]
+struct ViewArgs {
+    /// Account the contract is deployed on
+    contract_id: String,
+
+    /// Contract method to run
+    method: String,
+
+    /// Raw input passed to the method (read with `input()`)
+    #[arg(long, default_value = "")]
+    args: String,
+
+    /// Network name (mainnet, testnet) or RPC URL
+    #[arg(long, default_value = "testnet")]
+    network: String,
+}
+
+#[derive(Args)]
+struct StateArgs {
+    /// Account the contract is deployed on
+    contract_id: String,
+
+    /// Only show keys starting with this prefix
+    #[arg(long, default_value = "")]
+    prefix: String,
+
+    /// Output format
+    #[arg(long, value_enum, default_value_t = state::StateFormat::Text)]
+    format: state::StateFormat,
+
+    /// Network name (mainnet, testnet) or RPC URL
+    #[arg(long, default_value = "testnet")]
+    network: String,
+}
+
+#[derive(Args)]
+struct RunArgs {
+    /// Path to the Python source file
+    contract: PathBuf,
+
+    /// Contract method to run
+    method: String,
+
+    /// Raw input passed to the method (read with `input()`)
+    #[arg(long = "input", value_name = "DATA", default_value = "")]
+    args: String,
+
+    /// JSON file holding contract storage; read before the call and written
+    /// back after it succeeds [default: start from empty storage]
+    #[arg(long, value_name = "FILE")]
+    state: Option<PathBuf>,
+
+    /// Account the contract runs as
+    #[arg(long, default_value = "contract.test.near")]
+    account_id: String,
+
+    /// Account calling the method
+    #[arg(long, default_value = "alice.test.near")]
+    predecessor: String,
+
+    /// Account that signed the transaction [default: the predecessor]
+    #[arg(long)]
+    signer: Option<String>,
+
+    /// Attached deposit in yoctoNEAR
+    #[arg(long, default_value_t = 0)]
+    deposit: u128,
+
+    /// Block height reported to the contract
+    #[arg(long, default_value_t = 1)]
+    block_height: u64,
+
+    /// Block timestamp in nanoseconds reported to the contract
+    #[arg(long, default_value_t = 1_700_000_000_000_000_000)]
+    block_timestamp: u64,
+
+    /// Seed that `random_seed()` is derived from
+    #[arg(long, default_value_t = 0)]
+    seed: u64,
+}
+
+#[derive(Args)]
+struct TestArgs {
+    #[command(flatten)]
+    build: BuildArgs,
+
+    /// Test files to run [default: test_*.py next to the contract and in
+    /// tests/]
+    #[arg(long = "tests", value_name = "FILE")]
+    tests: Vec<PathBuf>,
+}
+
+#[derive(Args)]
+struct GasBenchArgs {
+    #[command(flatten)]
+    build: BuildArgs,
+
+    /// Contract method to call
+    method: String,
+
+    /// Raw input passed to the method (read with `input()`)
+    #[arg(long, default_value = "")]
+    args: String,
+
+    /// Number of calls to measure
+    #[arg(short = 'n', long, default_value_t = 5)]
+    repeat: usize,
+
+    /// Attached deposit in yoctoNEAR
+    #[arg(long, default_value_t = 0)]
+    deposit: u128,
+}
+
+/// Which methods to call and with what, shared by `gas-profile` and `bench`.
+#[derive(Args)]
+struct MethodCallArgs {
+    /// Sample input for a method, as METHOD=DATA (repeatable); methods
+    /// without one are called with empty input
+    #[arg(long = "args", value_name = "METHOD=DATA")]
+    args: Vec<String>,
+
+    /// Only call these methods, in this order (repeatable) [default: every
+    /// exported method, in source order]
+    #[arg(long = "method", value_name = "NAME")]
+    methods: Vec<String>,
+
+    /// Attached deposit in yoctoNEAR for every call
+    #[arg(long, default_value_t = 0)]
+    deposit: u128,
+}
+
+#[derive(Args)]
+struct BenchArgs {
+    #[command(flatten)]
+    build: BuildArgs,
+
+    #[command(flatten)]
+    calls: MethodCallArgs,
+
+    /// Number of calls to measure per method
+    #[arg(short = 'n', long, default_value_t = 5)]
+    repeat: usize,
+
+    /// Write the results as a baseline JSON file
+    #[arg(long, value_name = "FILE")]
+    save_baseline: Option<PathBuf>,
+
+    /// Compare against a baseline JSON file and fail on gas regressions
+    #[arg(long, value_name = "FILE")]
+    baseline: Option<PathBuf>,
+
+    /// Allowed increase in median gas over the baseline, in percent
+    #[arg(long, default_value_t = 1.0, requires = "baseline")]
+    threshold: f64,
+}
+
+#[derive(Args)]
+struct GasProfileArgs {
+    #[command(flatten)]
+    build: BuildArgs,
+
+    #[command(flatten)]
+    calls: MethodCallArgs,
+
+    /// Also list the cost of each host function per method
+    #[arg(long)]
+    detail: bool,
+}
+
+#[derive(Args)]
+struct VerifyOnchainArgs {
+    #[command(flatten)]
+    build: BuildArgs,
+
+    /// Account whose deployed contract to compare against
+    #[arg(long)]
+    account: String,
+
+    /// Network name (mainnet, testnet) or RPC URL
+    #[arg(long, default_value = "testnet")]
+    network: String,
+
+    /// Rebuild in the pinned container image, as `build --reproducible`
+    /// does, so the local toolchains don't affect the hash
+    #[arg(long, conflicts_with = "wasm_opt_path")]
+    reproducible: bool,
+
+    /// With --reproducible, build in IMAGE instead of the image built from
+    /// the CLI's pinned Dockerfile, e.g. the one the deployment was built in
+    #[arg(long, value_name = "IMAGE", requires = "reproducible")]
+    image: Option<String>,
+}
+
+#[derive(Args)]
+struct CompletionsArgs {
+    /// Shell to generate completions for
+    shell: clap_complete::Shell,
+}
+
+#[derive(Args)]
+struct SelfTestArgs {
+    /// Build for the current production NearVM (needed to run the calls on
+    /// a release near-sandbox)
+    #[arg(long)]
+    compat: bool,
+
+    /// Skip wasm-opt post-processing
+    #[arg(long)]
+    no_wasm_opt: bool,
 }
 
 // ---------------------------------------------------------------------------
 // External NEAR functions available to Python contracts
 // ---------------------------------------------------------------------------
 
-fn near_external_functions() -> Vec<String> {
-    [
-        // Existing
+/// Every NEAR host function callable from Python, as (name, Python
+/// signature, description). template/src/lib.rs implements each one;
+/// `stubs` turns this table into a `near.pyi`.
+const NEAR_HOST_FUNCTIONS: &[(&str, &str, &str)] = &[
+    // Core
+    (
         "value_return",
+        "(value: str | bytes) -> None",
+        "Set the method's return value.",
+    ),
+    (
         "input",
+        "() -> str",
+        "The method's input (`bytes` if it is not valid UTF-8).",
+    ),
+    (
         "log",
+        "(message: str) -> None",
+        "Write a message to the transaction logs.",
+    ),
+    (
         "storage_write",
+        "(key: str, value: str) -> bool",
+        "Store `value` under `key`; True if it replaced an existing value.",
+    ),
+    (
         "storage_read",
+        "(key: str) -> str | None",
+        "The value stored under `key`, or None.",
+    ),
+    (
         "storage_remove",
+        "(key: str) -> bool",
+        "Delete `key`; True if it existed.",
+    ),
+    (
         "storage_has_key",
+        "(key: str) -> bool",
+        "Whether a value is stored under `key`.",
+    ),
+    (
         "current_account_id",
+        "() -> str",
+        "The account the contract is deployed on.",
+    ),
+    (
         "predecessor_account_id",
+        "() -> str",
+        "The account that called this method.",
+    ),
+    (
         "signer_account_id",
+        "() -> str",
+        "The account that signed the original transaction.",
+    ),
+    (
         "block_height",
+        "() -> int",
+        "Height of the current block.",
+    ),
+    (
         "block_timestamp",
+        "() -> int",
+        "Timestamp of the current block in nanoseconds.",
+    ),
+    (
         "sha256",
+        "(data: str | bytes) -> str",
+        "SHA-256 of `data`, hex-encoded.",
+    ),
+    (
         "keccak256",
-        // Context
+        "(data: str | bytes) -> str",
+        "Keccak-256 of `data`, hex-encoded.",
+    ),
+    // Context
+    (
         "signer_account_pk",
+        "() -> str",
+        "Public key of the transaction signer, hex-encoded with a key-type prefix byte.",
+    ),
+    (
         "epoch_height",
+        "() -> int",
+        "Current epoch height.",
+    ),
+    (
         "storage_usage",
-        // Economics
+        "() -> int",
+        "Bytes of storage used by the contract account.",
+    ),
+    // Economics
+    (
         "account_balance",
+        "() -> str",
+        "Balance of the contract account in yoctoNEAR, as a decimal string.",
+    ),
+    (
         "account_locked_balance",
+        "() -> str",
+        "Locked (staked) balance in yoctoNEAR, as a decimal string.",
+    ),
+    (
         "attached_deposit",
+        "() -> str",
+        "Deposit attached to this call in yoctoNEAR, as a decimal string.",
+    ),
+    (
         "prepaid_gas",
+        "() -> int",
+        "Gas attached to this call.",
+    ),
+    (
         "used_gas",
-        // Math
+        "() -> int",
+        "Gas burnt so far by this call.",
+    ),
+    // Math
+    (
         "random_seed",
+        "() -> str",
+        "32 random bytes from the block's VRF, hex-encoded.",
+    ),
+    (
         "keccak512",
+        "(data: str | bytes) -> str",
+        "Keccak-512 of `data`, hex-encoded.",
+    ),
+    (
         "ripemd160",
+        "(data: str | bytes) -> str",
+        "RIPEMD-160 of `data`, hex-encoded.",
+    ),
+    (
         "ecrecover",
+        "(hash: str | bytes, signature: str | bytes, v: int, malleability_flag: int) -> str | None",
+        "Recover a secp256k1 public key (hex) from a signature; hex strings or bytes in. None if recovery fails.",
+    ),
+    (
         "ed25519_verify",
-        // Promises
+        "(signature: str | bytes, message: str | bytes, public_key: str | bytes) -> bool",
+        "Verify an ed25519 signature; `signature` and `public_key` as hex strings or bytes.",
+    ),
+    // Promises
+    (
         "promise_create",
+        "(account_id: str, function_name: str, arguments: str | bytes, amount: str | int, gas: int) -> int",
+        "Call `function_name` on `account_id` asynchronously; returns a promise index.",
+    ),
+    (
         "promise_then",
+        "(promise_index: int, account_id: str, function_name: str, arguments: str | bytes, amount: str | int, gas: int) -> int",
+        "Call `function_name` on `account_id` after `promise_index` completes; returns a promise index.",
+    ),
+    (
         "promise_and",
+        "(*promise_indices: int) -> int",
+        "A promise that completes when all of `promise_indices` have.",
+    ),
+    (
         "promise_batch_create",
+        "(account_id: str) -> int",
+        "Start an empty batch of actions on `account_id`; returns a promise index.",
+    ),
+    (
         "promise_batch_then",
+        "(promise_index: int, account_id: str) -> int",
+        "Start an empty batch on `account_id` that runs after `promise_index`.",
+    ),
+    (
         "promise_results_count",
+        "() -> int",
+        "Number of promise results available to this callback.",
+    ),
+    (
         "promise_result",
+        "(result_idx: int) -> str | None",
+        "The value of a successful promise result, or None if it failed.",
+    ),
+    (
         "promise_return",
-        // Promise batch actions
+        "(promise_index: int) -> None",
+        "Make the result of `promise_index` this method's return value.",
+    ),
+    (
+        "promise_yield_create",
+        "(function_name: str, arguments: str | bytes, gas: int, gas_weight: int = 1) -> tuple[int, str]",
+        "Create a promise calling this contract's `function_name` once resumed (NEP-519); returns its index and the hex yield ID.",
+    ),
+    (
+        "promise_yield_resume",
+        "(data_id: str | bytes, payload: str | bytes) -> bool",
+        "Resume yield `data_id` with `payload` as its result; False if it was already resumed or timed out.",
+    ),
+    // Promise batch actions
+    (
         "promise_batch_action_create_account",
+        "(promise_index: int) -> None",
+        "Add a CreateAccount action to a batch.",
+    ),
+    (
         "promise_batch_action_deploy_contract",
+        "(promise_index: int, code: str | bytes) -> None",
+        "Add a DeployContract action to a batch.",
+    ),
+    (
         "promise_batch_action_function_call",
+        "(promise_index: int, function_name: str, arguments: str | bytes, amount: str | int, gas: int) -> None",
+        "Add a FunctionCall action to a batch.",
+    ),
+    (
         "promise_batch_action_function_call_weight",
+        "(promise_index: int, function_name: str, arguments: str | bytes, amount: str | int, gas: int, weight: int = 1) -> None",
+        "Add a FunctionCall action that also gets a `weight` share of unused gas.",
+    ),
+    (
         "promise_batch_action_transfer",
+        "(promise_index: int, amount: str | int) -> None",
+        "Add a Transfer of `amount` yoctoNEAR to a batch.",
+    ),
+    (
         "promise_batch_action_stake",
+        "(promise_index: int, amount: str | int, public_key: str | bytes) -> None",
+        "Add a Stake action to a batch.",
+    ),
+    (
         "promise_batch_action_add_key_with_full_access",
+        "(promise_index: int, public_key: str | bytes, nonce: int) -> None",
+        "Add a full-access key to the batch's account.",
+    ),
+    (
         "promise_batch_action_add_key_with_function_call",
+        "(promise_index: int, public_key: str | bytes, nonce: int, allowance: str | int, receiver_id: str, function_names: str) -> None",
+        "Add a function-call access key; `function_names` is comma-separated.",
+    ),
+    (
         "promise_batch_action_delete_key",
+        "(promise_index: int, public_key: str | bytes) -> None",
+        "Delete a key from the batch's account.",
+    ),
+    (
         "promise_batch_action_delete_account",
-        // Validator
+        "(promise_index: int, beneficiary_id: str) -> None",
+        "Delete the batch's account, sending its balance to `beneficiary_id`.",
+    ),
+    (
+        "promise_batch_action_deploy_global_contract",
+        "(promise_index: int, code: str | bytes) -> None",
+        "Add a DeployGlobalContract action (NEP-591); other accounts use the code by its hash.",
+    ),
+    (
+        "promise_batch_action_deploy_global_contract_by_account_id",
+        "(promise_index: int, code: str | bytes) -> None",
+        "Add a DeployGlobalContract action (NEP-591); other accounts use the code by the batch's account ID.",
+    ),
+    (
+        "promise_batch_action_use_global_contract",
+        "(promise_index: int, code_hash: str | bytes) -> None",
+        "Add a UseGlobalContract action for the code with this hash, hex string or bytes.",
+    ),
+    (
+        "promise_batch_action_use_global_contract_by_account_id",
+        "(promise_index: int, account_id: str) -> None",
+        "Add a UseGlobalContract action for the code `account_id` deployed by account ID.",
+    ),
+    // Validator
+    (
         "validator_stake",
+        "(account_id: str) -> str",
+        "Stake of validator `account_id` in yoctoNEAR, as a decimal string.",
+    ),
+    (
         "validator_total_stake",
-        // Alt BN128
+        "() -> str",
+        "Total validator stake in yoctoNEAR, as a decimal string.",
+    ),
+    // Alt BN128
+    (
         "alt_bn128_g1_multiexp",
+        "(data: str | bytes) -> str",
+        "Multi-exponentiation on alt_bn128 G1, hex-encoded.",
+    ),
+    (
         "alt_bn128_g1_sum",
+        "(data: str | bytes) -> str",
+        "Sum of alt_bn128 G1 points, hex-encoded.",
+    ),
+    (
         "alt_bn128_pairing_check",
-        // BLS12-381
+        "(data: str | bytes) -> bool",
+        "alt_bn128 pairing check.",
+    ),
+    // BLS12-381
+    (
         "bls12381_p1_sum",
+        "(data: str | bytes) -> str | None",
+        "Sum of BLS12-381 G1 points, hex-encoded; None on invalid input.",
+    ),
+    (
         "bls12381_p2_sum",
+        "(data: str | bytes) -> str | None",
+        "Sum of BLS12-381 G2 points, hex-encoded; None on invalid input.",
+    ),
+    (
         "bls12381_g1_multiexp",
+        "(data: str | bytes) -> str | None",
+        "Multi-exponentiation on BLS12-381 G1, hex-encoded; None on invalid input.",
+    ),
+    (
         "bls12381_g2_multiexp",
+        "(data: str | bytes) -> str | None",
+        "Multi-exponentiation on BLS12-381 G2, hex-encoded; None on invalid input.",
+    ),
+    (
         "bls12381_map_fp_to_g1",
+        "(data: str | bytes) -> str | None",
+        "Map field elements to G1 points, hex-encoded; None on invalid input.",
+    ),
+    (
         "bls12381_map_fp2_to_g2",
+        "(data: str | bytes) -> str | None",
+        "Map Fp2 elements to G2 points, hex-encoded; None on invalid input.",
+    ),
+    (
         "bls12381_pairing_check",
+        "(data: str | bytes) -> bool",
+        "BLS12-381 pairing check.",
+    ),
+    (
         "bls12381_p1_decompress",
+        "(data: str | bytes) -> str | None",
+        "Decompress G1 points, hex-encoded; None on invalid input.",
+    ),
+    (
         "bls12381_p2_decompress",
-    ]
-    .iter()
-    .map(|s| s.to_string())
-    .collect()
+        "(data: str | bytes) -> str | None",
+        "Decompress G2 points, hex-encoded; None on invalid input.",
+    ),
+];
+
+fn near_external_functions() -> Vec<String> {
+    NEAR_HOST_FUNCTIONS
+        .iter()
+        .map(|(name, _, _)| name.to_string())
+        .collect()
 }
 
 // ---------------------------------------------------------------------------
 // Python source parsing — find exported top-level functions
 // ---------------------------------------------------------------------------
 
-/// Find top-level function names that don't start with `_`.
+/// Read a contract source file and canonicalize it for parsing.
+fn read_source(input: &Path) -> Result<String> {
+    let source =
+        fs::read_to_string(input).with_context(|| format!("failed to read {}", input.display()))?;
+    Ok(canonicalize_source(&source))
+}
+
+/// Strip a UTF-8 BOM and normalize CRLF / lone CR line endings to LF.
 ///
-/// Uses ruff's Python parser (the same parser Monty uses) to walk the AST
-/// rather than fragile string matching on `def ` prefixes.
-fn find_exported_functions(source: &str) -> Result<Vec<String>> {
-    let parsed = parse_module(source).map_err(|e| anyhow::anyhow!("Python parse error: {e}"))?;
-    let module = parsed.into_syntax();
+/// Editors on different platforms save the same contract with different
+/// bytes; normalizing first keeps parsing and the compiled bytecode identical.
+fn canonicalize_source(source: &str) -> String {
+    let source = source.strip_prefix('\u{feff}').unwrap_or(source);
+    source.replace("\r\n", "\n").replace('\r', "\n")
+}
 
-    let mut functions = Vec::new();
-    for stmt in &module.body {
-        if let Stmt::FunctionDef(func) = stmt {
-            let name = func.name.as_str();
-            if !name.starts_with('_') {
-                functions.push(name.to_string());
-            }
+/// A parsed contract and what the static checks learned about it.
+struct Contract {
+    module: ModModule,
+    methods: Vec<Method>,
+    /// Artifact name declared with `__contract_name__ = "..."`.
+    name: Option<String>,
+    /// The modules it imports.
+    bundle: imports::Bundle,
+}
+
+/// Parse and statically check the contract, collecting every finding into
+/// `diagnostics`. Returns `None` if the source didn't parse.
+fn analyze_contract(file: SourceFile, diagnostics: &mut Vec<Diagnostic>) -> Option<Contract> {
+    // ruff's Python parser is the same parser Monty uses
+    let module = match parse_module(file.text) {
+        Ok(parsed) => parsed.into_syntax(),
+        Err(e) => {
+            let offset = e.location.start().to_usize();
+            diagnostics.push(
+                file.error(
+                    "parse-error",
+                    offset,
+                    format!("Python parse error: {}", e.error),
+                )
+                .with_frame(file.frame(offset)),
+            );
+            return None;
         }
+    };
+
+    let mut bundle = imports::resolve(file, &module, diagnostics);
+    let methods = find_exported_functions(&module, file, diagnostics);
+    check_view_writes(&module, &methods, file, diagnostics);
+    check_unused_functions(&module, &methods, file, diagnostics);
+    if let Some(class) = contract_class::find_class(&module) {
+        contract_class::check_class(&module, class, file, diagnostics);
     }
-    Ok(functions)
+    check_host_calls(&module, file, diagnostics);
+    check_top_level_effects(&module, file, diagnostics);
+    unsupported::check(&module, file, imports::root(file.path), diagnostics);
+    bundle.builtins.events |= events::check(&module, file, diagnostics);
+    bundle.builtins.collections |= collections::check(&module, file, diagnostics);
+    bundle.builtins.promises |= promises::check(&module, file, diagnostics);
+    bundle.builtins.promises |= async_calls::check(&module, file, true, diagnostics);
+    bundle.builtins.yields |= yield_resume::check(&module, file, diagnostics);
+    bundle.builtins.deposits |= calls_helper(&module, DEPOSIT_HELPERS);
+    let name = find_contract_name(&module, file, diagnostics);
+    Some(Contract {
+        module,
+        methods,
+        name,
+        bundle,
+    })
 }
 
-// ---------------------------------------------------------------------------
-// Pre-compilation — compile source + dispatcher to single Monty bytecode blob
-// ---------------------------------------------------------------------------
+/// Read a module-level `__contract_name__ = "..."`, used to name the output
+/// file and the generated crate. The last assignment wins, as in Python.
+fn find_contract_name(
+    module: &ModModule,
+    file: SourceFile,
+    diagnostics: &mut Vec<Diagnostic>,
+) -> Option<String> {
+    let mut name = None;
+    for stmt in &module.body {
+        let Stmt::Assign(assign) = stmt else {
+            continue;
+        };
+        let [Expr::Name(target)] = &assign.targets[..] else {
+            continue;
+        };
+        if target.id.as_str() != "__contract_name__" {
+            continue;
+        }
 
-/// Generate a Python dispatcher that routes `_method` to the correct function.
-fn generate_dispatcher(method_names: &[String]) -> String {
-    let mut dispatcher = String::new();
-    for (i, name) in method_names.iter().enumerate() {
-        if i == 0 {
-            dispatcher.push_str(&format!("if _method == \"{name}\":\n    {name}()\n"));
-        } else {
-            dispatcher.push_str(&format!("elif _method == \"{name}\":\n    {name}()\n"));
+        let offset = assign.range.start().to_usize();
+        let Expr::StringLiteral(lit) = assign.value.as_ref() else {
+            diagnostics.push(file.error(
+                "invalid-contract-name",
+                offset,
+                "__contract_name__ must be a string literal",
+            ));
+            continue;
+        };
+        let value = lit.value.to_str();
+        if let Err(message) = validate_contract_name(value) {
+            diagnostics.push(file.error("invalid-contract-name", offset, message));
+            continue;
         }
+        name = Some(value.to_string());
     }
-    dispatcher
+    name
 }
 
-/// Compile the full source with a dispatcher into a single bytecode blob.
-fn precompile_contract(source: &str, method_names: &[String]) -> Result<Vec<u8>> {
-    let dispatcher = generate_dispatcher(method_names);
-    let program = format!("{source}\n\n{dispatcher}");
-    let external_functions = near_external_functions();
+/// Check a contract name is usable as both a file name and a crate name.
+fn validate_contract_name(name: &str) -> Result<(), String> {
+    let valid = name.starts_with(|c: char| c.is_ascii_lowercase())
+        && name
+            .chars()
+            .all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '_' || c == '-');
+    if !valid {
+        return Err(format!(
+            "contract name `{name}` must be lowercase letters, digits, `_`, \
+             and `-`, starting with a letter"
+        ));
+    }
+    if RESERVED_CRATE_NAMES.contains(&name.replace('-', "_").as_str()) {
+        return Err(format!(
+            "contract name `{name}` clashes with a crate the build depends on"
+        ));
+    }
+    Ok(())
+}
 
-    // `_method` is an input variable — the Rust runtime passes the method name at call time.
-    let runner = MontyRun::new(
-        program,
-        "contract.py",
-        vec!["_method".to_string()],
-        external_functions,
-    )
-    .map_err(|e| anyhow::anyhow!("compilation failed: {e}"))?;
+/// Crate names the generated project can't take: its own dependencies and
+/// the sysroot crates.
+const RESERVED_CRATE_NAMES: &[&str] = &[
+    "monty",
+    "near_sys",
+    "getrandom",
+    "std",
+    "core",
+    "alloc",
+    "proc_macro",
+    "test",
+];
 
-    runner.dump().context("serialization failed")
+/// An exported contract method and the attributes set by its decorators.
+#[derive(Clone)]
+struct Method {
+    /// The exported name callers use.
+    name: String,
+    /// The Python function the dispatcher calls: the method's own name,
+    /// unless `@export(name="...")` exports it as another.
+    function: String,
+    /// Bytecode group from `@group("...")`; `None` is the default group.
+    group: Option<String>,
+    kind: MethodKind,
+    /// `@payable`: the method accepts an attached deposit.
+    payable: bool,
+    /// `@one_yocto`: the method requires exactly 1 yoctoNEAR attached, so
+    /// only a full-access key can call it. Implies `payable`.
+    one_yocto: bool,
+    /// `@private`: only the contract account itself may call the method.
+    private: bool,
+    /// `@init`: the method initializes the contract, and may run only once.
+    init: bool,
+    /// The method refuses to run until an `@init` method has. Set on every
+    /// other method when one is `@init(required=True)`.
+    requires_init: bool,
+    /// `@owner_only`: only the contract's owner may call the method.
+    owner_only: bool,
+    /// A method of `class Contract`, whose fields are saved after it runs.
+    state: bool,
+    /// `@migrate`: a `class Contract` method that rewrites state stored with
+    /// an earlier layout. Its first parameter gets the stored values, and
+    /// the fields start from their defaults.
+    migrate: bool,
+    /// Parameters, filled from the JSON object the method is called with.
+    params: Vec<Param>,
+    /// `@callback`: the leading parameters, filled from the results of the
+    /// promises the method is a callback of, in order.
+    results: Vec<Param>,
+    /// The method has a `return <value>`, which the dispatcher passes to
+    /// `value_return`.
+    returns: bool,
+    /// Where the return annotation has a `U128`, the schema converting
+    /// `int` to decimal strings; see [`u128_schema`].
+    returns_u128: Option<String>,
+    /// `@raw_io`: the method reads `input()` itself, and what it returns is
+    /// passed to `value_return` as is rather than as JSON.
+    raw_io: bool,
+    /// `@borsh_io`: arguments and result are Borsh rather than JSON.
+    borsh: Option<borsh_io::Signature>,
 }
 
+/// A parameter of an exported method.
+#[derive(Clone)]
+struct Param {
+    name: String,
+    /// Source of the default value, used when the arguments leave it out.
+    default: Option<String>,
+    /// Declared after `*`, so it can only be passed by keyword.
+    keyword_only: bool,
+    /// The JSON type the annotation asks for, checked before the call.
+    check: Option<TypeCheck>,
+    /// Where the annotation has a `U128`, the schema converting decimal
+    /// strings to `int`; see [`u128_schema`].
+    u128: Option<String>,
+    /// The annotation allows `PromiseError`: for a promise result, a failed
+    /// promise is passed as `PromiseError` rather than failing the call.
+    failable: bool,
+}
+
+/// For a promise result's annotation, the annotation without `|
+/// PromiseError`, and whether it had it.
+fn split_promise_error(annotation: &Expr) -> (&Expr, bool) {
+    let is_error =
+        |expr: &Expr| matches!(expr, Expr::Name(name) if name.id.as_str() == "PromiseError");
+    match annotation {
+        Expr::BinOp(union) if union.op == Operator::BitOr => {
+            if is_error(&union.right) {
+                (&union.left, true)
+            } else if is_error(&union.left) {
+                (&union.right, true)
+            } else {
+                (annotation, false)
+            }
+        }
+        _ => (annotation, false),
+    }
+}
+
+/// Annotations for integers that JSON carries as decimal strings, as
+/// near-sdk's `U128` does: JavaScript numbers can't hold a balance.
+const U128_TYPES: &[&str] = &["U128", "Balance"];
+
+/// Where `annotation` has a [`U128_TYPES`] integer, a schema (as Python) for
+/// converting between JSON strings and `int`: `"u128"` for the integer
+/// itself, `["list", s]` and `["dict", s]` for items and values. `None` if
+/// there's nothing to convert.
+fn u128_schema(annotation: &Expr) -> Option<String> {
+    match annotation {
+        Expr::Name(name) if U128_TYPES.contains(&name.id.as_str()) => Some("\"u128\"".into()),
+        Expr::BinOp(union) if union.op == Operator::BitOr => {
+            match (union.left.as_ref(), union.right.as_ref()) {
+                (Expr::NoneLiteral(_), other) | (other, Expr::NoneLiteral(_)) => u128_schema(other),
+                _ => None,
+            }
+        }
+        Expr::Subscript(generic) => {
+            let Expr::Name(base) = generic.value.as_ref() else {
+                return None;
+            };
+            match (base.id.as_str(), generic.slice.as_ref()) {
+                ("Optional", item) => u128_schema(item),
+                ("list" | "List" | "set" | "Set", item) => {
+                    Some(format!("[\"list\", {}]", u128_schema(item)?))
+                }
+                ("dict" | "Dict", Expr::Tuple(tuple)) if tuple.elts.len() == 2 => {
+                    Some(format!("[\"dict\", {}]", u128_schema(&tuple.elts[1])?))
+                }
+                _ => None,
+            }
+        }
+        _ => None,
+    }
+}
+
+/// A check on an argument's JSON type, from a parameter annotation.
+#[derive(Clone, Copy)]
+struct TypeCheck {
+    /// The Python type: `int`, `float`, `str`, `bool`, `list`, or `dict`.
+    kind: &'static str,
+    /// `T | None` or `Optional[T]`: `null` passes too.
+    nullable: bool,
+}
+
+impl TypeCheck {
+    /// The check for `annotation`, if it names a type JSON can carry.
+    /// Unions other than with `None`, and contents of containers, aren't
+    /// checked.
+    fn of(annotation: &Expr) -> Option<Self> {
+        let nullable = |inner: &Expr| {
+            Self::of(inner).map(|check| TypeCheck {
+                nullable: true,
+                ..check
+            })
+        };
+        match annotation {
+            Expr::Name(name) => {
+                let kind = match name.id.as_str() {
+                    "int" => "int",
+                    "float" => "float",
+                    "str" => "str",
+                    "bool" => "bool",
+                    "list" | "List" | "tuple" | "Tuple" | "set" | "Set" => "list",
+                    "dict" | "Dict" => "dict",
+                    _ => return None,
+                };
+                Some(TypeCheck {
+                    kind,
+                    nullable: false,
+                })
+            }
+            Expr::Subscript(generic) => match generic.value.as_ref() {
+                Expr::Name(name) if name.id.as_str() == "Optional" => nullable(&generic.slice),
+                value => Self::of(value),
+            },
+            Expr::BinOp(union) if union.op == Operator::BitOr => {
+                match (union.left.as_ref(), union.right.as_ref()) {
+                    (Expr::NoneLiteral(_), other) | (other, Expr::NoneLiteral(_)) => {
+                        nullable(other)
+                    }
+                    _ => None,
+                }
+            }
+            _ => None,
+        }
+    }
+}
+
+impl Param {
+    /// The parameters of `func` that callers fill, skipping the `self` of a
+    /// `class Contract` method. `*args` and `**kwargs` are left out.
+    fn of(func: &StmtFunctionDef, state: bool, source: &str) -> Vec<Self> {
+        let params = &func.parameters;
+        let positional = params
+            .posonlyargs
+            .iter()
+            .chain(&params.args)
+            .map(|p| (p, false));
+        let keyword = params.kwonlyargs.iter().map(|p| (p, true));
+        positional
+            .chain(keyword)
+            .skip(usize::from(state))
+            .map(|(p, keyword_only)| {
+                let annotation = p.parameter.annotation.as_deref().map(split_promise_error);
+                Param {
+                    name: p.parameter.name.to_string(),
+                    default: p.default.as_deref().map(|d| source[d.range()].to_string()),
+                    keyword_only,
+                    check: annotation.and_then(|(a, _)| TypeCheck::of(a)),
+                    u128: annotation.and_then(|(a, _)| u128_schema(a)),
+                    failable: annotation.is_some_and(|(_, failable)| failable),
+                }
+            })
+            .collect()
+    }
+}
+
+/// Storage key the dispatcher writes when an `@init` method runs.
+const INIT_STATE_KEY: &str = "STATE_INITIALIZED";
+
+/// Name of the constant, or storage key, holding the owner account that
+/// `@owner_only` methods check the caller against.
+const OWNER_NAME: &str = "OWNER";
+
+/// Where `@owner_only` methods find the owner account.
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Owner {
+    /// A module-level `OWNER` constant, in the source or from `--define`.
+    Constant,
+    /// The `OWNER` storage key, which the contract writes (usually in its
+    /// `@init` method).
+    Storage,
+}
+
+impl Owner {
+    fn of(module: &ModModule, defines: &[Define]) -> Self {
+        let defined = defines.iter().any(|d| d.name == OWNER_NAME)
+            || module_assignments(module).any(|(name, _)| name.id.as_str() == OWNER_NAME);
+        if defined {
+            Owner::Constant
+        } else {
+            Owner::Storage
+        }
+    }
+
+    /// The owner account as a Python expression.
+    fn expression(self) -> String {
+        match self {
+            Owner::Constant => OWNER_NAME.to_string(),
+            Owner::Storage => format!("storage_read(\"{OWNER_NAME}\")"),
+        }
+    }
+}
+
+/// Whether a method may change state (`@call`, the default) or only reads it
+/// (`@view`).
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum MethodKind {
+    Call,
+    View,
+}
+
+/// Decorators the compiler understands. Monty itself doesn't support
+/// decorators, so these are removed from the source before compilation.
+const CONTRACT_DECORATORS: &[&str] = &[
+    "group",
+    "view",
+    "call",
+    "payable",
+    "one_yocto",
+    "private",
+    "init",
+    "owner_only",
+    "raw_io",
+    "borsh_io",
+    "callback",
+    "migrate",
+    "export",
+    "no_export",
+];
+
+/// Decorators that can't be combined, and why.
+const CONFLICTING_DECORATORS: &[(&str, &str, &str)] = &[
+    ("view", "call", "a method is one or the other"),
+    ("view", "payable", "view calls can't attach a deposit"),
+    ("view", "one_yocto", "view calls can't attach a deposit"),
+    (
+        "view",
+        "private",
+        "view calls have no predecessor account to check",
+    ),
+    ("view", "init", "initializing writes state"),
+    ("raw_io", "borsh_io", "a method's input has one format"),
+    ("callback", "borsh_io", "promise results are read as JSON"),
+    ("view", "callback", "callbacks are call methods"),
+    ("view", "migrate", "migrating writes state"),
+    (
+        "init",
+        "migrate",
+        "a migration rewrites state an earlier version stored",
+    ),
+    (
+        "callback",
+        "migrate",
+        "both fill the method's first parameter",
+    ),
+    (
+        "migrate",
+        "borsh_io",
+        "a migration's other arguments are JSON",
+    ),
+    (
+        "view",
+        "owner_only",
+        "view calls have no predecessor account to check",
+    ),
+];
+
+/// NEAR's `max_length_method_name`: longer names can't be called.
+const MAX_METHOD_NAME_LEN: usize = 256;
+
+/// Export names the WASM module already uses, and what for.
+const RESERVED_EXPORTS: &[(&str, &str)] = &[(
+    "memory",
+    "the runtime reads the contract's linear memory through it",
+)];
+
+/// Check that `name` can be a method's export: a NEAR method name of ASCII
+/// letters, digits and underscores, and not one of [`RESERVED_EXPORTS`] or
+/// `__`-prefixed, as the linker's and the runtime's own exports are.
+fn check_export_name(name: &str) -> Result<(), String> {
+    if let Some((_, reason)) = RESERVED_EXPORTS.iter().find(|(export, _)| *export == name) {
+        return Err(format!("`{name}` is a reserved export: {reason}"));
+    }
+    if name.starts_with("__") {
+        return Err(format!(
+            "`{name}` starts with `__`, which is reserved for the linker's and the runtime's exports"
+        ));
+    }
+    if name.len() > MAX_METHOD_NAME_LEN {
+        return Err(format!(
+            "`{name}` is {} bytes long; NEAR method names are at most {MAX_METHOD_NAME_LEN}",
+            name.len()
+        ));
+    }
+    if !name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_') {
+        return Err(format!(
+            "`{name}` isn't a valid method name: use ASCII letters, digits, and underscores, \
+             as NEAR tools and function-call access keys expect"
+        ));
+    }
+    Ok(())
+}
+
+/// Top-level functions, then the methods of `class Contract`, each with
+/// whether it's one of the methods.
+fn contract_functions(module: &ModModule) -> impl Iterator<Item = (&StmtFunctionDef, bool)> {
+    let top_level = module.body.iter().filter_map(|stmt| match stmt {
+        Stmt::FunctionDef(func) => Some((func, false)),
+        _ => None,
+    });
+    let methods = contract_class::find_class(module)
+        .into_iter()
+        .flat_map(|class| contract_class::methods(class).map(|func| (func, true)));
+    top_level.chain(methods)
+}
+
+/// Find top-level functions and `class Contract` methods that don't start
+/// with `_` and read their decorators. Once the contract lists its exports
+/// in `__all__` or marks any with `@export`, only those are exported, and
+/// other public functions are reported unless marked `@no_export`.
+///
+/// Walks the AST rather than doing fragile string matching on `def ` prefixes.
+fn find_exported_functions(
+    module: &ModModule,
+    file: SourceFile,
+    diagnostics: &mut Vec<Diagnostic>,
+) -> Vec<Method> {
+    let all = find_all(module, file, diagnostics);
+    if let Some(all) = &all {
+        for (name, offset) in all {
+            if !contract_functions(module).any(|(func, _)| func.name.as_str() == name) {
+                diagnostics.push(file.error(
+                    "invalid-all",
+                    *offset,
+                    format!("__all__ lists `{name}`, which isn't a function of the contract"),
+                ));
+            }
+        }
+    }
+
+    let marked = |func: &StmtFunctionDef, marker: &str| {
+        func.decorator_list
+            .iter()
+            .any(|decorator| decorator_name(decorator) == Some(marker))
+    };
+    let explicit =
+        all.is_some() || contract_functions(module).any(|(func, _)| marked(func, "export"));
+
+    let mut methods: Vec<Method> = Vec::new();
+    for (func, state) in contract_functions(module) {
+        let name = func.name.as_str();
+        let listed =
+            all.iter().flatten().any(|(listed, _)| listed == name) || marked(func, "export");
+        let exported = if marked(func, "no_export") {
+            if listed {
+                diagnostics.push(file.error(
+                    "invalid-decorator",
+                    func.range.start().to_usize(),
+                    format!("`{name}` is marked @no_export but also exported"),
+                ));
+            }
+            false
+        } else if explicit {
+            listed
+        } else {
+            !name.starts_with('_')
+        };
+        if !exported {
+            if !state && async_calls::splits(func) {
+                diagnostics.push(file.error(
+                    "invalid-await",
+                    func.range.start().to_usize(),
+                    format!("`{name}` awaits cross_call(), which only exported methods can: the result comes back in a callback into the contract"),
+                ));
+            }
+            if explicit && !name.starts_with('_') && !marked(func, "no_export") {
+                diagnostics.push(
+                    file.warning(
+                        "unexported-function",
+                        func.range.start().to_usize(),
+                        format!("`{name}` is public but not exported"),
+                    )
+                    .with_note(
+                        "export it with @export or __all__, or mark it @no_export (or start its name with `_`) if it's a helper",
+                    ),
+                );
+            }
+            for decorator in func
+                .decorator_list
+                .iter()
+                .filter(|decorator| decorator_name(decorator) != Some("no_export"))
+            {
+                diagnostics.push(file.error(
+                    "invalid-decorator",
+                    decorator.range.start().to_usize(),
+                    format!("decorators are only supported on exported methods, not `{name}`"),
+                ));
+            }
+            continue;
+        }
+
+        let mut method = Method {
+            name: name.to_string(),
+            function: name.to_string(),
+            group: None,
+            kind: MethodKind::Call,
+            payable: false,
+            one_yocto: false,
+            private: false,
+            init: false,
+            requires_init: false,
+            owner_only: false,
+            state,
+            migrate: false,
+            params: Param::of(func, state, file.text),
+            results: Vec::new(),
+            returns: returns_value(&func.body),
+            returns_u128: func.returns.as_deref().and_then(u128_schema),
+            raw_io: false,
+            borsh: None,
+        };
+        for decorator in &func.decorator_list {
+            if let Err(message) = apply_decorator(&mut method, decorator) {
+                diagnostics.push(file.error(
+                    "invalid-decorator",
+                    decorator.range.start().to_usize(),
+                    message,
+                ));
+            }
+        }
+
+        if method.raw_io && !method.params.is_empty() {
+            diagnostics.push(file.error(
+                "invalid-decorator",
+                func.range.start().to_usize(),
+                format!("`{name}` is @raw_io, so it reads its arguments with input() and can't take parameters"),
+            ));
+        }
+
+        let params = &func.parameters;
+        for (star, param) in [("*", &params.vararg), ("**", &params.kwarg)] {
+            if let Some(param) = param {
+                diagnostics.push(
+                    file.error(
+                        "invalid-parameter",
+                        param.range.start().to_usize(),
+                        format!("`{name}` takes `{star}{}`, which callers can't fill", param.name),
+                    )
+                    .with_note("the dispatcher passes each JSON field to the parameter of the same name; declare the parameters the method takes"),
+                );
+            }
+        }
+        // @raw_io methods take none, and @borsh_io ones report their own
+        let typed = !method.raw_io && !marked(func, "borsh_io");
+        for param in params
+            .posonlyargs
+            .iter()
+            .chain(&params.args)
+            .chain(&params.kwonlyargs)
+            .skip(usize::from(state))
+            .filter(|param| typed && param.parameter.annotation.is_none())
+        {
+            let param_name = &param.parameter.name;
+            diagnostics.push(
+                file.warning(
+                    "missing-type-hint",
+                    param.range.start().to_usize(),
+                    format!("parameter `{param_name}` of `{name}` has no type hint"),
+                )
+                .with_note(format!(
+                    "the ABI lists it as any JSON value and the call doesn't check it; \
+                     annotate it, e.g. `{param_name}: str`"
+                )),
+            );
+        }
+
+        let decorators: Vec<&str> = func
+            .decorator_list
+            .iter()
+            .filter_map(decorator_name)
+            .collect();
+        if decorators.contains(&"borsh_io") {
+            method.borsh = Some(borsh_io::signature(
+                func,
+                state,
+                method.returns,
+                file,
+                diagnostics,
+            ));
+        }
+        for (a, b, reason) in CONFLICTING_DECORATORS {
+            if decorators.contains(a) && decorators.contains(b) {
+                diagnostics.push(file.error(
+                    "invalid-decorator",
+                    func.range.start().to_usize(),
+                    format!("`{name}` can't be both @{a} and @{b}: {reason}"),
+                ));
+            }
+        }
+        let continuations = if state {
+            Vec::new()
+        } else {
+            async_calls::continuations(&method, func)
+        };
+        for exported in std::iter::once(&method).chain(&continuations) {
+            if let Err(message) = check_export_name(&exported.name) {
+                diagnostics.push(file.error(
+                    "invalid-export-name",
+                    func.range.start().to_usize(),
+                    message,
+                ));
+            }
+            // Two functions exported under one method name; one function
+            // defined twice is reported with the file's other definitions
+            if let Some(other) = methods
+                .iter()
+                .find(|m| m.name == exported.name && m.function != exported.function)
+            {
+                diagnostics.push(file.error(
+                    "duplicate-definition",
+                    func.range.start().to_usize(),
+                    format!(
+                        "`{}` is exported twice, by `{}` and `{}`",
+                        exported.name, other.function, exported.function
+                    ),
+                ));
+            }
+        }
+        if !continuations.is_empty() {
+            let returns_promise = [
+                (method.kind == MethodKind::View, "view"),
+                (method.raw_io, "raw_io"),
+                (method.borsh.is_some(), "borsh_io"),
+            ];
+            for (_, decorator) in returns_promise.iter().filter(|(set, _)| *set) {
+                diagnostics.push(file.error(
+                    "invalid-await",
+                    func.range.start().to_usize(),
+                    format!("`{name}` awaits cross_call(), so it returns a promise, which a @{decorator} method can't"),
+                ));
+            }
+            method.returns = true;
+        }
+        methods.push(method);
+        methods.extend(continuations);
+    }
+
+    let required = methods.iter().any(|m| m.init && m.requires_init);
+    for method in &mut methods {
+        method.requires_init = required && !method.init;
+    }
+    methods
+}
+
+/// Read a module-level `__all__ = [...]` (or a tuple): the names to export,
+/// each with the offset of its string. The last assignment wins, as in
+/// Python.
+fn find_all(
+    module: &ModModule,
+    file: SourceFile,
+    diagnostics: &mut Vec<Diagnostic>,
+) -> Option<Vec<(String, usize)>> {
+    let mut all = None;
+    for (target, value) in module_assignments(module) {
+        if target.id.as_str() != "__all__" {
+            continue;
+        }
+        let elements = match value {
+            Expr::List(list) => &list.elts,
+            Expr::Tuple(tuple) => &tuple.elts,
+            _ => {
+                diagnostics.push(file.error(
+                    "invalid-all",
+                    value.range().start().to_usize(),
+                    "__all__ must be a list or tuple of string literals",
+                ));
+                continue;
+            }
+        };
+        let mut names = Vec::new();
+        for element in elements {
+            match element {
+                Expr::StringLiteral(lit) => {
+                    names.push((lit.value.to_str().to_string(), lit.range.start().to_usize()))
+                }
+                _ => diagnostics.push(file.error(
+                    "invalid-all",
+                    element.range().start().to_usize(),
+                    "__all__ must be a list or tuple of string literals",
+                )),
+            }
+        }
+        all = Some(names);
+    }
+    all
+}
+
+/// Whether `body` has a `return` with a value, outside nested functions.
+fn returns_value(body: &[Stmt]) -> bool {
+    struct Returns(bool);
+    impl<'a> Visitor<'a> for Returns {
+        fn visit_stmt(&mut self, stmt: &'a Stmt) {
+            match stmt {
+                Stmt::Return(ret) if ret.value.is_some() => self.0 = true,
+                Stmt::FunctionDef(_) | Stmt::ClassDef(_) => {}
+                _ => visitor::walk_stmt(self, stmt),
+            }
+        }
+    }
+    let mut returns = Returns(false);
+    returns.visit_body(body);
+    returns.0
+}
+
+/// The name a decorator is written with: `view` for both `@view` and `@view()`.
+fn decorator_name(decorator: &Decorator) -> Option<&str> {
+    match &decorator.expression {
+        Expr::Name(name) => Some(name.id.as_str()),
+        Expr::Call(call) => match call.func.as_ref() {
+            Expr::Name(name) => Some(name.id.as_str()),
+            _ => None,
+        },
+        _ => None,
+    }
+}
+
+/// Record what a single decorator means for `method`, or describe why it's invalid.
+fn apply_decorator(method: &mut Method, decorator: &Decorator) -> Result<(), String> {
+    let (name, args) = match &decorator.expression {
+        Expr::Name(name) => (name.id.as_str(), None),
+        Expr::Call(call) => match call.func.as_ref() {
+            Expr::Name(name) => (name.id.as_str(), Some(&call.arguments)),
+            _ => return Err(format!("unsupported decorator on `{}`", method.name)),
+        },
+        _ => return Err(format!("unsupported decorator on `{}`", method.name)),
+    };
+
+    match name {
+        "group" => {
+            let group = match args.map(|a| (&a.args[..], &a.keywords[..])) {
+                Some(([Expr::StringLiteral(lit)], [])) => lit.value.to_str(),
+                _ => return Err(format!("expected @group(\"name\") on `{}`", method.name)),
+            };
+            let valid = group.starts_with(|c: char| c.is_ascii_lowercase())
+                && group
+                    .chars()
+                    .all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '_');
+            if !valid {
+                return Err(format!(
+                    "group name `{group}` must be lowercase letters, digits, \
+                     and underscores, starting with a letter"
+                ));
+            }
+            method.group = Some(group.to_string());
+        }
+        "export" => match args.map(|a| (&a.args[..], &a.keywords[..])) {
+            // Selected the method for export
+            None | Some(([], [])) => {}
+            Some(([], [keyword])) if keyword.arg.as_ref().is_some_and(|k| k.as_str() == "name") => {
+                match &keyword.value {
+                    Expr::StringLiteral(lit) if !lit.value.to_str().is_empty() => {
+                        method.name = lit.value.to_str().to_string();
+                    }
+                    _ => return Err("@export(name=...) takes a non-empty string literal".into()),
+                }
+            }
+            _ => return Err("expected @export(name=\"...\") or @export".into()),
+        },
+        "view" | "call" | "payable" | "one_yocto" | "private" | "owner_only" | "raw_io"
+        | "borsh_io" => {
+            if args.is_some_and(|a| !a.args.is_empty() || !a.keywords.is_empty()) {
+                return Err(format!("@{name} takes no arguments"));
+            }
+            match name {
+                "view" => method.kind = MethodKind::View,
+                "call" => method.kind = MethodKind::Call,
+                "payable" => method.payable = true,
+                "one_yocto" => {
+                    method.one_yocto = true;
+                    method.payable = true;
+                }
+                "private" => method.private = true,
+                "owner_only" => method.owner_only = true,
+                "raw_io" => method.raw_io = true,
+                // Read once all the decorators are known
+                _ => {}
+            }
+        }
+        "callback" => {
+            let count = match args.map(|a| (&a.args[..], &a.keywords[..])) {
+                None | Some(([], [])) => 1,
+                Some(([], [keyword]))
+                    if keyword
+                        .arg
+                        .as_ref()
+                        .is_some_and(|k| k.as_str() == "results") =>
+                {
+                    let count = match &keyword.value {
+                        Expr::NumberLiteral(number) => match &number.value {
+                            Number::Int(n) => n.as_u64(),
+                            _ => None,
+                        },
+                        _ => None,
+                    };
+                    match count {
+                        Some(n @ 1..) => n as usize,
+                        _ => return Err("expected @callback(results=N) with N >= 1".into()),
+                    }
+                }
+                _ => return Err("expected @callback(results=N) or @callback".into()),
+            };
+            if method.params.len() < count {
+                return Err(format!(
+                    "@callback `{}` needs a parameter for each of its {count} promise result(s)",
+                    method.name
+                ));
+            }
+            // Only the contract itself schedules its callbacks
+            method.private = true;
+            method.results = method.params.drain(..count).collect();
+        }
+        "migrate" => {
+            if args.is_some() {
+                return Err("@migrate takes no arguments".into());
+            }
+            if !method.state {
+                return Err(format!(
+                    "@migrate `{}` must be a method of class {}, whose state it rewrites",
+                    method.name,
+                    contract_class::CLASS_NAME
+                ));
+            }
+            if method.params.is_empty() {
+                return Err(format!(
+                    "@migrate `{}` needs a parameter for the values the previous version stored",
+                    method.name
+                ));
+            }
+            // Run by `upgrade` as the contract account itself
+            method.private = true;
+            method.migrate = true;
+            method.params.remove(0);
+        }
+        "init" => {
+            let required = match args.map(|a| (&a.args[..], &a.keywords[..])) {
+                None | Some(([], [])) => false,
+                Some(([], [keyword]))
+                    if keyword
+                        .arg
+                        .as_ref()
+                        .is_some_and(|k| k.as_str() == "required") =>
+                {
+                    match &keyword.value {
+                        Expr::BooleanLiteral(lit) => lit.value,
+                        _ => return Err("expected @init(required=True) or @init".into()),
+                    }
+                }
+                _ => return Err("expected @init(required=True) or @init".into()),
+            };
+            method.init = true;
+            // Spread to the other methods once they're all known
+            method.requires_init = required;
+        }
+        _ => {
+            return Err(format!(
+                "unsupported decorator @{name} on `{}` (supported: {})",
+                method.name,
+                CONTRACT_DECORATORS
+                    .iter()
+                    .map(|d| format!("@{d}"))
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            ))
+        }
+    }
+    Ok(())
+}
+
+/// Replace the bytes in `ranges` with spaces, keeping newlines so that line
+/// numbers and byte offsets of the remaining code are unchanged.
+fn blank_ranges(source: &str, ranges: &[(usize, usize)]) -> String {
+    let mut bytes = source.as_bytes().to_vec();
+    for &(start, end) in ranges {
+        for byte in &mut bytes[start..end] {
+            if *byte != b'\n' {
+                *byte = b' ';
+            }
+        }
+    }
+    // Only whole UTF-8 sequences were replaced, with ASCII spaces
+    String::from_utf8(bytes).expect("blanked source is valid UTF-8")
+}
+
+/// The contract source as Monty compiles it, minus the bundled modules that
+/// run first: decorators stripped and imports of bundled modules rewritten,
+/// with every remaining line and offset where it was.
+fn prepare_source(source: &str, contract: &Contract) -> String {
+    contract.bundle.rewrite(
+        &strip_decorators(source, &contract.module),
+        &contract.module,
+    )
+}
+
+/// Remove contract decorators from top-level functions and `class Contract`
+/// methods.
+fn strip_decorators(source: &str, module: &ModModule) -> String {
+    let mut ranges = Vec::new();
+    for (func, _) in contract_functions(module) {
+        for decorator in &func.decorator_list {
+            let mut start = decorator.range.start().to_usize();
+            // Include the `@` if the decorator's range starts after it
+            if start > 0 && source.as_bytes()[start - 1] == b'@' {
+                start -= 1;
+            }
+            ranges.push((start, decorator.range.end().to_usize()));
+        }
+    }
+    blank_ranges(source, &ranges)
+}
+
+// ---------------------------------------------------------------------------
+// Host function resolution — explain calls to NEAR functions we don't expose
+// ---------------------------------------------------------------------------
+
+/// NEAR host functions that exist in nearcore but are not callable from
+/// Python contracts, with the reason shown when a contract calls one.
+const UNAVAILABLE_HOST_FUNCTIONS: &[(&str, &str)] = &[
+    (
+        "storage_iter_prefix",
+        "storage iterators are deprecated and disabled in nearcore",
+    ),
+    (
+        "storage_iter_range",
+        "storage iterators are deprecated and disabled in nearcore",
+    ),
+    (
+        "storage_iter_next",
+        "storage iterators are deprecated and disabled in nearcore",
+    ),
+    ("log_utf8", "use log() — it passes the string to log_utf8"),
+    (
+        "log_utf16",
+        "use log() — strings are always passed as UTF-8",
+    ),
+    (
+        "panic",
+        "unsupported — a failing `assert` aborts the call with a panic",
+    ),
+    (
+        "panic_utf8",
+        "unsupported — a failing `assert` aborts the call with a panic",
+    ),
+    (
+        "abort",
+        "unsupported — a failing `assert` aborts the call with a panic",
+    ),
+    (
+        "read_register",
+        "registers are handled by the runtime; host functions return values directly",
+    ),
+    (
+        "register_len",
+        "registers are handled by the runtime; host functions return values directly",
+    ),
+    (
+        "write_register",
+        "registers are handled by the runtime; host functions return values directly",
+    ),
+    ("block_index", "exposed under the name block_height()"),
+];
+
+/// Collects every call whose callee is a bare name.
+#[derive(Default)]
+struct NameCallCollector<'a> {
+    calls: Vec<(&'a str, usize)>,
+}
+
+impl<'a> Visitor<'a> for NameCallCollector<'a> {
+    fn visit_expr(&mut self, expr: &'a Expr) {
+        if let Expr::Call(call) = expr {
+            if let Expr::Name(name) = call.func.as_ref() {
+                self.calls
+                    .push((name.id.as_str(), call.range.start().to_usize()));
+            }
+        }
+        visitor::walk_expr(self, expr);
+    }
+}
+
+/// Report every call to a NEAR host function that isn't in
+/// `near_external_functions()` and isn't defined by the contract.
+fn check_host_calls(module: &ModModule, file: SourceFile, diagnostics: &mut Vec<Diagnostic>) {
+    let defined: Vec<&str> = module
+        .body
+        .iter()
+        .filter_map(|stmt| match stmt {
+            Stmt::FunctionDef(func) => Some(func.name.as_str()),
+            _ => None,
+        })
+        .collect();
+
+    let mut collector = NameCallCollector::default();
+    collector.visit_body(&module.body);
+
+    for (name, offset) in collector.calls {
+        if defined.contains(&name) {
+            continue;
+        }
+        if let Some((_, reason)) = UNAVAILABLE_HOST_FUNCTIONS.iter().find(|(n, _)| *n == name) {
+            diagnostics.push(
+                file.error(
+                    "unavailable-host-function",
+                    offset,
+                    format!("NEAR host function `{name}` is not available to Python contracts"),
+                )
+                .with_note(*reason),
+            );
+        }
+    }
+}
+
+/// Functions that, called at a contract's top level, would repeat their
+/// effect on every method call, besides the `storage_*` and `promise_*`
+/// host functions: logging, setting the result, and the promise and event
+/// helpers.
+const SIDE_EFFECT_FUNCTIONS: &[&str] = &[
+    "log",
+    "value_return",
+    "emit_event",
+    "Promise",
+    "yield_create",
+    "yield_resume",
+];
+
+/// Warn about top-level code in `module` that reads or writes storage, or
+/// has another of the [`SIDE_EFFECT_FUNCTIONS`]' effects. The program runs
+/// from the top for every method call, so it would repeat on each one.
+fn check_top_level_effects(
+    module: &ModModule,
+    file: SourceFile,
+    diagnostics: &mut Vec<Diagnostic>,
+) {
+    let defined: Vec<&str> = module
+        .body
+        .iter()
+        .filter_map(|stmt| match stmt {
+            Stmt::FunctionDef(func) => Some(func.name.as_str()),
+            _ => None,
+        })
+        .collect();
+
+    let mut collector = NameCallCollector::default();
+    for stmt in &module.body {
+        if !matches!(stmt, Stmt::FunctionDef(_) | Stmt::ClassDef(_)) {
+            collector.visit_stmt(stmt);
+        }
+    }
+    for (name, offset) in collector.calls {
+        let effect = name.starts_with("storage_")
+            || name.starts_with("promise_")
+            || SIDE_EFFECT_FUNCTIONS.contains(&name);
+        if effect && !defined.contains(&name) {
+            diagnostics.push(
+                file.warning(
+                    "top-level-side-effect",
+                    offset,
+                    format!("`{name}()` at the top level runs again on every method call"),
+                )
+                .with_note(
+                    "the contract's top-level code runs before each method, views included, \
+                     which can't write storage or create promises; \
+                     move it into an @init method or the method that needs it",
+                ),
+            );
+        }
+    }
+}
+
+/// Functions that change state or create promises, which view calls can't
+/// do. So do the `promise_*` host functions, apart from [`PROMISE_READS`].
+const STATE_CHANGES: &[&str] = &[
+    "storage_write",
+    "storage_remove",
+    "Promise",
+    "cross_call",
+    "yield_create",
+    "yield_resume",
+];
+
+/// The `promise_*` host functions that only read.
+const PROMISE_READS: &[&str] = &["promise_results_count", "promise_result"];
+
+/// A contract function's state changes, with their offsets, and the
+/// contract functions it calls.
+type FunctionEffects<'a> = (Vec<(String, usize)>, Vec<&'a str>);
+
+/// What a contract function does itself: the state changes it makes, and
+/// the contract functions it calls.
+struct Effects<'a> {
+    /// Top-level functions and `class Contract` methods.
+    defined: &'a HashSet<&'a str>,
+    collections: &'a [&'a str],
+    method: Option<&'a StmtFunctionDef>,
+    writes: Vec<(String, usize)>,
+    calls: Vec<&'a str>,
+}
+
+impl<'a> Visitor<'a> for Effects<'a> {
+    fn visit_expr(&mut self, expr: &'a Expr) {
+        if let Expr::Call(call) = expr {
+            let offset = call.range.start().to_usize();
+            let self_call = self
+                .method
+                .and_then(|method| contract_class::self_call(method, call));
+            match call.func.as_ref() {
+                _ if self_call.is_some_and(|name| self.defined.contains(name)) => {
+                    self.calls.extend(self_call);
+                }
+                Expr::Name(name) => {
+                    let name = name.id.as_str();
+                    if self.defined.contains(name) {
+                        self.calls.push(name);
+                    } else if STATE_CHANGES.contains(&name)
+                        || (name.starts_with("promise_") && !PROMISE_READS.contains(&name))
+                    {
+                        self.writes.push((format!("{name}()"), offset));
+                    }
+                }
+                Expr::Attribute(attribute) => {
+                    if let Expr::Name(name) = attribute.value.as_ref() {
+                        let method = attribute.attr.as_str();
+                        if self.collections.contains(&name.id.as_str())
+                            && collections::writes(method)
+                        {
+                            self.writes
+                                .push((format!("{}.{method}()", name.id), offset));
+                        }
+                    }
+                }
+                _ => {}
+            }
+        }
+        visitor::walk_expr(self, expr);
+    }
+}
+
+/// Report `@view` methods that change state, directly or through the
+/// contract functions they call: writing storage, a collection, or a
+/// `class Contract` field, or creating a promise. A view call can't.
+fn check_view_writes(
+    module: &ModModule,
+    methods: &[Method],
+    file: SourceFile,
+    diagnostics: &mut Vec<Diagnostic>,
+) {
+    if !methods.iter().any(|m| m.kind == MethodKind::View) {
+        return;
+    }
+    let defined: HashSet<&str> = contract_functions(module)
+        .map(|(func, _)| func.name.as_str())
+        .collect();
+    let collections = collections::declared(module);
+    let mut graph: HashMap<&str, FunctionEffects> = HashMap::new();
+    for (func, in_class) in contract_functions(module) {
+        let mut effects = Effects {
+            defined: &defined,
+            collections: &collections,
+            method: in_class.then_some(func),
+            writes: Vec::new(),
+            calls: Vec::new(),
+        };
+        effects.visit_body(&func.body);
+        if in_class {
+            for (field, offset) in contract_class::field_writes(func) {
+                effects.writes.push((format!("self.{field}"), offset));
+            }
+        }
+        effects.writes.sort_by_key(|&(_, offset)| offset);
+        graph.insert(func.name.as_str(), (effects.writes, effects.calls));
+    }
+
+    for method in methods.iter().filter(|m| m.kind == MethodKind::View) {
+        // Breadth first, for the shortest chain of calls to a write
+        let mut path: HashMap<&str, &str> = HashMap::new();
+        let mut queue = std::collections::VecDeque::from([method.function.as_str()]);
+        let mut seen = HashSet::from([method.function.as_str()]);
+        let mut found = None;
+        while let Some(name) = queue.pop_front() {
+            let Some((writes, calls)) = graph.get(name) else {
+                continue;
+            };
+            if let Some(write) = writes.first() {
+                found = Some((name, write));
+                break;
+            }
+            for &callee in calls {
+                if seen.insert(callee) {
+                    path.insert(callee, name);
+                    queue.push_back(callee);
+                }
+            }
+        }
+        let Some((mut name, (write, offset))) = found else {
+            continue;
+        };
+        let mut chain = vec![name];
+        while let Some(&caller) = path.get(name) {
+            chain.push(caller);
+            name = caller;
+        }
+        chain.reverse();
+        let through = if chain.len() > 1 {
+            format!(", through {}", chain.join(" → "))
+        } else {
+            String::new()
+        };
+        let start = contract_functions(module)
+            .find(|(func, _)| func.name.as_str() == method.function)
+            .map_or(0, |(func, _)| func.range.start().to_usize());
+        diagnostics.push(
+            file.error(
+                "view-changes-state",
+                start,
+                format!(
+                    "view method `{}` changes state: {write} at {}{through}",
+                    method.name,
+                    file.position(*offset)
+                ),
+            )
+            .with_note(
+                "a view call can't write storage or create promises; \
+                 remove @view to make it a call method",
+            ),
+        );
+    }
+}
+
+/// The deposit checks the build defines when a contract calls them.
+const DEPOSIT_HELPERS: &[&str] = &["assert_one_yocto", "assert_min_deposit"];
+
+/// Whether `module` calls one of `helpers` without defining it.
+fn calls_helper(module: &ModModule, helpers: &[&str]) -> bool {
+    let defined = module.body.iter().any(|stmt| match stmt {
+        Stmt::FunctionDef(func) => helpers.contains(&func.name.as_str()),
+        _ => false,
+    });
+    let mut collector = NameCallCollector::default();
+    collector.visit_body(&module.body);
+    !defined
+        && collector
+            .calls
+            .iter()
+            .any(|(name, _)| helpers.contains(name))
+}
+
+// ---------------------------------------------------------------------------
+// Pre-compilation — compile source + dispatcher to single Monty bytecode blob
+// ---------------------------------------------------------------------------
+
+/// Helpers a contract uses without defining them, which the build defines
+/// ahead of the dispatcher.
+#[derive(Clone, Copy, Default)]
+struct Builtins {
+    /// `emit_event`.
+    events: bool,
+    /// The functions collection methods are lowered to.
+    collections: bool,
+    /// `Promise` and the functions its methods are lowered to.
+    promises: bool,
+    /// `yield_create` and `yield_resume`.
+    yields: bool,
+    /// `assert_one_yocto` and `assert_min_deposit`.
+    deposits: bool,
+    /// The bundled `json` module, which wraps the JSON helpers.
+    json: bool,
+}
+
+/// The program Monty compiles for `source`: async methods split at their
+/// awaits, and collections, promise methods and `class Contract` lowered to
+/// plain functions.
+fn lower(source: &str) -> String {
+    let source = collections::lower(&async_calls::lower(source));
+    contract_class::lower(&promises::lower(&source))
+}
+
+/// Generate a Python dispatcher that routes `_method` to the correct function.
+///
+/// With `guards`, the checks near-sdk contracts make run before the method:
+/// `@init` methods run once and record that in storage, other methods may
+/// require that record, `@private` methods reject callers other than the
+/// contract itself, `@owner_only` methods reject callers other than `owner`,
+/// `@one_yocto` methods reject any deposit but 1 yoctoNEAR, and call methods
+/// that aren't `@payable` reject an attached deposit. Views
+/// are left alone: `attached_deposit` isn't available in view calls.
+///
+/// Methods with parameters are called with the fields of the JSON object
+/// `input()` holds, falling back to the parameters' defaults and checked
+/// against the parameters' type annotations first, and what a
+/// method returns (other than `None`) is passed to `value_return` as JSON,
+/// or as is for `@raw_io` methods. `@borsh_io` methods use Borsh both ways
+/// instead. The JSON and Borsh helpers are defined ahead of the dispatcher
+/// when a method needs them, and so are the `builtins` the contract uses.
+/// Promises a method creates are scheduled once it returns, and one it
+/// returns becomes its result.
+fn generate_dispatcher(
+    methods: &[Method],
+    guards: bool,
+    owner: Owner,
+    builtins: Builtins,
+) -> String {
+    let mut dispatcher = String::new();
+    let callbacks = methods.iter().any(|m| !m.results.is_empty());
+    if builtins.json
+        || builtins.events
+        || builtins.collections
+        || builtins.promises
+        || builtins.yields
+        || callbacks
+        || methods
+            .iter()
+            .any(|m| m.borsh.is_none() && (!m.params.is_empty() || (m.returns && !m.raw_io)))
+    {
+        dispatcher.push_str(TEMPLATE_JSON);
+        dispatcher.push('\n');
+    }
+    if builtins.events {
+        dispatcher.push_str(TEMPLATE_EVENTS);
+        dispatcher.push('\n');
+    }
+    if builtins.collections {
+        dispatcher.push_str(TEMPLATE_COLLECTIONS);
+        dispatcher.push('\n');
+    }
+    if builtins.promises {
+        dispatcher.push_str(TEMPLATE_PROMISE);
+        dispatcher.push('\n');
+    }
+    if builtins.yields {
+        dispatcher.push_str(TEMPLATE_YIELD);
+        dispatcher.push('\n');
+    }
+    if builtins.deposits {
+        dispatcher.push_str(TEMPLATE_DEPOSIT);
+        dispatcher.push('\n');
+    }
+    if callbacks {
+        dispatcher.push_str(TEMPLATE_CALLBACK);
+        dispatcher.push('\n');
+    }
+    if methods.iter().any(|m| m.borsh.is_some()) {
+        dispatcher.push_str(TEMPLATE_BORSH);
+        dispatcher.push('\n');
+    }
+    for (i, method) in methods.iter().enumerate() {
+        let name = &method.name;
+        let keyword = if i == 0 { "if" } else { "elif" };
+        dispatcher.push_str(&format!("{keyword} _method == \"{name}\":\n"));
+        if guards && method.init {
+            dispatcher.push_str(&format!(
+                "    if storage_has_key(\"{INIT_STATE_KEY}\"):\n        \
+                 raise ValueError(\"The contract has already been initialized\")\n    \
+                 storage_write(\"{INIT_STATE_KEY}\", \"1\")\n"
+            ));
+        }
+        if guards && method.requires_init {
+            dispatcher.push_str(&format!(
+                "    if not storage_has_key(\"{INIT_STATE_KEY}\"):\n        \
+                 raise ValueError(\"The contract is not initialized\")\n"
+            ));
+        }
+        if guards && method.private {
+            dispatcher.push_str(&format!(
+                "    if predecessor_account_id() != current_account_id():\n        \
+                 raise ValueError(\"Method {name} is private\")\n"
+            ));
+        }
+        if guards && method.owner_only {
+            dispatcher.push_str(&format!(
+                "    if predecessor_account_id() != {}:\n        \
+                 raise ValueError(\"Method {name} can only be called by the owner\")\n",
+                owner.expression()
+            ));
+        }
+        if guards && method.one_yocto {
+            dispatcher.push_str(&format!(
+                "    if attached_deposit() != \"1\":\n        \
+                 raise ValueError(\"Method {name} requires attached deposit of exactly 1 yoctoNEAR\")\n"
+            ));
+        }
+        if guards && method.kind == MethodKind::Call && !method.payable {
+            dispatcher.push_str(&format!(
+                "    if attached_deposit() != \"0\":\n        \
+                 raise ValueError(\"Method {name} doesn't accept deposit\")\n"
+            ));
+        }
+        if method.state {
+            let stored = if method.migrate {
+                "[]".to_string()
+            } else {
+                format!("{}()", contract_class::STORED_FN)
+            };
+            dispatcher.push_str(&format!(
+                "    {} = {}({stored})\n",
+                contract_class::STATE_VAR,
+                contract_class::LOAD_FN
+            ));
+        }
+        let mut args = match &method.borsh {
+            Some(signature) => borsh_arguments(method, signature, &mut dispatcher),
+            None => json_arguments(method, &mut dispatcher),
+        };
+        if method.migrate {
+            args.insert(0, format!("{}()", contract_class::STORED_FN));
+        }
+        let call = format!("{}({})", method.function, args.join(", "));
+        if method.returns {
+            dispatcher.push_str(&format!("    __result__ = {call}\n"));
+        } else {
+            dispatcher.push_str(&format!("    {call}\n"));
+        }
+        if method.state && method.kind == MethodKind::Call {
+            dispatcher.push_str(&format!("    {}()\n", contract_class::SAVE_FN));
+        }
+        if builtins.promises {
+            // A returned promise becomes the method's result
+            if method.returns && method.borsh.is_none() && !method.raw_io {
+                dispatcher.push_str("    __result__ = __promise_returned__(__result__)\n");
+            }
+            dispatcher.push_str("    __promise_flush__()\n");
+        }
+        if method.returns {
+            match method.borsh.as_ref().and_then(|s| s.returns.as_ref()) {
+                // `None` has an encoding too, if the type is optional
+                Some(returns) => dispatcher.push_str(&format!(
+                    "    value_return(__borsh_write__(__result__, {}))\n",
+                    returns.schema()
+                )),
+                None => {
+                    let result = match &method.returns_u128 {
+                        _ if method.raw_io => "__result__".to_string(),
+                        Some(schema) => {
+                            format!("__json_dumps__(__json_to_u128__(__result__, {schema}))")
+                        }
+                        None => "__json_dumps__(__result__)".to_string(),
+                    };
+                    dispatcher.push_str(&format!(
+                        "    if __result__ is not None:\n        value_return({result})\n"
+                    ));
+                }
+            }
+        }
+    }
+    dispatcher
+}
+
+/// Read `method`'s promise results into `__results__` and its JSON
+/// arguments into `__args__`, checking their types; the expressions passing
+/// them to the method.
+fn json_arguments(method: &Method, dispatcher: &mut String) -> Vec<String> {
+    if !method.results.is_empty() {
+        let names: Vec<String> = method
+            .results
+            .iter()
+            .map(|p| format!("\"{}\"", p.name))
+            .collect();
+        let failable: Vec<&str> = method
+            .results
+            .iter()
+            .map(|p| if p.failable { "True" } else { "False" })
+            .collect();
+        dispatcher.push_str(&format!(
+            "    __results__ = __promise_results__([{}], [{}], \"{}\")\n",
+            names.join(", "),
+            failable.join(", "),
+            method.name
+        ));
+    }
+    if !method.params.is_empty() {
+        dispatcher.push_str("    __args__ = __json_args__()\n");
+    }
+    let params = (method.results.iter().map(|p| (p, "__results__")))
+        .chain(method.params.iter().map(|p| (p, "__args__")));
+    for (param, values) in params.clone() {
+        if let Some(check) = param.check {
+            dispatcher.push_str(&format!(
+                "    __json_check__({values}, \"{}\", \"{}\", {})\n",
+                param.name,
+                check.kind,
+                if check.nullable { "True" } else { "False" }
+            ));
+        }
+    }
+    params
+        .map(|(param, values)| {
+            let key = &param.name;
+            let convert = |value: String| match &param.u128 {
+                Some(schema) => format!("__json_from_u128__({value}, {schema}, \"{key}\")"),
+                None => value,
+            };
+            let value = match &param.default {
+                // Failed promises are missing from `__results__`
+                _ if values == "__results__" && param.failable => format!(
+                    "({} if \"{key}\" in __results__ else PromiseError)",
+                    convert(format!("__results__[\"{key}\"]"))
+                ),
+                _ if values == "__results__" => convert(format!("__results__[\"{key}\"]")),
+                Some(default) => format!(
+                    "({} if \"{key}\" in __args__ else {default})",
+                    convert(format!("__args__[\"{key}\"]"))
+                ),
+                None => convert(format!("__json_arg__(__args__, \"{key}\")")),
+            };
+            if param.keyword_only {
+                format!("{key}={value}")
+            } else {
+                value
+            }
+        })
+        .collect()
+}
+
+/// Decode `method`'s Borsh arguments into `__args__`; the expressions
+/// passing them to the method.
+fn borsh_arguments(
+    method: &Method,
+    signature: &borsh_io::Signature,
+    dispatcher: &mut String,
+) -> Vec<String> {
+    if signature.params.is_empty() {
+        return Vec::new();
+    }
+    let schemas: Vec<String> = signature.params.iter().map(|t| t.schema()).collect();
+    dispatcher.push_str(&format!(
+        "    __args__ = __borsh_args__([{}])\n",
+        schemas.join(", ")
+    ));
+    method
+        .params
+        .iter()
+        .enumerate()
+        .map(|(i, param)| {
+            if param.keyword_only {
+                format!("{}=__args__[{i}]", param.name)
+            } else {
+                format!("__args__[{i}]")
+            }
+        })
+        .collect()
+}
+
+/// A `--define`: a constant name and its value as a Python literal.
+struct Define {
+    name: String,
+    literal: String,
+}
+
+/// Parse `--define NAME=VALUE` options. Later definitions of a name win.
+fn parse_defines(defines: &[String]) -> Result<Vec<Define>> {
+    let mut parsed: Vec<Define> = Vec::new();
+    for define in defines {
+        let Some((name, value)) = define.split_once('=') else {
+            bail!("invalid --define `{define}`: expected NAME=VALUE");
+        };
+        let is_identifier = name
+            .chars()
+            .next()
+            .is_some_and(|c| c.is_alphabetic() || c == '_')
+            && name.chars().all(|c| c.is_alphanumeric() || c == '_');
+        if !is_identifier {
+            bail!("invalid --define `{define}`: `{name}` is not a Python identifier");
+        }
+        parsed.retain(|d| d.name != name);
+        parsed.push(Define {
+            name: name.to_string(),
+            literal: define_literal(value),
+        });
+    }
+    Ok(parsed)
+}
+
+/// A `--define` value as Python source: literals are kept as written and
+/// anything else is quoted as a string.
+fn define_literal(value: &str) -> String {
+    let is_literal = match value {
+        "True" | "False" | "None" => true,
+        _ if value.parse::<i64>().is_ok() || value.parse::<f64>().is_ok() => {
+            // Reject forms Python doesn't accept, like `inf` or `1e5.`
+            value
+                .chars()
+                .all(|c| c.is_ascii_digit() || "+-.eE_".contains(c))
+        }
+        _ if value.starts_with(['"', '\'']) => parse_module(value).is_ok_and(|parsed| {
+            matches!(
+                &parsed.syntax().body[..],
+                [Stmt::Expr(expr)] if matches!(expr.value.as_ref(), Expr::StringLiteral(_))
+            )
+        }),
+        _ => false,
+    };
+    if is_literal {
+        value.to_string()
+    } else {
+        // A JSON string is also a valid Python string literal
+        serde_json::to_string(value).expect("strings serialize")
+    }
+}
+
+/// Module-level assignments of a single name (`NAME = value` or
+/// `NAME: T = value`): the target and the value.
+fn module_assignments(module: &ModModule) -> impl Iterator<Item = (&ExprName, &Expr)> {
+    module.body.iter().filter_map(|stmt| match stmt {
+        Stmt::Assign(assign) => match &assign.targets[..] {
+            [Expr::Name(target)] => Some((target, assign.value.as_ref())),
+            _ => None,
+        },
+        Stmt::AnnAssign(assign) => match (assign.target.as_ref(), &assign.value) {
+            (Expr::Name(target), Some(value)) => Some((target, value.as_ref())),
+            _ => None,
+        },
+        _ => None,
+    })
+}
+
+/// Apply `defines` to `source`: replace the value of module-level
+/// assignments to a defined name. The remaining names are defined by
+/// [`define_header`].
+fn apply_defines(source: &str, module: &ModModule, defines: &[Define]) -> String {
+    let mut replacements: Vec<(usize, usize, &str)> = Vec::new();
+    for (target, value) in module_assignments(module) {
+        if let Some(define) = defines.iter().find(|d| d.name == target.id.as_str()) {
+            let range = value.range();
+            replacements.push((
+                range.start().to_usize(),
+                range.end().to_usize(),
+                &define.literal,
+            ));
+        }
+    }
+
+    let mut out = source.to_string();
+    for (start, end, literal) in replacements.into_iter().rev() {
+        out.replace_range(start..end, literal);
+    }
+    out
+}
+
+/// The line defining the `defines` that no module-level assignment in
+/// `module` takes, to run before the source; empty if there are none.
+fn define_header(module: &ModModule, defines: &[Define]) -> String {
+    let assigned: HashSet<&str> = module_assignments(module)
+        .map(|(target, _)| target.id.as_str())
+        .collect();
+    let header: Vec<String> = defines
+        .iter()
+        .filter(|d| !assigned.contains(d.name.as_str()))
+        .map(|d| format!("{} = {}", d.name, d.literal))
+        .collect();
+    if header.is_empty() {
+        String::new()
+    } else {
+        format!("{}\n", header.join("; "))
+    }
+}
+
+/// Compile the full source with a dispatcher into a single bytecode blob.
+/// Errors are mapped back to the files `map` says the source comes from.
+fn precompile_contract(
+    source: &str,
+    map: &SourceMap,
+    methods: &[Method],
+    guards: bool,
+    owner: Owner,
+    builtins: Builtins,
+) -> Result<(Vec<u8>, BTreeSet<&'static str>)> {
+    let dispatcher = generate_dispatcher(methods, guards, owner, builtins);
+    let program = dead_code::eliminate(&format!("{}\n\n{dispatcher}", lower(source)));
+    let external_functions = near_external_functions();
+
+    // `_method` is an input variable — the Rust runtime passes the method name at call time.
+    let runner = MontyRun::new(
+        program.clone(),
+        source_map::SCRIPT_NAME,
+        vec!["_method".to_string()],
+        external_functions,
+    )
+    .map_err(|e| CompileError::new(&e.to_string(), &program, map))?;
+
+    let bytecode = runner.dump().context("serialization failed")?;
+    Ok((bytecode, host_functions(&program)))
+}
+
+/// The NEAR host functions `program` calls, apart from any it defines
+/// itself: the contract's own calls, and those of the bundled modules and
+/// the generated dispatcher and guards.
+fn host_functions(program: &str) -> BTreeSet<&'static str> {
+    let Ok(parsed) = parse_module(program) else {
+        return BTreeSet::new();
+    };
+    let module = parsed.into_syntax();
+    let defined: HashSet<&str> = module
+        .body
+        .iter()
+        .filter_map(|stmt| match stmt {
+            Stmt::FunctionDef(func) => Some(func.name.as_str()),
+            _ => None,
+        })
+        .collect();
+    let mut collector = NameCallCollector::default();
+    collector.visit_body(&module.body);
+    let called: HashSet<&str> = collector.calls.iter().map(|(name, _)| *name).collect();
+    NEAR_HOST_FUNCTIONS
+        .iter()
+        .map(|(name, _, _)| *name)
+        .filter(|name| called.contains(name) && !defined.contains(name))
+        .collect()
+}
+
+/// Whether `allowed` lets a contract call the host function `name`: it
+/// lists the name, or a prefix of it followed by `*`.
+fn host_function_allowed(allowed: &[String], name: &str) -> bool {
+    allowed
+        .iter()
+        .any(|pattern| match pattern.strip_suffix('*') {
+            Some(prefix) => name.starts_with(prefix),
+            None => pattern == name,
+        })
+}
+
+/// Report the host functions `blobs` call that `--allow-host-function`
+/// doesn't allow, at the contract's first call of each where it makes one
+/// itself.
+fn check_allowed_host_functions(
+    args: &BuildArgs,
+    file: SourceFile,
+    module: &ModModule,
+    blobs: &[Blob],
+    diagnostics: &mut Vec<Diagnostic>,
+) {
+    if args.allowed_host_functions.is_empty() {
+        return;
+    }
+    let mut collector = NameCallCollector::default();
+    collector.visit_body(&module.body);
+    let used: BTreeSet<&str> = blobs
+        .iter()
+        .flat_map(|blob| blob.host_functions.iter().copied())
+        .collect();
+    let note = format!(
+        "allowed: {}; allow more with --allow-host-function or `allowed-host-functions` in {}",
+        args.allowed_host_functions.join(", "),
+        config::CONFIG_FILE
+    );
+    for name in used {
+        if host_function_allowed(&args.allowed_host_functions, name) {
+            continue;
+        }
+        let diagnostic = match collector.calls.iter().find(|(called, _)| *called == name) {
+            Some((_, offset)) => file.error(
+                "host-function-not-allowed",
+                *offset,
+                format!("host function `{name}` isn't allowed"),
+            ),
+            None => Diagnostic::error(
+                "host-function-not-allowed",
+                format!(
+                    "host function `{name}` isn't allowed, and code the build adds calls it: \
+                     a decorator's checks, a bundled module, or the collection or Promise helpers"
+                ),
+            ),
+        };
+        diagnostics.push(diagnostic.with_note(note.clone()));
+    }
+}
+
+/// A compiled bytecode blob and the method group it serves.
+struct Blob {
+    group: Option<String>,
+    bytecode: Vec<u8>,
+    /// The NEAR host functions it calls.
+    host_functions: BTreeSet<&'static str>,
+}
+
+/// Method groups in first-appearance order.
+fn method_groups(methods: &[Method]) -> Vec<Option<String>> {
+    let mut groups: Vec<Option<String>> = Vec::new();
+    for method in methods {
+        if !groups.contains(&method.group) {
+            groups.push(method.group.clone());
+        }
+    }
+    groups
+}
+
+/// Compile one blob per method group. Each blob keeps the shared code
+/// (helpers and top-level statements) plus its own group's methods; methods
+/// of other groups are dropped unless the kept code refers to them.
+/// The bundled modules run before the source in every blob, and `guards`
+/// is passed on to [`generate_dispatcher`]. `source` is the contract in
+/// `file` as [`prepare_source`] leaves it, which compile errors point into.
+fn precompile_groups(
+    file: SourceFile,
+    source: &str,
+    bundle: &imports::Bundle,
+    module: &ModModule,
+    methods: &[Method],
+    defines: &[Define],
+    guards: bool,
+) -> Result<Vec<Blob>> {
+    let owner = Owner::of(module, defines);
+    let header = define_header(module, defines);
+    let map = SourceMap::new(file, bundle, &header);
+    let groups = method_groups(methods);
+    if groups.len() == 1 {
+        let (bytecode, host_functions) = precompile_contract(
+            &(bundle.prelude.clone() + &header + &apply_defines(source, module, defines)),
+            &map,
+            methods,
+            guards,
+            owner,
+            bundle.builtins,
+        )?;
+        return Ok(vec![Blob {
+            group: groups[0].clone(),
+            bytecode,
+            host_functions,
+        }]);
+    }
+
+    let mut blobs = Vec::new();
+    for group in groups {
+        let in_group = |name: &str| {
+            methods
+                .iter()
+                .any(|m| m.function == name && m.group == group)
+        };
+        let is_method = |name: &str| methods.iter().any(|m| m.function == name);
+
+        // Fixpoint: keep other groups' methods only if kept code references them
+        let mut kept: HashSet<&str> = HashSet::new();
+        let mut referenced: HashSet<&str> = HashSet::new();
+        loop {
+            let mut collector = NameCollector::default();
+            for stmt in &module.body {
+                match stmt {
+                    Stmt::FunctionDef(func) if is_method(func.name.as_str()) => {
+                        let name = func.name.as_str();
+                        if in_group(name) || referenced.contains(name) {
+                            kept.insert(name);
+                            collector.visit_stmt(stmt);
+                        }
+                    }
+                    _ => collector.visit_stmt(stmt),
+                }
+            }
+            if collector.names.is_subset(&referenced) {
+                break;
+            }
+            referenced.extend(collector.names);
+        }
+
+        let dropped: Vec<(usize, usize)> = module
+            .body
+            .iter()
+            .filter_map(|stmt| match stmt {
+                Stmt::FunctionDef(func)
+                    if is_method(func.name.as_str()) && !kept.contains(func.name.as_str()) =>
+                {
+                    Some((func.range.start().to_usize(), func.range.end().to_usize()))
+                }
+                _ => None,
+            })
+            .collect();
+        let group_source = bundle.prelude.clone()
+            + &header
+            + &apply_defines(&blank_ranges(source, &dropped), module, defines);
+        let group_methods: Vec<Method> = methods
+            .iter()
+            .filter(|m| m.group == group)
+            .cloned()
+            .collect();
+
+        let (bytecode, host_functions) = precompile_contract(
+            &group_source,
+            &map,
+            &group_methods,
+            guards,
+            owner,
+            bundle.builtins,
+        )?;
+        blobs.push(Blob {
+            group,
+            bytecode,
+            host_functions,
+        });
+    }
+    Ok(blobs)
+}
+
+/// Report top-level functions that are neither exported nor referenced
+/// anywhere in the contract: dead code, or a method meant to be exported.
+fn check_unused_functions(
+    module: &ModModule,
+    methods: &[Method],
+    file: SourceFile,
+    diagnostics: &mut Vec<Diagnostic>,
+) {
+    let mut collector = NameCollector::default();
+    collector.visit_body(&module.body);
+    for stmt in &module.body {
+        let Stmt::FunctionDef(func) = stmt else {
+            continue;
+        };
+        let name = func.name.as_str();
+        if name.starts_with("__")
+            || methods.iter().any(|m| m.function == name)
+            || collector.names.contains(name)
+        {
+            continue;
+        }
+        diagnostics.push(
+            file.warning(
+                "unused-function",
+                func.range.start().to_usize(),
+                format!("`{name}` is never called"),
+            )
+            .with_note("remove it, or export it if it's meant to be a method"),
+        );
+    }
+}
+
+/// Collects every name referenced anywhere in the visited code.
+#[derive(Default)]
+struct NameCollector<'a> {
+    names: HashSet<&'a str>,
+}
+
+impl<'a> Visitor<'a> for NameCollector<'a> {
+    fn visit_expr(&mut self, expr: &'a Expr) {
+        if let Expr::Name(name) = expr {
+            self.names.insert(name.id.as_str());
+        }
+        visitor::walk_expr(self, expr);
+    }
+}
+
+// ---------------------------------------------------------------------------
+// Code generation — splice generated code into the template
+// ---------------------------------------------------------------------------
+
+/// Name of the Rust static holding a group's bytecode.
+fn bytecode_static_name(group: Option<&str>) -> String {
+    match group {
+        None => "CONTRACT_BYTECODE".to_string(),
+        Some(group) => format!("CONTRACT_BYTECODE_{}", group.to_uppercase()),
+    }
+}
+
+/// File name of a group's serialized bytecode inside the generated project.
+fn bytecode_file_name(group: Option<&str>) -> String {
+    match group {
+        None => "contract.bin".to_string(),
+        Some(group) => format!("contract_{group}.bin"),
+    }
+}
+
+/// File of the generated project holding where each of the contract's
+/// files is in the program, which the runtime maps tracebacks with.
+const SOURCE_LINES_FILE: &str = "source_lines.bin";
+
+/// Generate the `lib.rs` source with one bytecode static per method group
+/// and thin exports, under each method's exported name, that pass it on.
+fn generate_lib_rs(
+    template: &str,
+    methods: &[Method],
+    allocator: &str,
+    source_metadata: Option<&str>,
+    compressed: bool,
+    patchable: bool,
+) -> String {
+    let mut bytecode_statics = String::new();
+    for group in method_groups(methods) {
+        bytecode_statics.push_str(&format!(
+            "static {}: &[u8] = include_bytes!(\"{}\");\n",
+            bytecode_static_name(group.as_deref()),
+            bytecode_file_name(group.as_deref()),
+        ));
+    }
+    // A data file like the bytecode, so a patched rebuild updates it too
+    bytecode_statics.push_str(&format!(
+        "static SOURCE_LINES: &[u8] = include_bytes!(\"{SOURCE_LINES_FILE}\");\n\n\
+         /// `(start, lines, file)` for each of the contract's files, from\n\
+         /// `SOURCE_LINES`: one tab-separated line per file.\n\
+         fn source_lines() -> impl Iterator<Item = (u32, u32, &'static str)> {{\n    \
+         let table = core::str::from_utf8({}).unwrap_or_default();\n    \
+         table.lines().filter_map(|line| {{\n        \
+         let mut fields = line.splitn(3, '\\t');\n        \
+         let start = fields.next()?.parse::<u32>().ok()?;\n        \
+         let lines = fields.next()?.parse::<u32>().ok()?;\n        \
+         Some((start, lines, fields.next()?))\n    \
+         }})\n}}\n",
+        if patchable {
+            "blob_bytecode(SOURCE_LINES)"
+        } else {
+            "SOURCE_LINES"
+        }
+    ));
+    if patchable {
+        bytecode_statics.push_str(
+            "\n/// The bytecode in a blob written as its length (4 bytes, little endian),\n\
+             /// the bytecode, and zeros, so a rebuild can patch in new bytecode.\n\
+             /// `black_box` keeps the compiler from building the length into the code.\n\
+             fn blob_bytecode(blob: &'static [u8]) -> &'static [u8] {\n    \
+             let blob = core::hint::black_box(blob);\n    \
+             let len = u32::from_le_bytes([blob[0], blob[1], blob[2], blob[3]]) as usize;\n    \
+             &blob[4..4 + len]\n}\n",
+        );
+    }
+    if compressed {
+        bytecode_statics.push_str(
+            "\n/// Bytecode the build deflated (`--compress-bytecode`), inflated for a call.\n\
+             fn inflate(compressed: &[u8]) -> Vec<u8> {\n    \
+             miniz_oxide::inflate::decompress_to_vec(compressed)\n        \
+             .unwrap_or_else(|_| panic!(\"corrupt compressed bytecode\"))\n}\n",
+        );
+    }
+
+    // Numbered, so no function name can clash with the template's own
+    let mut exports = String::new();
+    for (i, method) in methods.iter().enumerate() {
+        let name = &method.name;
+        let bytecode = bytecode_static_name(method.group.as_deref());
+        let bytecode = if patchable {
+            format!("blob_bytecode({bytecode})")
+        } else {
+            bytecode
+        };
+        let bytecode = if compressed {
+            format!("&inflate({bytecode})")
+        } else {
+            bytecode
+        };
+        exports.push_str(&format!(
+            "#[export_name = \"{name}\"]\npub extern \"C\" fn export_{i}() {{\n    run_method({bytecode}, \"{name}\");\n}}\n\n",
+        ));
+    }
+    if let Some(json) = source_metadata {
+        // `{:?}` of a str is a valid Rust string literal
+        exports.push_str(&format!(
+            "#[no_mangle]\npub extern \"C\" fn {}() {{\n    near_value_return({json:?}.as_bytes());\n}}\n\n",
+            metadata::METHOD_NAME,
+        ));
+    }
+
+    template
+        .replace(MARKER_ALLOCATOR, allocator)
+        .replace(MARKER_BYTECODE, &bytecode_statics)
+        .replace(MARKER_EXPORTS, &exports)
+}
+
+// ---------------------------------------------------------------------------
+// Project scaffolding — write the temporary Rust project to disk
+// ---------------------------------------------------------------------------
+
+/// The template at `path` (relative to template/): the file in
+/// --template-dir if it has one, else the embedded `builtin`.
+fn template_file(args: &BuildArgs, path: &str, builtin: &'static str) -> Result<Cow<'static, str>> {
+    if let Some(dir) = &args.template_dir {
+        let file = dir.join(path);
+        if file.is_file() {
+            let contents = fs::read_to_string(&file)
+                .with_context(|| format!("failed to read {}", file.display()))?;
+            return Ok(Cow::Owned(contents));
+        }
+    }
+    Ok(Cow::Borrowed(builtin))
+}
+
+fn write_project(
+    dir: &Path,
+    crate_name: &str,
+    compiled: &CompiledContract,
+    cargo_lock: Option<&str>,
+    args: &BuildArgs,
+) -> Result<()> {
+    let methods = &compiled.methods;
+    let cargo_toml = template_file(args, "Cargo.toml", TEMPLATE_CARGO_TOML)?;
+    let (cargo_toml, lib_template, markers) = match args.format {
+        OutputFormat::Near => (
+            cargo_toml.into_owned(),
+            template_file(args, "src/lib.rs", TEMPLATE_LIB_RS)?,
+            &[MARKER_BYTECODE, MARKER_EXPORTS][..],
+        ),
+        OutputFormat::Component => {
+            // No NEAR host imports may leak into a component
+            let wit_dir = dir.join("wit");
+            fs::create_dir_all(&wit_dir)?;
+            fs::write(
+                wit_dir.join("world.wit"),
+                template_file(args, "component/wit/world.wit", TEMPLATE_COMPONENT_WIT)?.as_ref(),
+            )?;
+            (
+                cargo_toml.replace("near-sys = \"0.2\"\n", ""),
+                template_file(args, "component/src/lib.rs", TEMPLATE_COMPONENT_LIB_RS)?,
+                &[MARKER_BYTECODE][..],
+            )
+        }
+    };
+    let cargo_toml = match monty_dependency(args)? {
+        Some(dependency) => {
+            let Some(line) = cargo_toml.lines().find(|l| l.starts_with("monty = ")) else {
+                bail!("the Cargo.toml template has no `monty = ...` dependency line to replace");
+            };
+            cargo_toml.replacen(line, &dependency, 1)
+        }
+        None => cargo_toml,
+    };
+    // An overriding lib.rs still has to say where the generated code goes
+    if let Some(marker) = markers.iter().find(|m| !lib_template.contains(*m)) {
+        bail!("the lib.rs template in --template-dir has no `{marker}` line");
+    }
+    let cargo_toml = cargo_toml.replace(
+        &format!("name = \"{DEFAULT_CRATE_NAME}\""),
+        &format!("name = \"{crate_name}\""),
+    );
+    let (allocator_template, allocator_dependency) = args.allocator.unwrap_or_default().template();
+    let cargo_toml = match allocator_dependency {
+        Some(dependency) => cargo_toml.replacen(
+            "[dependencies]\n",
+            &format!("[dependencies]\n{dependency}"),
+            1,
+        ),
+        None => cargo_toml,
+    };
+    let cargo_toml = if args.compress_bytecode {
+        cargo_toml.replacen(
+            "[dependencies]\n",
+            &format!("[dependencies]\n{INFLATE_DEPENDENCY}"),
+            1,
+        )
+    } else {
+        cargo_toml
+    };
+    // Profiles are all built as cargo's release profile with their settings,
+    // so the artifact path doesn't depend on the profile
+    let profile = &args.build_profile;
+    let cargo_toml = cargo_toml.replace(
+        "opt-level = \"s\"\nlto = true\ncodegen-units = 1\n",
+        &format!(
+            "opt-level = {}\nlto = {}\ncodegen-units = {}\n",
+            profile.opt_level.to_toml(),
+            profile.lto,
+            profile.codegen_units
+        ),
+    );
+    fs::write(dir.join("Cargo.toml"), cargo_toml)?;
+
+    let mut cargo_config = if args.compat {
+        // Nightly toolchain with rust-src for -Zbuild-std
+        fs::write(
+            dir.join("rust-toolchain.toml"),
+            format!(
+                "[toolchain]\nchannel = \"{}\"\ntargets = [\"wasm32-unknown-unknown\"]\ncomponents = [\"rust-src\"]\n",
+                if reproducible::in_container() {
+                    reproducible::NIGHTLY
+                } else {
+                    "nightly"
+                }
+            ),
+        )?;
+
+        // Add -Ctarget-cpu=mvp to disable bulk-memory instructions
+        "[build]\ntarget = \"wasm32-unknown-unknown\"\n\n[target.wasm32-unknown-unknown]\nrustflags = [\n    \"-C\", \"link-arg=-s\",\n    # @MONTY_RUSTFLAGS\n    \"-C\", \"target-cpu=mvp\",\n    \"--cfg\", \"getrandom_backend=\\\"custom\\\"\",\n]\n".to_string()
+    } else {
+        fs::write(
+            dir.join("rust-toolchain.toml"),
+            template_file(args, "rust-toolchain.toml", TEMPLATE_RUST_TOOLCHAIN)?.as_ref(),
+        )?;
+        template_file(args, ".cargo/config.toml", TEMPLATE_CARGO_CONFIG)?.into_owned()
+    };
+
+    // Keep machine-specific paths out of the binary so rebuilds elsewhere
+    // produce the same code hash
+    if !cargo_config.contains(MARKER_RUSTFLAGS) {
+        bail!(
+            "the .cargo/config.toml template in --template-dir has no `{}` line in its rustflags",
+            MARKER_RUSTFLAGS.trim()
+        );
+    }
+    cargo_config = cargo_config.replacen(
+        MARKER_RUSTFLAGS,
+        &format!(
+            "{}{}",
+            memory_rustflags(args),
+            path_remap_rustflags(dir, shared_target_root(args).as_deref())
+        ),
+        1,
+    );
+
+    if let Some(url) = &args.registry {
+        cargo_config.push_str(&cargo_registry_config(url));
+    }
+
+    let cargo_dir = dir.join(".cargo");
+    fs::create_dir_all(&cargo_dir)?;
+    fs::write(cargo_dir.join("config.toml"), cargo_config)?;
+
+    // A recorded lock wins; otherwise the container starts from the CLI's
+    // own, and --update-lock drops the one left by the last build
+    let lock_path = dir.join("Cargo.lock");
+    match cargo_lock {
+        Some(lock) => fs::write(&lock_path, lock)?,
+        None if reproducible::in_container() => {
+            fs::write(&lock_path, reproducible::CLI_CARGO_LOCK)?
+        }
+        None if args.update_lock && lock_path.exists() => fs::remove_file(&lock_path)?,
+        None => {}
+    }
+
+    let src_dir = dir.join("src");
+    fs::create_dir_all(&src_dir)?;
+    fs::write(
+        src_dir.join("lib.rs"),
+        generate_lib_rs(
+            &lib_template,
+            methods,
+            &match allocator_template {
+                Some((path, builtin)) => template_file(args, path, builtin)?,
+                None => Cow::Borrowed(""),
+            },
+            metadata::source_metadata(args, methods).as_deref(),
+            args.compress_bytecode,
+            args.format == OutputFormat::Near,
+        ),
+    )?;
+    let source_lines: String = compiled
+        .source_lines
+        .iter()
+        .map(|(start, lines, file)| format!("{start}\t{lines}\t{file}\n"))
+        .collect();
+    let data_files = compiled
+        .blobs
+        .iter()
+        .map(|blob| {
+            (
+                bytecode_file_name(blob.group.as_deref()),
+                &blob.bytecode[..],
+            )
+        })
+        .chain([(SOURCE_LINES_FILE.to_string(), source_lines.as_bytes())]);
+    for (name, data) in data_files {
+        let path = src_dir.join(name);
+        match args.format {
+            OutputFormat::Near => fs::write(path, patch::blob(data))?,
+            OutputFormat::Component => fs::write(path, data)?,
+        }
+    }
+
+    Ok(())
+}
+
+/// Size of a WebAssembly memory page.
+const WASM_PAGE_SIZE: u64 = 64 * 1024;
+
+/// Linker flags (as TOML array lines) for `--stack-size` and the memory
+/// page options.
+fn memory_rustflags(args: &BuildArgs) -> String {
+    let mut link_args = Vec::new();
+    if let Some(bytes) = args.stack_size {
+        link_args.push(format!("-zstack-size={bytes}"));
+    }
+    if let Some(pages) = args.initial_memory_pages {
+        link_args.push(format!(
+            "--initial-memory={}",
+            pages as u64 * WASM_PAGE_SIZE
+        ));
+    }
+    if let Some(pages) = args.max_memory_pages {
+        link_args.push(format!("--max-memory={}", pages as u64 * WASM_PAGE_SIZE));
+    }
+    link_args
+        .iter()
+        .map(|arg| format!("    \"-C\", \"link-arg={arg}\",\n"))
+        .collect()
+}
+
+/// Report invalid or ineffective memory options.
+fn check_memory_options(args: &BuildArgs, diagnostics: &mut Vec<Diagnostic>) {
+    // wasm32 addresses at most 4 GiB
+    const MAX_PAGES: u32 = 65536;
+    if let Some(bytes) = args.stack_size {
+        if bytes == 0 || bytes % 16 != 0 {
+            diagnostics.push(Diagnostic::error(
+                "invalid-option",
+                format!("--stack-size {bytes} must be a positive multiple of 16"),
+            ));
+        }
+    }
+    for (flag, pages) in [
+        ("--initial-memory-pages", args.initial_memory_pages),
+        ("--max-memory-pages", args.max_memory_pages),
+    ] {
+        if pages.is_some_and(|p| p == 0 || p > MAX_PAGES) {
+            diagnostics.push(Diagnostic::error(
+                "invalid-option",
+                format!("{flag} must be between 1 and {MAX_PAGES}"),
+            ));
+        }
+    }
+    if let (Some(initial), Some(max)) = (args.initial_memory_pages, args.max_memory_pages) {
+        if initial > max {
+            diagnostics.push(Diagnostic::error(
+                "invalid-option",
+                format!("--initial-memory-pages {initial} exceeds --max-memory-pages {max}"),
+            ));
+        }
+    }
+    if let (Some(stack), Some(initial)) = (args.stack_size, args.initial_memory_pages) {
+        if stack as u64 >= initial as u64 * WASM_PAGE_SIZE {
+            diagnostics.push(Diagnostic::error(
+                "invalid-option",
+                format!(
+                    "--stack-size {stack} does not fit in --initial-memory-pages {initial} \
+                     ({} bytes) alongside the contract's data",
+                    initial as u64 * WASM_PAGE_SIZE
+                ),
+            ));
+        }
+    }
+    let sets_pages = args.initial_memory_pages.is_some() || args.max_memory_pages.is_some();
+    if sets_pages && args.format == OutputFormat::Near {
+        diagnostics.push(Diagnostic::warning(
+            "invalid-option",
+            "--initial-memory-pages and --max-memory-pages have no effect on NEAR, \
+             which replaces the contract's memory limits with the protocol's",
+        ));
+    }
+}
+
+/// `--remap-path-prefix` rustflags (as TOML array lines) that replace the
+/// build directory, cargo home, and rustup home with fixed placeholders.
+/// Projects building into the shared target cache under `shared_root` remap
+/// it instead of their own directory: the flags must be the same for every
+/// project to share compiled crates, cargo hands rustc the project's own
+/// sources by relative path, and build scripts write into the cache.
+fn path_remap_rustflags(build_dir: &Path, shared_root: Option<&Path>) -> String {
+    let home = std::env::var_os("HOME").map(PathBuf::from);
+    let cargo_home = std::env::var_os("CARGO_HOME")
+        .map(PathBuf::from)
+        .or_else(|| home.as_ref().map(|h| h.join(".cargo")));
+    let rustup_home = std::env::var_os("RUSTUP_HOME")
+        .map(PathBuf::from)
+        .or_else(|| home.as_ref().map(|h| h.join(".rustup")));
+
+    let mut flags = String::new();
+    let remaps = [
+        match shared_root {
+            Some(root) => (Some(root.to_path_buf()), "/target"),
+            None => (Some(build_dir.to_path_buf()), "/build"),
+        },
+        (cargo_home, "/cargo"),
+        (rustup_home, "/rustup"),
+    ];
+    for (from, to) in remaps {
+        if let Some(from) = from {
+            let from = from
+                .display()
+                .to_string()
+                .replace('\\', "\\\\")
+                .replace('"', "\\\"");
+            flags.push_str(&format!("    \"--remap-path-prefix\", \"{from}={to}\",\n"));
+        }
+    }
+    flags
+}
+
+/// Source replacement that points crates.io at a private mirror.
+fn cargo_registry_config(url: &str) -> String {
+    format!(
+        "\n[source.crates-io]\nreplace-with = \"monty-near-mirror\"\n\n\
+         [source.monty-near-mirror]\nregistry = \"{url}\"\n"
+    )
+}
+
+/// The `monty = ...` line for --monty-version or --monty-path, if set.
+fn monty_dependency(args: &BuildArgs) -> Result<Option<String>> {
+    if let Some(version) = &args.monty_version {
+        return Ok(Some(format!(
+            "monty = {}",
+            toml::Value::from(version.as_str())
+        )));
+    }
+    if let Some(path) = &args.monty_path {
+        // The generated project lives elsewhere, so relative paths won't do
+        let path = fs::canonicalize(path)
+            .with_context(|| format!("--monty-path {} not found", path.display()))?;
+        if !path.join("Cargo.toml").is_file() {
+            bail!("--monty-path {} has no Cargo.toml", path.display());
+        }
+        let path = toml::Value::from(path.display().to_string());
+        return Ok(Some(format!("monty = {{ path = {path} }}")));
+    }
+    Ok(None)
+}
+
+/// Cargo flags that would move or retarget the artifact the build picks up.
+const RESERVED_CARGO_FLAGS: &[&str] = &["--target", "--target-dir", "--manifest-path", "--profile"];
+
+/// Check `--features` and `--cargo-flags`. The generated package has no
+/// features of its own, so only dependency features can be enabled.
+fn check_cargo_passthrough(args: &BuildArgs) -> Result<()> {
+    for feature in &args.features {
+        let feature = feature.trim();
+        if !feature.contains('/') {
+            bail!("invalid --features `{feature}`: expected DEP/FEATURE, e.g. monty/<feature>");
+        }
+    }
+    for flag in args.cargo_flags.iter().flat_map(|f| f.split_whitespace()) {
+        let name = flag.split('=').next().unwrap_or(flag);
+        if RESERVED_CARGO_FLAGS.contains(&name) {
+            bail!("--cargo-flags can't include {name}: monty-near-cli sets where cargo builds to");
+        }
+    }
+    Ok(())
+}
+
+/// Reject registry URLs cargo would not accept as a source replacement.
+fn validate_registry_url(url: &str) -> Result<()> {
+    let index = url.strip_prefix("sparse+").unwrap_or(url);
+    let valid_scheme = ["https://", "http://", "file://", "ssh://", "git://"]
+        .iter()
+        .any(|scheme| index.starts_with(scheme) && index.len() > scheme.len());
+    if !valid_scheme || url.contains(['"', '\\', ' ']) {
+        bail!(
+            "invalid --registry URL `{url}`\n    \
+             Expected a git index (https://...) or sparse index (sparse+https://...)"
+        );
+    }
+    Ok(())
+}
+
+// ---------------------------------------------------------------------------
+// Build execution
+// ---------------------------------------------------------------------------
+
+/// Parent of the generated Rust projects, relative to the current directory.
+const BUILD_ROOT: &str = "target/monty-near-build";
+
+/// Where to generate the Rust project for `args`: `--build-dir`, or a
+/// directory under [`BUILD_ROOT`] named by a hash of the source path and build
+/// target, so different contracts and targets never share a project. Each
+/// project's `target/` holds the compiled dependencies reused across builds.
+fn build_dir(args: &BuildArgs) -> Result<PathBuf> {
+    let cwd = std::env::current_dir()?;
+    if let Some(dir) = &args.build_dir {
+        return Ok(cwd.join(dir));
+    }
+    let input = fs::canonicalize(args.input())
+        .with_context(|| format!("failed to resolve {}", args.input().display()))?;
+    let mut hasher = Sha256::new();
+    hasher.update(input.to_string_lossy().as_bytes());
+    hasher.update([args.compat as u8, args.format as u8]);
+    hasher.update(args.build_profile.name.as_bytes());
+    // Container builds use other toolchains; keep their cargo cache apart
+    if reproducible::in_container() {
+        hasher.update(b"reproducible");
+    }
+    let key: String = hasher.finalize()[..6]
+        .iter()
+        .map(|b| format!("{b:02x}"))
+        .collect();
+    let stem = imports::contract_stem(&input);
+    Ok(cwd.join(BUILD_ROOT).join(format!("{stem}-{key}")))
+}
+
+/// The shared target cache's root, unless `--no-shared-cache` is set or the
+/// build runs in the reproducible-build container, which starts from a
+/// clean cache every time.
+fn shared_target_root(args: &BuildArgs) -> Option<PathBuf> {
+    if args.no_shared_cache || reproducible::in_container() {
+        return None;
+    }
+    target_cache::root()
+}
+
+/// Take an exclusive lock on the build directory `dir`, waiting for any other
+/// build using it to finish. The lock is held until the file is dropped.
+fn lock_build_dir(dir: &Path) -> Result<fs::File> {
+    fs::create_dir_all(dir)?;
+    let path = dir.join(".lock");
+    let lock = fs::OpenOptions::new()
+        .create(true)
+        .truncate(false)
+        .write(true)
+        .open(&path)
+        .with_context(|| format!("failed to open {}", path.display()))?;
+    if lock.try_lock().is_err() {
+        eprintln!(
+            "  Waiting for another build in {} to finish...",
+            dir.display()
+        );
+        lock.lock()
+            .with_context(|| format!("failed to lock {}", path.display()))?;
+    }
+    Ok(lock)
+}
+
+/// Every build directory under [`BUILD_ROOT`].
+fn build_dirs() -> Result<Vec<PathBuf>> {
+    let root = std::env::current_dir()?.join(BUILD_ROOT);
+    let Ok(entries) = fs::read_dir(&root) else {
+        return Ok(Vec::new());
+    };
+    let mut dirs = Vec::new();
+    for entry in entries {
+        let path = entry?.path();
+        if path.join("Cargo.toml").is_file() {
+            dirs.push(path);
+        }
+    }
+    dirs.sort();
+    Ok(dirs)
+}
+
+/// The inner `cargo build` of the project in `project_dir` into
+/// `target_dir`.
+fn cargo_build_command(
+    project_dir: &Path,
+    target_dir: &Path,
+    build: &BuildArgs,
+    locked: bool,
+) -> Command {
+    let mut args = vec!["build", "--release"];
+    if build.compat {
+        args.extend(["-Zbuild-std=std,panic_abort"]);
+    }
+    if locked {
+        args.push("--locked");
+    }
+    if build.offline {
+        args.push("--offline");
+    }
+    let jobs = build.jobs.map(|jobs| jobs.to_string());
+    if let Some(jobs) = &jobs {
+        args.extend(["--jobs", jobs]);
+    }
+    let features = build
+        .features
+        .iter()
+        .map(|f| f.trim())
+        .collect::<Vec<_>>()
+        .join(",");
+    if !features.is_empty() {
+        args.extend(["--features", &features]);
+    }
+    args.extend(build.cargo_flags.iter().flat_map(|f| f.split_whitespace()));
+
+    let mut command = Command::new("cargo");
+    command
+        .args(&args)
+        .current_dir(project_dir)
+        .env("CARGO_TARGET_DIR", target_dir)
+        .env_remove("RUSTUP_TOOLCHAIN");
+    command
+}
+
+fn build_wasm(
+    project_dir: &Path,
+    target_dir: &Path,
+    crate_name: &str,
+    build: &BuildArgs,
+    locked: bool,
+) -> Result<PathBuf> {
+    let output = cargo_build_command(project_dir, target_dir, build, locked)
+        .output()
+        .context("failed to run cargo build")?;
+
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        let stdout = String::from_utf8_lossy(&output.stdout);
+        if locked && stderr.contains("--locked") {
+            bail!(
+                "the generated project's dependencies no longer match the lockfile; \
+                 rerun with --update-lock to record new ones\n--- stderr ---\n{stderr}"
+            );
+        }
+        if build.offline && stderr.contains("offline") {
+            bail!(
+                "cargo build --offline failed: dependencies are missing from the cargo cache. \
+                 Build once with network access (or use --vendor-dir) first\n--- stderr ---\n{stderr}"
+            );
+        }
+        bail!("cargo build failed:\n--- stderr ---\n{stderr}\n--- stdout ---\n{stdout}",);
+    }
+
+    let wasm_path = built_wasm_path(target_dir, crate_name);
+    if !wasm_path.exists() {
+        bail!("WASM output not found at {}", wasm_path.display());
+    }
+
+    Ok(wasm_path)
+}
+
+// ---------------------------------------------------------------------------
+// Main entry point
 // ---------------------------------------------------------------------------
-// Code generation — splice generated code into the template
-// ---------------------------------------------------------------------------
 
-/// Generate the `lib.rs` source with a single shared bytecode blob and
-/// thin `#[no_mangle]` exports that pass the method name.
-fn generate_lib_rs(method_names: &[String]) -> String {
-    let bytecode_static = "static CONTRACT_BYTECODE: &[u8] = include_bytes!(\"contract.bin\");\n";
+fn main() -> Result<()> {
+    let mut cli = Cli::parse();
+    let config = config::Config::load()?;
+    let mut format = DiagnosticsFormat::Human;
+    if let Some(build) = cli.command.build_args_mut() {
+        config.apply(build)?;
+        format = build.diagnostics_format;
+        // The built-in wasm-opt reads it from this process's environment;
+        // set it here, before any build thread or child process starts
+        if let Some(jobs) = build.jobs {
+            std::env::set_var("BINARYEN_CORES", jobs.to_string());
+        }
+    }
+
+    let result = run(cli, &config);
+    // Tools reading JSON lines see every failure, not just the checks'
+    if let Err(e) = &result {
+        diagnostics::emit_failure(e, format);
+    }
+    result
+}
+
+fn run(cli: Cli, config: &config::Config) -> Result<()> {
+    match cli.command {
+        Commands::Build(args) if args.reproducible && !reproducible::in_container() => {
+            reproducible::run(&args.build, args.image.as_deref())?;
+        }
+        Commands::Build(args) if args.preflight_only => {
+            preflight(&args.build)?;
+        }
+        Commands::Build(args) if args.watch => {
+            watch::watch(&args.build, args.deploy)?;
+        }
+        Commands::Build(args) if args.all_targets => {
+            build_all_targets(&args.build)?;
+        }
+        Commands::Build(args) => {
+            build_contract(&args.build)?;
+        }
+        Commands::Check(args) => {
+            check(&args)?;
+        }
+        Commands::Abi(args) => {
+            write_abi(&args)?;
+        }
+        Commands::Stubs(args) => stubs::write_stubs(&args.out_dir)?,
+        Commands::Doctor(args) => {
+            doctor::doctor(&doctor::Requirements {
+                compat: args.compat,
+                wasm_opt: true,
+                wasm_opt_program: wasm_opt_program(config.wasm_opt_path().as_deref()),
+                component: false,
+            })?;
+        }
+        Commands::Size(args) => {
+            let build_dirs = match &args.build_dir {
+                Some(dir) => vec![dir.clone()],
+                None => build_dirs()?,
+            };
+            let bytecode_dirs: Vec<PathBuf> = build_dirs.iter().map(|d| d.join("src")).collect();
+            size::report(&args.wasm, &bytecode_dirs, args.top)?;
+        }
+        Commands::Clean(args) => {
+            clean(args.all, args.build_dir.as_deref())?;
+            if args.shared_cache {
+                target_cache::clean()?;
+            }
+            if args.build_cache {
+                build_cache::clean()?;
+            }
+        }
+        Commands::Init(args) => {
+            let name = match &args.name {
+                Some(name) => name.clone(),
+                None => args
+                    .path
+                    .file_name()
+                    .and_then(|n| n.to_str())
+                    .context("cannot derive a contract name from the path; pass --name")?
+                    .to_string(),
+            };
+            validate_contract_name(&name).map_err(anyhow::Error::msg)?;
+            init::init_project(&args.path, &name)?;
+            eprintln!();
+            eprintln!("  Next steps:");
+            eprintln!("    cd {}", args.path.display());
+            eprintln!("    monty-near-cli build contract.py");
+        }
+        Commands::New(args) => {
+            init::new_contract(&args.path, args.template)?;
+        }
+        Commands::Deploy(args) => {
+            let (account_id, network) = config.deploy_target(
+                args.target.account_id.as_deref(),
+                args.target.network.as_deref(),
+                args.target.target.as_deref(),
+            )?;
+            let wasm_path = build_contract(&args.build)?;
+            let wasm = fs::read(&wasm_path)?;
+            deploy::deploy(&wasm, &account_id, &network)?;
+        }
+        Commands::Upgrade(args) => upgrade_contract(&args, config)?,
+        Commands::DevDeploy(args) => {
+            let wasm_path = build_contract(&args.build)?;
+            let wasm = fs::read(&wasm_path)?;
+            match args.network {
+                DevNetwork::Testnet => deploy::dev_deploy_testnet(&wasm)?,
+                DevNetwork::Sandbox => deploy::dev_deploy_sandbox(&wasm)?,
+            }
+        }
+        Commands::Call(args) => {
+            call::call(
+                &args.contract_id,
+                &args.method,
+                args.args.as_bytes(),
+                args.account_id.as_deref().unwrap_or(&args.contract_id),
+                &args.network,
+                args.deposit,
+                args.gas,
+            )?;
+        }
+        Commands::View(args) => {
+            call::view(
+                &args.contract_id,
+                &args.method,
+                args.args.as_bytes(),
+                &args.network,
+            )?;
+        }
+        Commands::State(args) => {
+            state::dump_state(&args.contract_id, &args.network, &args.prefix, args.format)?;
+        }
+        Commands::Run(args) => run_locally(&args)?,
+        Commands::Test(args) => {
+            let files = if args.tests.is_empty() {
+                testing::discover(args.build.input())
+            } else {
+                args.tests.clone()
+            };
+            let wasm_path = build_contract(&args.build)?;
+            testing::run_tests(&fs::read(&wasm_path)?, &files)?;
+        }
+        Commands::GasBench(args) => {
+            let wasm_path = build_contract(&args.build)?;
+            let wasm = fs::read(&wasm_path)?;
+            bench::gas_bench(
+                &wasm,
+                &args.method,
+                args.args.as_bytes(),
+                args.deposit,
+                args.repeat,
+            )?;
+        }
+        Commands::Bench(args) => {
+            let calls = method_calls(args.build.input(), &args.calls)?;
+            // Read the baseline up front so a bad path fails before the slow part
+            let baseline = match &args.baseline {
+                Some(path) => {
+                    let contents = fs::read_to_string(path)
+                        .with_context(|| format!("failed to read {}", path.display()))?;
+                    let value: serde_json::Value = serde_json::from_str(&contents)
+                        .with_context(|| format!("invalid baseline {}", path.display()))?;
+                    Some(value)
+                }
+                None => None,
+            };
+            let wasm_path = build_contract(&args.build)?;
+            let wasm = fs::read(&wasm_path)?;
+            let results = bench::bench(&wasm, &calls, args.calls.deposit, args.repeat)?;
+            if let Some(path) = &args.save_baseline {
+                let json = serde_json::to_string_pretty(&bench::baseline(&results))?;
+                fs::write(path, json + "\n")?;
+                eprintln!("  \u{2713} Baseline written to {}", path.display());
+            }
+            if let Some(baseline) = &baseline {
+                bench::check_baseline(&results, baseline, args.threshold)?;
+            }
+        }
+        Commands::GasProfile(args) => {
+            let calls = method_calls(args.build.input(), &args.calls)?;
+            let wasm_path = build_contract(&args.build)?;
+            let wasm = fs::read(&wasm_path)?;
+            bench::gas_profile(&wasm, &calls, args.calls.deposit, args.detail)?;
+        }
+        Commands::VerifyOnchain(args) if args.reproducible && !reproducible::in_container() => {
+            reproducible::run(&args.build, args.image.as_deref())?;
+        }
+        Commands::VerifyOnchain(args) => {
+            let wasm_path = build_contract(&args.build)?;
+            let wasm = fs::read(&wasm_path)?;
+            verify::verify_onchain(&wasm, &args.account, &args.network)?;
+        }
+        Commands::SelfTest(args) => {
+            let dir =
+                std::env::temp_dir().join(format!("monty-near-self-test-{}", std::process::id()));
+            let result = self_test(&dir, &args);
+            let _ = fs::remove_dir_all(&dir);
+            result?;
+            eprintln!("  Self-test passed");
+        }
+        Commands::Completions(args) => {
+            // Complete the installed binary's name, not the `monty-near` display name
+            clap_complete::generate(
+                args.shell,
+                &mut Cli::command(),
+                env!("CARGO_BIN_NAME"),
+                &mut std::io::stdout(),
+            );
+        }
+    }
+
+    Ok(())
+}
+
+/// Read and analyze a contract for commands that don't build it, reporting
+/// any diagnostics as text.
+fn load_contract(input: &Path) -> Result<(String, Contract)> {
+    let source = read_source(input)?;
+    let file = SourceFile {
+        path: input,
+        text: &source,
+    };
+    let mut diagnostics = Vec::new();
+    let contract = analyze_contract(file, &mut diagnostics);
+    diagnostics::emit(&diagnostics, DiagnosticsFormat::Human, &Lints::default())?;
+    let contract = contract.context("contract failed to parse")?;
+    Ok((source, contract))
+}
+
+/// `run`: execute one method of the contract source against the mock host.
+fn run_locally(args: &RunArgs) -> Result<()> {
+    let (source, contract) = load_contract(&args.contract)?;
+
+    let Some(method) = contract.methods.iter().find(|m| m.name == args.method) else {
+        bail!(
+            "`{}` is not an exported method (available: {})",
+            args.method,
+            contract
+                .methods
+                .iter()
+                .map(|m| m.name.as_str())
+                .collect::<Vec<_>>()
+                .join(", ")
+        );
+    };
+
+    let mut storage = match &args.state {
+        Some(path) => mockhost::Storage::load(path)?,
+        None => mockhost::Storage::default(),
+    };
+    let context = mockhost::CallContext {
+        current_account_id: args.account_id.clone(),
+        predecessor_account_id: args.predecessor.clone(),
+        signer_account_id: args.signer.clone().unwrap_or(args.predecessor.clone()),
+        attached_deposit: args.deposit,
+        account_balance: 100 * 10u128.pow(24),
+        prepaid_gas: tx::DEFAULT_GAS,
+        block_height: args.block_height,
+        block_timestamp: args.block_timestamp,
+        epoch_height: 1,
+        seed: args.seed,
+    };
+    let program = contract.bundle.prelude.clone() + &prepare_source(&source, &contract);
+    let outcome = mockhost::run_method(
+        &program,
+        &args.contract.display().to_string(),
+        method,
+        &contract,
+        args.args.as_bytes(),
+        &context,
+        &mut storage,
+    )?;
+
+    call::print_logs(&outcome.logs);
+    call::print_value(&outcome.value);
+    if let Some(path) = &args.state {
+        storage.save(path)?;
+    }
+    Ok(())
+}
+
+/// The methods `gas-profile` and `bench` call and the input each gets.
+fn method_calls(input: &Path, args: &MethodCallArgs) -> Result<Vec<(String, Vec<u8>)>> {
+    let (_, contract) = load_contract(input)?;
+    let exported: Vec<&str> = contract.methods.iter().map(|m| m.name.as_str()).collect();
+
+    let mut inputs = HashMap::new();
+    for arg in &args.args {
+        let (method, data) = arg
+            .split_once('=')
+            .with_context(|| format!("--args `{arg}` is not METHOD=DATA"))?;
+        inputs.insert(method, data.as_bytes().to_vec());
+    }
+
+    let methods: Vec<&str> = if args.methods.is_empty() {
+        exported.clone()
+    } else {
+        args.methods.iter().map(String::as_str).collect()
+    };
+    for method in methods.iter().chain(inputs.keys()) {
+        if !exported.contains(method) {
+            bail!(
+                "`{method}` is not an exported method (available: {})",
+                exported.join(", ")
+            );
+        }
+    }
+    Ok(methods
+        .iter()
+        .map(|m| (m.to_string(), inputs.get(m).cloned().unwrap_or_default()))
+        .collect())
+}
+
+/// `abi`: analyze the contract and write its near-abi document.
+fn write_abi(args: &AbiArgs) -> Result<()> {
+    let (_, contract) = load_contract(&args.input)?;
+    let abi = contract_abi(&args.input, &contract);
+    let json = serde_json::to_string_pretty(&abi)?;
+    match &args.output {
+        Some(path) => {
+            fs::write(path, json + "\n")?;
+            eprintln!("  \u{2713} {}", path.display());
+        }
+        None => println!("{json}"),
+    }
+    Ok(())
+}
+
+/// The near-abi document for `contract`, named after `__contract_name__` or
+/// the source file (the directory, for a package).
+fn contract_abi(input: &Path, contract: &Contract) -> serde_json::Value {
+    let name = contract
+        .name
+        .clone()
+        .unwrap_or_else(|| imports::contract_stem(input));
+    abi::generate_abi(&name, &contract.module, &contract.methods)
+}
+
+/// `upgrade`: build, check compatibility with the deployed contract, and
+/// deploy with an optional migration call.
+fn upgrade_contract(args: &UpgradeArgs, config: &config::Config) -> Result<()> {
+    let (account_id, network) = config.deploy_target(
+        args.target.account_id.as_deref(),
+        args.target.network.as_deref(),
+        args.target.target.as_deref(),
+    )?;
+    let (_, contract) = load_contract(args.build.input())?;
+    if let Some(method) = &args.migrate {
+        if !contract.methods.iter().any(|m| &m.name == method) {
+            bail!("--migrate `{method}` is not an exported method of the new build");
+        }
+    }
+    let abi = contract_abi(args.build.input(), &contract);
+    let previous_abi = match &args.previous_abi {
+        Some(path) => {
+            let contents = fs::read_to_string(path)
+                .with_context(|| format!("failed to read {}", path.display()))?;
+            let value: serde_json::Value = serde_json::from_str(&contents)
+                .with_context(|| format!("invalid ABI file {}", path.display()))?;
+            Some(value)
+        }
+        None => None,
+    };
+
+    let wasm_path = build_contract(&args.build)?;
+    let wasm = fs::read(&wasm_path)?;
+    let migration = args.migrate.as_deref().map(|method| upgrade::Migration {
+        method,
+        args: args.migrate_args.as_bytes(),
+        gas: args.gas,
+    });
+    let migrate_methods = contract
+        .methods
+        .iter()
+        .filter(|m| m.migrate)
+        .map(|m| m.name.as_str())
+        .collect();
+    upgrade::upgrade(
+        &wasm,
+        &abi,
+        previous_abi.as_ref(),
+        &account_id,
+        &network,
+        upgrade::Options {
+            migration,
+            migrate_methods,
+            force: args.force,
+        },
+    )
+}
+
+/// `clean`: remove the generated project files and the WASM they produced,
+/// keeping cargo's dependency caches unless `all` is set. Cleans `build_dir`
+/// if given, otherwise every build directory under [`BUILD_ROOT`].
+fn clean(all: bool, build_dir: Option<&Path>) -> Result<()> {
+    let cwd = std::env::current_dir()?;
+    let target = build_dir.map_or_else(|| cwd.join(BUILD_ROOT), |dir| cwd.join(dir));
+    if !target.exists() {
+        eprintln!("  Nothing to clean");
+        return Ok(());
+    }
+    if all {
+        fs::remove_dir_all(&target)?;
+        eprintln!("  Removed {}", target.display());
+        return Ok(());
+    }
+
+    let dirs = match build_dir {
+        Some(_) => vec![target],
+        None => build_dirs()?,
+    };
+    for dir in &dirs {
+        clean_build_dir(dir)?;
+    }
+    Ok(())
+}
+
+fn clean_build_dir(build_dir: &Path) -> Result<()> {
+    // Keep the lock file so a concurrent build still excludes others
+    let _lock = lock_build_dir(build_dir)?;
+    for entry in fs::read_dir(build_dir)? {
+        let path = entry?.path();
+        if path
+            .file_name()
+            .is_some_and(|n| n == "target" || n == ".lock")
+        {
+            continue;
+        }
+        if path.is_dir() {
+            fs::remove_dir_all(&path)?;
+        } else {
+            fs::remove_file(&path)?;
+        }
+        eprintln!("  Removed {}", path.display());
+    }
+
+    // Final artifacts only; dependency rlibs under deps/ stay cached
+    let release_dir = build_dir.join("target/wasm32-unknown-unknown/release");
+    if let Ok(entries) = fs::read_dir(&release_dir) {
+        for entry in entries {
+            let path = entry?.path();
+            if path.extension().is_some_and(|ext| ext == "wasm") {
+                fs::remove_file(&path)?;
+                eprintln!("  Removed {}", path.display());
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Build the embedded example contract inside `dir` and check the result.
+fn self_test(dir: &Path, args: &SelfTestArgs) -> Result<()> {
+    let input = selftest::write_example(dir)?;
+    let output = dir.join("example.wasm");
+    let mut argv = vec![
+        input.as_os_str(),
+        "-o".as_ref(),
+        output.as_os_str(),
+        "--no-cache".as_ref(),
+    ];
+    if args.compat {
+        argv.push("--compat".as_ref());
+    }
+    if args.no_wasm_opt {
+        argv.push("--no-wasm-opt".as_ref());
+    }
+    let build = BuildArgs::from_args(argv)?;
+    let wasm_path = build_contract(&build)?;
+    selftest::check_example(&fs::read(&wasm_path)?, &input)
+}
+
+/// Every check that doesn't need external tools: build options, the static
+/// analysis of the source, and the combinations of the two.
+fn check_contract(
+    args: &BuildArgs,
+    file: SourceFile,
+    diagnostics: &mut Vec<Diagnostic>,
+) -> Option<Contract> {
+    if let Some(url) = &args.registry {
+        if let Err(e) = validate_registry_url(url) {
+            diagnostics.push(Diagnostic::error("invalid-option", format!("{e:#}")));
+        }
+    }
+    if let Err(e) = parse_defines(&args.defines) {
+        diagnostics.push(Diagnostic::error("invalid-option", format!("{e:#}")));
+    }
+    if args.vendor_dir.is_some() && args.compat {
+        diagnostics.push(Diagnostic::error(
+            "invalid-option",
+            "--vendor-dir can't be combined with --compat: -Zbuild-std fetches the standard \
+             library's dependencies itself. Use --offline with a cargo cache filled by an \
+             online --compat build instead",
+        ));
+    }
+    if let Some(dir) = args.template_dir.as_ref().filter(|dir| !dir.is_dir()) {
+        diagnostics.push(Diagnostic::error(
+            "invalid-option",
+            format!("--template-dir {} is not a directory", dir.display()),
+        ));
+    }
+    if let Err(e) = check_cargo_passthrough(args) {
+        diagnostics.push(Diagnostic::error("invalid-option", format!("{e:#}")));
+    }
+    if let Err(e) = metadata::check_standards(&args.standards) {
+        diagnostics.push(Diagnostic::error("invalid-option", format!("{e:#}")));
+    }
+    for pattern in &args.allowed_host_functions {
+        if !NEAR_HOST_FUNCTIONS
+            .iter()
+            .any(|(name, _, _)| host_function_allowed(std::slice::from_ref(pattern), name))
+        {
+            diagnostics.push(Diagnostic::error(
+                "invalid-option",
+                format!("--allow-host-function {pattern} matches no NEAR host function"),
+            ));
+        }
+    }
+    check_memory_options(args, diagnostics);
+    if args.allocator.is_some_and(|a| a != Allocator::Dlmalloc)
+        && args.format == OutputFormat::Component
+    {
+        diagnostics.push(Diagnostic::error(
+            "invalid-option",
+            "--allocator is not supported with --format component",
+        ));
+    }
+    if args.compress_bytecode && args.format == OutputFormat::Component {
+        diagnostics.push(Diagnostic::error(
+            "invalid-option",
+            "--compress-bytecode is not supported with --format component",
+        ));
+    }
+    if args.compat && args.format == OutputFormat::Component {
+        diagnostics.push(Diagnostic::error(
+            "invalid-option",
+            "--compat targets NearVM and cannot be combined with --format component",
+        ));
+    }
+    if args.split_methods && args.format == OutputFormat::Component {
+        diagnostics.push(Diagnostic::error(
+            "invalid-option",
+            "--split-methods is not supported with --format component",
+        ));
+    }
+
+    let mut contract = analyze_contract(file, diagnostics)?;
+    if contract.methods.is_empty() {
+        diagnostics.push(file.error(
+            "no-exported-methods",
+            0,
+            "no exported functions found (functions must not start with _)",
+        ));
+    }
+    if args.format == OutputFormat::Component && contract.methods.iter().any(|m| m.group.is_some())
+    {
+        diagnostics.push(Diagnostic::error(
+            "invalid-option",
+            "method groups are not supported with --format component",
+        ));
+    }
+    if args.format == OutputFormat::Component
+        && contract_class::find_class(&contract.module).is_some()
+    {
+        diagnostics.push(Diagnostic::error(
+            "invalid-option",
+            "class Contract keeps its fields in storage, which --format component doesn't have",
+        ));
+    }
+    if args.split_methods && args.format == OutputFormat::Near {
+        split_methods(&mut contract.methods);
+    }
+    Some(contract)
+}
+
+/// The `--split-methods` tradeoff: the bytecode the WASM carries, against
+/// `whole` bytes for the contract as one blob, and the bytecode each
+/// method's calls load and deserialize, which their gas grows with.
+fn report_split(methods: &[Method], blobs: &[Blob], whole: usize) {
+    let total: usize = blobs.iter().map(|b| b.bytecode.len()).sum();
+    let change = |bytes: usize| (bytes as f64 / whole.max(1) as f64 - 1.0) * 100.0;
+    eprintln!(
+        "  Split methods: {total} bytes of bytecode in {} blobs, against {whole} as one blob ({:+.0}% in the WASM)",
+        blobs.len(),
+        change(total)
+    );
+    eprintln!("  Bytecode loaded per call:");
+    let width = methods.iter().map(|m| m.name.len()).max().unwrap_or(0);
+    for method in methods {
+        let Some(blob) = blobs.iter().find(|b| b.group == method.group) else {
+            continue;
+        };
+        let bytes = blob.bytecode.len();
+        eprintln!(
+            "    {:<width$}  {bytes:>8} bytes ({:+.0}%)",
+            method.name,
+            change(bytes)
+        );
+    }
+}
 
-    let mut exports = String::new();
-    for name in method_names {
-        exports.push_str(&format!(
-            "#[no_mangle]\npub extern \"C\" fn {name}() {{\n    run_method(CONTRACT_BYTECODE, \"{name}\");\n}}\n\n",
-        ));
+/// `--split-methods`: put each exported function in a method group of its
+/// own, named after it, replacing any `@group`. Exports of the same
+/// function share a group, and names differing only in case get a suffix,
+/// as the bytecode statics are named in uppercase.
+fn split_methods(methods: &mut [Method]) {
+    let mut groups: HashMap<String, String> = HashMap::new();
+    for method in methods.iter_mut() {
+        if !groups.contains_key(&method.function) {
+            let base = method.function.to_lowercase();
+            let mut group = base.clone();
+            let mut n = 1;
+            while groups.values().any(|taken| *taken == group) {
+                n += 1;
+                group = format!("{base}_{n}");
+            }
+            groups.insert(method.function.clone(), group);
+        }
+        method.group = Some(groups[&method.function].clone());
     }
+}
 
-    TEMPLATE_LIB_RS
-        .replace(MARKER_BYTECODE, bytecode_static)
-        .replace(MARKER_EXPORTS, &exports)
+/// A failed compile as a diagnostic, located when Monty named a line.
+fn compile_diagnostic(e: &anyhow::Error) -> Diagnostic {
+    match e.downcast_ref::<CompileError>() {
+        Some(e) => e.diagnostic(),
+        None => Diagnostic::error("compile-error", format!("{e:#}")),
+    }
 }
 
-// ---------------------------------------------------------------------------
-// Project scaffolding — write the temporary Rust project to disk
-// ---------------------------------------------------------------------------
+/// A contract that passed the static checks and compiled to Monty bytecode.
+struct CheckedContract {
+    contract: Contract,
+    /// The source as compiled, from [`prepare_source`].
+    source: String,
+    defines: Vec<Define>,
+    blobs: Vec<Blob>,
+}
 
-fn write_project(dir: &Path, method_names: &[String], bytecode: &[u8], compat: bool) -> Result<()> {
-    fs::write(dir.join("Cargo.toml"), TEMPLATE_CARGO_TOML)?;
+/// Run the static checks on the contract in `file` and compile it to Monty
+/// bytecode in memory, collecting every problem into `diagnostics`. The
+/// front half of every build, which `check` and `--preflight-only` stop
+/// after; returns the contract if it compiled.
+fn check_and_compile(
+    args: &BuildArgs,
+    file: SourceFile,
+    diagnostics: &mut Vec<Diagnostic>,
+) -> Option<CheckedContract> {
+    let contract = check_contract(args, file, diagnostics);
 
-    if compat {
-        // Nightly toolchain with rust-src for -Zbuild-std
-        fs::write(
-            dir.join("rust-toolchain.toml"),
-            "[toolchain]\nchannel = \"nightly\"\ntargets = [\"wasm32-unknown-unknown\"]\ncomponents = [\"rust-src\"]\n",
+    // Compiling only makes sense once everything above checked out
+    let has_errors = diagnostics.iter().any(|d| d.severity == Severity::Error);
+    let contract = contract.filter(|_| !has_errors)?;
+    let source = prepare_source(file.text, &contract);
+    let compiled = parse_defines(&args.defines).and_then(|defines| {
+        let blobs = precompile_groups(
+            file,
+            &source,
+            &contract.bundle,
+            &contract.module,
+            &contract.methods,
+            &defines,
+            args.format == OutputFormat::Near,
         )?;
+        Ok((defines, blobs))
+    });
+    match compiled {
+        Ok((defines, blobs)) => {
+            let errors = diagnostics.len();
+            check_allowed_host_functions(args, file, &contract.module, &blobs, diagnostics);
+            (diagnostics.len() == errors).then_some(CheckedContract {
+                contract,
+                source,
+                defines,
+                blobs,
+            })
+        }
+        Err(e) => {
+            diagnostics.push(compile_diagnostic(&e));
+            None
+        }
+    }
+}
 
-        // Add -Ctarget-cpu=mvp to disable bulk-memory instructions
-        let cargo_dir = dir.join(".cargo");
-        fs::create_dir_all(&cargo_dir)?;
-        fs::write(
-            cargo_dir.join("config.toml"),
-            "[build]\ntarget = \"wasm32-unknown-unknown\"\n\n[target.wasm32-unknown-unknown]\nrustflags = [\n    \"-C\", \"link-arg=-s\",\n    \"-C\", \"target-cpu=mvp\",\n    \"--cfg\", \"getrandom_backend=\\\"custom\\\"\",\n]\n",
-        )?;
-    } else {
-        fs::write(dir.join("rust-toolchain.toml"), TEMPLATE_RUST_TOOLCHAIN)?;
+/// `check`: the static checks and Monty compilation only, without the
+/// toolchain checks or cargo, so it's fast enough to run on every save.
+fn check(args: &BuildArgs) -> Result<()> {
+    let started = std::time::Instant::now();
+    let input = args.input();
+    let source = read_source(input)?;
+    let file = SourceFile {
+        path: input,
+        text: &source,
+    };
 
-        let cargo_dir = dir.join(".cargo");
-        fs::create_dir_all(&cargo_dir)?;
-        fs::write(cargo_dir.join("config.toml"), TEMPLATE_CARGO_CONFIG)?;
+    let mut diagnostics = Vec::new();
+    let checked = check_and_compile(args, file, &mut diagnostics);
+    diagnostics::emit(&diagnostics, args.diagnostics_format, &args.lints)?;
+    if let Some(checked) = checked {
+        let methods = checked.contract.methods.len();
+        eprintln!(
+            "  \u{2713} {} compiles: {methods} method{} ({} ms)",
+            input.display(),
+            if methods == 1 { "" } else { "s" },
+            started.elapsed().as_millis()
+        );
     }
+    Ok(())
+}
 
-    let src_dir = dir.join("src");
-    fs::create_dir_all(&src_dir)?;
-    fs::write(src_dir.join("lib.rs"), generate_lib_rs(method_names))?;
-    fs::write(src_dir.join("contract.bin"), bytecode)?;
+/// `build --preflight-only`: run all checks, including compiling to Monty
+/// bytecode in memory, but never invoke cargo or write artifacts.
+fn preflight(args: &BuildArgs) -> Result<()> {
+    let input = args.input();
+    let source = read_source(input)?;
+    let file = SourceFile {
+        path: input,
+        text: &source,
+    };
 
+    let mut diagnostics = Vec::new();
+    check_and_compile(args, file, &mut diagnostics);
+    diagnostics.extend(doctor::tool_diagnostics(&doctor::Requirements {
+        compat: args.compat,
+        wasm_opt: !args.no_wasm_opt,
+        wasm_opt_program: wasm_opt_program(args.wasm_opt_path.as_deref()),
+        component: args.format == OutputFormat::Component,
+    }));
+
+    diagnostics::emit(&diagnostics, args.diagnostics_format, &args.lints)?;
+    eprintln!("  \u{2713} {} passed preflight checks", input.display());
     Ok(())
 }
 
-// ---------------------------------------------------------------------------
-// Build execution
-// ---------------------------------------------------------------------------
+/// Make sure the toolchains the cargo build needs (the pinned one, plus
+/// nightly and rust-src if `compat`) are installed. The reproducible build
+/// image has them already.
+fn provision_toolchain(args: &BuildArgs, compat: bool) -> Result<()> {
+    if reproducible::in_container() {
+        return Ok(());
+    }
+    doctor::provision(compat, args.yes)
+}
 
-fn build_wasm(project_dir: &Path, compat: bool) -> Result<PathBuf> {
-    let mut args = vec!["build", "--release"];
-    if compat {
-        args.extend(["-Zbuild-std=std,panic_abort"]);
+/// Run the full build pipeline and return the path of the final WASM.
+fn build_contract(args: &BuildArgs) -> Result<PathBuf> {
+    if args.compat {
+        eprintln!("  Mode: compat (NearVM — nightly + -Zbuild-std -Ctarget-cpu=mvp)");
     }
+    let cache_key = build_cache::key(args)?;
+    if let Some(hit) = cache_key.as_deref().and_then(build_cache::lookup) {
+        eprintln!("  Nothing changed since a cached build: copying it (--no-cache builds anyway)");
+        let output = output_path(args, hit.name.as_deref())?;
+        fs::write(&output, &hit.wasm)
+            .with_context(|| format!("failed to write {}", output.display()))?;
+        report_output(args, &hit.wasm, &output)?;
+        return Ok(output);
+    }
+    provision_toolchain(args, args.compat)?;
+    let warm_up = WarmUp::start(args);
+    let compiled = compile_contract(args)?;
 
-    let output = Command::new("cargo")
-        .args(&args)
-        .current_dir(project_dir)
-        .env_remove("RUSTUP_TOOLCHAIN")
-        .output()
-        .context("failed to run cargo build")?;
+    eprintln!("  Building WASM...");
+    if let Some(warm_up) = warm_up {
+        warm_up.finish();
+    }
+    let built = cargo_build(args, &compiled)?;
+    let output = output_path(args, compiled.name.as_deref())?;
+    let wasm = finish_build(args, &built, &compiled.methods, &output)?;
+    if let Some(key) = &cache_key {
+        if let Err(e) = build_cache::store(key, compiled.name.as_deref(), &wasm) {
+            eprintln!("  Could not cache the build: {e:#}");
+        }
+    }
 
-    if !output.status.success() {
-        let stderr = String::from_utf8_lossy(&output.stderr);
-        let stdout = String::from_utf8_lossy(&output.stdout);
-        bail!("cargo build failed:\n--- stderr ---\n{stderr}\n--- stdout ---\n{stdout}",);
+    if let Some(dir) = &args.out_dir {
+        let target = match (args.format, args.compat) {
+            (OutputFormat::Component, _) => "component",
+            (_, true) => "compat",
+            (_, false) => "standard",
+        };
+        let artifacts = [artifacts::Artifact {
+            path: wasm.clone(),
+            target,
+        }];
+        artifacts::write_out_dir(
+            dir,
+            &build_record(args, &compiled, &built, &wasm),
+            &artifacts,
+        )?;
     }
+    Ok(wasm)
+}
 
-    let wasm_path =
-        project_dir.join("target/wasm32-unknown-unknown/release/monty_near_contract.wasm");
+/// `build --all-targets`: compile the contract once, then build the standard
+/// and compat artifacts side by side.
+fn build_all_targets(args: &BuildArgs) -> Result<Vec<PathBuf>> {
+    if args.format == OutputFormat::Component {
+        bail!("--all-targets builds NEAR contracts and cannot be combined with --format component");
+    }
+    let emit_to = |target: &str| args.emit_project.as_ref().map(|dir| dir.join(target));
+    let standard = BuildArgs {
+        compat: false,
+        emit_project: emit_to("standard"),
+        ..args.clone()
+    };
+    let compat = BuildArgs {
+        compat: true,
+        emit_project: emit_to("compat"),
+        code_hash_file: args.code_hash_file.as_deref().map(compat_sibling),
+        ..args.clone()
+    };
 
-    if !wasm_path.exists() {
-        bail!("WASM output not found at {}", wasm_path.display());
+    eprintln!("  Mode: all targets (standard + compat)");
+    provision_toolchain(args, true)?;
+    let warm_ups = [WarmUp::start(&standard), WarmUp::start(&compat)];
+    let compiled = compile_contract(&standard)?;
+
+    eprintln!("  Building WASM for both targets...");
+    for warm_up in warm_ups.into_iter().flatten() {
+        warm_up.finish();
     }
+    // Separate build directories, so the builds only share the CPU
+    let (standard_built, compat_built) = std::thread::scope(|scope| {
+        let compat_build = scope.spawn(|| cargo_build(&compat, &compiled));
+        let standard_built = cargo_build(&standard, &compiled);
+        let compat_built = compat_build.join().expect("compat build thread panicked");
+        (standard_built, compat_built)
+    });
+    let standard_built = standard_built.context("standard build failed")?;
+    let compat_built = compat_built.context("compat build failed")?;
 
-    Ok(wasm_path)
-}
+    let output = output_path(args, compiled.name.as_deref())?;
+    let compat_output = compat_sibling(&output);
+    let standard_wasm = finish_build(&standard, &standard_built, &compiled.methods, &output)?;
+    let compat_wasm = finish_build(&compat, &compat_built, &compiled.methods, &compat_output)?;
 
-// ---------------------------------------------------------------------------
-// Main entry point
-// ---------------------------------------------------------------------------
+    if let Some(dir) = &args.out_dir {
+        let artifacts = [
+            artifacts::Artifact {
+                path: standard_wasm.clone(),
+                target: "standard",
+            },
+            artifacts::Artifact {
+                path: compat_wasm.clone(),
+                target: "compat",
+            },
+        ];
+        let mut record = build_record(&standard, &compiled, &standard_built, &standard_wasm);
+        record.settings["compat"] = serde_json::json!("both");
+        artifacts::write_out_dir(dir, &record, &artifacts)?;
+    }
+    Ok(vec![standard_wasm, compat_wasm])
+}
 
-fn main() -> Result<()> {
-    let cli = Cli::parse();
+/// `path` with `-compat` added to its file stem: `contract.wasm` becomes
+/// `contract-compat.wasm`.
+fn compat_sibling(path: &Path) -> PathBuf {
+    let stem = path.file_stem().unwrap_or_default().to_string_lossy();
+    let name = match path.extension() {
+        Some(ext) => format!("{stem}-compat.{}", ext.to_string_lossy()),
+        None => format!("{stem}-compat"),
+    };
+    path.with_file_name(name)
+}
 
-    match cli.command {
-        Commands::Build {
-            input,
-            output,
-            compat,
-            no_wasm_opt,
-        } => {
-            build_contract(&input, &output, compat, no_wasm_opt)?;
-        }
+/// What `--out-dir` records about a build of `compiled` to `wasm`.
+fn build_record<'a>(
+    args: &'a BuildArgs,
+    compiled: &'a CompiledContract,
+    built: &'a CargoBuild,
+    wasm: &'a Path,
+) -> artifacts::BuildRecord<'a> {
+    let contract_name = wasm
+        .file_stem()
+        .and_then(|stem| stem.to_str())
+        .unwrap_or("contract");
+    artifacts::BuildRecord {
+        contract_name,
+        source: args.input(),
+        methods: compiled.methods.iter().map(|m| m.name.as_str()).collect(),
+        abi: &compiled.abi,
+        settings: build_settings(args),
+        build_dir: &built.build_dir,
+        started: compiled.started,
     }
+}
 
-    Ok(())
+/// The options of a build that shape its output: the key `--out-dir`'s
+/// manifest records each under, the `build` flag that sets it, and its value.
+/// Paths and other settings a build command line shouldn't carry have no
+/// flag; a `no-` flag is passed when its setting is off.
+fn build_options(args: &BuildArgs) -> Vec<(&'static str, Option<&'static str>, serde_json::Value)> {
+    use serde_json::json;
+    let name =
+        |value: Option<clap::builder::PossibleValue>| value.map(|v| v.get_name().to_string());
+    vec![
+        ("compat", Some("compat"), json!(args.compat)),
+        (
+            "format",
+            Some("format"),
+            json!(name(args.format.to_possible_value())),
+        ),
+        ("profile", Some("profile"), json!(args.build_profile.name)),
+        ("wasm_opt", Some("no-wasm-opt"), json!(!args.no_wasm_opt)),
+        (
+            "wasm_opt_level",
+            Some("wasm-opt-level"),
+            json!((!args.no_wasm_opt)
+                .then(|| name(args.wasm_opt_level.unwrap_or_default().to_possible_value()))),
+        ),
+        (
+            "wasm_opt_args",
+            Some("wasm-opt-arg"),
+            json!(args.wasm_opt_args),
+        ),
+        (
+            "allocator",
+            Some("allocator"),
+            json!(name(args.allocator.unwrap_or_default().to_possible_value())),
+        ),
+        (
+            "compress_bytecode",
+            Some("compress-bytecode"),
+            json!(args.compress_bytecode),
+        ),
+        (
+            "split_methods",
+            Some("split-methods"),
+            json!(args.split_methods),
+        ),
+        ("stack_size", Some("stack-size"), json!(args.stack_size)),
+        (
+            "initial_memory_pages",
+            Some("initial-memory-pages"),
+            json!(args.initial_memory_pages),
+        ),
+        (
+            "max_memory_pages",
+            Some("max-memory-pages"),
+            json!(args.max_memory_pages),
+        ),
+        ("defines", Some("define"), json!(args.defines)),
+        (
+            "source_metadata",
+            Some("no-source-metadata"),
+            json!(!args.no_source_metadata),
+        ),
+        (
+            "allowed_host_functions",
+            None,
+            json!(args.allowed_host_functions),
+        ),
+        ("registry", None, json!(args.registry)),
+        (
+            "monty_version",
+            Some("monty-version"),
+            json!(args.monty_version),
+        ),
+        ("monty_path", None, json!(args.monty_path)),
+        ("features", Some("features"), json!(args.features)),
+        ("cargo_flags", Some("cargo-flags"), json!(args.cargo_flags)),
+    ]
 }
 
-fn build_contract(input: &Path, output: &Path, compat: bool, no_wasm_opt: bool) -> Result<()> {
-    if compat {
-        eprintln!("  Mode: compat (NearVM — nightly + -Zbuild-std -Ctarget-cpu=mvp)");
+/// [`build_options`] as `--out-dir`'s manifest records them.
+fn build_settings(args: &BuildArgs) -> serde_json::Value {
+    build_options(args)
+        .into_iter()
+        .map(|(key, _, value)| (key.to_string(), value))
+        .collect::<serde_json::Map<_, _>>()
+        .into()
+}
+
+/// A contract checked and compiled to Monty bytecode, ready for scaffolding.
+struct CompiledContract {
+    name: Option<String>,
+    methods: Vec<Method>,
+    blobs: Vec<Blob>,
+    /// Where the contract's files are in the program, from
+    /// [`SourceMap::line_table`], so runtime errors can name the line.
+    source_lines: Vec<(usize, usize, String)>,
+    /// near-abi document, for `--out-dir`.
+    abi: serde_json::Value,
+    /// When the build started, for `--out-dir`'s manifest.
+    started: std::time::SystemTime,
+}
+
+/// Parse, check, and compile the contract `args` names, reporting progress.
+fn compile_contract(args: &BuildArgs) -> Result<CompiledContract> {
+    let started = std::time::SystemTime::now();
+    let input = args.input();
+    if args.format == OutputFormat::Component {
+        eprintln!("  Mode: component (experimental — no NEAR host functions)");
+    }
+    let profile = &args.build_profile;
+    if !profile.is_release() {
+        eprintln!(
+            "  Profile: {} (opt-level {}, {}, {})",
+            profile.name,
+            profile.opt_level.to_toml(),
+            if profile.lto { "LTO" } else { "no LTO" },
+            if args.no_wasm_opt {
+                "no wasm-opt"
+            } else {
+                "wasm-opt"
+            }
+        );
+    }
+    // The CLI's own Monty still compiles the bytecode the runtime loads
+    if let Some(version) = &args.monty_version {
+        eprintln!(
+            "  Monty runtime: {version} from crates.io (bytecode format must match this CLI's)"
+        );
+    } else if let Some(path) = &args.monty_path {
+        eprintln!(
+            "  Monty runtime: {} (bytecode format must match this CLI's)",
+            path.display()
+        );
     }
     eprintln!("  Parsing {}...", input.display());
-    let source =
-        fs::read_to_string(input).with_context(|| format!("failed to read {}", input.display()))?;
+    let source = read_source(input)?;
+
+    let mut diagnostics = Vec::new();
+    let file = SourceFile {
+        path: input,
+        text: &source,
+    };
+    // Every diagnostic of the build goes out together, as one SARIF document
+    let checked = check_and_compile(args, file, &mut diagnostics);
+    diagnostics::emit(&diagnostics, args.diagnostics_format, &args.lints)?;
+    let CheckedContract {
+        contract,
+        source,
+        defines,
+        blobs,
+    } = checked.context("contract failed to compile")?;
+    let abi = contract_abi(input, &contract);
+    let Contract {
+        module,
+        methods,
+        name,
+        bundle,
+    } = contract;
 
-    let method_names = find_exported_functions(&source)?;
-    if method_names.is_empty() {
-        bail!("no exported functions found (functions must not start with _)");
-    }
     eprintln!(
         "  Found {} methods: {}",
-        method_names.len(),
-        method_names.join(", ")
+        methods.len(),
+        methods
+            .iter()
+            .map(|m| m.name.as_str())
+            .collect::<Vec<_>>()
+            .join(", ")
+    );
+    if !bundle.files.is_empty() {
+        eprintln!(
+            "  Bundled {}",
+            bundle
+                .files
+                .iter()
+                .map(|f| f.display().to_string())
+                .collect::<Vec<_>>()
+                .join(", ")
+        );
+    }
+
+    let source_lines =
+        SourceMap::new(file, &bundle, &define_header(&module, &defines)).line_table();
+    if let [blob] = &blobs[..] {
+        eprintln!("  Compiled: {} bytes (single blob)", blob.bytecode.len());
+    } else {
+        let sizes: Vec<String> = blobs
+            .iter()
+            .map(|b| {
+                let group = b.group.as_deref().unwrap_or("default");
+                format!("{group} {} bytes", b.bytecode.len())
+            })
+            .collect();
+        eprintln!("  Compiled: {} blobs: {}", blobs.len(), sizes.join(", "));
+    }
+    if args.split_methods {
+        // The same contract as one blob, to compare against
+        let unsplit: Vec<Method> = methods
+            .iter()
+            .map(|m| Method {
+                group: None,
+                ..m.clone()
+            })
+            .collect();
+        let whole = precompile_groups(file, &source, &bundle, &module, &unsplit, &defines, true)?;
+        report_split(&methods, &blobs, whole[0].bytecode.len());
+    }
+    let mut blobs = blobs;
+    if args.compress_bytecode {
+        let before: usize = blobs.iter().map(|b| b.bytecode.len()).sum();
+        for blob in &mut blobs {
+            blob.bytecode = miniz_oxide::deflate::compress_to_vec(&blob.bytecode, 10);
+        }
+        let after: usize = blobs.iter().map(|b| b.bytecode.len()).sum();
+        eprintln!(
+            "  Compressed bytecode: {before} \u{2192} {after} bytes ({:.0}% smaller)",
+            before.saturating_sub(after) as f64 * 100.0 / before.max(1) as f64
+        );
+    }
+    let host_functions: BTreeSet<&str> = blobs
+        .iter()
+        .flat_map(|blob| blob.host_functions.iter().copied())
+        .collect();
+    eprintln!(
+        "  Host functions: {}",
+        if host_functions.is_empty() {
+            "none".to_string()
+        } else {
+            host_functions.into_iter().collect::<Vec<_>>().join(", ")
+        }
     );
 
-    eprint!("  Compiling...");
-    let bytecode = precompile_contract(&source, &method_names)?;
-    eprintln!(" {} bytes (single blob)", bytecode.len());
+    Ok(CompiledContract {
+        name,
+        methods,
+        blobs,
+        source_lines,
+        abi,
+        started,
+    })
+}
 
-    eprintln!("  Building WASM...");
-    let build_dir = std::env::current_dir()?.join("target/monty-near-build");
-    if build_dir.exists() {
-        let src_dir = build_dir.join("src");
-        if src_dir.exists() {
-            fs::remove_dir_all(&src_dir)?;
+/// Output of the inner cargo build, with the build directory still locked.
+struct CargoBuild {
+    build_dir: PathBuf,
+    wasm_path: PathBuf,
+    _lock: fs::File,
+}
+
+/// Where cargo leaves the WASM of crate `crate_name` in `target_dir`.
+fn built_wasm_path(target_dir: &Path, crate_name: &str) -> PathBuf {
+    target_dir
+        .join("wasm32-unknown-unknown/release")
+        .join(format!("{}.wasm", crate_name.replace('-', "_")))
+}
+
+/// The last build's WASM, copied into the build directory: projects sharing
+/// a target directory overwrite each other's output there.
+const BUILT_WASM: &str = "built.wasm";
+
+/// The build options that change the binary without showing in the
+/// generated project's files, for [`patch::patch`].
+fn patch_options(args: &BuildArgs) -> String {
+    format!(
+        "{} {} {:?} {:?} {:?}",
+        env!("CARGO_PKG_VERSION"),
+        args.compat,
+        args.features,
+        args.cargo_flags,
+        std::env::var("RUSTFLAGS").ok()
+    )
+}
+
+/// The Cargo.lock recorded under `lock_key` that the build must use, unless
+/// `--update-lock` asks for new versions.
+fn recorded_lock(args: &BuildArgs, lock_key: &str) -> Result<Option<String>> {
+    match &args.lockfile {
+        Some(path) if !args.update_lock => lockfile::read(path, lock_key),
+        _ => Ok(None),
+    }
+}
+
+/// Scaffold the Rust project for `compiled` and build it with cargo, or,
+/// when only the bytecode changed, patch it into the last build's binary.
+fn cargo_build(args: &BuildArgs, compiled: &CompiledContract) -> Result<CargoBuild> {
+    let build_dir = build_dir(args)?;
+    // Held until the build is copied out, so concurrent builds in the same
+    // directory can't overwrite each other's project or output
+    let lock = lock_build_dir(&build_dir)?;
+    let last_blobs = patch::blobs(&build_dir);
+    let src_dir = build_dir.join("src");
+    if src_dir.exists() {
+        fs::remove_dir_all(&src_dir)?;
+    }
+
+    let crate_name = compiled.name.as_deref().unwrap_or(DEFAULT_CRATE_NAME);
+    let lock_key = lockfile::project_key(args, crate_name);
+    let cargo_lock = recorded_lock(args, &lock_key)?;
+    write_project(
+        &build_dir,
+        crate_name,
+        compiled,
+        cargo_lock.as_deref(),
+        args,
+    )?;
+    if let Some(dir) = &args.vendor_dir {
+        vendor::use_vendor_dir(&build_dir, dir, args)?;
+    }
+    if let Some(dir) = &args.emit_project {
+        emit_project(&build_dir, dir, args.compat)?;
+    }
+
+    let options = patch_options(args);
+    let wasm_path = build_dir.join(BUILT_WASM);
+    if args.format == OutputFormat::Near
+        && !args.rebuild
+        && patch::patch(&build_dir, &wasm_path, &last_blobs, &options)?
+    {
+        eprintln!(
+            "  Only the bytecode changed: patched the last build's WASM (--rebuild runs cargo)"
+        );
+        return Ok(CargoBuild {
+            build_dir,
+            wasm_path,
+            _lock: lock,
+        });
+    }
+
+    let target_dir = match shared_target_root(args) {
+        Some(root) => target_cache::dir(&root, &build_dir),
+        None => build_dir.join("target"),
+    };
+    {
+        // Other projects sharing the target directory may build the same
+        // crate name, until the output is copied out
+        let _shared = lock_build_dir(&target_dir)?;
+        let built = build_wasm(
+            &build_dir,
+            &target_dir,
+            crate_name,
+            args,
+            cargo_lock.is_some(),
+        )?;
+        fs::copy(&built, &wasm_path)?;
+    }
+    if let (Some(path), None) = (&args.lockfile, &cargo_lock) {
+        let resolved = fs::read_to_string(build_dir.join("Cargo.lock"))
+            .context("cargo build left no Cargo.lock")?;
+        lockfile::record(path, &lock_key, &resolved)?;
+    }
+    patch::record(&build_dir, &options)?;
+    Ok(CargoBuild {
+        build_dir,
+        wasm_path,
+        _lock: lock,
+    })
+}
+
+/// A `cargo build` of the unchanged template in a build directory that
+/// hasn't been built yet, run while the contract is compiled to bytecode.
+/// The template refers to code the CLI generates, so cargo stops after
+/// compiling the runtime's dependencies, which the real build then reuses.
+/// Dropping it before [`WarmUp::finish`] kills cargo.
+struct WarmUp {
+    cargo: std::process::Child,
+    /// The build and target directories, until cargo is done with them.
+    _locks: [fs::File; 2],
+}
+
+impl WarmUp {
+    /// Start a warm-up for `args`, if it would help: not once the build
+    /// directory holds a build, whose dependencies are compiled and whose
+    /// project patching compares against, nor for components or
+    /// `--vendor-dir`, which need the real project first. Failing to start
+    /// one only costs time, so it's reported and the build goes on.
+    fn start(args: &BuildArgs) -> Option<WarmUp> {
+        if args.format != OutputFormat::Near || args.vendor_dir.is_some() {
+            return None;
+        }
+        Self::try_start(args).unwrap_or_else(|e| {
+            eprintln!("  Could not start compiling dependencies early: {e:#}");
+            None
+        })
+    }
+
+    fn try_start(args: &BuildArgs) -> Result<Option<WarmUp>> {
+        let build_dir = build_dir(args)?;
+        if build_dir.join(BUILT_WASM).exists() {
+            return Ok(None);
         }
+        // The crate name is part of the recorded lock's key, and of the lock
+        let source = read_source(args.input())?;
+        let crate_name = parse_module(&source)
+            .ok()
+            .and_then(|parsed| {
+                let file = SourceFile {
+                    path: args.input(),
+                    text: &source,
+                };
+                find_contract_name(parsed.syntax(), file, &mut Vec::new())
+            })
+            .unwrap_or_else(|| DEFAULT_CRATE_NAME.to_string());
+        let cargo_lock = recorded_lock(args, &lockfile::project_key(args, &crate_name))?;
+
+        let lock = lock_build_dir(&build_dir)?;
+        let template = CompiledContract {
+            name: None,
+            methods: Vec::new(),
+            blobs: Vec::new(),
+            source_lines: Vec::new(),
+            abi: serde_json::Value::Null,
+            started: std::time::SystemTime::now(),
+        };
+        write_project(
+            &build_dir,
+            &crate_name,
+            &template,
+            cargo_lock.as_deref(),
+            args,
+        )?;
+        fs::write(
+            build_dir.join("src/lib.rs"),
+            template_file(args, "src/lib.rs", TEMPLATE_LIB_RS)?.as_ref(),
+        )?;
+
+        let target_dir = match shared_target_root(args) {
+            Some(root) => target_cache::dir(&root, &build_dir),
+            None => build_dir.join("target"),
+        };
+        let target_lock = lock_build_dir(&target_dir)?;
+        let cargo = cargo_build_command(&build_dir, &target_dir, args, cargo_lock.is_some())
+            .stdout(std::process::Stdio::null())
+            .stderr(std::process::Stdio::null())
+            .spawn()
+            .context("failed to run cargo build")?;
+        Ok(Some(WarmUp {
+            cargo,
+            _locks: [lock, target_lock],
+        }))
+    }
+
+    /// Wait for cargo, then hand the directories to the real build.
+    fn finish(mut self) {
+        let _ = self.cargo.wait();
     }
-    fs::create_dir_all(&build_dir)?;
+}
 
-    write_project(&build_dir, &method_names, &bytecode, compat)?;
+impl Drop for WarmUp {
+    fn drop(&mut self) {
+        // Already reaped after `finish`, when both are no-ops
+        let _ = self.cargo.kill();
+        let _ = self.cargo.wait();
+    }
+}
 
-    let wasm_path = build_wasm(&build_dir, compat)?;
+/// `--emit-project`: copy the generated project in `build_dir` to `dir`,
+/// leaving out cargo's output, the build lock, and what patching uses.
+fn emit_project(build_dir: &Path, dir: &Path, compat: bool) -> Result<()> {
+    // Bytecode files of groups that no longer exist would be stale
+    let src_dir = dir.join("src");
+    if src_dir.exists() {
+        fs::remove_dir_all(&src_dir)?;
+    }
+    copy_dir(
+        build_dir,
+        dir,
+        &["target", ".lock", BUILT_WASM, patch::STAMP_FILE],
+    )
+    .with_context(|| format!("failed to write the generated project to {}", dir.display()))?;
 
-    let output_abs = if output.is_absolute() {
-        output.to_path_buf()
+    let command = if compat {
+        "cargo build --release -Zbuild-std=std,panic_abort"
     } else {
-        std::env::current_dir()?.join(output)
+        "cargo build --release"
+    };
+    eprintln!(
+        "  Wrote the generated project to {} (rebuild with `{command}` there)",
+        dir.display()
+    );
+    Ok(())
+}
+
+/// Recursively copy `from` into `to`, skipping top-level entries named in
+/// `skip`.
+fn copy_dir(from: &Path, to: &Path, skip: &[&str]) -> Result<()> {
+    fs::create_dir_all(to)?;
+    for entry in fs::read_dir(from)? {
+        let entry = entry?;
+        let name = entry.file_name();
+        if skip.iter().any(|s| name == *s) {
+            continue;
+        }
+        let path = entry.path();
+        if path.is_dir() {
+            copy_dir(&path, &to.join(&name), &[])?;
+        } else {
+            fs::copy(&path, to.join(&name))?;
+        }
+    }
+    Ok(())
+}
+
+/// Where the artifact goes: `-o` if given (it wins over the name declared
+/// in the source), else `<__contract_name__>.wasm`, else `contract.wasm`.
+fn output_path(args: &BuildArgs, name: Option<&str>) -> Result<PathBuf> {
+    let output = match (&args.output, name) {
+        (Some(output), _) => output.clone(),
+        (None, Some(name)) => PathBuf::from(format!("{name}.wasm")),
+        (None, None) => PathBuf::from("contract.wasm"),
+    };
+    let output = match &args.out_dir {
+        Some(dir) => {
+            fs::create_dir_all(dir)
+                .with_context(|| format!("failed to create {}", dir.display()))?;
+            // A file name from monty-near.toml's `output` still applies
+            dir.join(output.file_name().unwrap_or(output.as_os_str()))
+        }
+        None => output,
     };
-    fs::copy(&wasm_path, &output_abs)?;
+    Ok(if output.is_absolute() {
+        output
+    } else {
+        std::env::current_dir()?.join(&output)
+    })
+}
+
+/// Copy the cargo output to `output_abs` and post-process it.
+fn finish_build(
+    args: &BuildArgs,
+    built: &CargoBuild,
+    methods: &[Method],
+    output_abs: &Path,
+) -> Result<PathBuf> {
+    fs::copy(&built.wasm_path, output_abs)?;
 
-    let raw_size = fs::metadata(&output_abs)?.len();
+    let raw_size = fs::metadata(output_abs)?.len();
 
-    if !no_wasm_opt {
-        run_wasm_opt(&output_abs, compat, raw_size)?;
+    if !args.no_wasm_opt {
+        if args.wasm_opt_report {
+            report_wasm_opt_passes(output_abs, args)?;
+        }
+        run_wasm_opt(output_abs, args, raw_size)?;
     }
 
-    let final_size = fs::metadata(&output_abs)?.len();
-    let size_kb = final_size as f64 / 1024.0;
-    eprintln!();
-    eprintln!("  \u{2713} {} ({:.0} KB)", output_abs.display(), size_kb);
+    if args.format == OutputFormat::Component {
+        componentize(&built.build_dir, output_abs)?;
+    }
 
-    if compat {
-        verify_no_bulk_memory(&output_abs)?;
+    let wasm = fs::read(output_abs)?;
+    if args.format == OutputFormat::Near {
+        verify_exports(&wasm, methods, args)
+            .with_context(|| format!("{} has the wrong exports", output_abs.display()))?;
+        limits::check(&wasm)?;
     }
+    report_output(args, &wasm, output_abs)?;
 
-    Ok(())
+    if args.compat {
+        verify_no_bulk_memory(&wasm)?;
+    }
+
+    Ok(output_abs.to_path_buf())
 }
 
-fn run_wasm_opt(wasm_path: &Path, compat: bool, raw_size: u64) -> Result<()> {
-    let wasm_str = wasm_path.display().to_string();
-    let mut args = vec!["-Oz", &wasm_str, "-o", &wasm_str];
+/// Print the size and code hash of the final `wasm` at `output_abs`, and
+/// write `--code-hash-file`.
+fn report_output(args: &BuildArgs, wasm: &[u8], output_abs: &Path) -> Result<()> {
+    let size_kb = wasm.len() as f64 / 1024.0;
+    let hash = code_hash(wasm);
+    eprintln!();
+    eprintln!("  \u{2713} {} ({:.0} KB)", output_abs.display(), size_kb);
+    eprintln!("    Code hash: {hash}");
+    if let Some(path) = &args.code_hash_file {
+        fs::write(path, format!("{hash}\n"))
+            .with_context(|| format!("failed to write {}", path.display()))?;
+    }
+    Ok(())
+}
 
+/// Feature flags wasm-opt needs to accept the binary.
+fn wasm_opt_feature_flags(compat: bool) -> &'static [&'static str] {
     // Default builds use post-MVP features that wasm-opt must be told about
-    if !compat {
-        args.extend([
+    if compat {
+        &[]
+    } else {
+        &[
             "--enable-bulk-memory",
             "--enable-nontrapping-float-to-int",
             "--enable-reference-types",
             "--enable-sign-ext",
-        ]);
+        ]
     }
+}
 
-    eprint!("  Optimizing with wasm-opt -Oz...");
+/// NEAR code hash of a contract: base58 of its sha256, as reported by RPC.
+fn code_hash(wasm: &[u8]) -> String {
+    bs58::encode(Sha256::digest(wasm)).into_string()
+}
 
-    let output = Command::new("wasm-opt").args(&args).output();
+/// Version of the `wasm-opt` crate, and the binaryen it bundles, that the
+/// build runs unless a wasm-opt binary is named.
+const WASM_OPT_CRATE_VERSION: &str = "0.116.1";
 
-    match output {
-        Ok(result) if result.status.success() => {
-            let opt_size = fs::metadata(wasm_path)?.len();
-            let saved = raw_size.saturating_sub(opt_size);
-            let pct = if raw_size > 0 {
-                (saved as f64 / raw_size as f64) * 100.0
-            } else {
-                0.0
-            };
-            eprintln!(" saved {:.0} KB ({pct:.0}%)", saved as f64 / 1024.0);
-            Ok(())
-        }
+/// The wasm-opt binary to run instead of the built-in one: `explicit` (from
+/// --wasm-opt-path or monty-near.toml), else `$WASM_OPT`.
+fn wasm_opt_program(explicit: Option<&Path>) -> Option<PathBuf> {
+    explicit
+        .map(Path::to_path_buf)
+        .or_else(|| std::env::var_os("WASM_OPT").map(PathBuf::from))
+}
+
+/// Run wasm-opt with `args`: the named binary if there is one, else the
+/// binaryen built into the CLI, either limited to `--jobs` threads.
+fn wasm_opt(build: &BuildArgs, args: &[&str]) -> Result<()> {
+    let Some(program) = wasm_opt_program(build.wasm_opt_path.as_deref()) else {
+        // `main` sets BINARYEN_CORES for the built-in one
+        let mut command = wasm_opt::integration::Command::new("wasm-opt");
+        command.args(args);
+        return wasm_opt::integration::run_from_command_args(command)
+            .map_err(|err| anyhow::Error::new(err).context("wasm-opt failed"));
+    };
+
+    let mut command = Command::new(&program);
+    if let Some(jobs) = build.jobs {
+        command.env("BINARYEN_CORES", jobs.to_string());
+    }
+    match command.args(args).output() {
+        Ok(result) if result.status.success() => Ok(()),
         Ok(result) => {
             let stderr = String::from_utf8_lossy(&result.stderr);
-            eprintln!(" failed");
             bail!("wasm-opt failed:\n{stderr}");
         }
-        Err(_) => {
-            eprintln!(
-                " skipped (not found)\n    \
-                 Install with: cargo install wasm-opt"
-            );
-            Ok(())
+        Err(_) => bail!("wasm-opt not found at {}", program.display()),
+    }
+}
+
+fn run_wasm_opt(wasm_path: &Path, build: &BuildArgs, raw_size: u64) -> Result<()> {
+    let wasm_str = wasm_path.display().to_string();
+    let level = build.wasm_opt_level.unwrap_or_default().flag();
+    let mut args = vec![level, &wasm_str, "-o", &wasm_str];
+    args.extend(wasm_opt_feature_flags(build.compat));
+    args.extend(build.wasm_opt_args.iter().map(String::as_str));
+
+    eprint!("  Optimizing with wasm-opt {level}...");
+
+    if let Err(err) = wasm_opt(build, &args) {
+        eprintln!(" failed");
+        return Err(err);
+    }
+    let opt_size = fs::metadata(wasm_path)?.len();
+    let saved = raw_size.saturating_sub(opt_size);
+    let pct = if raw_size > 0 {
+        (saved as f64 / raw_size as f64) * 100.0
+    } else {
+        0.0
+    };
+    eprintln!(" saved {:.0} KB ({pct:.0}%)", saved as f64 / 1024.0);
+    Ok(())
+}
+
+/// The main size-relevant passes of `-Oz`, in roughly the order binaryen
+/// schedules them. Used by `--wasm-opt-report`.
+const WASM_OPT_REPORT_PASSES: &[&str] = &[
+    "remove-unused-module-elements",
+    "dce",
+    "remove-unused-names",
+    "remove-unused-brs",
+    "optimize-instructions",
+    "precompute",
+    "simplify-locals",
+    "vacuum",
+    "reorder-locals",
+    "coalesce-locals",
+    "merge-blocks",
+    "local-cse",
+    "code-folding",
+    "merge-locals",
+    "inlining-optimizing",
+    "duplicate-function-elimination",
+    "simplify-globals-optimizing",
+    "merge-similar-functions",
+    "reorder-functions",
+    "memory-packing",
+];
+
+/// Apply each report pass cumulatively to a scratch copy and print the size
+/// change it caused. The real `-Oz` run iterates passes to a fixpoint, so the
+/// per-pass numbers are an approximation of where its savings come from.
+fn report_wasm_opt_passes(wasm_path: &Path, build: &BuildArgs) -> Result<()> {
+    let compat = build.compat;
+    let scratch = wasm_path.with_extension("opt-report.wasm");
+    fs::copy(wasm_path, &scratch)?;
+    let scratch_str = scratch.display().to_string();
+
+    eprintln!("  wasm-opt pass report (cumulative, -Oz order):");
+    let start_size = fs::metadata(&scratch)?.len();
+    let mut size = start_size;
+    let mut contributions = Vec::new();
+
+    for pass in WASM_OPT_REPORT_PASSES {
+        let pass_flag = format!("--{pass}");
+        let mut args = vec![
+            scratch_str.as_str(),
+            "-o",
+            scratch_str.as_str(),
+            "--optimize-level",
+            "2",
+            "--shrink-level",
+            "2",
+            pass_flag.as_str(),
+        ];
+        args.extend(wasm_opt_feature_flags(compat));
+
+        if let Err(err) = wasm_opt(build, &args) {
+            eprintln!("    {pass:<32} failed: {}", format!("{err:#}").trim());
+            continue;
+        }
+
+        let new_size = fs::metadata(&scratch)?.len();
+        contributions.push((*pass, size as i64 - new_size as i64));
+        size = new_size;
+    }
+    let _ = fs::remove_file(&scratch);
+
+    let mut changed = 0;
+    for (pass, saved) in &contributions {
+        if *saved != 0 {
+            changed += 1;
+            eprintln!("    {pass:<32} {:>+8} bytes", -saved);
         }
     }
+    if changed == 0 {
+        eprintln!("    no pass changed the binary");
+    }
+    eprintln!(
+        "    {:<32} {:>+8} bytes",
+        "total",
+        size as i64 - start_size as i64
+    );
+
+    Ok(())
 }
 
-fn verify_no_bulk_memory(wasm_path: &Path) -> Result<()> {
-    let output = Command::new("wasm-tools")
-        .args([
-            "validate",
-            "--features=-bulk-memory",
-            &wasm_path.display().to_string(),
-        ])
-        .output();
+/// Wrap a core module as a WebAssembly component: embed the WIT world as a
+/// custom section, then let wasm-tools lift the canonical-ABI exports.
+fn componentize(project_dir: &Path, wasm_path: &Path) -> Result<()> {
+    eprint!("  Creating component...");
+    let wasm_str = wasm_path.display().to_string();
+    let wit_str = project_dir.join("wit").display().to_string();
 
-    match output {
-        Ok(result) if result.status.success() => {
-            eprintln!("  \u{2713} Verified: no bulk-memory instructions (NearVM compatible)");
-            Ok(())
+    let steps: [&[&str]; 2] = [
+        &["component", "embed", &wit_str, &wasm_str, "-o", &wasm_str],
+        &["component", "new", &wasm_str, "-o", &wasm_str],
+    ];
+    for step in steps {
+        let output = Command::new("wasm-tools").args(step).output();
+        match output {
+            Ok(result) if result.status.success() => {}
+            Ok(result) => {
+                let stderr = String::from_utf8_lossy(&result.stderr);
+                eprintln!(" failed");
+                bail!("wasm-tools {} failed:\n{stderr}", step[..2].join(" "));
+            }
+            Err(_) => {
+                eprintln!(" failed");
+                bail!(
+                    "wasm-tools not found (required for --format component)\n    \
+                     Install with: cargo install wasm-tools"
+                );
+            }
         }
-        Ok(result) => {
-            let stderr = String::from_utf8_lossy(&result.stderr);
-            bail!(
-                "compat build still contains bulk-memory instructions!\n\
-                 wasm-tools validate failed:\n{stderr}\n\
-                 This is a bug — please report it."
-            );
+    }
+    eprintln!(" done");
+    Ok(())
+}
+
+/// Check that the final `wasm` exports each method exactly once, the
+/// source metadata view if the build adds it, and nothing else apart from
+/// the `__`-prefixed exports of the linker and runtime. wasm-opt or a
+/// changed template could otherwise drop a method or leak a function.
+fn verify_exports(wasm: &[u8], methods: &[Method], args: &BuildArgs) -> Result<()> {
+    let exports = size::exported_functions(wasm)?;
+    let mut expected: Vec<&str> = methods.iter().map(|m| m.name.as_str()).collect();
+    if !args.no_source_metadata && !expected.contains(&metadata::METHOD_NAME) {
+        expected.push(metadata::METHOD_NAME);
+    }
+    let mut problems = Vec::new();
+    for name in &expected {
+        match exports.iter().filter(|export| export == name).count() {
+            0 => problems.push(format!("method `{name}` isn't exported")),
+            1 => {}
+            n => problems.push(format!("method `{name}` is exported {n} times")),
         }
-        Err(_) => {
-            eprintln!(
-                "  ! wasm-tools not found — skipping bulk-memory verification.\n    \
-                 Install with: cargo install wasm-tools"
-            );
-            Ok(())
+    }
+    for export in &exports {
+        if !export.starts_with("__") && !expected.contains(&export.as_str()) {
+            problems.push(format!("`{export}` is exported but isn't a method"));
         }
     }
+    if !problems.is_empty() {
+        bail!(
+            "{}\n\
+             This is a bug — please report it.",
+            problems.join("\n")
+        );
+    }
+    eprintln!(
+        "  \u{2713} Verified: exports match the {} method(s)",
+        methods.len()
+    );
+    Ok(())
+}
+
+/// Validate `wasm` with the bulk-memory proposal turned off, so a compat
+/// build that still contains `memory.copy` or `memory.fill` fails here
+/// rather than on deploy to NearVM.
+fn verify_no_bulk_memory(wasm: &[u8]) -> Result<()> {
+    let features = wasmparser::WasmFeatures::default() - wasmparser::WasmFeatures::BULK_MEMORY;
+    if let Err(err) = wasmparser::Validator::new_with_features(features).validate_all(wasm) {
+        bail!(
+            "compat build still contains bulk-memory instructions!\n\
+             validation without bulk-memory failed: {err}\n\
+             This is a bug — please report it."
+        );
+    }
+    eprintln!("  \u{2713} Verified: no bulk-memory instructions (NearVM compatible)");
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn canonicalize_strips_leading_bom() {
+        assert_eq!(canonicalize_source("\u{feff}x = 1\n"), "x = 1\n");
+    }
+
+    #[test]
+    fn canonicalize_keeps_bom_that_isnt_leading() {
+        assert_eq!(canonicalize_source("x = '\u{feff}'\n"), "x = '\u{feff}'\n");
+    }
+
+    #[test]
+    fn canonicalize_normalizes_crlf() {
+        assert_eq!(canonicalize_source("a = 1\r\nb = 2\r\n"), "a = 1\nb = 2\n");
+    }
+
+    #[test]
+    fn canonicalize_normalizes_lone_cr() {
+        assert_eq!(canonicalize_source("a = 1\rb = 2\r"), "a = 1\nb = 2\n");
+    }
+
+    #[test]
+    fn canonicalize_normalizes_mixed_endings() {
+        assert_eq!(
+            canonicalize_source("\u{feff}a = 1\r\nb = 2\rc = 3\nd = 4"),
+            "a = 1\nb = 2\nc = 3\nd = 4"
+        );
+    }
+
+    #[test]
+    fn canonicalize_treats_cr_crlf_as_two_line_breaks() {
+        assert_eq!(canonicalize_source("a = 1\r\r\nb = 2"), "a = 1\n\nb = 2");
+    }
 }
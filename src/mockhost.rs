@@ -0,0 +1,215 @@
+// Local execution — runs a contract method on the host with Monty, answering
+// NEAR host functions from an in-memory mock instead of a real runtime.
+
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::Path;
+
+use anyhow::{bail, Context, Result};
+use monty::MontyObject;
+use sha2::{Digest, Sha256};
+use sha3::{Keccak256, Keccak512};
+
+use crate::interp::{arg_bytes, arg_str};
+use crate::{Contract, Method, Owner};
+
+/// Per-record overhead NEAR adds when accounting storage usage.
+const STORAGE_RECORD_OVERHEAD: u64 = 40;
+
+/// Execution context the mock host reports to the contract.
+pub struct CallContext {
+    pub current_account_id: String,
+    pub predecessor_account_id: String,
+    pub signer_account_id: String,
+    pub attached_deposit: u128,
+    pub account_balance: u128,
+    pub prepaid_gas: u64,
+    pub block_height: u64,
+    pub block_timestamp: u64,
+    pub epoch_height: u64,
+    /// Seed for `random_seed()`, so runs are reproducible.
+    pub seed: u64,
+}
+
+/// Contract storage, loaded from and saved to a JSON object of strings.
+#[derive(Default)]
+pub struct Storage(BTreeMap<String, String>);
+
+impl Storage {
+    /// Read storage from `path`; a missing file is empty storage.
+    pub fn load(path: &Path) -> Result<Self> {
+        match fs::read_to_string(path) {
+            Ok(contents) => serde_json::from_str(&contents)
+                .map(Self)
+                .with_context(|| format!("{} is not a JSON object of strings", path.display())),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(Self::default()),
+            Err(e) => Err(e).with_context(|| format!("failed to read {}", path.display())),
+        }
+    }
+
+    pub fn save(&self, path: &Path) -> Result<()> {
+        fs::write(path, serde_json::to_string_pretty(&self.0)? + "\n")
+            .with_context(|| format!("failed to write {}", path.display()))
+    }
+
+    fn usage(&self) -> u64 {
+        self.0
+            .iter()
+            .map(|(k, v)| (k.len() + v.len()) as u64 + STORAGE_RECORD_OVERHEAD)
+            .sum()
+    }
+}
+
+/// What a method did: its logs and the value it returned, if any.
+pub struct Outcome {
+    pub logs: Vec<String>,
+    pub value: Vec<u8>,
+}
+
+/// Run `method` of `contract`, whose decorator-stripped code is `source`,
+/// against `storage`, behind the same checks as on chain (deposit,
+/// `@one_yocto`, `@private`, `@init`, `@owner_only` against the contract's
+/// `owner`).
+///
+/// On error the caller should discard `storage`, as a failed transaction
+/// would roll back its writes.
+pub fn run_method(
+    source: &str,
+    script_name: &str,
+    method: &Method,
+    contract: &Contract,
+    input: &[u8],
+    context: &CallContext,
+    storage: &mut Storage,
+) -> Result<Outcome> {
+    let external_functions = crate::near_external_functions();
+    let external_functions: Vec<&str> = external_functions.iter().map(String::as_str).collect();
+    // Defines are a build option, so `owner` is whatever the source assigns
+    let owner = Owner::of(&contract.module, &[]);
+    let dispatcher = crate::generate_dispatcher(
+        std::slice::from_ref(method),
+        true,
+        owner,
+        contract.bundle.builtins,
+    );
+    let program = format!(
+        "{}\n\n_method = {:?}\n{dispatcher}",
+        crate::lower(source),
+        method.name
+    );
+
+    let mut host = MockHost {
+        context,
+        input,
+        storage,
+        outcome: Outcome {
+            logs: Vec::new(),
+            value: Vec::new(),
+        },
+    };
+    crate::interp::run(
+        program,
+        script_name,
+        &external_functions,
+        |function, args| host.call(function, args),
+    )?;
+    Ok(host.outcome)
+}
+
+struct MockHost<'a> {
+    context: &'a CallContext,
+    input: &'a [u8],
+    storage: &'a mut Storage,
+    outcome: Outcome,
+}
+
+impl MockHost<'_> {
+    /// Answer a host function call the way template/src/lib.rs does on chain.
+    fn call(&mut self, function: &str, args: &[MontyObject]) -> Result<MontyObject> {
+        let ctx = self.context;
+        let text = |s: &str| MontyObject::String(s.to_string());
+        let key = || arg_str(args, 0).map(str::to_string);
+
+        Ok(match function {
+            "value_return" => {
+                self.outcome.value = match args.first() {
+                    Some(MontyObject::String(_) | MontyObject::Bytes(_)) | None => {
+                        arg_bytes(args, 0)
+                    }
+                    Some(other) => format!("{other:?}").into_bytes(),
+                };
+                MontyObject::None
+            }
+            "input" => match String::from_utf8(self.input.to_vec()) {
+                Ok(s) => MontyObject::String(s),
+                Err(e) => MontyObject::Bytes(e.into_bytes()),
+            },
+            "log" => {
+                let msg = match args.first() {
+                    Some(MontyObject::String(s)) => s.clone(),
+                    Some(other) => format!("{other:?}"),
+                    None => String::new(),
+                };
+                self.outcome.logs.push(msg);
+                MontyObject::None
+            }
+
+            "storage_write" => match (key(), arg_str(args, 1)) {
+                (Some(k), Some(v)) => {
+                    MontyObject::Bool(self.storage.0.insert(k, v.to_string()).is_some())
+                }
+                _ => MontyObject::None,
+            },
+            "storage_read" => match key() {
+                Some(k) => self
+                    .storage
+                    .0
+                    .get(&k)
+                    .map_or(MontyObject::None, |v| text(v)),
+                None => MontyObject::None,
+            },
+            "storage_remove" => match key() {
+                Some(k) => MontyObject::Bool(self.storage.0.remove(&k).is_some()),
+                None => MontyObject::None,
+            },
+            "storage_has_key" => match key() {
+                Some(k) => MontyObject::Bool(self.storage.0.contains_key(&k)),
+                None => MontyObject::None,
+            },
+
+            "current_account_id" => text(&ctx.current_account_id),
+            "predecessor_account_id" => text(&ctx.predecessor_account_id),
+            "signer_account_id" => text(&ctx.signer_account_id),
+            "block_height" => MontyObject::Int(ctx.block_height as i64),
+            "block_timestamp" => MontyObject::Int(ctx.block_timestamp as i64),
+            "epoch_height" => MontyObject::Int(ctx.epoch_height as i64),
+            "storage_usage" => MontyObject::Int(self.storage.usage() as i64),
+            "signer_account_pk" => {
+                // ED25519 key type byte followed by a key derived from the signer id
+                let mut pk = vec![0u8];
+                pk.extend_from_slice(&Sha256::digest(ctx.signer_account_id.as_bytes()));
+                text(&hex(&pk))
+            }
+
+            "account_balance" => text(&ctx.account_balance.to_string()),
+            "account_locked_balance" => text("0"),
+            "attached_deposit" => text(&ctx.attached_deposit.to_string()),
+            "prepaid_gas" => MontyObject::Int(ctx.prepaid_gas as i64),
+            "used_gas" => MontyObject::Int(0),
+
+            "sha256" => text(&hex(&Sha256::digest(arg_bytes(args, 0)))),
+            "keccak256" => text(&hex(&Keccak256::digest(arg_bytes(args, 0)))),
+            "keccak512" => text(&hex(&Keccak512::digest(arg_bytes(args, 0)))),
+            "random_seed" => text(&hex(&Sha256::digest(ctx.seed.to_le_bytes()))),
+
+            other => bail!(
+                "`{other}` is not available in the mock host \
+                 (use `monty-near test` to run against a sandbox)"
+            ),
+        })
+    }
+}
+
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{b:02x}")).collect()
+}
@@ -0,0 +1,230 @@
+// Bytecode patching — when only the Python changed, the generated project
+// differs from the last build's in its bytecode blobs alone, and cargo would
+// rebuild the same runtime around them. Each blob is written as its length,
+// the bytecode, and zeros up to a fixed capacity, and the runtime reads the
+// length when it runs, so the build can overwrite the blobs in the last
+// binary in place instead of running cargo.
+
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::Path;
+
+use anyhow::Result;
+use sha2::{Digest, Sha256};
+
+/// Bytes the length takes at the start of a blob.
+const HEADER: usize = 4;
+
+/// Blob capacities are multiples of this, so bytecode can grow a little
+/// before the layout changes and cargo has to run again.
+const BLOCK: usize = 4096;
+
+/// Where the build directory records what its binary was built from.
+pub const STAMP_FILE: &str = "patch-stamp";
+
+/// `bytecode` as the runtime reads it: its length (4 bytes, little endian),
+/// the bytecode, and zeros up to a multiple of [`BLOCK`].
+pub fn blob(bytecode: &[u8]) -> Vec<u8> {
+    let capacity = (HEADER + bytecode.len()).div_ceil(BLOCK) * BLOCK;
+    let mut blob = Vec::with_capacity(capacity);
+    blob.extend((bytecode.len() as u32).to_le_bytes());
+    blob.extend(bytecode);
+    blob.resize(capacity, 0);
+    blob
+}
+
+/// The bytecode in `blob`, if [`blob`] wrote it.
+pub fn unpack(blob: &[u8]) -> Option<&[u8]> {
+    let len = u32::from_le_bytes(blob.get(..HEADER)?.try_into().ok()?) as usize;
+    let end = HEADER.checked_add(len)?;
+    let padding = blob.get(end..)?;
+    (blob.len().is_multiple_of(BLOCK) && padding.iter().all(|&b| b == 0))
+        .then(|| &blob[HEADER..end])
+}
+
+/// The bytecode blobs of the project in `dir`, by file name.
+pub fn blobs(dir: &Path) -> BTreeMap<String, Vec<u8>> {
+    let Ok(entries) = fs::read_dir(dir.join("src")) else {
+        return BTreeMap::new();
+    };
+    entries
+        .filter_map(|entry| entry.ok().map(|e| e.path()))
+        .filter(|path| path.extension().is_some_and(|ext| ext == "bin"))
+        .filter_map(|path| {
+            let name = path.file_name()?.to_string_lossy().into_owned();
+            Some((name, fs::read(&path).ok()?))
+        })
+        .collect()
+}
+
+/// A fingerprint of what goes into the binary built in `dir`, apart from
+/// what the blobs hold: the project's files, the blobs' names and sizes,
+/// and `options`, the build options that don't show in the files.
+fn stamp(dir: &Path, options: &str) -> String {
+    let mut hasher = Sha256::new();
+    hasher.update(options.as_bytes());
+    for file in [
+        "Cargo.toml",
+        "Cargo.lock",
+        "rust-toolchain.toml",
+        ".cargo/config.toml",
+    ] {
+        hasher.update(file.as_bytes());
+        hasher.update(fs::read(dir.join(file)).unwrap_or_default());
+    }
+    let mut sources: Vec<_> = fs::read_dir(dir.join("src"))
+        .map(|entries| entries.filter_map(|e| e.ok().map(|e| e.path())).collect())
+        .unwrap_or_default();
+    sources.sort();
+    for path in sources {
+        hasher.update(path.file_name().unwrap_or_default().as_encoded_bytes());
+        let contents = fs::read(&path).unwrap_or_default();
+        if path.extension().is_some_and(|ext| ext == "bin") {
+            hasher.update((contents.len() as u64).to_le_bytes());
+        } else {
+            hasher.update(&contents);
+        }
+    }
+    format!("{:x}", hasher.finalize())
+}
+
+/// Record that cargo just built the project in `dir` with `options`.
+pub fn record(dir: &Path, options: &str) -> Result<()> {
+    fs::write(dir.join(STAMP_FILE), stamp(dir, options))?;
+    Ok(())
+}
+
+/// Overwrite the `old` blobs in the binary at `wasm` with the project's
+/// current ones, if nothing else changed since cargo built it with
+/// `options`. Returns whether it did; if not, cargo has to run.
+pub fn patch(
+    dir: &Path,
+    wasm: &Path,
+    old: &BTreeMap<String, Vec<u8>>,
+    options: &str,
+) -> Result<bool> {
+    let recorded = fs::read_to_string(dir.join(STAMP_FILE)).ok();
+    if recorded.as_deref() != Some(stamp(dir, options).as_str()) {
+        return Ok(false);
+    }
+    let Ok(mut binary) = fs::read(wasm) else {
+        return Ok(false);
+    };
+    for (name, blob) in blobs(dir) {
+        let Some(old) = old.get(&name) else {
+            return Ok(false);
+        };
+        // The blob must be in the binary once, exactly as written
+        let mut found = binary
+            .windows(old.len())
+            .enumerate()
+            .filter(|(_, window)| window == old)
+            .map(|(at, _)| at);
+        let (Some(at), None) = (found.next(), found.next()) else {
+            return Ok(false);
+        };
+        binary[at..at + old.len()].copy_from_slice(&blob);
+    }
+    fs::write(wasm, binary)?;
+    Ok(true)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// An empty project directory under the system temp directory.
+    fn project(name: &str) -> std::path::PathBuf {
+        let dir =
+            std::env::temp_dir().join(format!("monty-near-patch-{name}-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(dir.join("src")).unwrap();
+        fs::write(dir.join("src/lib.rs"), "// lib").unwrap();
+        dir
+    }
+
+    /// A fake binary holding `blob` between other bytes.
+    fn binary(blob: &[u8]) -> Vec<u8> {
+        [b"\0asm head".as_slice(), blob, b"tail"].concat()
+    }
+
+    #[test]
+    fn blob_has_length_header_and_zero_padding() {
+        let blob = blob(b"abc");
+        assert_eq!(blob.len(), BLOCK);
+        assert_eq!(&blob[..HEADER], &3u32.to_le_bytes());
+        assert_eq!(&blob[HEADER..HEADER + 3], b"abc");
+        assert!(blob[HEADER + 3..].iter().all(|&b| b == 0));
+        assert_eq!(unpack(&blob), Some(&b"abc"[..]));
+    }
+
+    #[test]
+    fn blob_grows_by_whole_blocks() {
+        assert_eq!(blob(&[1; BLOCK - HEADER]).len(), BLOCK);
+        assert_eq!(blob(&[1; BLOCK - HEADER + 1]).len(), 2 * BLOCK);
+    }
+
+    #[test]
+    fn unpack_rejects_what_blob_did_not_write() {
+        assert_eq!(unpack(b"ab"), None);
+        // Length past the end
+        let mut bad = blob(b"abc");
+        bad[..HEADER].copy_from_slice(&(BLOCK as u32).to_le_bytes());
+        assert_eq!(unpack(&bad), None);
+        // Non-zero padding
+        let mut bad = blob(b"abc");
+        bad[BLOCK - 1] = 1;
+        assert_eq!(unpack(&bad), None);
+    }
+
+    #[test]
+    fn patch_overwrites_blob_in_place() {
+        let dir = project("in-place");
+        let old = blob(b"old bytecode");
+        fs::write(dir.join("src/contract.bin"), &old).unwrap();
+        record(&dir, "opts").unwrap();
+        let wasm = dir.join("built.wasm");
+        fs::write(&wasm, binary(&old)).unwrap();
+
+        let last = blobs(&dir);
+        let new = blob(b"new bytecode, longer");
+        fs::write(dir.join("src/contract.bin"), &new).unwrap();
+        assert!(patch(&dir, &wasm, &last, "opts").unwrap());
+        assert_eq!(fs::read(&wasm).unwrap(), binary(&new));
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn patch_refuses_blob_that_outgrows_its_padding() {
+        let dir = project("too-big");
+        let old = blob(b"old");
+        fs::write(dir.join("src/contract.bin"), &old).unwrap();
+        record(&dir, "opts").unwrap();
+        let wasm = dir.join("built.wasm");
+        fs::write(&wasm, binary(&old)).unwrap();
+
+        let last = blobs(&dir);
+        fs::write(dir.join("src/contract.bin"), blob(&[7; BLOCK])).unwrap();
+        assert!(!patch(&dir, &wasm, &last, "opts").unwrap());
+        assert_eq!(fs::read(&wasm).unwrap(), binary(&old));
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn patch_refuses_other_changes() {
+        let dir = project("other");
+        let old = blob(b"old");
+        fs::write(dir.join("src/contract.bin"), &old).unwrap();
+        record(&dir, "opts").unwrap();
+        let wasm = dir.join("built.wasm");
+        fs::write(&wasm, binary(&old)).unwrap();
+        let last = blobs(&dir);
+        fs::write(dir.join("src/contract.bin"), blob(b"new")).unwrap();
+
+        assert!(!patch(&dir, &wasm, &last, "other opts").unwrap());
+        fs::write(dir.join("src/lib.rs"), "// changed").unwrap();
+        assert!(!patch(&dir, &wasm, &last, "opts").unwrap());
+        assert_eq!(fs::read(&wasm).unwrap(), binary(&old));
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,301 @@
+// Promise builder — `Promise("bob.near").function_call("ft_transfer", args,
+// deposit=1).then(...)` instead of promise_batch_* host calls. A promise is
+// a dict at runtime, since Monty has no classes, so the compiler rewrites
+// each method call on something it knows is a promise into a call of the
+// helper function for that method: `p.transfer(1)` becomes
+// `__promise_transfer__(p, 1)`. A promise is a `Promise(...)` call, a
+// promise method's result, a variable assigned one, a parameter annotated
+// `Promise`, or a call of a function annotated `-> Promise`.
+
+use std::collections::HashSet;
+
+use ruff_python_ast::visitor::{self, Visitor};
+use ruff_python_ast::{Expr, ExprCall, ModModule, Parameters, Stmt};
+use ruff_python_parser::parse_module;
+use ruff_text_size::Ranged;
+
+use crate::diagnostics::{Diagnostic, SourceFile};
+
+/// The constructor, and the annotation marking promises.
+const PROMISE: &str = "Promise";
+
+/// Promise methods; `and_` is near-sdk's `and`, a keyword in Python.
+const METHODS: &[&str] = &[
+    "create_account",
+    "deploy_contract",
+    "function_call",
+    "transfer",
+    "stake",
+    "add_full_access_key",
+    "add_access_key",
+    "delete_key",
+    "delete_account",
+    "deploy_global_contract",
+    "deploy_global_contract_by_account_id",
+    "use_global_contract",
+    "use_global_contract_by_account_id",
+    "then",
+    "and_",
+    "as_return",
+];
+
+/// What the code around an expression knows to be promises.
+struct Known<'a, 'b> {
+    /// Top-level functions annotated `-> Promise`.
+    functions: &'b HashSet<&'a str>,
+    /// Variables and parameters of the current scope.
+    names: HashSet<&'a str>,
+}
+
+impl Known<'_, '_> {
+    fn is_promise(&self, expr: &Expr) -> bool {
+        match expr {
+            Expr::Name(name) => self.names.contains(name.id.as_str()),
+            Expr::Call(call) => match call.func.as_ref() {
+                Expr::Name(name) => {
+                    name.id.as_str() == PROMISE || self.functions.contains(name.id.as_str())
+                }
+                Expr::Attribute(attribute) => {
+                    METHODS.contains(&attribute.attr.as_str()) && self.is_promise(&attribute.value)
+                }
+                _ => false,
+            },
+            _ => false,
+        }
+    }
+}
+
+/// Whether `annotation` is `Promise`.
+fn is_annotation(annotation: Option<&Expr>) -> bool {
+    matches!(annotation, Some(Expr::Name(name)) if name.id.as_str() == PROMISE)
+}
+
+/// Collects the single-name assignments of a scope, not entering nested
+/// functions.
+#[derive(Default)]
+struct Assignments<'a> {
+    values: Vec<(&'a str, &'a Expr)>,
+    annotated: Vec<&'a str>,
+}
+
+impl<'a> Visitor<'a> for Assignments<'a> {
+    fn visit_stmt(&mut self, stmt: &'a Stmt) {
+        match stmt {
+            Stmt::FunctionDef(_) | Stmt::ClassDef(_) => {}
+            Stmt::Assign(assign) => {
+                if let [Expr::Name(target)] = &assign.targets[..] {
+                    self.values.push((target.id.as_str(), &assign.value));
+                }
+            }
+            Stmt::AnnAssign(assign) => {
+                if let Expr::Name(target) = assign.target.as_ref() {
+                    if is_annotation(Some(&assign.annotation)) {
+                        self.annotated.push(target.id.as_str());
+                    } else if let Some(value) = &assign.value {
+                        self.values.push((target.id.as_str(), value));
+                    }
+                }
+            }
+            _ => visitor::walk_stmt(self, stmt),
+        }
+    }
+}
+
+/// The promise variables of a scope: its parameters annotated `Promise`,
+/// and the names it assigns promises to.
+fn scope<'a, 'b>(
+    functions: &'b HashSet<&'a str>,
+    parameters: Option<&'a Parameters>,
+    body: &'a [Stmt],
+) -> Known<'a, 'b> {
+    let mut known = Known {
+        functions,
+        names: HashSet::new(),
+    };
+    if let Some(params) = parameters {
+        for param in params
+            .posonlyargs
+            .iter()
+            .chain(&params.args)
+            .chain(&params.kwonlyargs)
+        {
+            if is_annotation(param.parameter.annotation.as_deref()) {
+                known.names.insert(param.parameter.name.as_str());
+            }
+        }
+    }
+    let mut assignments = Assignments::default();
+    for stmt in body {
+        assignments.visit_stmt(stmt);
+    }
+    known.names.extend(assignments.annotated);
+    // `q = p.then(...)` may come before or after `p = Promise(...)` in the
+    // source, in a loop
+    loop {
+        let before = known.names.len();
+        for (name, value) in &assignments.values {
+            if known.is_promise(value) {
+                known.names.insert(name);
+            }
+        }
+        if known.names.len() == before {
+            break;
+        }
+    }
+    known
+}
+
+/// A method call on a promise: the receiver, the method, and the call.
+type MethodCall<'a> = (&'a Expr, &'a str, &'a ExprCall);
+
+/// Collects method calls on promises, scope by scope.
+struct Calls<'a, 'b> {
+    known: Known<'a, 'b>,
+    found: Vec<MethodCall<'a>>,
+    /// `Promise(...)` calls.
+    constructors: Vec<&'a ExprCall>,
+}
+
+impl<'a> Visitor<'a> for Calls<'a, '_> {
+    fn visit_stmt(&mut self, stmt: &'a Stmt) {
+        let Stmt::FunctionDef(func) = stmt else {
+            visitor::walk_stmt(self, stmt);
+            return;
+        };
+        let mut inner = Calls {
+            known: scope(self.known.functions, Some(&func.parameters), &func.body),
+            found: Vec::new(),
+            constructors: Vec::new(),
+        };
+        for stmt in &func.body {
+            inner.visit_stmt(stmt);
+        }
+        self.found.extend(inner.found);
+        self.constructors.extend(inner.constructors);
+    }
+
+    fn visit_expr(&mut self, expr: &'a Expr) {
+        if let Expr::Call(call) = expr {
+            match call.func.as_ref() {
+                Expr::Attribute(attribute) if self.known.is_promise(&attribute.value) => {
+                    self.found
+                        .push((&attribute.value, attribute.attr.as_str(), call));
+                }
+                Expr::Name(name) if name.id.as_str() == PROMISE => {
+                    self.constructors.push(call);
+                }
+                _ => {}
+            }
+        }
+        visitor::walk_expr(self, expr);
+    }
+}
+
+/// The top-level functions of `module` annotated `-> Promise`, or `None` if
+/// it defines a `Promise` of its own.
+fn promise_functions(module: &ModModule) -> Option<HashSet<&str>> {
+    let mut functions = HashSet::new();
+    for stmt in &module.body {
+        match stmt {
+            Stmt::FunctionDef(func) if func.name.as_str() == PROMISE => return None,
+            Stmt::ClassDef(class) if class.name.as_str() == PROMISE => return None,
+            Stmt::FunctionDef(func) if is_annotation(func.returns.as_deref()) => {
+                functions.insert(func.name.as_str());
+            }
+            _ => {}
+        }
+    }
+    Some(functions)
+}
+
+/// The method calls on promises in `module`, outer calls of a chain first,
+/// and its `Promise(...)` calls.
+fn calls<'a, 'b>(module: &'a ModModule, functions: &'b HashSet<&'a str>) -> Calls<'a, 'b> {
+    let mut calls = Calls {
+        known: scope(functions, None, &module.body),
+        found: Vec::new(),
+        constructors: Vec::new(),
+    };
+    for stmt in &module.body {
+        calls.visit_stmt(stmt);
+    }
+    calls
+}
+
+/// Report method calls on promises that aren't promise methods, and
+/// `Promise` calls without exactly one account. Returns whether `module`
+/// uses `Promise`, so the build adds the helpers.
+pub fn check(module: &ModModule, file: SourceFile, diagnostics: &mut Vec<Diagnostic>) -> bool {
+    let Some(functions) = promise_functions(module) else {
+        return false;
+    };
+    let calls = calls(module, &functions);
+    for (_, method, call) in &calls.found {
+        if !METHODS.contains(method) {
+            diagnostics.push(
+                file.error(
+                    "invalid-promise",
+                    call.range.start().to_usize(),
+                    format!("Promise has no method `{method}`"),
+                )
+                .with_note(format!("Promise methods: {}", METHODS.join(", "))),
+            );
+        }
+    }
+    for call in &calls.constructors {
+        let arguments = &call.arguments;
+        if arguments.args.len() != 1 || !arguments.keywords.is_empty() {
+            diagnostics.push(file.error(
+                "invalid-promise",
+                call.range.start().to_usize(),
+                "Promise() takes the account ID the actions run on".to_string(),
+            ));
+        }
+    }
+    !calls.constructors.is_empty()
+}
+
+/// Lower the promise method calls in `source` to calls of the helper
+/// functions, keeping every line where it was. Source that doesn't parse
+/// is returned unchanged.
+pub fn lower(source: &str) -> String {
+    let Ok(parsed) = parse_module(source) else {
+        return source.to_string();
+    };
+    let module = parsed.into_syntax();
+    let Some(functions) = promise_functions(&module) else {
+        return source.to_string();
+    };
+
+    // `p.method(` becomes `__promise_method__(p, `: the function's name goes
+    // before the receiver and `.method(` becomes the separator. Chained
+    // calls share their receiver's start, so the outer call's name must
+    // end up first.
+    let mut edits: Vec<(usize, usize, String)> = Vec::new();
+    for (receiver, method, call) in calls(&module, &functions).found {
+        let start = receiver.range().start().to_usize();
+        let end = receiver.range().end().to_usize();
+        let func_end = call.func.range().end().to_usize();
+        let Some(paren) = source[func_end..].find('(') else {
+            continue;
+        };
+        let arguments = &call.arguments;
+        let separator = if arguments.args.is_empty() && arguments.keywords.is_empty() {
+            ""
+        } else {
+            ", "
+        };
+        // Chains split across lines keep their line breaks
+        let breaks = "\n".repeat(source[end..func_end].matches('\n').count());
+        edits.push((start, start, format!("__promise_{method}__(")));
+        edits.push((end, func_end + paren + 1, format!("{separator}{breaks}")));
+    }
+    // Stable, so outer calls stay ahead of inner ones at the same offset,
+    // and applied from the end, so the inner ones are inserted first
+    edits.sort_by_key(|&(start, _, _)| start);
+    let mut out = source.to_string();
+    for (start, end, replacement) in edits.into_iter().rev() {
+        out.replace_range(start..end, &replacement);
+    }
+    out
+}
@@ -0,0 +1,215 @@
+// Reproducible builds — `build --reproducible` reruns the build inside a
+// pinned container image, so the same source produces the same WASM on
+// every machine: the same Rust toolchains and wasm-opt, and Monty and its
+// dependencies at the versions this CLI was built with.
+
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+
+use anyhow::{bail, Context, Result};
+use sha2::{Digest, Sha256};
+
+use crate::{metadata, BuildArgs, TEMPLATE_DOCKERFILE};
+
+/// Nightly toolchain `--compat` builds use inside the image.
+pub const NIGHTLY: &str = "nightly-2025-10-15";
+
+/// Version of the `wasm-opt` crate (and the binaryen it bundles) in the image.
+const WASM_OPT_VERSION: &str = "0.116.1";
+
+/// Set by the image, so the CLI knows it's the inside half of the build.
+const CONTAINER_ENV: &str = "MONTY_NEAR_REPRODUCIBLE";
+
+/// The image reference, passed into the container for the source metadata.
+const IMAGE_ENV: &str = "MONTY_NEAR_IMAGE";
+
+/// This CLI's lockfile. Generated projects built in the container start from
+/// it, which pins Monty and its dependencies to the versions that compiled
+/// the bytecode.
+pub const CLI_CARGO_LOCK: &str = include_str!("../Cargo.lock");
+
+/// Whether this process is running inside the reproducible build image.
+pub fn in_container() -> bool {
+    std::env::var_os(CONTAINER_ENV).is_some()
+}
+
+/// The image this process is running in, if it's inside one.
+pub fn image() -> Option<String> {
+    in_container().then(|| std::env::var(IMAGE_ENV).unwrap_or_else(|_| "unknown".into()))
+}
+
+/// Run this command line again inside `image` (default: the pinned image,
+/// built on first use), with the project mounted at the same path as on the
+/// host so every path on the command line and in monty-near.toml still
+/// resolves.
+pub fn run(args: &BuildArgs, image: Option<&str>) -> Result<()> {
+    let cwd = std::env::current_dir()?;
+    let root = metadata::git(&cwd, &["rev-parse", "--show-toplevel"])
+        .map_or_else(|| cwd.clone(), PathBuf::from);
+    let root = root.canonicalize().unwrap_or(root);
+    check_paths(args, &cwd, &root)?;
+
+    let image = match image {
+        Some(image) => image.to_string(),
+        None => ensure_image()?,
+    };
+    eprintln!("  Mode: reproducible ({image})");
+    let exe = std::env::current_exe().context("failed to locate the monty-near-cli binary")?;
+
+    let mut command = Command::new("docker");
+    command.args(["run", "--rm"]);
+    if let Some(user) = host_user() {
+        command.args(["--user", &user]);
+    }
+    command
+        .args(["--env", "HOME=/tmp", "--env", "WASM_OPT=wasm-opt"])
+        .arg("--env")
+        .arg(format!("{IMAGE_ENV}={image}"))
+        // The checkout belongs to the host user, whom git in the container
+        // may not recognize
+        .args([
+            "--env",
+            "GIT_CONFIG_COUNT=1",
+            "--env",
+            "GIT_CONFIG_KEY_0=safe.directory",
+            "--env",
+            "GIT_CONFIG_VALUE_0=*",
+        ])
+        .arg("--mount")
+        .arg(bind_mount(&root, &root, false))
+        .arg("--mount")
+        .arg(bind_mount(
+            &exe,
+            Path::new("/usr/local/bin/monty-near-cli"),
+            true,
+        ))
+        // Downloaded crates and git checkouts, kept between builds
+        .args([
+            "--mount",
+            "type=volume,source=monty-near-cargo-registry,target=/usr/local/cargo/registry",
+            "--mount",
+            "type=volume,source=monty-near-cargo-git,target=/usr/local/cargo/git",
+        ])
+        .arg("--workdir")
+        .arg(&cwd)
+        .arg(&image)
+        .arg("monty-near-cli")
+        .args(std::env::args_os().skip(1));
+
+    let status = command
+        .status()
+        .context("failed to run docker: --reproducible needs Docker")?;
+    if !status.success() {
+        bail!("reproducible build in {image} failed");
+    }
+    Ok(())
+}
+
+/// Only `root` is mounted, so every path the build reads or writes must be
+/// inside it.
+fn check_paths(args: &BuildArgs, cwd: &Path, root: &Path) -> Result<()> {
+    let paths = [
+        Some(args.input()),
+        args.output.as_deref(),
+        args.out_dir.as_deref(),
+        args.code_hash_file.as_deref(),
+        args.build_dir.as_deref(),
+        args.emit_project.as_deref(),
+        args.monty_path.as_deref(),
+    ];
+    for path in paths.into_iter().flatten() {
+        let absolute = cwd.join(path);
+        // Outputs may not exist yet; their parent directory has to be inside
+        let resolved = absolute
+            .ancestors()
+            .find_map(|p| p.canonicalize().ok())
+            .unwrap_or(absolute);
+        if !resolved.starts_with(root) {
+            bail!(
+                "--reproducible mounts only {} into the container, and {} is outside it",
+                root.display(),
+                path.display()
+            );
+        }
+    }
+    Ok(())
+}
+
+/// Build the pinned image unless it exists already; its tag.
+///
+/// The tag includes a hash of the Dockerfile and the pinned versions, so a
+/// CLI release that changes them gets a fresh image.
+fn ensure_image() -> Result<String> {
+    let mut hasher = Sha256::new();
+    hasher.update(TEMPLATE_DOCKERFILE.as_bytes());
+    hasher.update(NIGHTLY.as_bytes());
+    hasher.update(WASM_OPT_VERSION.as_bytes());
+    let key: String = hasher.finalize()[..6]
+        .iter()
+        .map(|b| format!("{b:02x}"))
+        .collect();
+    let tag = format!(
+        "{}-build:{}-{key}",
+        env!("CARGO_PKG_NAME"),
+        env!("CARGO_PKG_VERSION")
+    );
+
+    let exists = Command::new("docker")
+        .args(["image", "inspect", &tag])
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .status()
+        .context("failed to run docker: --reproducible needs Docker")?
+        .success();
+    if exists {
+        return Ok(tag);
+    }
+
+    eprintln!("  Building image {tag} (first run only, takes a few minutes)...");
+    let mut child = Command::new("docker")
+        .args(["build", "--tag", &tag])
+        .arg("--build-arg")
+        .arg(format!("NIGHTLY={NIGHTLY}"))
+        .arg("--build-arg")
+        .arg(format!("WASM_OPT_VERSION={WASM_OPT_VERSION}"))
+        // Dockerfile on stdin, no build context
+        .arg("-")
+        .stdin(Stdio::piped())
+        .stdout(std::io::stderr())
+        .spawn()
+        .context("failed to run docker build")?;
+    {
+        use std::io::Write;
+        let mut stdin = child.stdin.take().context("docker build has no stdin")?;
+        stdin.write_all(TEMPLATE_DOCKERFILE.as_bytes())?;
+    }
+    if !child.wait()?.success() {
+        bail!("failed to build the reproducible build image {tag}");
+    }
+    Ok(tag)
+}
+
+/// `uid:gid` of the current user, so files the container writes into the
+/// project aren't owned by root. `None` where `id` isn't available.
+fn host_user() -> Option<String> {
+    let id = |flag: &str| {
+        let output = Command::new("id").arg(flag).output().ok()?;
+        output
+            .status
+            .success()
+            .then(|| String::from_utf8_lossy(&output.stdout).trim().to_string())
+    };
+    Some(format!("{}:{}", id("-u")?, id("-g")?))
+}
+
+fn bind_mount(source: &Path, target: &Path, readonly: bool) -> String {
+    let mut mount = format!(
+        "type=bind,source={},target={}",
+        source.display(),
+        target.display()
+    );
+    if readonly {
+        mount.push_str(",readonly");
+    }
+    mount
+}
@@ -0,0 +1,372 @@
+// NEAR JSON-RPC client — just enough of the API for deploying contracts,
+// sending function calls, and reading back execution outcomes.
+
+use std::time::Duration;
+
+use anyhow::{bail, Context, Result};
+use base64::{engine::general_purpose::STANDARD as BASE64, Engine as _};
+use serde_json::{json, Value};
+
+/// RPC endpoint for a network name, or the argument itself if it's a URL.
+pub fn network_rpc_url(network: &str) -> Result<String> {
+    match network {
+        "mainnet" => Ok("https://rpc.mainnet.near.org".to_string()),
+        "testnet" => Ok("https://rpc.testnet.near.org".to_string()),
+        url if url.starts_with("http://") || url.starts_with("https://") => Ok(url.to_string()),
+        other => bail!("unknown network `{other}` (expected mainnet, testnet, or an RPC URL)"),
+    }
+}
+
+pub struct RpcClient {
+    url: String,
+    agent: ureq::Agent,
+}
+
+impl RpcClient {
+    pub fn new(url: &str) -> Self {
+        let agent = ureq::AgentBuilder::new()
+            .timeout(Duration::from_secs(60))
+            .build();
+        Self {
+            url: url.to_string(),
+            agent,
+        }
+    }
+
+    pub fn url(&self) -> &str {
+        &self.url
+    }
+
+    /// Issue a JSON-RPC request and return its `result` field.
+    pub fn call(&self, method: &str, params: Value) -> Result<Value> {
+        let request = json!({
+            "jsonrpc": "2.0",
+            "id": "monty-near",
+            "method": method,
+            "params": params,
+        });
+
+        let response: Value = match self.agent.post(&self.url).send_json(request) {
+            Ok(response) => response.into_json()?,
+            // NEAR RPC reports some errors with a non-2xx status but a JSON body
+            Err(ureq::Error::Status(_, response)) => response.into_json()?,
+            Err(e) => return Err(e).with_context(|| format!("RPC request to {} failed", self.url)),
+        };
+
+        if let Some(error) = response.get("error") {
+            let detail = error
+                .get("data")
+                .or_else(|| error.get("cause"))
+                .unwrap_or(error);
+            bail!("RPC {method} failed: {detail}");
+        }
+        response
+            .get("result")
+            .cloned()
+            .with_context(|| format!("RPC {method} returned no result"))
+    }
+
+    pub fn status(&self) -> Result<Value> {
+        self.call("status", json!([]))
+    }
+
+    /// Hash of the latest final block, used as the transaction reference block.
+    pub fn final_block_hash(&self) -> Result<[u8; 32]> {
+        let block = self.call("block", json!({ "finality": "final" }))?;
+        let hash = block["header"]["hash"]
+            .as_str()
+            .context("block response has no header.hash")?;
+        decode_hash(hash)
+    }
+
+    /// Current nonce of an access key.
+    pub fn access_key_nonce(&self, account_id: &str, public_key: &str) -> Result<u64> {
+        let result = self.call(
+            "query",
+            json!({
+                "request_type": "view_access_key",
+                "finality": "optimistic",
+                "account_id": account_id,
+                "public_key": public_key,
+            }),
+        )?;
+        result["nonce"]
+            .as_u64()
+            .with_context(|| format!("no access key {public_key} on {account_id}"))
+    }
+
+    /// Base58 sha256 of the contract code deployed on `account_id`.
+    pub fn code_hash(&self, account_id: &str) -> Result<String> {
+        let account = self.call(
+            "query",
+            json!({
+                "request_type": "view_account",
+                "finality": "final",
+                "account_id": account_id,
+            }),
+        )?;
+        account["code_hash"]
+            .as_str()
+            .map(str::to_string)
+            .with_context(|| format!("no code_hash in view_account for {account_id}"))
+    }
+
+    /// WASM code deployed on `account_id`.
+    pub fn view_code(&self, account_id: &str) -> Result<Vec<u8>> {
+        let result = self.call(
+            "query",
+            json!({
+                "request_type": "view_code",
+                "finality": "final",
+                "account_id": account_id,
+            }),
+        )?;
+        let code = result["code_base64"]
+            .as_str()
+            .context("view_code response has no code_base64")?;
+        BASE64
+            .decode(code)
+            .context("invalid base64 in view_code response")
+    }
+
+    /// Run a view call and return the raw result bytes and logs.
+    pub fn view_function(
+        &self,
+        account_id: &str,
+        method: &str,
+        args: &[u8],
+    ) -> Result<(Vec<u8>, Vec<String>)> {
+        let result = self.call(
+            "query",
+            json!({
+                "request_type": "call_function",
+                "finality": "optimistic",
+                "account_id": account_id,
+                "method_name": method,
+                "args_base64": BASE64.encode(args),
+            }),
+        )?;
+        if let Some(error) = result.get("error") {
+            bail!("view call {account_id}.{method} failed: {error}");
+        }
+        let bytes = result["result"]
+            .as_array()
+            .context("view response has no result bytes")?
+            .iter()
+            .map(|b| b.as_u64().unwrap_or(0) as u8)
+            .collect();
+        let logs = result["logs"]
+            .as_array()
+            .map(|logs| {
+                logs.iter()
+                    .filter_map(|l| l.as_str().map(str::to_string))
+                    .collect()
+            })
+            .unwrap_or_default();
+        Ok((bytes, logs))
+    }
+
+    /// Contract storage of `account_id` under keys starting with `prefix`,
+    /// as raw key/value pairs.
+    pub fn view_state(&self, account_id: &str, prefix: &[u8]) -> Result<Vec<(Vec<u8>, Vec<u8>)>> {
+        let result = self.call(
+            "query",
+            json!({
+                "request_type": "view_state",
+                "finality": "final",
+                "account_id": account_id,
+                "prefix_base64": BASE64.encode(prefix),
+            }),
+        )?;
+        let values = result["values"]
+            .as_array()
+            .context("view_state response has no values")?;
+        values
+            .iter()
+            .map(|entry| {
+                let decode = |field: &str| {
+                    BASE64
+                        .decode(entry[field].as_str().unwrap_or(""))
+                        .with_context(|| format!("invalid base64 in view_state {field}"))
+                };
+                Ok((decode("key")?, decode("value")?))
+            })
+            .collect()
+    }
+
+    /// Submit a borsh-encoded signed transaction and wait for it to execute.
+    pub fn send_tx(&self, signed_tx: &[u8]) -> Result<Value> {
+        self.call(
+            "send_tx",
+            json!({
+                "signed_tx_base64": BASE64.encode(signed_tx),
+                "wait_until": "EXECUTED_OPTIMISTIC",
+            }),
+        )
+    }
+}
+
+fn decode_hash(hash: &str) -> Result<[u8; 32]> {
+    let bytes = bs58::decode(hash)
+        .into_vec()
+        .with_context(|| format!("invalid base58 hash {hash}"))?;
+    bytes
+        .try_into()
+        .map_err(|_| anyhow::anyhow!("hash {hash} is not 32 bytes"))
+}
+
+// ---------------------------------------------------------------------------
+// Execution outcome helpers
+// ---------------------------------------------------------------------------
+
+/// Gas burnt by the transaction itself plus every receipt it spawned.
+pub fn total_gas_burnt(outcome: &Value) -> u64 {
+    let tx_gas = outcome["transaction_outcome"]["outcome"]["gas_burnt"]
+        .as_u64()
+        .unwrap_or(0);
+    tx_gas + receipts_gas_burnt(outcome)
+}
+
+/// Gas burnt by receipts only — for a function call this is the cost of
+/// executing the contract, excluding transaction-to-receipt conversion.
+pub fn receipts_gas_burnt(outcome: &Value) -> u64 {
+    outcome["receipts_outcome"]
+        .as_array()
+        .map(|receipts| {
+            receipts
+                .iter()
+                .filter_map(|r| r["outcome"]["gas_burnt"].as_u64())
+                .sum()
+        })
+        .unwrap_or(0)
+}
+
+/// Decoded `SuccessValue` of a transaction, or an error describing the failure.
+pub fn outcome_result(outcome: &Value) -> Result<Vec<u8>> {
+    let status = &outcome["status"];
+    if let Some(value) = status.get("SuccessValue").and_then(Value::as_str) {
+        return BASE64
+            .decode(value)
+            .context("invalid base64 in SuccessValue");
+    }
+    if status.get("SuccessReceiptId").is_some() {
+        return Ok(Vec::new());
+    }
+    if let Some(failure) = status.get("Failure") {
+        bail!("transaction failed: {failure}");
+    }
+    bail!("transaction has unexpected status: {status}")
+}
+
+/// Logs emitted by all receipts of a transaction, in execution order.
+pub fn outcome_logs(outcome: &Value) -> Vec<String> {
+    outcome["receipts_outcome"]
+        .as_array()
+        .map(|receipts| {
+            receipts
+                .iter()
+                .flat_map(|r| r["outcome"]["logs"].as_array().cloned().unwrap_or_default())
+                .filter_map(|l| l.as_str().map(str::to_string))
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+/// One entry of a receipt's gas profile: the fee category, the cost within
+/// it (e.g. `WASM_INSTRUCTION`, `STORAGE_WRITE_BASE`), and the gas charged.
+pub struct GasProfileEntry {
+    pub category: String,
+    pub cost: String,
+    pub gas: u64,
+}
+
+/// Gas profile entries of all receipts of a transaction, as reported in
+/// each receipt outcome's `metadata.gas_profile`.
+pub fn gas_profile(outcome: &Value) -> Vec<GasProfileEntry> {
+    let receipts = outcome["receipts_outcome"]
+        .as_array()
+        .cloned()
+        .unwrap_or_default();
+    receipts
+        .iter()
+        .flat_map(|r| {
+            r["outcome"]["metadata"]["gas_profile"]
+                .as_array()
+                .cloned()
+                .unwrap_or_default()
+        })
+        .map(|entry| GasProfileEntry {
+            category: entry["cost_category"].as_str().unwrap_or("").to_string(),
+            cost: entry["cost"].as_str().unwrap_or("").to_string(),
+            // Gas amounts are strings in the profile, as they can exceed 2^53
+            gas: entry["gas_used"]
+                .as_str()
+                .and_then(|g| g.parse().ok())
+                .unwrap_or(0),
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn outcome(status: Value) -> Value {
+        json!({
+            "status": status,
+            "transaction_outcome": { "outcome": { "gas_burnt": 100 } },
+            "receipts_outcome": [
+                { "outcome": { "gas_burnt": 20, "logs": ["a"], "metadata": { "gas_profile": [
+                    { "cost_category": "WASM_HOST_COST", "cost": "STORAGE_WRITE_BASE", "gas_used": "9007199254740993" },
+                ] } } },
+                { "outcome": { "gas_burnt": 3, "logs": ["b", "c"] } },
+            ],
+        })
+    }
+
+    #[test]
+    fn networks_resolve_to_rpc_urls() {
+        assert_eq!(
+            network_rpc_url("testnet").unwrap(),
+            "https://rpc.testnet.near.org"
+        );
+        assert_eq!(
+            network_rpc_url("http://localhost:3030").unwrap(),
+            "http://localhost:3030"
+        );
+        assert!(network_rpc_url("localnet").is_err());
+    }
+
+    #[test]
+    fn hashes_are_32_bytes_of_base58() {
+        let hash = bs58::encode([7u8; 32]).into_string();
+        assert_eq!(decode_hash(&hash).unwrap(), [7; 32]);
+        assert!(decode_hash(&bs58::encode([7u8; 31]).into_string()).is_err());
+        assert!(decode_hash("0OIl").is_err());
+    }
+
+    #[test]
+    fn results_are_decoded_from_the_status() {
+        let success = outcome(json!({ "SuccessValue": BASE64.encode("\"hi\"") }));
+        assert_eq!(outcome_result(&success).unwrap(), b"\"hi\"");
+        let receipt = outcome(json!({ "SuccessReceiptId": "abc" }));
+        assert!(outcome_result(&receipt).unwrap().is_empty());
+        let failure = outcome(json!({ "Failure": { "ActionError": {} } }));
+        assert!(outcome_result(&failure)
+            .unwrap_err()
+            .to_string()
+            .starts_with("transaction failed"));
+    }
+
+    #[test]
+    fn gas_and_logs_add_up_across_receipts() {
+        let outcome = outcome(json!({ "SuccessValue": "" }));
+        assert_eq!(receipts_gas_burnt(&outcome), 23);
+        assert_eq!(total_gas_burnt(&outcome), 123);
+        assert_eq!(outcome_logs(&outcome), ["a", "b", "c"]);
+        let profile = gas_profile(&outcome);
+        assert_eq!(profile.len(), 1);
+        assert_eq!(profile[0].cost, "STORAGE_WRITE_BASE");
+        assert_eq!(profile[0].gas, 9_007_199_254_740_993);
+    }
+}
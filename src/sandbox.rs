@@ -0,0 +1,152 @@
+// Local near-sandbox node — started on demand for commands that need a
+// chain to deploy to, and killed when the handle is dropped.
+
+use std::fs;
+use std::net::TcpListener;
+use std::path::{Path, PathBuf};
+use std::process::{Child, Command, Stdio};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use anyhow::{bail, Context, Result};
+use serde_json::Value;
+
+use crate::rpc::{outcome_result, RpcClient};
+use crate::tx::{Action, Signer, DEFAULT_GAS};
+
+pub struct Sandbox {
+    child: Child,
+    home: PathBuf,
+    pub rpc: RpcClient,
+    /// `test.near`, the genesis account holding the sandbox validator key.
+    pub root: Signer,
+}
+
+impl Sandbox {
+    /// Initialize a fresh sandbox home under `work_dir` and start a node.
+    pub fn start(work_dir: &Path) -> Result<Self> {
+        let bin = sandbox_binary()?;
+        let home = work_dir.join(format!("sandbox-{}", std::process::id()));
+        if home.exists() {
+            fs::remove_dir_all(&home)?;
+        }
+        fs::create_dir_all(&home)?;
+
+        let init = Command::new(&bin)
+            .arg("--home")
+            .arg(&home)
+            .arg("init")
+            .output()
+            .with_context(|| format!("failed to run {}", bin.display()))?;
+        if !init.status.success() {
+            let stderr = String::from_utf8_lossy(&init.stderr);
+            bail!("near-sandbox init failed:\n{stderr}");
+        }
+
+        let rpc_port = free_port()?;
+        let net_port = free_port()?;
+        let child = Command::new(&bin)
+            .arg("--home")
+            .arg(&home)
+            .arg("run")
+            .arg("--rpc-addr")
+            .arg(format!("127.0.0.1:{rpc_port}"))
+            .arg("--network-addr")
+            .arg(format!("127.0.0.1:{net_port}"))
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .spawn()
+            .context("failed to start near-sandbox")?;
+
+        let root = Signer::from_key_file(&home.join("validator_key.json"))?;
+        let sandbox = Self {
+            child,
+            home,
+            rpc: RpcClient::new(&format!("http://127.0.0.1:{rpc_port}")),
+            root,
+        };
+        sandbox.wait_ready()?;
+        Ok(sandbox)
+    }
+
+    /// Deploy `wasm` to the root account.
+    pub fn deploy(&self, wasm: &[u8]) -> Result<()> {
+        let outcome = self.root.send(
+            &self.rpc,
+            &self.root.account_id,
+            &[Action::DeployContract {
+                code: wasm.to_vec(),
+            }],
+        )?;
+        outcome_result(&outcome)?;
+        Ok(())
+    }
+
+    /// Call `method` on the root account's contract and return the outcome,
+    /// failing if the call itself failed.
+    pub fn call(&self, method: &str, args: &[u8], deposit: u128) -> Result<Value> {
+        let outcome = self.send_call(method, args, deposit)?;
+        outcome_result(&outcome)?;
+        Ok(outcome)
+    }
+
+    /// Call `method` on the root account's contract and return the outcome,
+    /// whether or not the call succeeded.
+    pub fn send_call(&self, method: &str, args: &[u8], deposit: u128) -> Result<Value> {
+        self.root.send(
+            &self.rpc,
+            &self.root.account_id,
+            &[Action::FunctionCall {
+                method_name: method.to_string(),
+                args: args.to_vec(),
+                gas: DEFAULT_GAS,
+                deposit,
+            }],
+        )
+    }
+
+    fn wait_ready(&self) -> Result<()> {
+        let deadline = Instant::now() + Duration::from_secs(60);
+        while Instant::now() < deadline {
+            if self.rpc.status().is_ok() && self.rpc.final_block_hash().is_ok() {
+                return Ok(());
+            }
+            thread::sleep(Duration::from_millis(250));
+        }
+        bail!("near-sandbox did not become ready within 60s")
+    }
+}
+
+impl Drop for Sandbox {
+    fn drop(&mut self) {
+        let _ = self.child.kill();
+        let _ = self.child.wait();
+        let _ = fs::remove_dir_all(&self.home);
+    }
+}
+
+/// Whether a near-sandbox binary can be found.
+pub fn is_available() -> bool {
+    sandbox_binary().is_ok()
+}
+
+/// Locate the near-sandbox binary: `NEAR_SANDBOX_BIN_PATH` (the variable
+/// near-workspaces uses) first, then `PATH`.
+fn sandbox_binary() -> Result<PathBuf> {
+    if let Some(path) = std::env::var_os("NEAR_SANDBOX_BIN_PATH") {
+        return Ok(PathBuf::from(path));
+    }
+    match Command::new("near-sandbox").arg("--version").output() {
+        Ok(_) => Ok(PathBuf::from("near-sandbox")),
+        Err(_) => bail!(
+            "near-sandbox not found\n    \
+             Install with: npm install -g near-sandbox\n    \
+             or set NEAR_SANDBOX_BIN_PATH to an existing binary"
+        ),
+    }
+}
+
+fn free_port() -> Result<u16> {
+    let listener = TcpListener::bind("127.0.0.1:0")?;
+    Ok(listener.local_addr()?.port())
+}
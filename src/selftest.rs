@@ -0,0 +1,107 @@
+// Installation smoke test — builds the bundled example contract and checks
+// a few of its methods return what they should, on near-sandbox or, without
+// it, the mock host.
+
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use anyhow::{bail, Result};
+
+use crate::mockhost::{self, CallContext, Storage};
+use crate::rpc::outcome_result;
+use crate::sandbox::{self, Sandbox};
+
+/// The example contract shipped in `examples/`, embedded so the self-test
+/// doesn't depend on the source checkout.
+const EXAMPLE_CONTRACT: &str = include_str!("../examples/example.py");
+
+/// Method, raw input, and expected return value. `counter` appears twice to
+/// check that storage writes persist between calls.
+const EXPECTED_CALLS: &[(&str, &str, &str)] = &[
+    ("hello", "", "Hello from Monty on NEAR!"),
+    ("greet", "Alice", "Hello, Alice!"),
+    ("echo", "ping", "ping"),
+    ("counter", "", "1"),
+    ("counter", "", "2"),
+    ("get_counter", "", "2"),
+];
+
+/// Write the example contract into `dir` and return its path.
+pub fn write_example(dir: &Path) -> Result<PathBuf> {
+    fs::create_dir_all(dir)?;
+    let path = dir.join("example.py");
+    fs::write(&path, EXAMPLE_CONTRACT)?;
+    Ok(path)
+}
+
+/// Check the built example: its WASM header, and the results of real calls
+/// against a local node if near-sandbox is available, or else of running the
+/// example's source at `input` against the mock host.
+pub fn check_example(wasm: &[u8], input: &Path) -> Result<()> {
+    if !wasm.starts_with(b"\0asm") {
+        bail!("build output is not a WebAssembly module");
+    }
+
+    if !sandbox::is_available() {
+        eprintln!("  near-sandbox not found; calling the methods on the mock host instead");
+        eprintln!("    Install with: npm install -g near-sandbox");
+        return check_on_mock_host(input);
+    }
+
+    eprintln!("  Starting sandbox...");
+    let sandbox = Sandbox::start(&std::env::temp_dir())?;
+    sandbox.deploy(wasm)?;
+
+    for (method, input, expected) in EXPECTED_CALLS {
+        let outcome = sandbox.call(method, input.as_bytes(), 0)?;
+        check_call(method, input, &outcome_result(&outcome)?, expected)?;
+    }
+    Ok(())
+}
+
+/// Make [`EXPECTED_CALLS`] on the example's source with `run`'s mock host,
+/// which checks the compiler and the contract without a NEAR runtime.
+fn check_on_mock_host(path: &Path) -> Result<()> {
+    let (source, contract) = crate::load_contract(path)?;
+    let program = contract.bundle.prelude.clone() + &crate::prepare_source(&source, &contract);
+    let context = CallContext {
+        current_account_id: "contract.test.near".into(),
+        predecessor_account_id: "alice.test.near".into(),
+        signer_account_id: "alice.test.near".into(),
+        attached_deposit: 0,
+        account_balance: 100 * 10u128.pow(24),
+        prepaid_gas: crate::tx::DEFAULT_GAS,
+        block_height: 1,
+        block_timestamp: 1_700_000_000_000_000_000,
+        epoch_height: 1,
+        seed: 0,
+    };
+    let mut storage = Storage::default();
+
+    for (method, input, expected) in EXPECTED_CALLS {
+        let Some(exported) = contract.methods.iter().find(|m| m.name == *method) else {
+            bail!("the example contract has no `{method}` method");
+        };
+        let outcome = mockhost::run_method(
+            &program,
+            &path.display().to_string(),
+            exported,
+            &contract,
+            input.as_bytes(),
+            &context,
+            &mut storage,
+        )?;
+        check_call(method, input, &outcome.value, expected)?;
+    }
+    Ok(())
+}
+
+/// Compare what `method` returned for `input` with what it should have.
+fn check_call(method: &str, input: &str, value: &[u8], expected: &str) -> Result<()> {
+    let actual = String::from_utf8_lossy(value);
+    if actual != expected {
+        bail!("{method}({input:?}) returned {actual:?}, expected {expected:?}");
+    }
+    eprintln!("  ok  {method}({input:?}) -> {actual:?}");
+    Ok(())
+}
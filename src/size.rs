@@ -0,0 +1,424 @@
+// Size breakdown — where the bytes of a built contract go, by WASM section,
+// Monty bytecode versus other data, and per function.
+
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use anyhow::{bail, Context, Result};
+
+/// Standard section names by id.
+const SECTION_NAMES: &[&str] = &[
+    "custom",
+    "type",
+    "import",
+    "function",
+    "table",
+    "memory",
+    "global",
+    "export",
+    "start",
+    "element",
+    "code",
+    "data",
+    "datacount",
+    "tag",
+];
+
+/// Print the size breakdown of the WASM file at `path`. Bytecode blobs found
+/// in `bytecode_dirs` (generated projects' `src/`) are attributed within the
+/// data section when their bytes appear there.
+pub fn report(path: &Path, bytecode_dirs: &[PathBuf], top: usize) -> Result<()> {
+    let wasm = fs::read(path).with_context(|| format!("failed to read {}", path.display()))?;
+    let module =
+        Module::parse(&wasm).with_context(|| format!("{} is not valid WASM", path.display()))?;
+    let total = wasm.len();
+
+    println!("{}: {total} bytes", path.display());
+    println!();
+    println!("  {:<36} {:>10} {:>7}", "section", "bytes", "%");
+    for section in &module.sections {
+        let label = match &section.custom_name {
+            Some(name) => format!("custom \"{name}\""),
+            None => SECTION_NAMES
+                .get(section.id as usize)
+                .map_or(format!("unknown ({})", section.id), |n| n.to_string()),
+        };
+        println!(
+            "  {label:<36} {:>10} {:>7}",
+            section.size,
+            percent(section.size, total)
+        );
+
+        if section.id == 11 {
+            let mut attributed = 0;
+            for (name, size) in find_bytecode(&module.data, bytecode_dirs) {
+                println!("    {:<34} {size:>10} {:>7}", name, percent(size, total));
+                attributed += size;
+            }
+            let other = module.data.iter().map(Vec::len).sum::<usize>() - attributed;
+            let label = if attributed > 0 {
+                "other data"
+            } else {
+                "segment contents"
+            };
+            println!("    {label:<34} {other:>10} {:>7}", percent(other, total));
+        }
+    }
+    let header = total - module.sections.iter().map(|s| s.size).sum::<usize>();
+    println!(
+        "  {:<36} {header:>10} {:>7}",
+        "header",
+        percent(header, total)
+    );
+
+    if top > 0 && !module.functions.is_empty() {
+        let mut functions: Vec<(usize, u32)> = module
+            .functions
+            .iter()
+            .enumerate()
+            .map(|(i, &size)| (size, module.imported_functions + i as u32))
+            .collect();
+        functions.sort_by_key(|&(size, index)| (std::cmp::Reverse(size), index));
+
+        println!();
+        println!(
+            "  Largest functions ({} of {}):",
+            top.min(functions.len()),
+            functions.len()
+        );
+        println!("  {:>10} {:>7}  name", "bytes", "%");
+        for (size, index) in functions.into_iter().take(top) {
+            let name = module
+                .names
+                .get(&index)
+                .cloned()
+                .unwrap_or(format!("func[{index}]"));
+            println!("  {size:>10} {:>7}  {name}", percent(size, total));
+        }
+        if !module.has_name_section {
+            println!();
+            println!(
+                "  No name section: only exported functions are named (builds strip symbols)."
+            );
+        }
+    }
+    Ok(())
+}
+
+/// Names of the functions a WASM module exports.
+pub fn exported_functions(wasm: &[u8]) -> Result<Vec<String>> {
+    Ok(Module::parse(wasm)?.exports)
+}
+
+/// What a WASM module declares, as the NEAR runtime limits it.
+pub struct Counts {
+    /// Imported and defined functions.
+    pub functions: u64,
+    /// Locals declared by all function bodies, not counting parameters.
+    pub locals: u64,
+    /// Imported and defined tables.
+    pub tables: u64,
+    pub exports: Vec<String>,
+}
+
+/// Count what the WASM module `wasm` declares.
+pub fn counts(wasm: &[u8]) -> Result<Counts> {
+    let module = Module::parse(wasm)?;
+    Ok(Counts {
+        functions: u64::from(module.imported_functions) + module.functions.len() as u64,
+        locals: module.locals,
+        tables: module.tables,
+        exports: module.exports,
+    })
+}
+
+fn percent(part: usize, total: usize) -> String {
+    format!("{:.1}%", part as f64 * 100.0 / total.max(1) as f64)
+}
+
+/// Bytecode files in `dir` whose contents appear in one of the data
+/// segments, with their sizes.
+fn find_bytecode(segments: &[Vec<u8>], dirs: &[PathBuf]) -> Vec<(String, usize)> {
+    let mut found: Vec<(String, Vec<u8>)> = Vec::new();
+    for dir in dirs {
+        let mut files: Vec<PathBuf> = fs::read_dir(dir)
+            .map(|entries| {
+                entries
+                    .filter_map(|e| e.ok().map(|e| e.path()))
+                    .filter(|p| p.extension().is_some_and(|ext| ext == "bin"))
+                    .collect()
+            })
+            .unwrap_or_default();
+        files.sort();
+
+        for path in files {
+            let Some(file) = fs::read(&path).ok().filter(|b| !b.is_empty()) else {
+                continue;
+            };
+            // NEAR builds pad the bytecode, and wasm-opt may drop the padding
+            let blob = crate::patch::unpack(&file).unwrap_or(&file).to_vec();
+            // The same blob may sit in several build directories
+            if found.iter().any(|(_, b)| *b == blob) {
+                continue;
+            }
+            let present = segments
+                .iter()
+                .any(|segment| segment.windows(blob.len()).any(|w| w == blob.as_slice()));
+            if present {
+                let name = path.file_name().unwrap_or_default().to_string_lossy();
+                let label = if name == crate::SOURCE_LINES_FILE {
+                    "Source line table".to_string()
+                } else {
+                    format!("Monty bytecode ({name})")
+                };
+                found.push((label, blob));
+            }
+        }
+    }
+    found
+        .into_iter()
+        .map(|(name, blob)| (name, blob.len()))
+        .collect()
+}
+
+// ---------------------------------------------------------------------------
+// Minimal WASM reader — just the structure the report needs
+// ---------------------------------------------------------------------------
+
+struct Section {
+    id: u8,
+    /// Name of a custom section.
+    custom_name: Option<String>,
+    /// Bytes including the id and size prefix.
+    size: usize,
+}
+
+#[derive(Default)]
+struct Module {
+    sections: Vec<Section>,
+    imported_functions: u32,
+    /// Body size of each defined function, in code section order.
+    functions: Vec<usize>,
+    /// Locals declared by all function bodies.
+    locals: u64,
+    /// Imported and defined tables.
+    tables: u64,
+    /// Function names from the name section, falling back to export names.
+    names: HashMap<u32, String>,
+    has_name_section: bool,
+    /// Names of exported functions.
+    exports: Vec<String>,
+    /// Contents of each data segment.
+    data: Vec<Vec<u8>>,
+}
+
+impl Module {
+    fn parse(wasm: &[u8]) -> Result<Self> {
+        if !wasm.starts_with(b"\0asm") || wasm.len() < 8 {
+            bail!("missing WASM header");
+        }
+        let mut module = Module::default();
+        let mut exports = HashMap::new();
+        let mut r = Reader {
+            bytes: wasm,
+            pos: 8,
+        };
+        while !r.done() {
+            let start = r.pos;
+            let id = r.byte()?;
+            let len = r.leb()? as usize;
+            let mut payload = Reader {
+                bytes: r.take(len)?,
+                pos: 0,
+            };
+            let mut custom_name = None;
+            match id {
+                0 => {
+                    let name = payload.name()?;
+                    if name == "name" {
+                        module.has_name_section = true;
+                        module.read_function_names(&mut payload)?;
+                    }
+                    custom_name = Some(name);
+                }
+                2 => module.read_imports(&mut payload)?,
+                4 => module.tables += u64::from(payload.leb()?),
+                7 => {
+                    for _ in 0..payload.leb()? {
+                        let name = payload.name()?;
+                        let kind = payload.byte()?;
+                        let index = payload.leb()?;
+                        if kind == 0 {
+                            module.exports.push(name.clone());
+                            exports.insert(index, name);
+                        }
+                    }
+                }
+                10 => {
+                    for _ in 0..payload.leb()? {
+                        let body_start = payload.pos;
+                        let len = payload.leb()? as usize;
+                        let mut body = Reader {
+                            bytes: payload.take(len)?,
+                            pos: 0,
+                        };
+                        // Runs of locals of one type: a count, then the type
+                        for _ in 0..body.leb()? {
+                            module.locals += u64::from(body.leb()?);
+                            body.byte()?;
+                        }
+                        module.functions.push(payload.pos - body_start);
+                    }
+                }
+                11 => module.read_data(&mut payload)?,
+                _ => {}
+            }
+            module.sections.push(Section {
+                id,
+                custom_name,
+                size: r.pos - start,
+            });
+        }
+        for (index, name) in exports {
+            module.names.entry(index).or_insert(name);
+        }
+        Ok(module)
+    }
+
+    fn read_imports(&mut self, r: &mut Reader) -> Result<()> {
+        for _ in 0..r.leb()? {
+            r.name()?;
+            r.name()?;
+            match r.byte()? {
+                0 => {
+                    r.leb()?;
+                    self.imported_functions += 1;
+                }
+                1 => {
+                    r.byte()?;
+                    r.limits()?;
+                    self.tables += 1;
+                }
+                2 => r.limits()?,
+                3 => {
+                    r.byte()?;
+                    r.byte()?;
+                }
+                kind => bail!("unknown import kind {kind}"),
+            }
+        }
+        Ok(())
+    }
+
+    fn read_data(&mut self, r: &mut Reader) -> Result<()> {
+        for _ in 0..r.leb()? {
+            match r.leb()? {
+                0 => r.const_expr()?,
+                1 => {}
+                2 => {
+                    r.leb()?;
+                    r.const_expr()?;
+                }
+                flags => bail!("unknown data segment flags {flags}"),
+            }
+            let len = r.leb()? as usize;
+            self.data.push(r.take(len)?.to_vec());
+        }
+        Ok(())
+    }
+
+    /// Read the function-names subsection of a `name` custom section.
+    fn read_function_names(&mut self, r: &mut Reader) -> Result<()> {
+        while !r.done() {
+            let id = r.byte()?;
+            let len = r.leb()? as usize;
+            let mut sub = Reader {
+                bytes: r.take(len)?,
+                pos: 0,
+            };
+            if id == 1 {
+                for _ in 0..sub.leb()? {
+                    let index = sub.leb()?;
+                    let name = sub.name()?;
+                    self.names.insert(index, name);
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+struct Reader<'a> {
+    bytes: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn done(&self) -> bool {
+        self.pos >= self.bytes.len()
+    }
+
+    fn byte(&mut self) -> Result<u8> {
+        let byte = *self
+            .bytes
+            .get(self.pos)
+            .context("unexpected end of input")?;
+        self.pos += 1;
+        Ok(byte)
+    }
+
+    fn take(&mut self, len: usize) -> Result<&'a [u8]> {
+        let end = self
+            .pos
+            .checked_add(len)
+            .filter(|&end| end <= self.bytes.len())
+            .context("unexpected end of input")?;
+        let slice = &self.bytes[self.pos..end];
+        self.pos = end;
+        Ok(slice)
+    }
+
+    /// Unsigned LEB128, as used for sizes, counts, and indices.
+    fn leb(&mut self) -> Result<u32> {
+        let mut result: u64 = 0;
+        for shift in (0..35).step_by(7) {
+            let byte = self.byte()?;
+            result |= u64::from(byte & 0x7f) << shift;
+            if byte & 0x80 == 0 {
+                return u32::try_from(result).context("LEB128 value out of range");
+            }
+        }
+        bail!("LEB128 value too long")
+    }
+
+    fn name(&mut self) -> Result<String> {
+        let len = self.leb()? as usize;
+        Ok(String::from_utf8_lossy(self.take(len)?).into_owned())
+    }
+
+    fn limits(&mut self) -> Result<()> {
+        let flags = self.byte()?;
+        self.leb()?;
+        if flags & 1 != 0 {
+            self.leb()?;
+        }
+        Ok(())
+    }
+
+    /// Skip a constant expression (a data segment offset) through its `end`.
+    fn const_expr(&mut self) -> Result<()> {
+        loop {
+            match self.byte()? {
+                0x0b => return Ok(()),
+                // i32.const / i64.const: signed LEB128 immediate
+                0x41 | 0x42 => while self.byte()? & 0x80 != 0 {},
+                // global.get
+                0x23 => {
+                    self.leb()?;
+                }
+                op => bail!("unsupported opcode {op:#04x} in constant expression"),
+            }
+        }
+    }
+}
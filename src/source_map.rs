@@ -0,0 +1,180 @@
+// Source maps — Monty compiles one program: the bundled modules, the
+// contract, then the generated dispatcher. The lowering passes keep every
+// line of the contract and its modules where it was, so a line Monty
+// reports maps back to a line of the file it came from, and a compile
+// error can show the contract's own code.
+
+use std::fmt;
+use std::path::{Path, PathBuf};
+
+use crate::diagnostics::{code_frame, Diagnostic, Location, SourceFile};
+
+/// The name Monty compiles the program under, and its errors refer to.
+pub const SCRIPT_NAME: &str = "contract.py";
+
+/// A file in the program.
+#[derive(Clone)]
+pub struct Segment {
+    pub path: PathBuf,
+    pub text: String,
+    /// The 0-based line of the program its first line lands on.
+    pub start: usize,
+}
+
+/// Where the lines of a program come from.
+pub struct SourceMap {
+    segments: Vec<Segment>,
+}
+
+impl SourceMap {
+    /// The map of a program running `bundle`'s modules, then `header`
+    /// (the `--define` assignments), then the contract in `file`.
+    pub fn new(file: SourceFile, bundle: &crate::imports::Bundle, header: &str) -> Self {
+        let mut segments = bundle.segments.clone();
+        segments.push(Segment {
+            path: file.path.to_path_buf(),
+            text: file.text.to_string(),
+            start: bundle.prelude.matches('\n').count() + header.matches('\n').count(),
+        });
+        Self { segments }
+    }
+
+    /// `(start, lines, file)` for each file: the 0-based program line it
+    /// starts on, how many lines it has, and its path relative to the
+    /// contract's directory. The runtime maps tracebacks with it.
+    pub fn line_table(&self) -> Vec<(usize, usize, String)> {
+        let root = self
+            .segments
+            .last()
+            .and_then(|s| s.path.parent())
+            .unwrap_or(Path::new(""));
+        self.segments
+            .iter()
+            .map(|s| {
+                let path = s.path.strip_prefix(root).unwrap_or(&s.path);
+                (s.start, s.text.lines().count(), path.display().to_string())
+            })
+            .collect()
+    }
+
+    /// The file and 1-based line that 1-based `line` of the program comes
+    /// from, or `None` for code the compiler generated.
+    fn locate(&self, line: usize) -> Option<(&Segment, usize)> {
+        let index = line.checked_sub(1)?;
+        let segment = self.segments.iter().rev().find(|s| s.start <= index)?;
+        let line = index - segment.start + 1;
+        (line <= segment.text.lines().count()).then_some((segment, line))
+    }
+}
+
+/// The 1-based line, and column if given, of the program that Monty's
+/// `message` points at: the last `"contract.py", line N` of a traceback, or
+/// `contract.py:N:M`, or failing both the last `line N`.
+fn program_position(message: &str) -> Option<(usize, Option<usize>)> {
+    let number = |s: &str| -> Option<(usize, usize)> {
+        let end = s.find(|c: char| !c.is_ascii_digit()).unwrap_or(s.len());
+        Some((s[..end].parse().ok()?, end))
+    };
+    if let Some(at) = message.rfind(SCRIPT_NAME) {
+        let rest = message[at + SCRIPT_NAME.len()..].trim_start_matches('"');
+        if let Some(rest) = rest.strip_prefix(':') {
+            let (line, end) = number(rest)?;
+            let column = rest[end..].strip_prefix(':').and_then(number);
+            return Some((line, column.map(|(column, _)| column)));
+        }
+        if let Some(rest) = rest.strip_prefix(", line ") {
+            let (line, end) = number(rest)?;
+            let column = rest[end..].strip_prefix(", column ").and_then(number);
+            return Some((line, column.map(|(column, _)| column)));
+        }
+    }
+    let at = message.rfind("line ")?;
+    let (line, end) = number(&message[at + 5..])?;
+    let rest = &message[at + 5 + end..];
+    let column = rest.strip_prefix(", column ").and_then(number);
+    Some((line, column.map(|(column, _)| column)))
+}
+
+/// Monty failing to compile the program, pointing into the contract or a
+/// bundled module where it names a line.
+#[derive(Debug)]
+pub struct CompileError {
+    message: String,
+    /// File, 1-based line, and 1-based column.
+    location: Option<(PathBuf, usize, usize)>,
+    frame: Option<String>,
+    /// For errors in generated code, which line of the program.
+    generated: Option<usize>,
+}
+
+impl CompileError {
+    /// Map Monty's `message` about `program` back through `map`.
+    pub fn new(message: &str, program: &str, map: &SourceMap) -> Self {
+        let Some((line, column)) = program_position(message) else {
+            return Self {
+                message: message.trim().to_string(),
+                location: None,
+                frame: None,
+                generated: None,
+            };
+        };
+        // The last line of a traceback is the error itself
+        let summary = message
+            .lines()
+            .rev()
+            .find(|l| !l.trim().is_empty())
+            .unwrap_or(message)
+            .trim()
+            .to_string();
+        match map.locate(line) {
+            Some((segment, line)) => Self {
+                message: summary,
+                location: Some((segment.path.clone(), line, column.unwrap_or(1))),
+                frame: Some(code_frame(&segment.text, line, column)),
+                generated: None,
+            },
+            None => Self {
+                message: summary,
+                location: None,
+                frame: Some(code_frame(program, line, column)),
+                generated: Some(line),
+            },
+        }
+    }
+
+    /// The error as a `compile-error` diagnostic.
+    pub fn diagnostic(&self) -> Diagnostic {
+        let mut diagnostic = Diagnostic::error("compile-error", self.message.clone());
+        if let Some((file, line, column)) = &self.location {
+            diagnostic.location = Some(Location {
+                file: file.clone(),
+                line: *line,
+                column: *column,
+            });
+        }
+        if let Some(frame) = &self.frame {
+            diagnostic = diagnostic.with_frame(frame.clone());
+        }
+        if let Some(line) = self.generated {
+            diagnostic = diagnostic.with_note(format!(
+                "at line {line} of the code the compiler generates around the contract; please report this as a bug"
+            ));
+        }
+        diagnostic
+    }
+}
+
+impl fmt::Display for CompileError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "compilation failed: {}", self.message)?;
+        if let Some((file, line, column)) = &self.location {
+            write!(f, "\n  --> {}:{line}:{column}", file.display())?;
+        }
+        if let Some(frame) = &self.frame {
+            write!(f, "\n{frame}")?;
+        }
+        Ok(())
+    }
+}
+
+impl std::error::Error for CompileError {}
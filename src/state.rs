@@ -0,0 +1,103 @@
+// Contract storage inspection — fetch a deployed contract's state over RPC
+// and print its keys and values decoded.
+
+use anyhow::{Context, Result};
+use base64::{engine::general_purpose::STANDARD as BASE64, Engine as _};
+use clap::ValueEnum;
+use serde_json::{Map, Value};
+
+use crate::rpc::{network_rpc_url, RpcClient};
+
+#[derive(Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum StateFormat {
+    /// Keys grouped by prefix, values decoded where possible
+    Text,
+    /// A JSON object of keys to values, usable as `run --state`
+    Json,
+}
+
+/// Print the storage of `account_id` under keys starting with `prefix`.
+pub fn dump_state(
+    account_id: &str,
+    network: &str,
+    prefix: &str,
+    format: StateFormat,
+) -> Result<()> {
+    let rpc = RpcClient::new(&network_rpc_url(network)?);
+    let entries = rpc
+        .view_state(account_id, prefix.as_bytes())
+        .with_context(|| {
+            format!(
+                "failed to read the state of {account_id} \
+                 (RPC nodes refuse state over 50 KB; narrow it with --prefix)"
+            )
+        })?;
+
+    let bytes: usize = entries.iter().map(|(k, v)| k.len() + v.len()).sum();
+    eprintln!(
+        "  {} keys, {bytes} bytes of storage on {account_id}",
+        entries.len()
+    );
+
+    match format {
+        StateFormat::Json => {
+            let object: Map<String, Value> = entries
+                .iter()
+                .map(|(k, v)| (text_or_base64(k), Value::String(text_or_base64(v))))
+                .collect();
+            println!("{}", serde_json::to_string_pretty(&object)?);
+        }
+        StateFormat::Text => {
+            let mut current_group: Option<&[u8]> = None;
+            for (i, (key, value)) in entries.iter().enumerate() {
+                let group = key_group(key).filter(|g| {
+                    // Only group keys whose prefix is shared with a neighbour
+                    let shares = |other: Option<&(Vec<u8>, Vec<u8>)>| {
+                        other.is_some_and(|(k, _)| key_group(k) == Some(*g))
+                    };
+                    shares(i.checked_sub(1).and_then(|j| entries.get(j)))
+                        || shares(entries.get(i + 1))
+                });
+                if group != current_group {
+                    if let Some(group) = group {
+                        println!("{}:", text_or_base64(group));
+                    }
+                    current_group = group;
+                }
+                let indent = if group.is_some() { "  " } else { "" };
+                println!(
+                    "{indent}{} = {}",
+                    text_or_base64(key),
+                    display_value(value, indent)
+                );
+            }
+        }
+    }
+    Ok(())
+}
+
+/// The part of a key before its first `:`, the separator contracts use to
+/// namespace keys (e.g. `balance:alice.near`).
+fn key_group(key: &[u8]) -> Option<&[u8]> {
+    let end = key.iter().position(|&b| b == b':')?;
+    Some(&key[..end])
+}
+
+/// Bytes as text if they're printable UTF-8, otherwise `base64:...`.
+fn text_or_base64(bytes: &[u8]) -> String {
+    match std::str::from_utf8(bytes) {
+        Ok(text) if !text.chars().any(char::is_control) => text.to_string(),
+        _ => format!("base64:{}", BASE64.encode(bytes)),
+    }
+}
+
+/// A value for text output: JSON objects and arrays pretty-printed and
+/// indented to line up under their key, anything else as text or base64.
+fn display_value(value: &[u8], indent: &str) -> String {
+    match serde_json::from_slice::<Value>(value) {
+        Ok(json @ (Value::Object(_) | Value::Array(_))) => serde_json::to_string_pretty(&json)
+            .unwrap_or_default()
+            .replace('\n', &format!("\n{indent}")),
+        _ => text_or_base64(value),
+    }
+}
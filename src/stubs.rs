@@ -0,0 +1,257 @@
+// Editor support — a `near.pyi` stub describing the host functions and
+// decorators contracts can use, generated from the compiler's own tables.
+
+use std::fs;
+use std::path::Path;
+
+use anyhow::{Context, Result};
+
+/// Makes the stubbed names builtins for Pyright / Pylance, matching how
+/// contracts use them without importing anything.
+const BUILTINS_STUB: &str = "from near import *\n";
+
+/// Collections declared as `name = LookupMap()`; the compiler lowers their
+/// method calls, so these classes only exist for editors.
+const COLLECTIONS_STUB: &str = r#"
+# Collections
+
+class LookupMap:
+    """A storage-backed map from strings to JSON values, stored under the variable's name."""
+    def get(self, key: str, default: object = None) -> Any: ...
+    def contains_key(self, key: str) -> bool: ...
+    def insert(self, key: str, value: object) -> Any:
+        """Store `value` under `key`; returns the previous value or None."""
+    def remove(self, key: str) -> Any:
+        """Delete `key`; returns its value or None."""
+
+class Vector:
+    """A storage-backed list of JSON values, stored under the variable's name."""
+    def len(self) -> int: ...
+    def is_empty(self) -> bool: ...
+    def get(self, index: int) -> Any:
+        """The item at `index`, or None if it's out of range."""
+    def set(self, index: int, value: object) -> Any:
+        """Replace the item at `index`; returns the previous one."""
+    def push(self, value: object) -> None: ...
+    def pop(self) -> Any:
+        """Remove and return the last item, or None if empty."""
+    def swap_remove(self, index: int) -> Any:
+        """Remove the item at `index`, moving the last item into its place."""
+    def to_list(self) -> list[Any]: ...
+
+class UnorderedSet:
+    """A storage-backed set of strings, stored under the variable's name."""
+    def len(self) -> int: ...
+    def is_empty(self) -> bool: ...
+    def contains(self, member: str) -> bool: ...
+    def insert(self, member: str) -> bool:
+        """Add `member`; False if it was already there."""
+    def remove(self, member: str) -> bool:
+        """Remove `member`; False if it wasn't there."""
+    def to_list(self) -> list[str]: ...
+"#;
+
+/// `Promise` is a function returning a dict at runtime; the compiler lowers
+/// its method calls, so this class only exists for editors.
+const PROMISE_STUB: &str = r#"
+# Promises
+
+class Promise:
+    """Actions on `account_id`, scheduled when the method returns; return one, or call `as_return()`, to make its result the method's."""
+    def __init__(self, account_id: str) -> None: ...
+    def create_account(self) -> Promise: ...
+    def deploy_contract(self, code: str | bytes) -> Promise: ...
+    def function_call(self, function_name: str, args: object = None, deposit: int = 0, gas: int | None = None, gas_weight: int = 1) -> Promise:
+        """Call `function_name` with `args` (JSON-encoded unless str or bytes); without `gas`, it gets a `gas_weight` share of the unused gas."""
+    def transfer(self, amount: int) -> Promise: ...
+    def stake(self, amount: int, public_key: str) -> Promise: ...
+    def add_full_access_key(self, public_key: str, nonce: int = 0) -> Promise: ...
+    def add_access_key(self, public_key: str, allowance: int, receiver_id: str, function_names: list[str] | str, nonce: int = 0) -> Promise: ...
+    def delete_key(self, public_key: str) -> Promise: ...
+    def delete_account(self, beneficiary_id: str) -> Promise: ...
+    def deploy_global_contract(self, code: str | bytes) -> Promise:
+        """Deploy `code` as a global contract (NEP-591), which accounts use by its hash."""
+    def deploy_global_contract_by_account_id(self, code: str | bytes) -> Promise:
+        """Deploy `code` as a global contract, which accounts use by this promise's account ID."""
+    def use_global_contract(self, code_hash: str | bytes) -> Promise:
+        """Use the global contract with SHA-256 `code_hash` (hex or 32 bytes) as the account's code."""
+    def use_global_contract_by_account_id(self, account_id: str) -> Promise:
+        """Use the global contract `account_id` deployed as the account's code."""
+    def then(self, other: Promise) -> Promise:
+        """Run `other` after this promise; returns `other`."""
+    def and_(self, other: Promise) -> Promise:
+        """A promise that completes when both have."""
+    def as_return(self) -> Promise:
+        """Make this promise's result the method's return value."""
+
+class PromiseError:
+    """A failed promise, passed to a `@callback` parameter annotated `T | PromiseError`."""
+"#;
+
+/// Write `near.pyi` and, unless one already exists, `__builtins__.pyi` to `dir`.
+pub fn write_stubs(dir: &Path) -> Result<()> {
+    fs::create_dir_all(dir)?;
+    let stub = dir.join("near.pyi");
+    fs::write(&stub, generate()).with_context(|| format!("failed to write {}", stub.display()))?;
+    eprintln!("  \u{2713} {}", stub.display());
+
+    let builtins = dir.join("__builtins__.pyi");
+    match fs::read_to_string(&builtins) {
+        Ok(existing) if existing.contains(BUILTINS_STUB.trim()) => {}
+        Ok(_) => eprintln!(
+            "  ! {} exists; add `{}` to it so editors see the host functions",
+            builtins.display(),
+            BUILTINS_STUB.trim()
+        ),
+        Err(_) => {
+            fs::write(&builtins, BUILTINS_STUB)
+                .with_context(|| format!("failed to write {}", builtins.display()))?;
+            eprintln!("  \u{2713} {}", builtins.display());
+        }
+    }
+    Ok(())
+}
+
+/// The contents of `near.pyi`.
+fn generate() -> String {
+    let mut out = format!(
+        "\"\"\"NEAR host functions and decorators available to monty-near contracts.\n\
+         \n\
+         Generated by monty-near-cli {}; regenerate with `monty-near stubs`.\n\
+         \"\"\"\n\
+         \n\
+         from typing import Any, Callable, TypeVar, overload\n\
+         \n\
+         _F = TypeVar(\"_F\", bound=Callable[..., object])\n",
+        env!("CARGO_PKG_VERSION")
+    );
+
+    out.push_str("\n# Decorators\n");
+    out.push_str(
+        "\ndef group(name: str) -> Callable[[_F], _F]:\n    \
+         \"\"\"Compile this method into a separate bytecode blob with the other methods of group `name`.\"\"\"\n    \
+         ...\n",
+    );
+    for (name, doc) in [
+        (
+            "view",
+            "Mark this method read-only; it is listed as a view in the ABI.",
+        ),
+        (
+            "call",
+            "Mark this method state-changing (the default for undecorated methods).",
+        ),
+        (
+            "payable",
+            "Allow callers to attach a deposit; other call methods reject one.",
+        ),
+        (
+            "one_yocto",
+            "Require exactly 1 yoctoNEAR attached, so only a full-access key can call this method (NEP-141/171 transfers).",
+        ),
+        (
+            "private",
+            "Only allow calls from the contract account itself, e.g. callbacks.",
+        ),
+        (
+            "owner_only",
+            "Only allow calls from the owner: the `OWNER` constant if defined, else the `OWNER` storage key.",
+        ),
+        (
+            "raw_io",
+            "Skip JSON: the method reads `input()` itself, and what it returns goes to `value_return` unchanged.",
+        ),
+        (
+            "borsh_io",
+            "Take arguments and return the result as Borsh, laid out by the annotations, instead of JSON.",
+        ),
+        (
+            "migrate",
+            "Make this `class Contract` method rewrite state stored with an earlier layout; its first parameter gets the stored values as strings.",
+        ),
+        (
+            "no_export",
+            "Keep this function a helper rather than an exported method.",
+        ),
+    ] {
+        out.push_str(&format!(
+            "\ndef {name}(func: _F) -> _F:\n    \"\"\"{doc}\"\"\"\n    ...\n"
+        ));
+    }
+    out.push_str(
+        "\n@overload\ndef init(func: _F) -> _F:\n    \
+         \"\"\"Mark this method as the initializer, which may run only once. With `required=True`, \
+         other methods fail until it has run.\"\"\"\n    \
+         ...\n\
+         \n@overload\ndef init(*, required: bool) -> Callable[[_F], _F]: ...\n",
+    );
+    out.push_str(
+        "\n@overload\ndef export(func: _F) -> _F:\n    \
+         \"\"\"Export this method; once any method uses it, only methods marked so (or listed in `__all__`) \
+         are exported. With `name=...`, export it under that name instead of the function's.\"\"\"\n    \
+         ...\n\
+         \n@overload\ndef export(*, name: str) -> Callable[[_F], _F]: ...\n",
+    );
+    out.push_str(
+        "\n@overload\ndef callback(func: _F) -> _F:\n    \
+         \"\"\"Make this method a private callback whose first parameter is the result of the \
+         promise it follows, read as JSON. With `results=N`, the first N parameters get the \
+         results of N joined promises.\"\"\"\n    \
+         ...\n\
+         \n@overload\ndef callback(*, results: int) -> Callable[[_F], _F]: ...\n",
+    );
+
+    out.push_str(
+        "\n# Integers JSON carries as decimal strings, as near-sdk's U128\n\n\
+         U128 = int\nBalance = int\n",
+    );
+    out.push_str("\n# Fixed-width integers for @borsh_io annotations\n\n");
+    for name in [
+        "u8", "u16", "u32", "u64", "u128", "i8", "i16", "i32", "i64", "i128",
+    ] {
+        out.push_str(&format!("{name} = int\n"));
+    }
+
+    out.push_str(
+        "\ndef emit_event(standard: str, version: str, event: str, data: object = None) -> None:\n    \
+         \"\"\"Log a NEP-297 event: `EVENT_JSON:` and the standard, version, event name, and `data` as JSON.\"\"\"\n    \
+         ...\n",
+    );
+
+    out.push_str(
+        "\ndef assert_one_yocto() -> None:\n    \
+         \"\"\"Fail unless exactly 1 yoctoNEAR is attached.\"\"\"\n    \
+         ...\n\
+         \ndef assert_min_deposit(amount: int) -> None:\n    \
+         \"\"\"Fail unless at least `amount` yoctoNEAR is attached.\"\"\"\n    \
+         ...\n",
+    );
+
+    out.push_str(COLLECTIONS_STUB);
+    out.push_str(PROMISE_STUB);
+    out.push_str(
+        "\ndef yield_create(function_name: str, args: object = None, gas: int = 0, gas_weight: int = 1) -> tuple[int, str]:\n    \
+         \"\"\"Create a promise calling this contract's `function_name` once `yield_resume` is called with the returned \
+         yield ID (NEP-519); returns the promise index, for `promise_return`, and the yield ID.\"\"\"\n    \
+         ...\n\
+         \ndef yield_resume(yield_id: str, payload: object = None) -> bool:\n    \
+         \"\"\"Resume a yielded promise with `payload`, JSON-encoded unless bytes, as its result; False if it \
+         was already resumed or timed out.\"\"\"\n    \
+         ...\n",
+    );
+
+    out.push_str(
+        "\nasync def cross_call(account_id: str, function_name: str, args: object = None, deposit: int = 0, gas: int | None = None, gas_weight: int = 1) -> Any:\n    \
+         \"\"\"Call `function_name` on `account_id` and wait for its result, read as JSON; only as `x = await cross_call(...)` \
+         directly in an async method.\"\"\"\n    \
+         ...\n",
+    );
+
+    out.push_str("\n# Host functions\n");
+    for (name, signature, doc) in crate::NEAR_HOST_FUNCTIONS {
+        out.push_str(&format!(
+            "\ndef {name}{signature}:\n    \"\"\"{doc}\"\"\"\n    ...\n"
+        ));
+    }
+    out
+}
@@ -0,0 +1,59 @@
+// Shared target cache — each generated project used to keep its own cargo
+// `target/`, so every contract directory compiled the Rust runtime and its
+// dependencies from scratch. Projects whose toolchain and cargo config are
+// the same build into one per-user target directory instead, under
+// `~/.cache/monty-near/target`, and cargo's fingerprints reuse whatever an
+// earlier project compiled.
+
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use anyhow::Result;
+use sha2::{Digest, Sha256};
+
+/// The files of a generated project that decide whether its compiled
+/// dependencies can be shared: the toolchain, and the rustflags and
+/// sources in the cargo config. Cargo tells the rest apart itself.
+const KEY_FILES: &[&str] = &["rust-toolchain.toml", ".cargo/config.toml"];
+
+/// The CLI's per-user cache directory: `$XDG_CACHE_HOME/monty-near`, or
+/// `~/.cache/monty-near`.
+pub fn cache_dir() -> Option<PathBuf> {
+    let cache = std::env::var_os("XDG_CACHE_HOME")
+        .filter(|dir| !dir.is_empty())
+        .map(PathBuf::from)
+        .or_else(|| std::env::var_os("HOME").map(|home| PathBuf::from(home).join(".cache")))?;
+    Some(cache.join("monty-near"))
+}
+
+/// The directory holding the shared target directories.
+pub fn root() -> Option<PathBuf> {
+    Some(cache_dir()?.join("target"))
+}
+
+/// The shared target directory under `root` for the project in
+/// `project_dir`, named by a hash of its [`KEY_FILES`].
+pub fn dir(root: &Path, project_dir: &Path) -> PathBuf {
+    let mut hasher = Sha256::new();
+    for file in KEY_FILES {
+        hasher.update(file.as_bytes());
+        hasher.update(fs::read(project_dir.join(file)).unwrap_or_default());
+    }
+    let key: String = hasher.finalize()[..8]
+        .iter()
+        .map(|b| format!("{b:02x}"))
+        .collect();
+    root.join(key)
+}
+
+/// `clean --shared-cache`: remove every shared target directory.
+pub fn clean() -> Result<()> {
+    match root().filter(|root| root.exists()) {
+        Some(root) => {
+            fs::remove_dir_all(&root)?;
+            eprintln!("  Removed {}", root.display());
+        }
+        None => eprintln!("  No shared target cache"),
+    }
+    Ok(())
+}
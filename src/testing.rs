@@ -0,0 +1,159 @@
+// Contract tests — Python `test_*` functions run on the host with Monty,
+// calling the contract deployed in a local near-sandbox.
+
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use anyhow::{bail, Context, Result};
+use monty::MontyObject;
+use ruff_python_ast::Stmt;
+use ruff_python_parser::parse_module;
+
+use crate::interp::{self, arg_bytes, arg_str};
+use crate::rpc::{outcome_logs, outcome_result};
+use crate::sandbox::Sandbox;
+use crate::tx::{Action, DEFAULT_GAS};
+
+/// Functions available to test code, on top of Python itself.
+const TEST_FUNCTIONS: &[&str] = &["call", "view", "call_error", "logs"];
+
+/// Test files for `contract`: `test_*.py` next to it and in a `tests/`
+/// directory beside it.
+pub fn discover(contract: &Path) -> Vec<PathBuf> {
+    let dir = contract
+        .parent()
+        .filter(|p| !p.as_os_str().is_empty())
+        .unwrap_or(Path::new("."));
+    let mut files: Vec<PathBuf> = [dir.to_path_buf(), dir.join("tests")]
+        .iter()
+        .filter_map(|d| fs::read_dir(d).ok())
+        .flatten()
+        .filter_map(|e| e.ok().map(|e| e.path()))
+        .filter(|p| {
+            let name = p.file_name().and_then(|n| n.to_str()).unwrap_or("");
+            name.starts_with("test_") && name.ends_with(".py")
+        })
+        .collect();
+    files.sort();
+    files
+}
+
+/// Deploy `wasm` to a fresh sandbox and run every test in `files` against it.
+pub fn run_tests(wasm: &[u8], files: &[PathBuf]) -> Result<()> {
+    if files.is_empty() {
+        bail!("no test files found (expected test_*.py next to the contract or in tests/)");
+    }
+
+    eprintln!("  Starting sandbox...");
+    let sandbox = Sandbox::start(&std::env::temp_dir())?;
+    sandbox.deploy(wasm)?;
+
+    let mut passed = 0;
+    let mut failed = Vec::new();
+    for file in files {
+        let source = crate::read_source(file)?;
+        for name in test_functions(&source)
+            .with_context(|| format!("failed to parse {}", file.display()))?
+        {
+            let program = format!("{source}\n\n{name}()\n");
+            let script = file.display().to_string();
+            let mut last_logs = Vec::new();
+            let result = interp::run(program, &script, TEST_FUNCTIONS, |function, args| {
+                host_call(&sandbox, function, args, &mut last_logs)
+            });
+            match result {
+                Ok(()) => {
+                    eprintln!("  test {name} ... ok");
+                    passed += 1;
+                }
+                Err(e) => {
+                    eprintln!("  test {name} ... FAILED");
+                    failed.push((name, format!("{e:#}")));
+                }
+            }
+        }
+    }
+
+    eprintln!();
+    for (name, error) in &failed {
+        eprintln!("  ---- {name} ----");
+        eprintln!("  {error}");
+        eprintln!();
+    }
+    eprintln!("  {passed} passed; {} failed", failed.len());
+    if !failed.is_empty() {
+        bail!("{} test(s) failed", failed.len());
+    }
+    Ok(())
+}
+
+/// Names of top-level `test_*` functions, in source order.
+fn test_functions(source: &str) -> Result<Vec<String>> {
+    let module = parse_module(source)
+        .map_err(|e| anyhow::anyhow!("{}", e.error))?
+        .into_syntax();
+    Ok(module
+        .body
+        .iter()
+        .filter_map(|stmt| match stmt {
+            Stmt::FunctionDef(func) if func.name.as_str().starts_with("test_") => {
+                Some(func.name.to_string())
+            }
+            _ => None,
+        })
+        .collect())
+}
+
+/// `call(method, args="", deposit=0)`, `view(method, args="")`,
+/// `call_error(method, args="", deposit=0)`, and `logs()`.
+fn host_call(
+    sandbox: &Sandbox,
+    function: &str,
+    args: &[MontyObject],
+    last_logs: &mut Vec<String>,
+) -> Result<MontyObject> {
+    let method = arg_str(args, 0).unwrap_or("");
+    let input = arg_bytes(args, 1);
+    let text = |bytes: Vec<u8>| MontyObject::String(String::from_utf8_lossy(&bytes).into_owned());
+
+    match function {
+        "call" | "call_error" => {
+            let deposit = String::from_utf8(arg_bytes(args, 2))
+                .ok()
+                .filter(|d| !d.is_empty())
+                .map_or(Ok(0), |d| d.parse::<u128>())
+                .context("deposit must be a non-negative integer")?;
+            let outcome = sandbox.root.send(
+                &sandbox.rpc,
+                &sandbox.root.account_id,
+                &[Action::FunctionCall {
+                    method_name: method.to_string(),
+                    args: input,
+                    gas: DEFAULT_GAS,
+                    deposit,
+                }],
+            )?;
+            *last_logs = outcome_logs(&outcome);
+            match (function, outcome_result(&outcome)) {
+                ("call", result) => Ok(text(result.with_context(|| format!("call({method:?})"))?)),
+                (_, Ok(_)) => Ok(MontyObject::None),
+                (_, Err(e)) => Ok(MontyObject::String(format!("{e:#}"))),
+            }
+        }
+        "view" => {
+            let (value, logs) = sandbox
+                .rpc
+                .view_function(&sandbox.root.account_id, method, &input)
+                .with_context(|| format!("view({method:?})"))?;
+            *last_logs = logs;
+            Ok(text(value))
+        }
+        "logs" => Ok(MontyObject::List(
+            last_logs
+                .iter()
+                .map(|l| MontyObject::String(l.clone()))
+                .collect(),
+        )),
+        other => bail!("unknown test function {other}"),
+    }
+}
@@ -0,0 +1,347 @@
+// NEAR transaction construction and signing.
+//
+// Transactions are borsh-encoded by hand: the handful of actions the CLI
+// needs doesn't justify pulling in near-primitives and its dependency tree.
+
+use std::path::{Path, PathBuf};
+
+use anyhow::{bail, Context, Result};
+use ed25519_dalek::{Signer as _, SigningKey};
+use serde::Deserialize;
+use serde_json::Value;
+use sha2::{Digest, Sha256};
+
+use crate::rpc::RpcClient;
+
+/// Default gas attached to function calls: 300 Tgas, the protocol maximum.
+pub const DEFAULT_GAS: u64 = 300_000_000_000_000;
+
+// ---------------------------------------------------------------------------
+// Keys
+// ---------------------------------------------------------------------------
+
+/// An account plus the ed25519 key used to sign on its behalf.
+pub struct Signer {
+    pub account_id: String,
+    key: SigningKey,
+}
+
+/// Key file layout shared by `~/.near-credentials` (`private_key`) and
+/// near-sandbox's `validator_key.json` (`secret_key`).
+#[derive(Deserialize)]
+struct KeyFile {
+    account_id: String,
+    #[serde(alias = "private_key")]
+    secret_key: String,
+}
+
+impl Signer {
+    pub fn from_secret_key(account_id: &str, secret_key: &str) -> Result<Self> {
+        let encoded = secret_key
+            .strip_prefix("ed25519:")
+            .context("only ed25519 keys are supported")?;
+        let bytes = bs58::decode(encoded)
+            .into_vec()
+            .context("secret key is not valid base58")?;
+        // NEAR secret keys are the 32-byte seed followed by the public key
+        let seed: [u8; 32] = bytes
+            .get(..32)
+            .and_then(|s| s.try_into().ok())
+            .context("secret key is too short")?;
+        Ok(Self {
+            account_id: account_id.to_string(),
+            key: SigningKey::from_bytes(&seed),
+        })
+    }
+
+    /// A fresh random key for `account_id`.
+    pub fn generate(account_id: &str) -> Result<Self> {
+        let mut seed = [0u8; 32];
+        getrandom::getrandom(&mut seed)
+            .map_err(|e| anyhow::anyhow!("failed to generate a key: {e}"))?;
+        Ok(Self {
+            account_id: account_id.to_string(),
+            key: SigningKey::from_bytes(&seed),
+        })
+    }
+
+    pub fn from_key_file(path: &Path) -> Result<Self> {
+        let contents = std::fs::read_to_string(path)
+            .with_context(|| format!("failed to read key file {}", path.display()))?;
+        let file: KeyFile = serde_json::from_str(&contents)
+            .with_context(|| format!("invalid key file {}", path.display()))?;
+        Self::from_secret_key(&file.account_id, &file.secret_key)
+    }
+
+    pub fn public_key_bytes(&self) -> [u8; 32] {
+        self.key.verifying_key().to_bytes()
+    }
+
+    /// Public key in NEAR's `ed25519:<base58>` string form.
+    pub fn public_key(&self) -> String {
+        format!(
+            "ed25519:{}",
+            bs58::encode(self.public_key_bytes()).into_string()
+        )
+    }
+
+    /// Secret key in NEAR's `ed25519:<base58(seed ++ public key)>` form.
+    pub fn secret_key(&self) -> String {
+        let mut bytes = self.key.to_bytes().to_vec();
+        bytes.extend_from_slice(&self.public_key_bytes());
+        format!("ed25519:{}", bs58::encode(bytes).into_string())
+    }
+
+    /// Sign `actions` against `receiver_id`, submit, and wait for execution.
+    pub fn send(&self, rpc: &RpcClient, receiver_id: &str, actions: &[Action]) -> Result<Value> {
+        let nonce = rpc.access_key_nonce(&self.account_id, &self.public_key())? + 1;
+        let block_hash = rpc.final_block_hash()?;
+        let tx = encode_transaction(self, nonce, receiver_id, block_hash, actions);
+        rpc.send_tx(&self.sign_transaction(&tx))
+    }
+
+    /// Append an ed25519 signature over `sha256(tx)` to produce a `SignedTransaction`.
+    fn sign_transaction(&self, tx: &[u8]) -> Vec<u8> {
+        let hash = Sha256::digest(tx);
+        let signature = self.key.sign(&hash);
+        let mut signed = tx.to_vec();
+        signed.push(0); // KeyType::ED25519
+        signed.extend_from_slice(&signature.to_bytes());
+        signed
+    }
+}
+
+/// Load credentials for `account_id` the way near-cli stores them: either
+/// `~/.near-credentials/<network>/<account_id>.json`, or a key file inside
+/// `~/.near-credentials/<network>/<account_id>/` (near-cli-rs).
+pub fn load_credentials(network: &str, account_id: &str) -> Result<Signer> {
+    let home = std::env::var_os("HOME").context("HOME is not set")?;
+    let dir = Path::new(&home).join(".near-credentials").join(network);
+
+    let file = dir.join(format!("{account_id}.json"));
+    if file.exists() {
+        return Signer::from_key_file(&file);
+    }
+
+    let key_dir = dir.join(account_id);
+    if let Ok(entries) = std::fs::read_dir(&key_dir) {
+        let mut keys: Vec<_> = entries
+            .filter_map(|e| e.ok().map(|e| e.path()))
+            .filter(|p| p.extension().is_some_and(|ext| ext == "json"))
+            .collect();
+        keys.sort();
+        if let Some(key) = keys.first() {
+            return Signer::from_key_file(key);
+        }
+    }
+
+    bail!(
+        "no credentials for {account_id} on {network} (looked in {})\n    \
+         Log in with: near account import-account",
+        dir.display()
+    )
+}
+
+/// Store `signer` where `load_credentials` (and near-cli) will find it.
+pub fn save_credentials(network: &str, signer: &Signer) -> Result<PathBuf> {
+    let home = std::env::var_os("HOME").context("HOME is not set")?;
+    let dir = Path::new(&home).join(".near-credentials").join(network);
+    std::fs::create_dir_all(&dir)?;
+    let path = dir.join(format!("{}.json", signer.account_id));
+    let contents = serde_json::json!({
+        "account_id": signer.account_id,
+        "public_key": signer.public_key(),
+        "private_key": signer.secret_key(),
+    });
+    std::fs::write(&path, serde_json::to_string_pretty(&contents)?)
+        .with_context(|| format!("failed to write {}", path.display()))?;
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        std::fs::set_permissions(&path, std::fs::Permissions::from_mode(0o600))?;
+    }
+    Ok(path)
+}
+
+// ---------------------------------------------------------------------------
+// Actions and borsh encoding
+// ---------------------------------------------------------------------------
+
+pub enum Action {
+    CreateAccount,
+    DeployContract {
+        code: Vec<u8>,
+    },
+    FunctionCall {
+        method_name: String,
+        args: Vec<u8>,
+        gas: u64,
+        deposit: u128,
+    },
+    Transfer {
+        deposit: u128,
+    },
+    AddFullAccessKey {
+        public_key: [u8; 32],
+    },
+}
+
+fn encode_transaction(
+    signer: &Signer,
+    nonce: u64,
+    receiver_id: &str,
+    block_hash: [u8; 32],
+    actions: &[Action],
+) -> Vec<u8> {
+    let mut buf = Vec::new();
+    put_str(&mut buf, &signer.account_id);
+    put_public_key(&mut buf, &signer.public_key_bytes());
+    buf.extend_from_slice(&nonce.to_le_bytes());
+    put_str(&mut buf, receiver_id);
+    buf.extend_from_slice(&block_hash);
+    buf.extend_from_slice(&(actions.len() as u32).to_le_bytes());
+    for action in actions {
+        put_action(&mut buf, action);
+    }
+    buf
+}
+
+fn put_action(buf: &mut Vec<u8>, action: &Action) {
+    match action {
+        Action::CreateAccount => buf.push(0),
+        Action::DeployContract { code } => {
+            buf.push(1);
+            put_bytes(buf, code);
+        }
+        Action::FunctionCall {
+            method_name,
+            args,
+            gas,
+            deposit,
+        } => {
+            buf.push(2);
+            put_str(buf, method_name);
+            put_bytes(buf, args);
+            buf.extend_from_slice(&gas.to_le_bytes());
+            buf.extend_from_slice(&deposit.to_le_bytes());
+        }
+        Action::Transfer { deposit } => {
+            buf.push(3);
+            buf.extend_from_slice(&deposit.to_le_bytes());
+        }
+        Action::AddFullAccessKey { public_key } => {
+            buf.push(5);
+            put_public_key(buf, public_key);
+            buf.extend_from_slice(&0u64.to_le_bytes()); // access key nonce
+            buf.push(1); // AccessKeyPermission::FullAccess
+        }
+    }
+}
+
+fn put_bytes(buf: &mut Vec<u8>, bytes: &[u8]) {
+    buf.extend_from_slice(&(bytes.len() as u32).to_le_bytes());
+    buf.extend_from_slice(bytes);
+}
+
+fn put_str(buf: &mut Vec<u8>, s: &str) {
+    put_bytes(buf, s.as_bytes());
+}
+
+fn put_public_key(buf: &mut Vec<u8>, key: &[u8; 32]) {
+    buf.push(0); // KeyType::ED25519
+    buf.extend_from_slice(key);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ed25519_dalek::{Signature, Verifier};
+
+    /// RFC 8032's first test key.
+    const SEED: [u8; 32] = [
+        0x9d, 0x61, 0xb1, 0x9d, 0xef, 0xfd, 0x5a, 0x60, 0xba, 0x84, 0x4a, 0xf4, 0x92, 0xec, 0x2c,
+        0xc4, 0x44, 0x49, 0xc5, 0x69, 0x7b, 0x32, 0x69, 0x19, 0x70, 0x3b, 0xac, 0x03, 0x1c, 0xae,
+        0x7f, 0x60,
+    ];
+    const PUBLIC_KEY: [u8; 32] = [
+        0xd7, 0x5a, 0x98, 0x01, 0x82, 0xb1, 0x0a, 0xb7, 0xd5, 0x4b, 0xfe, 0xd3, 0xc9, 0x64, 0x07,
+        0x3a, 0x0e, 0xe1, 0x72, 0xf3, 0xda, 0xa6, 0x23, 0x25, 0xaf, 0x02, 0x1a, 0x68, 0xf7, 0x07,
+        0x51, 0x1a,
+    ];
+
+    fn signer() -> Signer {
+        let mut secret = SEED.to_vec();
+        secret.extend_from_slice(&PUBLIC_KEY);
+        let secret = format!("ed25519:{}", bs58::encode(secret).into_string());
+        Signer::from_secret_key("alice.near", &secret).unwrap()
+    }
+
+    #[test]
+    fn secret_keys_round_trip() {
+        let signer = signer();
+        assert_eq!(signer.public_key_bytes(), PUBLIC_KEY);
+        let again = Signer::from_secret_key("alice.near", &signer.secret_key()).unwrap();
+        assert_eq!(again.public_key(), signer.public_key());
+    }
+
+    #[test]
+    fn secret_keys_must_be_ed25519() {
+        assert!(Signer::from_secret_key("alice.near", "secp256k1:abc").is_err());
+        assert!(Signer::from_secret_key("alice.near", "ed25519:abc").is_err());
+    }
+
+    #[test]
+    fn transactions_are_borsh_encoded() {
+        let tx = encode_transaction(
+            &signer(),
+            7,
+            "c.near",
+            [1; 32],
+            &[
+                Action::FunctionCall {
+                    method_name: "go".into(),
+                    args: b"{}".to_vec(),
+                    gas: 5,
+                    deposit: 1,
+                },
+                Action::Transfer { deposit: 2 },
+            ],
+        );
+
+        let mut expected = Vec::new();
+        expected.extend_from_slice(&10u32.to_le_bytes());
+        expected.extend_from_slice(b"alice.near");
+        expected.push(0);
+        expected.extend_from_slice(&PUBLIC_KEY);
+        expected.extend_from_slice(&7u64.to_le_bytes());
+        expected.extend_from_slice(&6u32.to_le_bytes());
+        expected.extend_from_slice(b"c.near");
+        expected.extend_from_slice(&[1; 32]);
+        expected.extend_from_slice(&2u32.to_le_bytes());
+        expected.push(2);
+        expected.extend_from_slice(&2u32.to_le_bytes());
+        expected.extend_from_slice(b"go");
+        expected.extend_from_slice(&2u32.to_le_bytes());
+        expected.extend_from_slice(b"{}");
+        expected.extend_from_slice(&5u64.to_le_bytes());
+        expected.extend_from_slice(&1u128.to_le_bytes());
+        expected.push(3);
+        expected.extend_from_slice(&2u128.to_le_bytes());
+        assert_eq!(tx, expected);
+    }
+
+    #[test]
+    fn signed_transactions_carry_a_signature_of_the_hash() {
+        let signer = signer();
+        let tx = encode_transaction(&signer, 1, "c.near", [0; 32], &[Action::CreateAccount]);
+        let signed = signer.sign_transaction(&tx);
+        assert_eq!(&signed[..tx.len()], &tx[..]);
+        assert_eq!(signed[tx.len()], 0);
+        let signature = Signature::from_slice(&signed[tx.len() + 1..]).unwrap();
+        signer
+            .key
+            .verifying_key()
+            .verify(&Sha256::digest(&tx), &signature)
+            .unwrap();
+    }
+}
@@ -0,0 +1,194 @@
+// Unsupported Python — Monty runs a subset of Python, and code outside it
+// only fails once Monty compiles it, or worse, when the deployed contract
+// reaches it. The build walks the contract and its modules first and
+// reports each such construct where it is, with what to write instead.
+
+use std::path::Path;
+
+use ruff_python_ast::visitor::{self, Visitor};
+use ruff_python_ast::{Expr, ModModule, Stmt, StmtClassDef};
+use ruff_text_size::Ranged;
+
+use crate::diagnostics::{Diagnostic, SourceFile};
+
+/// Modules Monty provides itself; other imports must be files of the
+/// contract or bundled modules.
+const MONTY_MODULES: &[&str] = &["asyncio", "sys", "typing"];
+
+/// Why a standard library module can't work in a contract, and what to use
+/// instead.
+const MODULE_HINTS: &[(&str, &str)] = &[
+    (
+        "threading",
+        "a contract runs single-threaded, one call at a time",
+    ),
+    (
+        "multiprocessing",
+        "a contract runs single-threaded, one call at a time",
+    ),
+    (
+        "concurrent",
+        "a contract runs single-threaded; await cross_call() to call other contracts",
+    ),
+    (
+        "os",
+        "a contract has no operating system or files: keep data in storage with storage_write()",
+    ),
+    (
+        "pathlib",
+        "a contract has no files: keep data in storage with storage_write()",
+    ),
+    ("subprocess", "a contract can't start processes"),
+    (
+        "socket",
+        "a contract can't reach the network: call other contracts with Promise or cross_call()",
+    ),
+    (
+        "urllib",
+        "a contract can't reach the network: call other contracts with Promise or cross_call()",
+    ),
+    (
+        "http",
+        "a contract can't reach the network: call other contracts with Promise or cross_call()",
+    ),
+    (
+        "requests",
+        "a contract can't reach the network: call other contracts with Promise or cross_call()",
+    ),
+    (
+        "random",
+        "use random_seed(), the block's random seed, which every validator agrees on",
+    ),
+    (
+        "secrets",
+        "use random_seed(), the block's random seed, which every validator agrees on",
+    ),
+    (
+        "time",
+        "use block_timestamp(), in nanoseconds, which every validator agrees on",
+    ),
+    (
+        "datetime",
+        "use block_timestamp(), in nanoseconds, which every validator agrees on",
+    ),
+    (
+        "hashlib",
+        "use the host's sha256(), keccak256(), keccak512() or ripemd160()",
+    ),
+    (
+        "pickle",
+        "store JSON with the bundled json module, or use @borsh_io",
+    ),
+];
+
+/// Collects the constructs Monty can't run.
+struct Finder<'a> {
+    found: Vec<(usize, String, &'static str)>,
+    /// The one class the compiler lowers, `class Contract`.
+    contract: Option<&'a StmtClassDef>,
+}
+
+impl<'a> Visitor<'a> for Finder<'a> {
+    fn visit_stmt(&mut self, stmt: &'a Stmt) {
+        let offset = stmt.range().start().to_usize();
+        match stmt {
+            Stmt::ClassDef(class) if !self.contract.is_some_and(|c| std::ptr::eq(c, class)) => {
+                self.found.push((
+                    offset,
+                    format!(
+                        "class `{}`: classes aren't supported, apart from `class Contract`",
+                        class.name
+                    ),
+                    "use functions and dicts; the contract's own state can be the fields of \
+                     `class Contract`",
+                ));
+            }
+            Stmt::Try(_) => self.found.push((
+                offset,
+                "`try` isn't supported: exceptions can't be caught".to_string(),
+                "check for the problem before it happens; an uncaught error aborts the call \
+                 and reverts its changes",
+            )),
+            _ => {}
+        }
+        visitor::walk_stmt(self, stmt);
+    }
+
+    fn visit_expr(&mut self, expr: &'a Expr) {
+        let kind = match expr {
+            Expr::ListComp(_) => Some("list comprehensions"),
+            Expr::SetComp(_) => Some("set comprehensions"),
+            Expr::DictComp(_) => Some("dict comprehensions"),
+            Expr::Generator(_) => Some("generator expressions"),
+            _ => None,
+        };
+        if let Some(kind) = kind {
+            self.found.push((
+                expr.range().start().to_usize(),
+                format!("{kind} aren't supported"),
+                "build the result in a `for` loop",
+            ));
+        }
+        visitor::walk_expr(self, expr);
+    }
+}
+
+/// Report the constructs in `module` Monty can't run, and imports of
+/// modules that aren't under `root`, bundled, or provided by Monty.
+pub fn check(module: &ModModule, file: SourceFile, root: &Path, diagnostics: &mut Vec<Diagnostic>) {
+    let mut finder = Finder {
+        found: Vec::new(),
+        contract: crate::contract_class::find_class(module),
+    };
+    finder.visit_body(&module.body);
+    for (offset, message, note) in finder.found {
+        diagnostics.push(
+            file.error("unsupported-syntax", offset, message)
+                .with_note(note),
+        );
+    }
+
+    for stmt in &module.body {
+        let names: Vec<(&str, usize)> = match stmt {
+            Stmt::Import(import) => import
+                .names
+                .iter()
+                .map(|alias| (alias.name.as_str(), alias.range.start().to_usize()))
+                .collect(),
+            // Relative imports are the contract's own modules
+            Stmt::ImportFrom(import) if import.level == 0 => match &import.module {
+                Some(name) => vec![(name.as_str(), import.range.start().to_usize())],
+                None => Vec::new(),
+            },
+            _ => continue,
+        };
+        for (name, offset) in names {
+            let top = name.split('.').next().unwrap_or(name);
+            if MONTY_MODULES.contains(&top) || crate::imports::exists(root, name) {
+                continue;
+            }
+            let note = match MODULE_HINTS.iter().find(|(module, _)| *module == top) {
+                Some((_, hint)) => hint.to_string(),
+                None => {
+                    let bundled: Vec<&str> = crate::imports::MODULES
+                        .iter()
+                        .map(|(name, _)| *name)
+                        .collect();
+                    format!(
+                        "a contract can import its own modules, the bundled {}, and Monty's {}",
+                        bundled.join(", "),
+                        MONTY_MODULES.join(", ")
+                    )
+                }
+            };
+            diagnostics.push(
+                file.error(
+                    "unsupported-import",
+                    offset,
+                    format!("module `{name}` isn't available in a contract"),
+                )
+                .with_note(note),
+            );
+        }
+    }
+}
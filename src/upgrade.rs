@@ -0,0 +1,334 @@
+// Contract upgrades — deploy a new build over an existing contract, checking
+// it still serves the methods callers rely on and can read the state it
+// stored, and optionally migrate state in the same transaction.
+
+use anyhow::{bail, Context, Result};
+use serde_json::Value;
+
+use crate::code_hash;
+use crate::contract_class;
+use crate::metadata;
+use crate::rpc::{network_rpc_url, outcome_logs, outcome_result, RpcClient};
+use crate::tx::{load_credentials, Action};
+
+/// A method to call right after the new code is deployed.
+pub struct Migration<'a> {
+    pub method: &'a str,
+    pub args: &'a [u8],
+    pub gas: u64,
+}
+
+/// How to upgrade, beyond what to deploy where.
+pub struct Options<'a> {
+    pub migration: Option<Migration<'a>>,
+    /// The new build's `@migrate` methods.
+    pub migrate_methods: Vec<&'a str>,
+    /// Upgrade despite incompatibilities.
+    pub force: bool,
+}
+
+/// A state layout: each field's name and type, in the order they're stored.
+type Layout = Vec<(String, String)>;
+
+/// What's known about the layout of the deployed contract's state.
+enum DeployedState {
+    /// From the deployed version's ABI.
+    Layout(Layout),
+    /// Only how many values its stored state has.
+    Values(usize),
+}
+
+/// Deploy `wasm` to `account_id`, replacing its current contract.
+///
+/// The deployed code's exports are compared with `abi` (the new build's
+/// near-abi document), and with `previous_abi` if given, to find methods
+/// that were removed or whose parameters or result changed. The state
+/// layout in `abi` is compared with the deployed state's, and a change
+/// needs a migration. Any such problem aborts the upgrade unless `force`
+/// is set.
+pub fn upgrade(
+    wasm: &[u8],
+    abi: &Value,
+    previous_abi: Option<&Value>,
+    account_id: &str,
+    network: &str,
+    options: Options,
+) -> Result<()> {
+    let Options {
+        migration,
+        migrate_methods,
+        force,
+    } = options;
+    let rpc = RpcClient::new(&network_rpc_url(network)?);
+    let signer = load_credentials(network, account_id)?;
+
+    eprintln!("  Checking the contract deployed on {account_id}...");
+    let deployed = rpc
+        .view_code(account_id)
+        .with_context(|| format!("{account_id} has no contract to upgrade"))?;
+    let deployed_methods = crate::size::exported_functions(&deployed)
+        .context("failed to read the exports of the deployed contract")?;
+
+    let mut problems = incompatibilities(&deployed_methods, abi, previous_abi);
+    if let Some(new_layout) = layout(abi) {
+        let previous_layout = previous_abi.and_then(layout);
+        let deployed = match &previous_layout {
+            Some(previous) => Some(DeployedState::Layout(previous.clone())),
+            None => stored_values(&rpc, account_id).map(DeployedState::Values),
+        };
+        if let Some(change) = deployed.and_then(|d| state_change(&d, &new_layout)) {
+            match &migration {
+                Some(migration) => {
+                    eprintln!("  {} migrates the state: {change}", migration.method)
+                }
+                None => {
+                    problems.push(format!("{change}, and no --migrate method was given"));
+                    match &migrate_methods[..] {
+                        [] => eprintln!(
+                            "  Add a migration to class {}, starting from:\n\n{}",
+                            contract_class::CLASS_NAME,
+                            migrate_skeleton(previous_layout.as_ref(), &new_layout)
+                        ),
+                        methods => eprintln!(
+                            "  Pass --migrate with the new build's @migrate method ({})",
+                            methods.join(", ")
+                        ),
+                    }
+                }
+            }
+        }
+    }
+    if !problems.is_empty() {
+        for problem in &problems {
+            eprintln!("  ! {problem}");
+        }
+        if !force {
+            bail!(
+                "the new build is not compatible with the deployed contract \
+                 (pass --force to upgrade anyway)"
+            );
+        }
+        eprintln!("  Continuing because of --force");
+    }
+
+    let mut actions = vec![Action::DeployContract {
+        code: wasm.to_vec(),
+    }];
+    if let Some(migration) = &migration {
+        eprintln!(
+            "  Deploying to {account_id} on {network} and calling {}...",
+            migration.method
+        );
+        actions.push(Action::FunctionCall {
+            method_name: migration.method.to_string(),
+            args: migration.args.to_vec(),
+            gas: migration.gas,
+            deposit: 0,
+        });
+    } else {
+        eprintln!("  Deploying to {account_id} on {network}...");
+    }
+    // Deploy and migration are one transaction: if the migration fails,
+    // the old code stays deployed
+    let outcome = signer.send(&rpc, account_id, &actions)?;
+    for log in outcome_logs(&outcome) {
+        eprintln!("  Log: {log}");
+    }
+    outcome_result(&outcome)
+        .context("upgrade transaction failed; the old code is still deployed")?;
+
+    let tx_hash = outcome["transaction"]["hash"].as_str().unwrap_or("unknown");
+    eprintln!("  \u{2713} Upgraded {account_id} to {}", code_hash(wasm));
+    eprintln!("    Transaction: {tx_hash}");
+    Ok(())
+}
+
+/// The state layout an ABI records, for a class-based contract.
+fn layout(abi: &Value) -> Option<Layout> {
+    let fields = abi["metadata"]["state"]["fields"].as_array()?;
+    Some(
+        fields
+            .iter()
+            .map(|field| {
+                let text = |key: &str| field[key].as_str().unwrap_or_default().to_string();
+                (text("name"), text("type"))
+            })
+            .collect(),
+    )
+}
+
+/// How many values the deployed contract's stored state has, or `None` if
+/// it has none or it can't be read.
+fn stored_values(rpc: &RpcClient, account_id: &str) -> Option<usize> {
+    let key = contract_class::STATE_KEY.as_bytes();
+    let entries = match rpc.view_state(account_id, key) {
+        Ok(entries) => entries,
+        Err(e) => {
+            eprintln!("  ! Couldn't read the deployed state to check its layout: {e:#}");
+            return None;
+        }
+    };
+    let (_, value) = entries.iter().find(|(k, _)| k.as_slice() == key)?;
+    let values = contract_class::stored_values(&String::from_utf8_lossy(value))?;
+    Some(values.len())
+}
+
+/// Why state stored by the deployed version can't be read with `layout`,
+/// if it can't. Fields may only be added at the end.
+fn state_change(deployed: &DeployedState, layout: &Layout) -> Option<String> {
+    let describe = |layout: &Layout| {
+        layout
+            .iter()
+            .map(|(name, ty)| format!("{name}: {ty}"))
+            .collect::<Vec<_>>()
+            .join(", ")
+    };
+    match deployed {
+        DeployedState::Layout(previous) => {
+            let kept = previous.len() <= layout.len() && layout[..previous.len()] == previous[..];
+            (!kept).then(|| {
+                format!(
+                    "the state layout changed from ({}) to ({})",
+                    describe(previous),
+                    describe(layout)
+                )
+            })
+        }
+        DeployedState::Values(count) => (*count > layout.len()).then(|| {
+            format!(
+                "the deployed state has {count} fields, but class {} declares {}",
+                contract_class::CLASS_NAME,
+                layout.len()
+            )
+        }),
+    }
+}
+
+/// A `@migrate` method to start from: one that reads each field the
+/// deployed version stored under the same name, when its layout is known.
+fn migrate_skeleton(previous: Option<&Layout>, layout: &Layout) -> String {
+    let mut out = String::from("    @migrate\n    def migrate(self, old: list[str]):\n");
+    match previous {
+        Some(previous) => {
+            let stored: Vec<String> = previous
+                .iter()
+                .map(|(name, ty)| format!("{name}: {ty}"))
+                .collect();
+            out.push_str(&format!(
+                "        # The deployed version stored, as strings: {}\n",
+                stored.join(", ")
+            ));
+            for (name, ty) in layout {
+                match previous.iter().position(|(old, _)| old == name) {
+                    Some(i) => {
+                        let value = format!("old[{i}]");
+                        let value = match ty.as_str() {
+                            "int" | "float" => format!("{ty}({value})"),
+                            "bool" => format!("{value} == \"True\""),
+                            _ => value,
+                        };
+                        out.push_str(&format!("        self.{name} = {value}\n"));
+                    }
+                    None => out.push_str(&format!("        # self.{name} = ...\n")),
+                }
+            }
+        }
+        None => {
+            out.push_str(
+                "        # `old` holds the deployed version's fields, in order, as strings\n",
+            );
+            for (name, _) in layout {
+                out.push_str(&format!("        # self.{name} = ...\n"));
+            }
+        }
+    }
+    out.push_str("        # Fields not set here keep their defaults\n");
+    out
+}
+
+/// Ways the new build breaks callers of the deployed contract.
+fn incompatibilities(
+    deployed_methods: &[String],
+    abi: &Value,
+    previous_abi: Option<&Value>,
+) -> Vec<String> {
+    let functions = |abi: &Value| {
+        abi["body"]["functions"]
+            .as_array()
+            .cloned()
+            .unwrap_or_default()
+    };
+    let new_functions = functions(abi);
+    let find = |name: &str| new_functions.iter().find(|f| f["name"] == name);
+
+    let mut problems = Vec::new();
+    // Runtime support exports like `__getrandom_v03_custom` aren't methods,
+    // and the build adds the source metadata view without listing it
+    let methods = deployed_methods
+        .iter()
+        .filter(|m| !m.starts_with('_') && m.as_str() != metadata::METHOD_NAME);
+    for method in methods {
+        if find(method).is_none() {
+            problems.push(format!(
+                "method `{method}` is deployed but missing from the new build"
+            ));
+        }
+    }
+
+    for old in previous_abi.map(functions).unwrap_or_default() {
+        let name = old["name"].as_str().unwrap_or("");
+        let Some(new) = find(name) else {
+            continue;
+        };
+        if old["params"] != new["params"] {
+            problems.push(format!("parameters of `{name}` changed"));
+        }
+        if old["result"] != new["result"] {
+            problems.push(format!("result type of `{name}` changed"));
+        }
+    }
+    problems
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn abi(functions: Value) -> Value {
+        json!({ "body": { "functions": functions } })
+    }
+
+    #[test]
+    fn upgrading_to_itself_is_compatible() {
+        let abi = abi(json!([
+            { "name": "get", "kind": "view", "result": { "type_schema": { "type": "string" } } },
+            { "name": "set", "kind": "call", "params": { "args": [{ "name": "value" }] } },
+        ]));
+        let deployed = [
+            "get",
+            "set",
+            metadata::METHOD_NAME,
+            "__getrandom_v03_custom",
+        ]
+        .map(String::from);
+        assert!(incompatibilities(&deployed, &abi, Some(&abi)).is_empty());
+    }
+
+    #[test]
+    fn removed_and_changed_methods_are_reported() {
+        let old = abi(json!([
+            { "name": "get", "params": { "args": [] } },
+            { "name": "gone" },
+        ]));
+        let new = abi(json!([{ "name": "get", "params": { "args": [{ "name": "key" }] } }]));
+        let deployed = ["get", "gone"].map(String::from);
+        assert_eq!(
+            incompatibilities(&deployed, &new, Some(&old)),
+            [
+                "method `gone` is deployed but missing from the new build",
+                "parameters of `get` changed",
+            ]
+        );
+    }
+}
@@ -0,0 +1,92 @@
+// Vendored dependencies — `build --vendor-dir` builds the generated project
+// from sources `cargo vendor` copied into a directory, so air-gapped builds
+// need neither crates.io nor the Monty git repository.
+
+use std::fs;
+use std::path::Path;
+use std::process::Command;
+
+use anyhow::{bail, Context, Result};
+use sha2::{Digest, Sha256};
+
+use crate::{cargo_registry_config, BuildArgs};
+
+/// The source replacement `cargo vendor` printed, kept in the vendor
+/// directory for later builds.
+const SOURCES_FILE: &str = ".monty-near-sources.toml";
+
+/// sha256 of every Cargo.lock whose dependencies the directory holds.
+const VENDORED_FILE: &str = ".monty-near-vendored";
+
+/// Make the generated project in `build_dir` build from `vendor_dir`,
+/// vendoring its dependencies first if the directory doesn't have them yet
+/// and the build may use the network.
+pub fn use_vendor_dir(build_dir: &Path, vendor_dir: &Path, args: &BuildArgs) -> Result<()> {
+    let vendor_dir = std::env::current_dir()?.join(vendor_dir);
+    let vendored = fs::read_to_string(vendor_dir.join(VENDORED_FILE)).unwrap_or_default();
+    let up_to_date = lock_hash(build_dir).is_some_and(|hash| vendored.lines().any(|l| l == hash));
+
+    if !up_to_date && !args.offline {
+        eprint!("  Vendoring dependencies into {}...", vendor_dir.display());
+        // The generated config is in place, so a --registry mirror serves
+        // the crates. --no-delete keeps other contracts' dependencies.
+        let output = Command::new("cargo")
+            .args(["vendor", "--no-delete", "--versioned-dirs"])
+            .arg(&vendor_dir)
+            .current_dir(build_dir)
+            .env_remove("RUSTUP_TOOLCHAIN")
+            .output()
+            .context("failed to run cargo vendor")?;
+        if !output.status.success() {
+            eprintln!(" failed");
+            let stderr = String::from_utf8_lossy(&output.stderr);
+            bail!("cargo vendor failed:\n{stderr}");
+        }
+        eprintln!(" done");
+        fs::write(vendor_dir.join(SOURCES_FILE), &output.stdout)?;
+        if let Some(hash) = lock_hash(build_dir) {
+            fs::write(vendor_dir.join(VENDORED_FILE), vendored + &hash + "\n")?;
+        }
+    }
+
+    let sources = match fs::read_to_string(vendor_dir.join(SOURCES_FILE)) {
+        Ok(sources) => sources,
+        Err(_) => bail!(
+            "no vendored dependencies in {}; run the build with --vendor-dir once without --offline to fill it",
+            vendor_dir.display()
+        ),
+    };
+    // The directory may have moved since it was filled (e.g. a CI cache
+    // restored elsewhere)
+    let sources: String = sources
+        .lines()
+        .map(|line| {
+            if line.starts_with("directory = ") {
+                format!("directory = {:?}\n", vendor_dir.display().to_string())
+            } else {
+                format!("{line}\n")
+            }
+        })
+        .collect();
+
+    // Vendored sources replace crates.io, so they take the mirror's place
+    let config_path = build_dir.join(".cargo/config.toml");
+    let mut config = fs::read_to_string(&config_path)?;
+    if let Some(url) = &args.registry {
+        config = config.replace(&cargo_registry_config(url), "");
+    }
+    config.push('\n');
+    config.push_str(&sources);
+    fs::write(&config_path, config)?;
+    Ok(())
+}
+
+fn lock_hash(build_dir: &Path) -> Option<String> {
+    let lock = fs::read(build_dir.join("Cargo.lock")).ok()?;
+    Some(
+        Sha256::digest(&lock)
+            .iter()
+            .map(|b| format!("{b:02x}"))
+            .collect(),
+    )
+}
@@ -0,0 +1,37 @@
+// On-chain verification — compare a local build against deployed code.
+
+use anyhow::{bail, Result};
+use sha2::{Digest, Sha256};
+
+use crate::rpc::{network_rpc_url, RpcClient};
+use crate::{artifacts, code_hash};
+
+/// The code hash of an account with no contract deployed.
+const EMPTY_CODE_HASH: &str = "11111111111111111111111111111111";
+
+/// Compare the code hash of `wasm` with the one deployed on `account_id`.
+/// Fails on mismatch so scripts can rely on the exit status.
+pub fn verify_onchain(wasm: &[u8], account_id: &str, network: &str) -> Result<()> {
+    let rpc = RpcClient::new(&network_rpc_url(network)?);
+    let local = code_hash(wasm);
+    let onchain = rpc.code_hash(account_id)?;
+
+    let sha256 = artifacts::hex(&Sha256::digest(wasm));
+
+    eprintln!();
+    eprintln!("  Local sha256:       {sha256}");
+    eprintln!("  Local code hash:    {local}");
+    eprintln!("  On-chain code hash: {onchain} ({account_id} on {network})");
+
+    if onchain == EMPTY_CODE_HASH {
+        bail!("{account_id} has no contract deployed");
+    }
+    if local != onchain {
+        bail!(
+            "code hash mismatch — the deployed contract was not built from this source \
+             with these flags"
+        );
+    }
+    eprintln!("  \u{2713} Match: {account_id} runs this contract");
+    Ok(())
+}
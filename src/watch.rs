@@ -0,0 +1,122 @@
+// Watch mode — rebuild whenever the contract source changes, reporting the
+// size difference and optionally redeploying to a sandbox dev account.
+
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::thread;
+use std::time::{Duration, SystemTime};
+
+use anyhow::{Context, Result};
+
+use crate::code_hash;
+use crate::rpc::outcome_result;
+use crate::sandbox::Sandbox;
+use crate::tx::{Action, Signer};
+use crate::BuildArgs;
+
+/// How often the watched files are checked for changes.
+const POLL_INTERVAL: Duration = Duration::from_millis(300);
+
+/// A sandbox kept running for the whole watch session, with the dev account
+/// each rebuild is deployed to.
+struct DevDeployment {
+    sandbox: Sandbox,
+    signer: Signer,
+}
+
+/// Build `args`, then rebuild every time a watched file changes. Build errors
+/// are reported and watching continues; only Ctrl-C stops the loop.
+pub fn watch(args: &BuildArgs, deploy: bool) -> Result<()> {
+    let mut deployment: Option<DevDeployment> = None;
+    let mut last_size: Option<usize> = None;
+
+    // Moved into `deployment` once the first build succeeds
+    let mut sandbox = if deploy {
+        eprintln!("  Starting sandbox...");
+        Some(Sandbox::start(&std::env::temp_dir())?)
+    } else {
+        None
+    };
+
+    loop {
+        // Imports may have changed since the last build
+        let files = watched_files(args.input());
+        let stamps = modified_times(&files);
+        match crate::build_contract(args).and_then(|path| {
+            fs::read(&path).with_context(|| format!("failed to read {}", path.display()))
+        }) {
+            Ok(wasm) => {
+                report_size(wasm.len(), last_size);
+                last_size = Some(wasm.len());
+                if let Some(sandbox) = sandbox.take() {
+                    let signer = crate::deploy::create_sandbox_dev_account(&sandbox, &wasm)?;
+                    deployment = Some(DevDeployment { sandbox, signer });
+                } else if let Some(deployment) = &deployment {
+                    if let Err(e) = redeploy(deployment, &wasm) {
+                        eprintln!("  error: {e:#}");
+                    }
+                }
+            }
+            Err(e) => eprintln!("  error: {e:#}"),
+        }
+
+        eprintln!();
+        eprintln!(
+            "  Watching {} for changes. Press Ctrl-C to stop.",
+            files
+                .iter()
+                .map(|f| f.display().to_string())
+                .collect::<Vec<_>>()
+                .join(", ")
+        );
+        while modified_times(&files) == stamps {
+            thread::sleep(POLL_INTERVAL);
+        }
+        // Let editors that write in several steps finish saving
+        thread::sleep(POLL_INTERVAL);
+        eprintln!();
+        eprintln!("  Change detected, rebuilding...");
+    }
+}
+
+/// Files whose changes trigger a rebuild: the contract and the modules it
+/// imports.
+fn watched_files(input: &Path) -> Vec<PathBuf> {
+    let mut files = vec![input.to_path_buf()];
+    files.extend(crate::imports::module_files(input));
+    files
+}
+
+/// Modification time of each file; `None` while a file is missing (e.g.
+/// mid-save by an editor that replaces the file).
+fn modified_times(files: &[PathBuf]) -> Vec<Option<SystemTime>> {
+    files
+        .iter()
+        .map(|f| fs::metadata(f).and_then(|m| m.modified()).ok())
+        .collect()
+}
+
+fn report_size(size: usize, previous: Option<usize>) {
+    match previous {
+        Some(previous) if previous != size => {
+            let delta = size as i64 - previous as i64;
+            eprintln!("  Size: {size} bytes ({delta:+} bytes)");
+        }
+        Some(_) => eprintln!("  Size: {size} bytes (unchanged)"),
+        None => eprintln!("  Size: {size} bytes"),
+    }
+}
+
+fn redeploy(deployment: &DevDeployment, wasm: &[u8]) -> Result<()> {
+    let account_id = &deployment.signer.account_id;
+    let outcome = deployment.signer.send(
+        &deployment.sandbox.rpc,
+        account_id,
+        &[Action::DeployContract {
+            code: wasm.to_vec(),
+        }],
+    )?;
+    outcome_result(&outcome).context("deploy transaction failed")?;
+    eprintln!("  \u{2713} Redeployed {} to {account_id}", code_hash(wasm));
+    Ok(())
+}
@@ -0,0 +1,82 @@
+// Yield/resume (NEP-519) — `yield_create("on_signature", args)` creates a
+// promise that calls one of the contract's own methods once someone calls
+// `yield_resume(yield_id, payload)`, or with a failed result if no one does
+// within about 200 blocks. The helpers wrap the promise_yield_* host
+// functions, encoding arguments and payloads as JSON, and are added to the
+// program when the contract calls them.
+
+use ruff_python_ast::visitor::{self, Visitor};
+use ruff_python_ast::{Expr, ExprCall, ModModule, Stmt};
+
+use crate::diagnostics::{Diagnostic, SourceFile};
+
+/// The helpers; like the host functions, they need no import.
+const HELPERS: [&str; 2] = ["yield_create", "yield_resume"];
+
+/// Collects calls to the helpers.
+#[derive(Default)]
+struct YieldCalls<'a> {
+    calls: Vec<(&'a str, &'a ExprCall)>,
+}
+
+impl<'a> Visitor<'a> for YieldCalls<'a> {
+    fn visit_expr(&mut self, expr: &'a Expr) {
+        if let Expr::Call(call) = expr {
+            if let Expr::Name(name) = call.func.as_ref() {
+                if let Some(helper) = HELPERS.iter().find(|h| **h == name.id.as_str()) {
+                    self.calls.push((helper, call));
+                }
+            }
+        }
+        visitor::walk_expr(self, expr);
+    }
+}
+
+/// Check that `yield_create` resumes into a method rather than a private
+/// helper, where its name is a literal. Returns whether `module` calls the
+/// helpers (rather than functions of its own by those names), so the build
+/// adds them.
+pub fn check(module: &ModModule, file: SourceFile, diagnostics: &mut Vec<Diagnostic>) -> bool {
+    let defined = module.body.iter().any(|stmt| match stmt {
+        Stmt::FunctionDef(func) => HELPERS.contains(&func.name.as_str()),
+        _ => false,
+    });
+    if defined {
+        return false;
+    }
+
+    let mut calls = YieldCalls::default();
+    calls.visit_body(&module.body);
+    for (helper, call) in &calls.calls {
+        if *helper != "yield_create" {
+            continue;
+        }
+        let function_name = call.arguments.args.first().or_else(|| {
+            call.arguments
+                .keywords
+                .iter()
+                .find(|k| {
+                    k.arg
+                        .as_ref()
+                        .is_some_and(|a| a.as_str() == "function_name")
+                })
+                .map(|k| &k.value)
+        });
+        if let Some(Expr::StringLiteral(lit)) = function_name {
+            let name = lit.value.to_str();
+            if name.is_empty() || name.starts_with('_') {
+                diagnostics.push(
+                    file.error(
+                        "invalid-yield",
+                        lit.range.start().to_usize(),
+                        format!(
+                            "yield_create() resumes into `{name}`, which isn't a contract method"
+                        ),
+                    )
+                    .with_note("the yielded promise calls an exported method of this contract"),
+                );
+            }
+        }
+    }
+    !calls.calls.is_empty()
+}
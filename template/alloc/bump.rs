@@ -0,0 +1,51 @@
+// Bump allocator: hands out memory by advancing a pointer and never frees
+// it. NEAR runs every call in a fresh instance, so memory only has to last
+// for one call; the allocator is a few dozen instructions instead of
+// dlmalloc's bookkeeping.
+
+struct BumpAlloc {
+    next: core::cell::Cell<usize>,
+    end: core::cell::Cell<usize>,
+}
+
+// Contracts are single-threaded
+unsafe impl Sync for BumpAlloc {}
+
+extern "C" {
+    // First byte after the stack and static data, provided by wasm-ld
+    static __heap_base: u8;
+}
+
+const WASM_PAGE_SIZE: usize = 64 * 1024;
+
+unsafe impl core::alloc::GlobalAlloc for BumpAlloc {
+    unsafe fn alloc(&self, layout: core::alloc::Layout) -> *mut u8 {
+        if self.end.get() == 0 {
+            self.next.set(core::ptr::addr_of!(__heap_base) as usize);
+            self.end
+                .set(core::arch::wasm32::memory_size(0) * WASM_PAGE_SIZE);
+        }
+        let align = layout.align();
+        let start = (self.next.get() + align - 1) & !(align - 1);
+        let Some(next) = start.checked_add(layout.size()) else {
+            return core::ptr::null_mut();
+        };
+        if next > self.end.get() {
+            let pages = (next - self.end.get()).div_ceil(WASM_PAGE_SIZE);
+            if core::arch::wasm32::memory_grow(0, pages) == usize::MAX {
+                return core::ptr::null_mut();
+            }
+            self.end.set(self.end.get() + pages * WASM_PAGE_SIZE);
+        }
+        self.next.set(next);
+        start as *mut u8
+    }
+
+    unsafe fn dealloc(&self, _ptr: *mut u8, _layout: core::alloc::Layout) {}
+}
+
+#[global_allocator]
+static ALLOCATOR: BumpAlloc = BumpAlloc {
+    next: core::cell::Cell::new(0),
+    end: core::cell::Cell::new(0),
+};
@@ -0,0 +1,4 @@
+// wee_alloc: a small allocator tuned for code size rather than speed.
+
+#[global_allocator]
+static ALLOCATOR: wee_alloc::WeeAlloc = wee_alloc::WeeAlloc::INIT;
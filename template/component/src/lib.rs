@@ -0,0 +1,190 @@
+// Component runtime for Monty (Python) contracts — EXPERIMENTAL.
+//
+// Auto-generated by monty-near-cli for `--format component`. Runs the same
+// precompiled bytecode as the NEAR runtime, but without any NEAR host imports
+// so the module can be wrapped as a WebAssembly component. Only `input`,
+// `value_return`, and `log` are available; any other NEAR host function traps.
+//
+// The `run` export and `cabi_*` functions implement the canonical ABI for
+// `world.wit` by hand, so no bindings generator is needed.
+
+use monty::{MontyObject, MontyRun, NoLimitTracker, NoPrint, RunProgress};
+
+// ---------------------------------------------------------------------------
+// Custom getrandom backend — fixed seed, there is no host randomness source
+// ---------------------------------------------------------------------------
+
+#[no_mangle]
+unsafe extern "Rust" fn __getrandom_v03_custom(
+    dest: *mut u8,
+    len: usize,
+) -> Result<(), getrandom::Error> {
+    unsafe {
+        core::ptr::write_bytes(dest, 0, len);
+    }
+    Ok(())
+}
+
+// ---------------------------------------------------------------------------
+// Canonical ABI glue
+// ---------------------------------------------------------------------------
+
+/// Return area for `run`: pointer and length of the result string.
+static mut RET_AREA: [usize; 2] = [0; 2];
+
+#[no_mangle]
+unsafe extern "C" fn cabi_realloc(
+    old_ptr: *mut u8,
+    old_size: usize,
+    align: usize,
+    new_size: usize,
+) -> *mut u8 {
+    use std::alloc::{alloc, realloc, Layout};
+
+    if new_size == 0 {
+        return align as *mut u8;
+    }
+    unsafe {
+        if old_size == 0 {
+            alloc(Layout::from_size_align_unchecked(new_size, align))
+        } else {
+            realloc(
+                old_ptr,
+                Layout::from_size_align_unchecked(old_size, align),
+                new_size,
+            )
+        }
+    }
+}
+
+#[export_name = "run"]
+unsafe extern "C" fn export_run(
+    method_ptr: *mut u8,
+    method_len: usize,
+    input_ptr: *mut u8,
+    input_len: usize,
+) -> *mut u8 {
+    // The host allocated both strings through `cabi_realloc` with align 1
+    let method = unsafe { String::from_raw_parts(method_ptr, method_len, method_len) };
+    let input = unsafe { String::from_raw_parts(input_ptr, input_len, input_len) };
+
+    let output = run_method(CONTRACT_BYTECODE, &method, input).into_boxed_str();
+    let len = output.len();
+    let ptr = Box::into_raw(output) as *mut u8;
+
+    unsafe {
+        let ret = core::ptr::addr_of_mut!(RET_AREA);
+        (*ret)[0] = ptr as usize;
+        (*ret)[1] = len;
+        ret as *mut u8
+    }
+}
+
+#[export_name = "cabi_post_run"]
+unsafe extern "C" fn export_post_run(ret: *mut u8) {
+    unsafe {
+        let [ptr, len] = *(ret as *const [usize; 2]);
+        drop(Box::from_raw(core::ptr::slice_from_raw_parts_mut(
+            ptr as *mut u8,
+            len,
+        )));
+    }
+}
+
+// ---------------------------------------------------------------------------
+// Python contract execution engine
+// ---------------------------------------------------------------------------
+
+fn run_method(bytecode: &[u8], method_name: &str, input: String) -> String {
+    let runner = MontyRun::load(bytecode).unwrap_or_else(|e| panic!("monty load error: {e}"));
+
+    let mut print = NoPrint;
+    let mut returned = String::new();
+
+    let mut progress = runner
+        .start(
+            vec![MontyObject::String(method_name.to_string())],
+            NoLimitTracker,
+            &mut print,
+        )
+        .unwrap_or_else(|e| panic!("{}", python_error(&e.to_string())));
+
+    loop {
+        match progress {
+            RunProgress::FunctionCall {
+                function_name,
+                args,
+                state,
+                ..
+            } => {
+                let result = match function_name.as_str() {
+                    "input" => MontyObject::String(input.clone()),
+                    "value_return" => {
+                        returned = match args.first() {
+                            Some(MontyObject::String(s)) => s.clone(),
+                            Some(MontyObject::Bytes(b)) => String::from_utf8_lossy(b).into_owned(),
+                            Some(other) => format!("{other:?}"),
+                            None => String::new(),
+                        };
+                        MontyObject::None
+                    }
+                    "log" => MontyObject::None,
+                    other => {
+                        panic!("{other} is a NEAR host function and is unavailable in a component")
+                    }
+                };
+                progress = state
+                    .run(result, &mut print)
+                    .unwrap_or_else(|e| panic!("{}", python_error(&e.to_string())));
+            }
+            RunProgress::Complete(_) => break,
+            RunProgress::OsCall { .. } => panic!("OS calls are not permitted in contracts"),
+            RunProgress::ResolveFutures(_) => {
+                panic!("async futures are not supported in contracts")
+            }
+        }
+    }
+
+    returned
+}
+
+/// `file.py:LINE in function: error` for a Python error, from the innermost
+/// frame of its traceback, mapped to the contract's files by `source_lines`.
+/// Just the error if the traceback has no frame in the program.
+fn python_error(error: &str) -> String {
+    const FRAME: &str = "\"contract.py\", line ";
+    let summary = error
+        .lines()
+        .rev()
+        .find(|line| !line.trim().is_empty())
+        .unwrap_or(error)
+        .trim();
+    let Some(at) = error.rfind(FRAME) else {
+        return summary.to_string();
+    };
+    let rest = &error[at + FRAME.len()..];
+    let digits = rest
+        .find(|c: char| !c.is_ascii_digit())
+        .unwrap_or(rest.len());
+    let Ok(line) = rest[..digits].parse::<u32>() else {
+        return summary.to_string();
+    };
+    let segment = source_lines().filter(|(start, _, _)| *start < line).last();
+    let location = match segment {
+        Some((start, lines, file)) if line - start <= lines => format!("{file}:{}", line - start),
+        // The dispatcher and helpers the compiler generates
+        _ => format!("<generated>:{line}"),
+    };
+    match rest[digits..].strip_prefix(", in ") {
+        Some(function) => {
+            let function = function.lines().next().unwrap_or_default().trim();
+            format!("{location} in {function}: {summary}")
+        }
+        None => format!("{location}: {summary}"),
+    }
+}
+
+// ---------------------------------------------------------------------------
+// Pre-compiled bytecode (generated by monty-near-cli)
+// ---------------------------------------------------------------------------
+// @MONTY_BYTECODE_STATICS
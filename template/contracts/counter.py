@@ -0,0 +1,47 @@
+# Counter — the smallest useful stateful contract.
+#
+# Methods (raw string input, no JSON):
+#   increment()        add 1, return the new value
+#   decrement()        subtract 1 (not below zero), return the new value
+#   get()              return the current value
+#   reset()            set back to 0; only the contract account may call it
+
+
+def increment():
+    count = _load() + 1
+    _store(count)
+    log("count is now " + str(count))
+    value_return(str(count))
+
+
+def decrement():
+    count = _load()
+    if count == 0:
+        raise ValueError("counter is already zero")
+    count = count - 1
+    _store(count)
+    log("count is now " + str(count))
+    value_return(str(count))
+
+
+@view
+def get():
+    value_return(str(_load()))
+
+
+def reset():
+    if predecessor_account_id() != current_account_id():
+        raise ValueError("only " + current_account_id() + " can reset the counter")
+    _store(0)
+    value_return("0")
+
+
+def _load():
+    count = storage_read("count")
+    if count is None:
+        return 0
+    return int(count)
+
+
+def _store(count):
+    storage_write("count", str(count))
@@ -0,0 +1,110 @@
+# Minimal DAO: a member list, text proposals, and majority voting.
+#
+# The contract account manages membership. A proposal passes once more than
+# half of the members vote yes, and fails once more than half vote no.
+#
+# Methods (raw string input, no JSON):
+#   add_member("<account_id>")           only the contract account
+#   remove_member("<account_id>")        only the contract account
+#   propose("<description>")             members only; returns the proposal id
+#   vote("<proposal_id>:yes" | ":no")    members only; one vote per member
+#   get_proposal("<proposal_id>")        "<status> yes=<n> no=<n> <description>"
+
+
+def add_member():
+    _require_self()
+    account = input()
+    if not storage_has_key("m:" + account):
+        storage_write("m:" + account, "1")
+        storage_write("members", str(_count("members") + 1))
+    value_return(str(_count("members")))
+
+
+def remove_member():
+    _require_self()
+    account = input()
+    if storage_has_key("m:" + account):
+        storage_remove("m:" + account)
+        storage_write("members", str(_count("members") - 1))
+    value_return(str(_count("members")))
+
+
+def propose():
+    _require_member()
+    description = input()
+    if description == "":
+        raise ValueError("proposal needs a description")
+    proposal_id = str(_count("proposals"))
+    storage_write("proposals", str(int(proposal_id) + 1))
+    p = "p:" + proposal_id
+    storage_write(p + ":desc", description)
+    storage_write(p + ":status", "open")
+    storage_write(p + ":yes", "0")
+    storage_write(p + ":no", "0")
+    log("proposal " + proposal_id + " by " + predecessor_account_id())
+    value_return(proposal_id)
+
+
+def vote():
+    voter = _require_member()
+    data = input()
+    pos = data.find(":")
+    if pos < 0:
+        raise ValueError("expected <proposal_id>:yes or <proposal_id>:no")
+    p = "p:" + data[0:pos]
+    choice = data[pos + 1 :]
+    if choice != "yes" and choice != "no":
+        raise ValueError("vote must be yes or no")
+
+    status = storage_read(p + ":status")
+    if status is None:
+        raise ValueError("no such proposal")
+    if status != "open":
+        raise ValueError("proposal is already " + status)
+    if storage_has_key(p + ":voted:" + voter):
+        raise ValueError(voter + " has already voted")
+
+    storage_write(p + ":voted:" + voter, choice)
+    votes = int(storage_read(p + ":" + choice)) + 1
+    storage_write(p + ":" + choice, str(votes))
+    if votes * 2 > _count("members"):
+        status = "passed" if choice == "yes" else "rejected"
+        storage_write(p + ":status", status)
+    value_return(status)
+
+
+@view
+def get_proposal():
+    p = "p:" + input()
+    status = storage_read(p + ":status")
+    if status is None:
+        value_return("")
+        return
+    value_return(
+        status
+        + " yes="
+        + storage_read(p + ":yes")
+        + " no="
+        + storage_read(p + ":no")
+        + " "
+        + storage_read(p + ":desc")
+    )
+
+
+def _require_self():
+    if predecessor_account_id() != current_account_id():
+        raise ValueError("only " + current_account_id() + " can manage members")
+
+
+def _require_member():
+    account = predecessor_account_id()
+    if not storage_has_key("m:" + account):
+        raise ValueError(account + " is not a member")
+    return account
+
+
+def _count(key):
+    value = storage_read(key)
+    if value is None:
+        return 0
+    return int(value)
@@ -0,0 +1,79 @@
+# Fungible token following the NEP-141 transfer rules.
+#
+# Methods take NEP-141's JSON arguments, with amounts as U128 decimal
+# strings, so wallets can call them; the balance bookkeeping,
+# one-yoctoNEAR requirement, and NEP-297 event logs follow the standard.
+# Storage management (NEP-145) and ft_transfer_call are left out.
+#
+# Methods:
+#   new(owner_id, total_supply)              mint the supply to owner_id; once
+#   ft_transfer(receiver_id, amount, memo?)  attach exactly 1 yoctoNEAR
+#   ft_balance_of(account_id)
+#   ft_total_supply()
+
+
+@init
+def new(owner_id: str, total_supply: U128):
+    _check_positive(total_supply)
+    storage_write("total_supply", str(total_supply))
+    _set_balance(owner_id, total_supply)
+    _emit("ft_mint", {"owner_id": owner_id, "amount": str(total_supply)})
+
+
+@one_yocto
+def ft_transfer(receiver_id: str, amount: U128, memo: str | None = None):
+    _check_positive(amount)
+    sender = predecessor_account_id()
+    if receiver_id == sender:
+        raise ValueError("sender and receiver must differ")
+
+    balance = _balance(sender)
+    if balance < amount:
+        raise ValueError("insufficient balance")
+    _set_balance(sender, balance - amount)
+    _set_balance(receiver_id, _balance(receiver_id) + amount)
+
+    event = {
+        "old_owner_id": sender,
+        "new_owner_id": receiver_id,
+        "amount": str(amount),
+    }
+    if memo is not None:
+        event["memo"] = memo
+    _emit("ft_transfer", event)
+
+
+@view
+def ft_balance_of(account_id: str) -> U128:
+    return _balance(account_id)
+
+
+@view
+def ft_total_supply() -> U128:
+    supply = storage_read("total_supply")
+    if supply is None:
+        return 0
+    return int(supply)
+
+
+def _check_positive(amount):
+    if amount <= 0:
+        raise ValueError("amount must be positive")
+
+
+def _balance(account_id):
+    balance = storage_read("b:" + account_id)
+    if balance is None:
+        return 0
+    return int(balance)
+
+
+def _set_balance(account_id, amount):
+    if amount == 0:
+        storage_remove("b:" + account_id)
+    else:
+        storage_write("b:" + account_id, str(amount))
+
+
+def _emit(event, data):
+    emit_event("nep141", "1.0.0", event, [data])
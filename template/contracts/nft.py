@@ -0,0 +1,76 @@
+# Non-fungible token following the NEP-171 ownership rules.
+#
+# Methods take NEP-171's JSON arguments, so marketplaces can call them;
+# ownership checks, the one-yoctoNEAR requirement, and NEP-297 event logs
+# follow the standard. Approvals (NEP-178), metadata (NEP-177), and
+# nft_transfer_call are left out, so approval_id must be omitted.
+#
+# Methods:
+#   nft_mint(token_id, receiver_id)   only the contract account may mint
+#   nft_transfer(receiver_id, token_id, approval_id?, memo?)
+#                                     attach exactly 1 yoctoNEAR
+#   nft_token(token_id)               {"token_id", "owner_id"}, or null
+#   nft_total_supply()
+
+
+def nft_mint(token_id: str, receiver_id: str):
+    if predecessor_account_id() != current_account_id():
+        raise ValueError("only " + current_account_id() + " can mint")
+    if storage_has_key("t:" + token_id):
+        raise ValueError("token " + token_id + " already exists")
+    storage_write("t:" + token_id, receiver_id)
+    storage_write("supply", str(_supply() + 1))
+    _emit("nft_mint", {"owner_id": receiver_id, "token_ids": [token_id]})
+
+
+@one_yocto
+def nft_transfer(
+    receiver_id: str,
+    token_id: str,
+    approval_id: int | None = None,
+    memo: str | None = None,
+):
+    if approval_id is not None:
+        raise ValueError("approvals are not supported")
+    owner = storage_read("t:" + token_id)
+    if owner is None:
+        raise ValueError("token " + token_id + " does not exist")
+    sender = predecessor_account_id()
+    if owner != sender:
+        raise ValueError("only the owner can transfer token " + token_id)
+    if receiver_id == owner:
+        raise ValueError("token is already owned by " + receiver_id)
+    storage_write("t:" + token_id, receiver_id)
+
+    event = {
+        "old_owner_id": owner,
+        "new_owner_id": receiver_id,
+        "token_ids": [token_id],
+    }
+    if memo is not None:
+        event["memo"] = memo
+    _emit("nft_transfer", event)
+
+
+@view
+def nft_token(token_id: str) -> dict | None:
+    owner = storage_read("t:" + token_id)
+    if owner is None:
+        return None
+    return {"token_id": token_id, "owner_id": owner}
+
+
+@view
+def nft_total_supply() -> U128:
+    return _supply()
+
+
+def _supply():
+    supply = storage_read("supply")
+    if supply is None:
+        return 0
+    return int(supply)
+
+
+def _emit(event, data):
+    emit_event("nep171", "1.0.0", event, [data])
@@ -0,0 +1,34 @@
+# @CONTRACT_NAME — a NEAR contract written in Python.
+#
+# Every top-level function not starting with `_` is an exported method
+# (or, if the module sets `__all__`, exactly the ones it lists).
+# NEAR host functions (input, value_return, storage_read, log, ...) are
+# builtins. Build with:
+#
+#     monty-near-cli build contract.py
+
+__contract_name__ = "@CONTRACT_NAME"
+
+
+@view
+def hello():
+    value_return("Hello from @CONTRACT_NAME!")
+
+
+def increment():
+    count = _get_count() + 1
+    storage_write("count", str(count))
+    log("count is now " + str(count))
+    value_return(str(count))
+
+
+@view
+def get_count():
+    value_return(str(_get_count()))
+
+
+def _get_count():
+    count = storage_read("count")
+    if count is None:
+        return 0
+    return int(count)
@@ -0,0 +1,53 @@
+// Run with: cd tests && bun install && bun test
+//
+// Builds the contract with monty-near-cli (which must be on PATH), deploys
+// it to a local sandbox, and calls its methods.
+
+import { Near } from "near-kit"
+import { Sandbox } from "near-kit/sandbox"
+import { beforeAll, afterAll, test, expect } from "bun:test"
+import { readFileSync } from "fs"
+import { execSync } from "child_process"
+import { resolve } from "path"
+
+const ROOT = resolve(import.meta.dir, "..")
+const WASM_OUT = resolve(ROOT, "target/test.wasm")
+
+let sandbox: Sandbox
+let near: Near
+let contractId: string
+
+const decode = (outcome: any): string => {
+  const b64 = outcome?.status?.SuccessValue
+  return b64 ? Buffer.from(b64, "base64").toString("utf-8") : ""
+}
+
+beforeAll(async () => {
+  execSync(`monty-near-cli build contract.py -o ${WASM_OUT}`, {
+    cwd: ROOT,
+    stdio: "inherit",
+  })
+
+  sandbox = await Sandbox.start({ version: "2.10.6" })
+  contractId = sandbox.rootAccount.id
+  near = new Near({ network: sandbox, defaultSignerId: contractId })
+
+  await near
+    .transaction(contractId)
+    .deployContract(contractId, readFileSync(WASM_OUT))
+    .send()
+}, 120_000)
+
+afterAll(async () => {
+  if (sandbox) await sandbox.stop()
+})
+
+test("hello", async () => {
+  expect(await near.view(contractId, "hello")).toBe("Hello from @CONTRACT_NAME!")
+})
+
+test("increment persists the count", async () => {
+  expect(decode(await near.call(contractId, "increment", new Uint8Array()))).toBe("1")
+  expect(decode(await near.call(contractId, "increment", new Uint8Array()))).toBe("2")
+  expect(await near.view(contractId, "get_count")).toBe("2")
+})
@@ -0,0 +1,15 @@
+# Run with: monty-near-cli test contract.py
+#
+# Each test_* function runs on the host against the contract deployed in a
+# local near-sandbox. Tests share one deployment and run in file order.
+
+
+def test_hello():
+    assert view("hello") == "Hello from @CONTRACT_NAME!"
+
+
+def test_increment():
+    assert call("increment") == "1"
+    assert logs() == ["count is now 1"]
+    assert call("increment") == "2"
+    assert view("get_count") == "2"
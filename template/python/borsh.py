@@ -0,0 +1,150 @@
+# Borsh for `@borsh_io` methods — added to the program by monty-near-cli when
+# a method uses it. The compiler turns each annotation into a schema: a type
+# name such as "u64" or "str", or a list such as ["list", "u8"] or
+# ["option", "str"]. Bad data can only abort the call, so every error raises
+# ValueError.
+
+# The fixed-width integer types annotations use; all of them are `int`
+u8 = int
+u16 = int
+u32 = int
+u64 = int
+u128 = int
+i8 = int
+i16 = int
+i32 = int
+i64 = int
+i128 = int
+U128 = int
+Balance = int
+
+__BORSH_INTS__ = {
+    "u8": (1, False),
+    "u16": (2, False),
+    "u32": (4, False),
+    "u64": (8, False),
+    "u128": (16, False),
+    "i8": (1, True),
+    "i16": (2, True),
+    "i32": (4, True),
+    "i64": (8, True),
+    "i128": (16, True),
+}
+
+
+def __borsh_error__(message):
+    raise ValueError("Failed to deserialize input from Borsh: " + message)
+
+
+def __borsh_take__(data, i, n):
+    if i + n > len(data):
+        __borsh_error__("unexpected end of input")
+    return data[i : i + n], i + n
+
+
+def __borsh_read__(data, i, schema):
+    if isinstance(schema, str):
+        if schema in __BORSH_INTS__:
+            size, signed = __BORSH_INTS__[schema]
+            raw, i = __borsh_take__(data, i, size)
+            value = 0
+            for k in range(size):
+                value += raw[k] << (8 * k)
+            if signed and value >= 1 << (8 * size - 1):
+                value -= 1 << (8 * size)
+            return value, i
+        if schema == "bool":
+            raw, i = __borsh_take__(data, i, 1)
+            if raw[0] > 1:
+                __borsh_error__("invalid bool")
+            return raw[0] == 1, i
+        n, i = __borsh_read__(data, i, "u32")
+        raw, i = __borsh_take__(data, i, n)
+        if schema == "bytes":
+            return raw, i
+        return raw.decode(), i
+    kind = schema[0]
+    if kind == "option":
+        tag, i = __borsh_read__(data, i, "u8")
+        if tag == 0:
+            return None, i
+        if tag != 1:
+            __borsh_error__("invalid option tag")
+        return __borsh_read__(data, i, schema[1])
+    if kind == "tuple":
+        items = []
+        for item in schema[1:]:
+            value, i = __borsh_read__(data, i, item)
+            items.append(value)
+        return tuple(items), i
+    n, i = __borsh_read__(data, i, "u32")
+    if kind == "list":
+        items = []
+        for _ in range(n):
+            value, i = __borsh_read__(data, i, schema[1])
+            items.append(value)
+        return items, i
+    result = {}
+    for _ in range(n):
+        key, i = __borsh_read__(data, i, schema[1])
+        value, i = __borsh_read__(data, i, schema[2])
+        result[key] = value
+    return result, i
+
+
+def __borsh_write__(value, schema):
+    if isinstance(schema, str):
+        if schema in __BORSH_INTS__:
+            size, signed = __BORSH_INTS__[schema]
+            if isinstance(value, bool) or not isinstance(value, int):
+                raise ValueError("Failed to serialize as Borsh: expected an integer for " + schema)
+            bits = 8 * size
+            low = -(1 << (bits - 1)) if signed else 0
+            high = 1 << (bits - 1) if signed else 1 << bits
+            if value < low or value >= high:
+                raise ValueError("Failed to serialize as Borsh: " + str(value) + " doesn't fit in " + schema)
+            if value < 0:
+                value += 1 << bits
+            out = []
+            for _ in range(size):
+                out.append(value % 256)
+                value = value // 256
+            return bytes(out)
+        if schema == "bool":
+            return bytes([1 if value else 0])
+        if schema == "str":
+            value = value.encode()
+        return __borsh_write__(len(value), "u32") + value
+    kind = schema[0]
+    if kind == "option":
+        if value is None:
+            return bytes([0])
+        return bytes([1]) + __borsh_write__(value, schema[1])
+    if kind == "tuple":
+        out = b""
+        for k in range(len(schema) - 1):
+            out += __borsh_write__(value[k], schema[k + 1])
+        return out
+    out = __borsh_write__(len(value), "u32")
+    if kind == "list":
+        for item in value:
+            out += __borsh_write__(item, schema[1])
+        return out
+    # Borsh orders map entries by key
+    for key in sorted(value):
+        out += __borsh_write__(key, schema[1]) + __borsh_write__(value[key], schema[2])
+    return out
+
+
+def __borsh_args__(schemas):
+    data = input()
+    if isinstance(data, str):
+        data = data.encode()
+    values = []
+    i = 0
+    for schema in schemas:
+        value, i = __borsh_read__(data, i, schema)
+        values.append(value)
+    if i != len(data):
+        __borsh_error__("trailing bytes")
+    return values
@@ -0,0 +1,28 @@
+# Promise results for `@callback` methods — added to the program by
+# monty-near-cli when a method is a callback. Each result is read as JSON
+# into the parameter for it; a failed promise aborts the call, unless the
+# parameter's annotation allows `PromiseError`, which it's passed instead.
+
+# What a failed promise is passed as
+PromiseError = {"__promise_error__": True}
+
+
+def __promise_results__(names, failable, method):
+    count = promise_results_count()
+    if count != len(names):
+        raise ValueError(
+            "Callback " + method + " expects " + str(len(names)) + " promise results, got " + str(count)
+        )
+    # Failed promises are left out, so the type checks skip them
+    results = {}
+    for i in range(count):
+        data = promise_result(i)
+        if data is None:
+            if not failable[i]:
+                raise ValueError("Promise " + str(i) + " of callback " + method + " failed")
+        elif data == "":
+            # A promise that returns nothing
+            results[names[i]] = None
+        else:
+            results[names[i]] = __json_loads__(data)
+    return results
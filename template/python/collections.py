@@ -0,0 +1,158 @@
+# Storage-backed collections — added to the program by monty-near-cli when
+# the contract declares one. Monty has no classes, so the compiler gives each
+# `name = LookupMap()` (or `Vector()`, `UnorderedSet()`) the key prefix
+# "name:" and compiles `name.method(...)` to a call of the function below
+# with that prefix first. Keys and set members are strings; values are stored
+# as JSON, with the JSON helpers.
+
+
+def __collection_key__(kind, key):
+    if not isinstance(key, str):
+        raise ValueError(kind + " keys must be strings, not " + str(type(key)))
+    return key
+
+
+def __collection_read__(key):
+    data = storage_read(key)
+    if data is None:
+        return None
+    return __json_loads__(data)
+
+
+# LookupMap: each entry under the prefix and its key
+
+
+def __lookup_map_get__(prefix, key, default=None):
+    data = storage_read(prefix + __collection_key__("LookupMap", key))
+    if data is None:
+        return default
+    return __json_loads__(data)
+
+
+def __lookup_map_contains_key__(prefix, key):
+    return storage_has_key(prefix + __collection_key__("LookupMap", key))
+
+
+def __lookup_map_insert__(prefix, key, value):
+    key = prefix + __collection_key__("LookupMap", key)
+    old = __collection_read__(key)
+    storage_write(key, __json_dumps__(value))
+    return old
+
+
+def __lookup_map_remove__(prefix, key):
+    key = prefix + __collection_key__("LookupMap", key)
+    old = __collection_read__(key)
+    storage_remove(key)
+    return old
+
+
+# Vector: the length under the prefix and "len", each item under the prefix
+# and its index
+
+
+def __vector_len__(prefix):
+    data = storage_read(prefix + "len")
+    if data is None:
+        return 0
+    return int(data)
+
+
+def __vector_is_empty__(prefix):
+    return __vector_len__(prefix) == 0
+
+
+def __vector_index__(prefix, index):
+    n = __vector_len__(prefix)
+    if isinstance(index, bool) or not isinstance(index, int) or index < 0 or index >= n:
+        raise ValueError("Vector index " + str(index) + " out of range for length " + str(n))
+    return prefix + str(index)
+
+
+def __vector_get__(prefix, index):
+    if isinstance(index, int) and 0 <= index < __vector_len__(prefix):
+        return __collection_read__(prefix + str(index))
+    return None
+
+
+def __vector_set__(prefix, index, value):
+    key = __vector_index__(prefix, index)
+    old = __collection_read__(key)
+    storage_write(key, __json_dumps__(value))
+    return old
+
+
+def __vector_push__(prefix, value):
+    n = __vector_len__(prefix)
+    storage_write(prefix + str(n), __json_dumps__(value))
+    storage_write(prefix + "len", str(n + 1))
+
+
+def __vector_pop__(prefix):
+    n = __vector_len__(prefix)
+    if n == 0:
+        return None
+    key = prefix + str(n - 1)
+    value = __collection_read__(key)
+    storage_remove(key)
+    storage_write(prefix + "len", str(n - 1))
+    return value
+
+
+def __vector_swap_remove__(prefix, index):
+    key = __vector_index__(prefix, index)
+    value = __collection_read__(key)
+    last = __vector_pop__(prefix)
+    if key != prefix + str(__vector_len__(prefix)):
+        storage_write(key, __json_dumps__(last))
+    return value
+
+
+def __vector_to_list__(prefix):
+    items = []
+    for i in range(__vector_len__(prefix)):
+        items.append(__collection_read__(prefix + str(i)))
+    return items
+
+
+# UnorderedSet: the members as a Vector under the prefix and "v:", and each
+# member's index under the prefix, "i:" and the member, so removal is a swap
+# with the last member
+
+
+def __unordered_set_len__(prefix):
+    return __vector_len__(prefix + "v:")
+
+
+def __unordered_set_is_empty__(prefix):
+    return __vector_len__(prefix + "v:") == 0
+
+
+def __unordered_set_contains__(prefix, member):
+    return storage_has_key(prefix + "i:" + __collection_key__("UnorderedSet", member))
+
+
+def __unordered_set_insert__(prefix, member):
+    key = prefix + "i:" + __collection_key__("UnorderedSet", member)
+    if storage_has_key(key):
+        return False
+    storage_write(key, str(__vector_len__(prefix + "v:")))
+    __vector_push__(prefix + "v:", member)
+    return True
+
+
+def __unordered_set_remove__(prefix, member):
+    key = prefix + "i:" + __collection_key__("UnorderedSet", member)
+    index = storage_read(key)
+    if index is None:
+        return False
+    storage_remove(key)
+    last = __vector_pop__(prefix + "v:")
+    if last != member:
+        storage_write(prefix + "v:" + index, __json_dumps__(last))
+        storage_write(prefix + "i:" + last, index)
+    return True
+
+
+def __unordered_set_to_list__(prefix):
+    return __vector_to_list__(prefix + "v:")
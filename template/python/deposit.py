@@ -0,0 +1,12 @@
+# Deposit checks — added to the program by monty-near-cli when the contract
+# calls them. attached_deposit() is a decimal string of yoctoNEAR.
+
+
+def assert_one_yocto():
+    if attached_deposit() != "1":
+        raise ValueError("Requires attached deposit of exactly 1 yoctoNEAR")
+
+
+def assert_min_deposit(amount):
+    if int(attached_deposit()) < int(amount):
+        raise ValueError("Requires attached deposit of at least " + str(amount) + " yoctoNEAR")
@@ -0,0 +1,15 @@
+# NEP-297 events — added to the program by monty-near-cli when the contract
+# calls emit_event, along with the JSON helpers it formats `data` with.
+# Indexers match the log line exactly: the EVENT_JSON: prefix, then one JSON
+# object with the fields in the standard's order and no whitespace.
+
+
+def emit_event(standard, version, event, data=None):
+    fields = []
+    for name, value in (("standard", standard), ("version", version), ("event", event)):
+        if not isinstance(value, str) or value == "":
+            raise ValueError("emit_event: " + name + " must be a non-empty string")
+        fields.append('"' + name + '":' + __json_quote__(value))
+    if data is not None:
+        fields.append('"data":' + __json_dumps__(data))
+    log("EVENT_JSON:{" + ",".join(fields) + "}")
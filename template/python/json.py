@@ -0,0 +1,285 @@
+# JSON for method arguments and return values — added to the program by
+# monty-near-cli when an exported method takes parameters or returns a
+# value, for the dispatcher to read them from `input()` and pass results to
+# `value_return`. Monty has no `json` module, and bad data can only abort the
+# call, so every error raises ValueError.
+
+# Annotations for integers that travel as decimal strings, as near-sdk's U128
+U128 = int
+Balance = int
+
+__JSON_ESCAPES__ = {
+    '"': '"',
+    "\\": "\\",
+    "/": "/",
+    "b": "\b",
+    "f": "\f",
+    "n": "\n",
+    "r": "\r",
+    "t": "\t",
+}
+
+
+def __json_error__(message):
+    raise ValueError("Failed to deserialize input from JSON: " + message)
+
+
+def __json_skip__(text, i):
+    while i < len(text) and text[i] in " \t\n\r":
+        i += 1
+    return i
+
+
+def __json_hex__(text, i):
+    digits = text[i : i + 4]
+    if len(digits) != 4:
+        __json_error__("invalid \\u escape")
+    return int(digits, 16)
+
+
+def __json_string__(text, i):
+    out = ""
+    i += 1
+    start = i
+    while i < len(text):
+        c = text[i]
+        if c == '"':
+            return out + text[start:i], i + 1
+        if c == "\\":
+            out += text[start:i]
+            escape = text[i + 1 : i + 2]
+            if escape == "u":
+                code = __json_hex__(text, i + 2)
+                i += 6
+                # A surrogate pair encodes one character past U+FFFF
+                if 0xD800 <= code < 0xDC00 and text[i : i + 2] == "\\u":
+                    low = __json_hex__(text, i + 2)
+                    if 0xDC00 <= low < 0xE000:
+                        code = 0x10000 + (code - 0xD800) * 0x400 + (low - 0xDC00)
+                        i += 6
+                out += chr(code)
+            elif escape in __JSON_ESCAPES__:
+                out += __JSON_ESCAPES__[escape]
+                i += 2
+            else:
+                __json_error__("invalid escape in string")
+            start = i
+        else:
+            i += 1
+    __json_error__("unterminated string")
+
+
+def __json_number__(text, i):
+    start = i
+    while i < len(text) and text[i] in "+-0123456789.eE":
+        i += 1
+    number = text[start:i]
+    if number == "":
+        __json_error__("unexpected character at position " + str(start))
+    if "." in number or "e" in number or "E" in number:
+        return float(number), i
+    return int(number), i
+
+
+def __json_value__(text, i):
+    i = __json_skip__(text, i)
+    if i >= len(text):
+        __json_error__("unexpected end of input")
+    c = text[i]
+    if c == "{":
+        result = {}
+        i = __json_skip__(text, i + 1)
+        if text[i : i + 1] == "}":
+            return result, i + 1
+        while True:
+            i = __json_skip__(text, i)
+            if text[i : i + 1] != '"':
+                __json_error__("expected a string key at position " + str(i))
+            key, i = __json_string__(text, i)
+            i = __json_skip__(text, i)
+            if text[i : i + 1] != ":":
+                __json_error__("expected ':' at position " + str(i))
+            value, i = __json_value__(text, i + 1)
+            result[key] = value
+            i = __json_skip__(text, i)
+            if text[i : i + 1] == "}":
+                return result, i + 1
+            if text[i : i + 1] != ",":
+                __json_error__("expected ',' or '}' at position " + str(i))
+            i += 1
+    if c == "[":
+        result = []
+        i = __json_skip__(text, i + 1)
+        if text[i : i + 1] == "]":
+            return result, i + 1
+        while True:
+            value, i = __json_value__(text, i)
+            result.append(value)
+            i = __json_skip__(text, i)
+            if text[i : i + 1] == "]":
+                return result, i + 1
+            if text[i : i + 1] != ",":
+                __json_error__("expected ',' or ']' at position " + str(i))
+            i += 1
+    if c == '"':
+        return __json_string__(text, i)
+    if text[i : i + 4] == "true":
+        return True, i + 4
+    if text[i : i + 5] == "false":
+        return False, i + 5
+    if text[i : i + 4] == "null":
+        return None, i + 4
+    return __json_number__(text, i)
+
+
+def __json_loads__(text):
+    value, i = __json_value__(text, 0)
+    i = __json_skip__(text, i)
+    if i != len(text):
+        __json_error__("trailing characters at position " + str(i))
+    return value
+
+
+def __json_args__():
+    text = input()
+    # Calls without arguments often send nothing at all
+    if __json_skip__(text, 0) == len(text):
+        return {}
+    args = __json_loads__(text)
+    if not isinstance(args, dict):
+        __json_error__("expected an object of arguments")
+    return args
+
+
+def __json_arg__(args, name):
+    if name not in args:
+        __json_error__("missing field `" + name + "`")
+    return args[name]
+
+
+__JSON_TYPES__ = {
+    "int": "an integer",
+    "float": "a number",
+    "str": "a string",
+    "bool": "a boolean",
+    "list": "an array",
+    "dict": "an object",
+}
+
+
+def __json_check__(args, name, kind, nullable):
+    if name not in args:
+        return
+    value = args[name]
+    if value is None and nullable:
+        return
+    # True is also an int, but JSON keeps booleans and numbers apart
+    if kind == "int":
+        valid = isinstance(value, int) and not isinstance(value, bool)
+    elif kind == "float":
+        valid = (isinstance(value, int) or isinstance(value, float)) and not isinstance(value, bool)
+    elif kind == "str":
+        valid = isinstance(value, str)
+    elif kind == "bool":
+        valid = isinstance(value, bool)
+    elif kind == "list":
+        valid = isinstance(value, list)
+    else:
+        valid = isinstance(value, dict)
+    if not valid:
+        expected = __JSON_TYPES__[kind]
+        if nullable:
+            expected += " or null"
+        raise ValueError(name + " must be " + expected)
+
+
+def __json_from_u128__(value, schema, name):
+    if value is None:
+        return None
+    if schema == "u128":
+        if not isinstance(value, str) or value == "" or not value.isdigit():
+            raise ValueError(name + " must be a decimal string")
+        number = int(value)
+        if number >= 1 << 128:
+            raise ValueError(name + " doesn't fit in a u128")
+        return number
+    if schema[0] == "list":
+        if not isinstance(value, list):
+            raise ValueError(name + " must be an array")
+        items = []
+        for item in value:
+            items.append(__json_from_u128__(item, schema[1], name))
+        return items
+    if not isinstance(value, dict):
+        raise ValueError(name + " must be an object")
+    result = {}
+    for key in value:
+        result[key] = __json_from_u128__(value[key], schema[1], name)
+    return result
+
+
+def __json_to_u128__(value, schema):
+    if value is None:
+        return None
+    if schema == "u128":
+        if isinstance(value, bool) or not isinstance(value, int) or value < 0 or value >= 1 << 128:
+            raise ValueError("Failed to serialize the return value as JSON: expected a u128, got " + str(value))
+        return str(value)
+    if schema[0] == "list":
+        items = []
+        for item in value:
+            items.append(__json_to_u128__(item, schema[1]))
+        return items
+    result = {}
+    for key in value:
+        result[key] = __json_to_u128__(value[key], schema[1])
+    return result
+
+
+def __json_quote__(text):
+    out = '"'
+    for c in text:
+        if c == '"':
+            out += '\\"'
+        elif c == "\\":
+            out += "\\\\"
+        elif c == "\n":
+            out += "\\n"
+        elif c == "\r":
+            out += "\\r"
+        elif c == "\t":
+            out += "\\t"
+        elif ord(c) < 0x20:
+            digits = "0123456789abcdef"
+            out += "\\u00" + digits[ord(c) // 16] + digits[ord(c) % 16]
+        else:
+            out += c
+    return out + '"'
+
+
+def __json_dumps__(value):
+    if value is None:
+        return "null"
+    # bool first: True is also an int
+    if isinstance(value, bool):
+        return "true" if value else "false"
+    if isinstance(value, int):
+        return str(value)
+    if isinstance(value, float):
+        if value != value or value in (float("inf"), float("-inf")):
+            raise ValueError("Failed to serialize the return value as JSON: " + str(value))
+        return str(value)
+    if isinstance(value, str):
+        return __json_quote__(value)
+    if isinstance(value, list) or isinstance(value, tuple):
+        items = []
+        for item in value:
+            items.append(__json_dumps__(item))
+        return "[" + ",".join(items) + "]"
+    if isinstance(value, dict):
+        items = []
+        for key in value:
+            name = key if isinstance(key, str) else __json_dumps__(key)
+            items.append(__json_quote__(name) + ":" + __json_dumps__(value[key]))
+        return "{" + ",".join(items) + "}"
+    raise ValueError("Failed to serialize the return value as JSON: unsupported " + str(type(value)))
@@ -0,0 +1,115 @@
+# `base64` for contracts — bundled by monty-near-cli in place of the standard
+# library module when a contract imports `base64` and has no base64.py of its
+# own. Encoders take bytes and return bytes; decoders take str or bytes.
+# Unlike CPython, decoding rejects characters outside the alphabet rather
+# than skipping them, and errors raise ValueError.
+
+__BASE64_ALPHABET__ = "ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/"
+__URLSAFE_ALPHABET__ = "ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789-_"
+__B16_ALPHABET__ = "0123456789ABCDEF"
+
+
+def __base64_bytes__(s, name):
+    if not isinstance(s, bytes):
+        raise ValueError(name + "() argument must be bytes, not " + str(type(s)))
+    return s
+
+
+def __base64_text__(s):
+    if isinstance(s, bytes):
+        return s.decode()
+    if not isinstance(s, str):
+        raise ValueError("argument should be str or bytes, not " + str(type(s)))
+    return s
+
+
+def __base64_encode__(s, alphabet):
+    out = ""
+    for i in range(0, len(s), 3):
+        n = s[i] << 16
+        if i + 1 < len(s):
+            n += s[i + 1] << 8
+        if i + 2 < len(s):
+            n += s[i + 2]
+        out += alphabet[(n >> 18) % 64] + alphabet[(n >> 12) % 64]
+        out += alphabet[(n >> 6) % 64] if i + 1 < len(s) else "="
+        out += alphabet[n % 64] if i + 2 < len(s) else "="
+    return out.encode()
+
+
+def __base64_digit__(c, alphabet):
+    if "A" <= c and c <= "Z":
+        return ord(c) - ord("A")
+    if "a" <= c and c <= "z":
+        return ord(c) - ord("a") + 26
+    if "0" <= c and c <= "9":
+        return ord(c) - ord("0") + 52
+    if c == alphabet[62]:
+        return 62
+    if c == alphabet[63]:
+        return 63
+    raise ValueError("Invalid base64 character " + repr(c))
+
+
+def __base64_decode__(s, alphabet):
+    if len(s) % 4 != 0:
+        raise ValueError("Incorrect padding")
+    out = []
+    for i in range(0, len(s), 4):
+        n = 0
+        padding = 0
+        for c in s[i : i + 4]:
+            if c == "=":
+                padding += 1
+                n = n << 6
+            elif padding > 0:
+                raise ValueError("Excess data after padding")
+            else:
+                n = (n << 6) + __base64_digit__(c, alphabet)
+        if padding > 2 or (padding > 0 and i + 4 != len(s)):
+            raise ValueError("Incorrect padding")
+        out.append((n >> 16) % 256)
+        if padding < 2:
+            out.append((n >> 8) % 256)
+        if padding < 1:
+            out.append(n % 256)
+    return bytes(out)
+
+
+def b64encode(s):
+    return __base64_encode__(__base64_bytes__(s, "b64encode"), __BASE64_ALPHABET__)
+
+
+def b64decode(s):
+    return __base64_decode__(__base64_text__(s), __BASE64_ALPHABET__)
+
+
+def urlsafe_b64encode(s):
+    return __base64_encode__(__base64_bytes__(s, "urlsafe_b64encode"), __URLSAFE_ALPHABET__)
+
+
+def urlsafe_b64decode(s):
+    return __base64_decode__(__base64_text__(s), __URLSAFE_ALPHABET__)
+
+
+def b16encode(s):
+    out = ""
+    for byte in __base64_bytes__(s, "b16encode"):
+        out += __B16_ALPHABET__[byte // 16] + __B16_ALPHABET__[byte % 16]
+    return out.encode()
+
+
+def b16decode(s, casefold=False):
+    s = __base64_text__(s)
+    if casefold:
+        s = s.upper()
+    if len(s) % 2 != 0:
+        raise ValueError("Odd-length string")
+    out = []
+    for i in range(0, len(s), 2):
+        high = __B16_ALPHABET__.find(s[i])
+        low = __B16_ALPHABET__.find(s[i + 1])
+        if high < 0 or low < 0:
+            raise ValueError("Non-base16 digit found")
+        out.append(high * 16 + low)
+    return bytes(out)
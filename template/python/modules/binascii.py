@@ -0,0 +1,35 @@
+# `binascii` for contracts — bundled by monty-near-cli in place of the
+# standard library module when a contract imports `binascii` and has no
+# binascii.py of its own. Only the hex conversions, which NEAR contracts use
+# for hashes and keys; errors raise ValueError.
+
+__HEX_DIGITS__ = "0123456789abcdef"
+
+
+def hexlify(data):
+    if not isinstance(data, bytes):
+        raise ValueError("hexlify() argument must be bytes, not " + str(type(data)))
+    out = ""
+    for byte in data:
+        out += __HEX_DIGITS__[byte // 16] + __HEX_DIGITS__[byte % 16]
+    return out.encode()
+
+
+def unhexlify(hexstr):
+    if isinstance(hexstr, bytes):
+        hexstr = hexstr.decode()
+    if len(hexstr) % 2 != 0:
+        raise ValueError("Odd-length string")
+    hexstr = hexstr.lower()
+    out = []
+    for i in range(0, len(hexstr), 2):
+        high = __HEX_DIGITS__.find(hexstr[i])
+        low = __HEX_DIGITS__.find(hexstr[i + 1])
+        if high < 0 or low < 0:
+            raise ValueError("Non-hexadecimal digit found")
+        out.append(high * 16 + low)
+    return bytes(out)
+
+
+b2a_hex = hexlify
+a2b_hex = unhexlify
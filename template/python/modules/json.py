@@ -0,0 +1,42 @@
+# `json` for contracts — bundled by monty-near-cli in place of the standard
+# library module, which Monty doesn't have, when a contract imports `json`
+# and has no json.py of its own. `loads` and `dumps` use the JSON helpers
+# the dispatcher does; errors raise ValueError, where CPython would raise
+# json.JSONDecodeError (a ValueError) or TypeError.
+
+
+def loads(s):
+    if isinstance(s, bytes):
+        s = s.decode()
+    if not isinstance(s, str):
+        raise ValueError("the JSON object must be str or bytes, not " + str(type(s)))
+    return __json_loads__(s)
+
+
+def dumps(obj, separators=None, sort_keys=False):
+    if separators is None:
+        separators = (", ", ": ")
+    return __json_module_dumps__(obj, separators[0], separators[1], sort_keys)
+
+
+def __json_module_dumps__(value, item_separator, key_separator, sort_keys):
+    if isinstance(value, list) or isinstance(value, tuple):
+        items = []
+        for item in value:
+            items.append(__json_module_dumps__(item, item_separator, key_separator, sort_keys))
+        return "[" + item_separator.join(items) + "]"
+    if isinstance(value, dict):
+        keys = sorted(value) if sort_keys else value
+        items = []
+        for key in keys:
+            name = key if isinstance(key, str) else __json_dumps__(key)
+            items.append(
+                __json_quote__(name)
+                + key_separator
+                + __json_module_dumps__(value[key], item_separator, key_separator, sort_keys)
+            )
+        return "{" + item_separator.join(items) + "}"
+    if value is None or isinstance(value, int) or isinstance(value, float) or isinstance(value, str):
+        # bool is an int too
+        return __json_dumps__(value)
+    raise ValueError("Object of type " + str(type(value)) + " is not JSON serializable")
@@ -0,0 +1,176 @@
+# Promises — added to the program by monty-near-cli when the contract calls
+# Promise(). Monty has no classes, so a promise is a dict and the compiler
+# turns `p.function_call(...)` into `__promise_function_call__(p, ...)`.
+# Like near-sdk's, promises are lazy: actions are collected, and every
+# promise is scheduled with the promise_batch_* host functions once the
+# method returns, or when `as_return()` makes one the method's result.
+
+__PROMISES__ = []
+
+
+def Promise(account_id):
+    promise = {
+        "__promise__": True,
+        "account_id": account_id,
+        "actions": [],
+        "after": None,
+        "joint": None,
+        "index": None,
+    }
+    __PROMISES__.append(promise)
+    return promise
+
+
+def __promise_action__(promise, action):
+    if promise["joint"] is not None:
+        raise ValueError("actions can't be added to a joint promise; add them to its parts")
+    if promise["index"] is not None:
+        raise ValueError("actions can't be added to a promise that has been scheduled")
+    promise["actions"].append(action)
+    return promise
+
+
+def __promise_create_account__(promise):
+    return __promise_action__(promise, ["create_account"])
+
+
+def __promise_deploy_contract__(promise, code):
+    return __promise_action__(promise, ["deploy_contract", code])
+
+
+def __promise_function_call__(promise, function_name, args=None, deposit=0, gas=None, gas_weight=1):
+    if args is None:
+        args = ""
+    elif not isinstance(args, str) and not isinstance(args, bytes):
+        args = __json_dumps__(args)
+    return __promise_action__(promise, ["function_call", function_name, args, deposit, gas, gas_weight])
+
+
+def __promise_transfer__(promise, amount):
+    return __promise_action__(promise, ["transfer", amount])
+
+
+def __promise_stake__(promise, amount, public_key):
+    return __promise_action__(promise, ["stake", amount, public_key])
+
+
+def __promise_add_full_access_key__(promise, public_key, nonce=0):
+    return __promise_action__(promise, ["add_full_access_key", public_key, nonce])
+
+
+def __promise_add_access_key__(promise, public_key, allowance, receiver_id, function_names, nonce=0):
+    if not isinstance(function_names, str):
+        function_names = ",".join(function_names)
+    return __promise_action__(
+        promise, ["add_access_key", public_key, nonce, allowance, receiver_id, function_names]
+    )
+
+
+def __promise_delete_key__(promise, public_key):
+    return __promise_action__(promise, ["delete_key", public_key])
+
+
+def __promise_delete_account__(promise, beneficiary_id):
+    return __promise_action__(promise, ["delete_account", beneficiary_id])
+
+
+def __promise_deploy_global_contract__(promise, code):
+    return __promise_action__(promise, ["deploy_global_contract", code])
+
+
+def __promise_deploy_global_contract_by_account_id__(promise, code):
+    return __promise_action__(promise, ["deploy_global_contract_by_account_id", code])
+
+
+def __promise_use_global_contract__(promise, code_hash):
+    return __promise_action__(promise, ["use_global_contract", code_hash])
+
+
+def __promise_use_global_contract_by_account_id__(promise, account_id):
+    return __promise_action__(promise, ["use_global_contract_by_account_id", account_id])
+
+
+def __promise_then__(promise, other):
+    if other["joint"] is not None:
+        raise ValueError("then() takes a promise on one account, not a joint promise")
+    if other["after"] is not None:
+        raise ValueError("then() takes a promise that isn't already waiting on another")
+    other["after"] = promise
+    return other
+
+
+def __promise_and___(promise, other):
+    return {
+        "__promise__": True,
+        "account_id": None,
+        "actions": [],
+        "after": None,
+        "joint": [promise, other],
+        "index": None,
+    }
+
+
+def __promise_schedule__(promise):
+    if promise["index"] is not None:
+        return promise["index"]
+    joint = promise["joint"]
+    if joint is not None:
+        index = promise_and(__promise_schedule__(joint[0]), __promise_schedule__(joint[1]))
+    elif promise["after"] is not None:
+        index = promise_batch_then(__promise_schedule__(promise["after"]), promise["account_id"])
+    else:
+        index = promise_batch_create(promise["account_id"])
+    promise["index"] = index
+    for action in promise["actions"]:
+        kind = action[0]
+        if kind == "function_call":
+            if action[4] is None:
+                promise_batch_action_function_call_weight(
+                    index, action[1], action[2], action[3], 0, action[5]
+                )
+            else:
+                promise_batch_action_function_call(index, action[1], action[2], action[3], action[4])
+        elif kind == "create_account":
+            promise_batch_action_create_account(index)
+        elif kind == "deploy_contract":
+            promise_batch_action_deploy_contract(index, action[1])
+        elif kind == "transfer":
+            promise_batch_action_transfer(index, action[1])
+        elif kind == "stake":
+            promise_batch_action_stake(index, action[1], action[2])
+        elif kind == "add_full_access_key":
+            promise_batch_action_add_key_with_full_access(index, action[1], action[2])
+        elif kind == "add_access_key":
+            promise_batch_action_add_key_with_function_call(
+                index, action[1], action[2], action[3], action[4], action[5]
+            )
+        elif kind == "delete_key":
+            promise_batch_action_delete_key(index, action[1])
+        elif kind == "deploy_global_contract":
+            promise_batch_action_deploy_global_contract(index, action[1])
+        elif kind == "deploy_global_contract_by_account_id":
+            promise_batch_action_deploy_global_contract_by_account_id(index, action[1])
+        elif kind == "use_global_contract":
+            promise_batch_action_use_global_contract(index, action[1])
+        elif kind == "use_global_contract_by_account_id":
+            promise_batch_action_use_global_contract_by_account_id(index, action[1])
+        else:
+            promise_batch_action_delete_account(index, action[1])
+    return index
+
+
+def __promise_as_return__(promise):
+    promise_return(__promise_schedule__(promise))
+    return promise
+
+
+def __promise_returned__(value):
+    if isinstance(value, dict) and "__promise__" in value:
+        __promise_as_return__(value)
+        return None
+    return value
+
+
+def __promise_flush__():
+    for promise in __PROMISES__:
+        __promise_schedule__(promise)
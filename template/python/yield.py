@@ -0,0 +1,19 @@
+# Yield/resume (NEP-519) — added to the program by monty-near-cli when the
+# contract calls yield_create() or yield_resume(). Arguments that aren't
+# already str or bytes are encoded as JSON, as Promise.function_call does,
+# and payloads other than bytes are too, so a `@callback` method reads the
+# payload back as its promise result.
+
+
+def yield_create(function_name, args=None, gas=0, gas_weight=1):
+    if args is None:
+        args = ""
+    elif not isinstance(args, str) and not isinstance(args, bytes):
+        args = __json_dumps__(args)
+    return promise_yield_create(function_name, args, gas, gas_weight)
+
+
+def yield_resume(yield_id, payload=None):
+    if not isinstance(payload, bytes):
+        payload = __json_dumps__(payload)
+    return promise_yield_resume(yield_id, payload)
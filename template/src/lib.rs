@@ -362,6 +362,37 @@ fn near_promise_return(promise_id: u64) {
     unsafe { promise_return(promise_id) }
 }
 
+fn near_promise_yield_create(
+    function_name: &str,
+    arguments: &[u8],
+    gas: u64,
+    gas_weight: u64,
+) -> (u64, Vec<u8>) {
+    unsafe {
+        let promise_index = promise_yield_create(
+            function_name.len() as u64,
+            function_name.as_ptr() as u64,
+            arguments.len() as u64,
+            arguments.as_ptr() as u64,
+            gas,
+            gas_weight,
+            0,
+        );
+        (promise_index, near_read_register_bytes(0))
+    }
+}
+
+fn near_promise_yield_resume(data_id: &[u8], payload: &[u8]) -> bool {
+    unsafe {
+        promise_yield_resume(
+            data_id.len() as u64,
+            data_id.as_ptr() as u64,
+            payload.len() as u64,
+            payload.as_ptr() as u64,
+        ) == 1
+    }
+}
+
 // ---------------------------------------------------------------------------
 // Promise batch actions
 // ---------------------------------------------------------------------------
@@ -496,6 +527,52 @@ fn near_promise_batch_action_delete_account(promise_index: u64, beneficiary_id:
     }
 }
 
+fn near_promise_batch_action_deploy_global_contract(promise_index: u64, code: &[u8]) {
+    unsafe {
+        promise_batch_action_deploy_global_contract(
+            promise_index,
+            code.len() as u64,
+            code.as_ptr() as u64,
+        )
+    }
+}
+
+fn near_promise_batch_action_deploy_global_contract_by_account_id(
+    promise_index: u64,
+    code: &[u8],
+) {
+    unsafe {
+        promise_batch_action_deploy_global_contract_by_account_id(
+            promise_index,
+            code.len() as u64,
+            code.as_ptr() as u64,
+        )
+    }
+}
+
+fn near_promise_batch_action_use_global_contract(promise_index: u64, code_hash: &[u8]) {
+    unsafe {
+        promise_batch_action_use_global_contract(
+            promise_index,
+            code_hash.len() as u64,
+            code_hash.as_ptr() as u64,
+        )
+    }
+}
+
+fn near_promise_batch_action_use_global_contract_by_account_id(
+    promise_index: u64,
+    account_id: &str,
+) {
+    unsafe {
+        promise_batch_action_use_global_contract_by_account_id(
+            promise_index,
+            account_id.len() as u64,
+            account_id.as_ptr() as u64,
+        )
+    }
+}
+
 // ---------------------------------------------------------------------------
 // Validator API
 // ---------------------------------------------------------------------------
@@ -644,7 +721,7 @@ fn run_method(bytecode: &[u8], method_name: &str) {
         )
         .unwrap_or_else(|e| {
             near_log(&format!("monty start error: {e}"));
-            panic!("monty start error");
+            panic!("{}", python_error(&e.to_string()));
         });
 
     loop {
@@ -658,7 +735,7 @@ fn run_method(bytecode: &[u8], method_name: &str) {
                 let result = dispatch_function(&function_name, &args);
                 progress = state.run(result, &mut print).unwrap_or_else(|e| {
                     near_log(&format!("monty runtime error: {e}"));
-                    panic!("monty runtime error");
+                    panic!("{}", python_error(&e.to_string()));
                 });
             }
             RunProgress::Complete(_) => break,
@@ -674,6 +751,42 @@ fn run_method(bytecode: &[u8], method_name: &str) {
     }
 }
 
+/// `file.py:LINE in function: error` for a Python error, from the innermost
+/// frame of its traceback, mapped to the contract's files by `source_lines`.
+/// Just the error if the traceback has no frame in the program.
+fn python_error(error: &str) -> String {
+    const FRAME: &str = "\"contract.py\", line ";
+    let summary = error
+        .lines()
+        .rev()
+        .find(|line| !line.trim().is_empty())
+        .unwrap_or(error)
+        .trim();
+    let Some(at) = error.rfind(FRAME) else {
+        return summary.to_string();
+    };
+    let rest = &error[at + FRAME.len()..];
+    let digits = rest
+        .find(|c: char| !c.is_ascii_digit())
+        .unwrap_or(rest.len());
+    let Ok(line) = rest[..digits].parse::<u32>() else {
+        return summary.to_string();
+    };
+    let segment = source_lines().filter(|(start, _, _)| *start < line).last();
+    let location = match segment {
+        Some((start, lines, file)) if line - start <= lines => format!("{file}:{}", line - start),
+        // The dispatcher and helpers the compiler generates
+        _ => format!("<generated>:{line}"),
+    };
+    match rest[digits..].strip_prefix(", in ") {
+        Some(function) => {
+            let function = function.lines().next().unwrap_or_default().trim();
+            format!("{location} in {function}: {summary}")
+        }
+        None => format!("{location}: {summary}"),
+    }
+}
+
 fn dispatch_function(name: &str, args: &[MontyObject]) -> MontyObject {
     // Argument extraction helpers
     let arg_str = |idx: usize| -> Option<&str> {
@@ -913,6 +1026,27 @@ fn dispatch_function(name: &str, args: &[MontyObject]) -> MontyObject {
             near_promise_return(promise_id);
             MontyObject::None
         }
+        "promise_yield_create" => {
+            let function_name = arg_str(0).unwrap_or("");
+            let arguments = arg_bytes(1).unwrap_or(b"");
+            let gas = arg_int(2).unwrap_or(0) as u64;
+            let gas_weight = arg_int(3).unwrap_or(1) as u64;
+            let (promise_index, data_id) =
+                near_promise_yield_create(function_name, arguments, gas, gas_weight);
+            MontyObject::Tuple(vec![
+                MontyObject::Int(promise_index as i64),
+                MontyObject::String(to_hex(&data_id)),
+            ])
+        }
+        "promise_yield_resume" => {
+            let data_id = match args.first() {
+                Some(MontyObject::String(s)) => from_hex(s),
+                Some(MontyObject::Bytes(b)) => b.clone(),
+                _ => return MontyObject::Bool(false),
+            };
+            let payload = arg_bytes(1).unwrap_or(b"");
+            MontyObject::Bool(near_promise_yield_resume(&data_id, payload))
+        }
 
         // --- Promise batch actions ---
         "promise_batch_action_create_account" => {
@@ -1023,6 +1157,34 @@ fn dispatch_function(name: &str, args: &[MontyObject]) -> MontyObject {
             near_promise_batch_action_delete_account(promise_index, beneficiary_id);
             MontyObject::None
         }
+        "promise_batch_action_deploy_global_contract" => {
+            let promise_index = arg_int(0).unwrap_or(0) as u64;
+            let code = arg_bytes(1).unwrap_or(b"");
+            near_promise_batch_action_deploy_global_contract(promise_index, code);
+            MontyObject::None
+        }
+        "promise_batch_action_deploy_global_contract_by_account_id" => {
+            let promise_index = arg_int(0).unwrap_or(0) as u64;
+            let code = arg_bytes(1).unwrap_or(b"");
+            near_promise_batch_action_deploy_global_contract_by_account_id(promise_index, code);
+            MontyObject::None
+        }
+        "promise_batch_action_use_global_contract" => {
+            let promise_index = arg_int(0).unwrap_or(0) as u64;
+            let code_hash = match args.get(1) {
+                Some(MontyObject::String(s)) => from_hex(s),
+                Some(MontyObject::Bytes(b)) => b.clone(),
+                _ => return MontyObject::None,
+            };
+            near_promise_batch_action_use_global_contract(promise_index, &code_hash);
+            MontyObject::None
+        }
+        "promise_batch_action_use_global_contract_by_account_id" => {
+            let promise_index = arg_int(0).unwrap_or(0) as u64;
+            let account_id = arg_str(1).unwrap_or("");
+            near_promise_batch_action_use_global_contract_by_account_id(promise_index, account_id);
+            MontyObject::None
+        }
 
         // --- Validator API ---
         "validator_stake" => {
@@ -1114,6 +1276,11 @@ fn dispatch_function(name: &str, args: &[MontyObject]) -> MontyObject {
     }
 }
 
+// ---------------------------------------------------------------------------
+// Global allocator (generated by monty-near-cli; empty for std's dlmalloc)
+// ---------------------------------------------------------------------------
+// @MONTY_ALLOCATOR
+
 // ---------------------------------------------------------------------------
 // Pre-compiled bytecode (generated by monty-near-cli)
 // ---------------------------------------------------------------------------
@@ -0,0 +1,31 @@
+import { beforeAll, afterAll, test, expect, describe } from "bun:test"
+import { deployFixture, decodeResult, type Fixture } from "./helpers"
+
+let fixture: Fixture
+
+beforeAll(async () => {
+  fixture = await deployFixture("async_calls")
+}, 120_000)
+
+afterAll(async () => {
+  if (fixture) await fixture.sandbox.stop()
+})
+
+describe("async methods", () => {
+  test("each await continues with the call's result and the method's locals", async () => {
+    const { near, contractId } = fixture
+    const o = await near.call(contractId, "relay", { value: "x" })
+    expect(JSON.parse(decodeResult(o))).toBe("x -> x!!")
+  })
+
+  test("a failed call is passed as PromiseError when annotated", async () => {
+    const { near, contractId } = fixture
+    const o = await near.call(contractId, "relay_failing", {})
+    expect(JSON.parse(decodeResult(o))).toBe("caught")
+  })
+
+  test("the generated callbacks are private", async () => {
+    const { near, contractId } = fixture
+    await expect(near.call(contractId, "relay__await_1", { value: "x" })).rejects.toThrow()
+  })
+})
@@ -0,0 +1,46 @@
+import { beforeAll, afterAll, test, expect, describe } from "bun:test"
+import { deployFixture, resultBytes, type Fixture } from "./helpers"
+
+let fixture: Fixture
+
+beforeAll(async () => {
+  fixture = await deployFixture("borsh_io")
+}, 120_000)
+
+afterAll(async () => {
+  if (fixture) await fixture.sandbox.stop()
+})
+
+const u32 = (n: number) => {
+  const b = Buffer.alloc(4)
+  b.writeUInt32LE(n)
+  return b
+}
+const u64 = (n: bigint) => {
+  const b = Buffer.alloc(8)
+  b.writeBigUInt64LE(n)
+  return b
+}
+const str = (s: string) => Buffer.concat([u32(Buffer.byteLength(s)), Buffer.from(s)])
+
+describe("@borsh_io", () => {
+  test("decodes a list and an option, and encodes the result", async () => {
+    const { near, contractId } = fixture
+    const args = Buffer.concat([u32(3), u32(1), u32(2), u32(3), Buffer.from([1]), u64(10n)])
+    const o = await near.call(contractId, "total", new Uint8Array(args))
+    expect(resultBytes(o).readBigUInt64LE()).toBe(16n)
+  })
+
+  test("an absent option is None", async () => {
+    const { near, contractId } = fixture
+    const args = Buffer.concat([u32(2), u32(4), u32(5), Buffer.from([0])])
+    const o = await near.call(contractId, "total", new Uint8Array(args))
+    expect(resultBytes(o)).toEqual(u64(9n))
+  })
+
+  test("strings are length-prefixed", async () => {
+    const { near, contractId } = fixture
+    const o = await near.call(contractId, "greet", new Uint8Array(str("Borsh")))
+    expect(resultBytes(o)).toEqual(str("Hello, Borsh!"))
+  })
+})
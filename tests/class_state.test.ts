@@ -0,0 +1,42 @@
+import { beforeAll, afterAll, test, expect, describe } from "bun:test"
+import { deployFixture, type Fixture } from "./helpers"
+
+let fixture: Fixture
+
+beforeAll(async () => {
+  fixture = await deployFixture("class_state")
+}, 120_000)
+
+afterAll(async () => {
+  if (fixture) await fixture.sandbox.stop()
+})
+
+describe("class Contract", () => {
+  test("fields start at their defaults", async () => {
+    const { near, contractId } = fixture
+    expect(await near.view(contractId, "get")).toBe(0)
+    expect(await near.view(contractId, "owner_id")).toBe("")
+  })
+
+  test("call methods save the fields", async () => {
+    const { near, contractId } = fixture
+    await near.call(contractId, "new", {})
+    await near.call(contractId, "increment", {})
+    await near.call(contractId, "increment", {})
+    expect(await near.view(contractId, "get")).toBe(2)
+    expect(await near.view(contractId, "owner_id")).toBe(contractId)
+  })
+
+  test("the state is stored length-prefixed, in declaration order", async () => {
+    const { near, contractId } = fixture
+    const state = await near.view(contractId, "raw_state")
+    expect(state).toBe(`${contractId.length}:${contractId}1:24:True`)
+  })
+
+  test("@init runs only once", async () => {
+    const { near, contractId } = fixture
+    await expect(near.call(contractId, "new", {})).rejects.toThrow(
+      "The contract has already been initialized"
+    )
+  })
+})
@@ -0,0 +1,45 @@
+import { beforeAll, afterAll, test, expect, describe } from "bun:test"
+import { deployFixture, decodeResult, type Fixture } from "./helpers"
+
+let fixture: Fixture
+
+beforeAll(async () => {
+  fixture = await deployFixture("collections")
+}, 120_000)
+
+afterAll(async () => {
+  if (fixture) await fixture.sandbox.stop()
+})
+
+describe("collections", () => {
+  test("entries persist between calls", async () => {
+    const { near, contractId } = fixture
+    await near.call(contractId, "deposit", { account: "alice", amount: 5 })
+    await near.call(contractId, "deposit", { account: "bob", amount: 7 })
+    await near.call(contractId, "deposit", { account: "alice", amount: 1 })
+
+    expect(await near.view(contractId, "balance_of", { account: "alice" })).toBe(6)
+    expect(await near.view(contractId, "balance_of", { account: "carol" })).toBe(0)
+    const holders = (await near.view(contractId, "holder_list")) as string[]
+    expect(holders.sort()).toEqual(["alice", "bob"])
+    expect(await near.view(contractId, "history_list")).toEqual([
+      { account: "alice", amount: 5 },
+      { account: "bob", amount: 7 },
+      { account: "alice", amount: 1 },
+    ])
+  })
+
+  test("remove returns the previous value", async () => {
+    const { near, contractId } = fixture
+    const o = await near.call(contractId, "withdraw_all", { account: "bob" })
+    expect(decodeResult(o)).toBe("7")
+    expect(await near.view(contractId, "balance_of", { account: "bob" })).toBe(0)
+    expect(await near.view(contractId, "holder_list")).toEqual(["alice"])
+  })
+
+  test("each entry lives under the collection's name", async () => {
+    const { near, contractId } = fixture
+    expect(await near.view(contractId, "raw", { key: "balances:alice" })).toBe("6")
+    expect(await near.view(contractId, "raw", { key: "balances:bob" })).toBeNull()
+  })
+})
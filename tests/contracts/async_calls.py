@@ -0,0 +1,22 @@
+# async methods awaiting cross_call, split into generated callbacks.
+
+
+def pong(value: str) -> str:
+    return value + "!"
+
+
+def fail():
+    raise ValueError("nope")
+
+
+async def relay(value: str) -> str:
+    first: str = await cross_call(current_account_id(), "pong", {"value": value})
+    second: str = await cross_call(current_account_id(), "pong", {"value": first})
+    return value + " -> " + second
+
+
+async def relay_failing() -> str:
+    result: str | PromiseError = await cross_call(current_account_id(), "fail")
+    if result == PromiseError:
+        return "caught"
+    return result
@@ -0,0 +1,16 @@
+# Borsh-encoded arguments and results.
+
+
+@borsh_io
+def total(values: list[u32], bonus: u64 | None) -> u64:
+    result = 0
+    for value in values:
+        result = result + value
+    if bonus is not None:
+        result = result + bonus
+    return result
+
+
+@borsh_io
+def greet(name: str) -> str:
+    return "Hello, " + name + "!"
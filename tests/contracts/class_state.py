@@ -0,0 +1,32 @@
+# A class Contract, whose fields are loaded from and saved to the STATE key.
+
+
+class Contract:
+    owner: str = ""
+    count: int = 0
+    active = False
+
+    @init
+    def new(self):
+        self.owner = predecessor_account_id()
+        self.active = True
+
+    def increment(self) -> int:
+        self._bump(1)
+        return self.count
+
+    @view
+    def get(self) -> int:
+        return self.count
+
+    @view
+    def owner_id(self) -> str:
+        return self.owner
+
+    def _bump(self, by):
+        self.count += by
+
+
+@view
+def raw_state() -> str:
+    return storage_read("STATE")
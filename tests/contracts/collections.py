@@ -0,0 +1,37 @@
+# LookupMap, UnorderedSet, and Vector, each under its own key prefix.
+
+balances = LookupMap()
+holders = UnorderedSet()
+history = Vector()
+
+
+def deposit(account: str, amount: int):
+    balances.insert(account, balances.get(account, 0) + amount)
+    holders.insert(account)
+    history.push({"account": account, "amount": amount})
+
+
+def withdraw_all(account: str) -> int:
+    amount = balances.remove(account)
+    holders.remove(account)
+    return amount
+
+
+@view
+def balance_of(account: str) -> int:
+    return balances.get(account, 0)
+
+
+@view
+def holder_list() -> list:
+    return holders.to_list()
+
+
+@view
+def history_list() -> list:
+    return history.to_list()
+
+
+@view
+def raw(key: str) -> str | None:
+    return storage_read(key)
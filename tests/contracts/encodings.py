@@ -0,0 +1,20 @@
+# The bundled base64 and binascii modules.
+
+import base64
+from binascii import hexlify, unhexlify
+
+
+@view
+def reencode(data: str) -> dict:
+    raw = base64.b64decode(data)
+    return {
+        "hex": hexlify(raw).decode(),
+        "b64": base64.b64encode(raw).decode(),
+        "urlsafe": base64.urlsafe_b64encode(raw).decode(),
+        "b16": base64.b16encode(raw).decode(),
+    }
+
+
+@view
+def from_hex(data: str) -> str:
+    return base64.b64encode(unhexlify(data)).decode()
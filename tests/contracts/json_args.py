@@ -0,0 +1,28 @@
+# JSON arguments and results, and the bundled json module.
+
+import json
+
+
+def set_greeting(greeting: str, repeat: int = 1):
+    storage_write("greeting", greeting * repeat)
+
+
+@view
+def get_greeting() -> dict:
+    greeting = storage_read("greeting")
+    return {"greeting": greeting, "length": len(greeting)}
+
+
+@view
+def add(a: int, b: int) -> int:
+    return a + b
+
+
+def set_config(raw: str):
+    config = json.loads(raw)
+    storage_write("config", json.dumps(config, sort_keys=True, separators=(",", ":")))
+
+
+@view
+def get_config() -> str:
+    return storage_read("config")
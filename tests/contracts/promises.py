@@ -0,0 +1,32 @@
+# Promise chains calling back into the same contract.
+
+
+def pong(value: str) -> str:
+    return value + "!"
+
+
+def ping() -> Promise:
+    return Promise(current_account_id()).function_call("pong", {"value": "hi"})
+
+
+def ping_then(suffix: str) -> Promise:
+    return (
+        Promise(current_account_id())
+        .function_call("pong", {"value": "a"})
+        .then(Promise(current_account_id()).function_call("on_pong", {"suffix": suffix}))
+    )
+
+
+def ping_failing() -> Promise:
+    return (
+        Promise(current_account_id())
+        .function_call("missing_method")
+        .then(Promise(current_account_id()).function_call("on_pong", {"suffix": "?"}))
+    )
+
+
+@callback
+def on_pong(result: str | PromiseError, suffix: str) -> str:
+    if result == PromiseError:
+        return "failed" + suffix
+    return result + suffix
@@ -0,0 +1,26 @@
+# Built with --split-methods: every method in a bytecode blob of its own.
+
+GREETING = "Hello"
+
+
+def _greeting(name):
+    return GREETING + ", " + name + "!"
+
+
+@view
+def hello(name: str) -> str:
+    return _greeting(name)
+
+
+def remember(name: str):
+    storage_write("name", name)
+
+
+@view
+def greet_remembered() -> str:
+    return _greeting(storage_read("name"))
+
+
+@view
+def shout(name: str) -> str:
+    return hello(name).upper()
@@ -0,0 +1,16 @@
+# U128 arguments and results, passed as decimal strings.
+
+
+@view
+def double(amount: U128) -> U128:
+    return amount * 2
+
+
+@view
+def total(amounts: list[U128], extra: U128 | None = None) -> U128:
+    result = 0
+    for amount in amounts:
+        result = result + amount
+    if extra is not None:
+        result = result + extra
+    return result
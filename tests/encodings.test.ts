@@ -0,0 +1,37 @@
+import { beforeAll, afterAll, test, expect, describe } from "bun:test"
+import { deployFixture, type Fixture } from "./helpers"
+
+let fixture: Fixture
+
+beforeAll(async () => {
+  fixture = await deployFixture("encodings")
+}, 120_000)
+
+afterAll(async () => {
+  if (fixture) await fixture.sandbox.stop()
+})
+
+describe("base64 and binascii", () => {
+  test("decoded bytes re-encode in every alphabet", async () => {
+    const { near, contractId } = fixture
+    const raw = Buffer.from([0xfb, 0xff, 0x00, 0x41])
+    const result = await near.view(contractId, "reencode", { data: raw.toString("base64") })
+    expect(result).toEqual({
+      hex: "fbff0041",
+      b64: raw.toString("base64"),
+      urlsafe: raw.toString("base64").replace(/\+/g, "-").replace(/\//g, "_"),
+      b16: "FBFF0041",
+    })
+  })
+
+  test("hex decodes to bytes", async () => {
+    const { near, contractId } = fixture
+    const result = await near.view(contractId, "from_hex", { data: "68656c6c6f" })
+    expect(result).toBe(Buffer.from("hello").toString("base64"))
+  })
+
+  test("characters outside the alphabet are rejected", async () => {
+    const { near, contractId } = fixture
+    await expect(near.view(contractId, "reencode", { data: "not base64!" })).rejects.toThrow()
+  })
+})
@@ -0,0 +1,53 @@
+import { Near } from "near-kit"
+import { Sandbox } from "near-kit/sandbox"
+import { readFileSync, existsSync } from "fs"
+import { execSync } from "child_process"
+import { resolve } from "path"
+
+const ROOT = resolve(import.meta.dir, "..")
+const CLI_BIN = resolve(ROOT, "target/release/monty-near-cli")
+
+export const encode = (s = "") => new TextEncoder().encode(s)
+
+export function resultBytes(outcome: any): Buffer {
+  const b64 = outcome?.status?.SuccessValue
+  return Buffer.from(b64 ?? "", "base64")
+}
+
+export function decodeResult(outcome: any): string {
+  return resultBytes(outcome).toString("utf-8")
+}
+
+export function getLogs(outcome: any): string[] {
+  return (outcome?.receipts_outcome ?? []).flatMap(
+    (r: any) => r?.outcome?.logs ?? []
+  )
+}
+
+export interface Fixture {
+  sandbox: Sandbox
+  near: Near
+  contractId: string
+}
+
+// Build tests/contracts/<name>.py with `flags` and deploy it to the root
+// account of a fresh sandbox (master: Wasmtime with bulk-memory support)
+export async function deployFixture(name: string, flags = ""): Promise<Fixture> {
+  const input = resolve(import.meta.dir, "contracts", `${name}.py`)
+  const wasmOut = resolve(ROOT, `target/${name}_test.wasm`)
+  execSync("cargo build --release", { cwd: ROOT, stdio: "inherit" })
+  execSync(`${CLI_BIN} build ${input} -o ${wasmOut} --no-cache ${flags}`, {
+    cwd: ROOT,
+    stdio: "inherit",
+  })
+  if (!existsSync(wasmOut)) throw new Error(`WASM not found at ${wasmOut}`)
+
+  const sandbox = await Sandbox.start({ version: "master" })
+  const contractId = sandbox.rootAccount.id
+  const near = new Near({ network: sandbox, defaultSignerId: contractId })
+  await near
+    .transaction(contractId)
+    .deployContract(contractId, readFileSync(wasmOut))
+    .send()
+  return { sandbox, near, contractId }
+}
@@ -0,0 +1,52 @@
+import { beforeAll, afterAll, test, expect, describe } from "bun:test"
+import { deployFixture, type Fixture } from "./helpers"
+
+let fixture: Fixture
+
+beforeAll(async () => {
+  fixture = await deployFixture("json_args")
+}, 120_000)
+
+afterAll(async () => {
+  if (fixture) await fixture.sandbox.stop()
+})
+
+describe("JSON arguments", () => {
+  test("fields are passed as parameters, and results returned as JSON", async () => {
+    const { near, contractId } = fixture
+    await near.call(contractId, "set_greeting", { greeting: "hi", repeat: 2 })
+    const result = await near.view(contractId, "get_greeting")
+    expect(result).toEqual({ greeting: "hihi", length: 4 })
+  })
+
+  test("a missing field takes the parameter's default", async () => {
+    const { near, contractId } = fixture
+    await near.call(contractId, "set_greeting", { greeting: "yo" })
+    const result = await near.view(contractId, "get_greeting")
+    expect(result).toEqual({ greeting: "yo", length: 2 })
+  })
+
+  test("a missing field without a default fails the call", async () => {
+    const { near, contractId } = fixture
+    await expect(near.call(contractId, "set_greeting", { repeat: 2 })).rejects.toThrow(
+      "missing field"
+    )
+  })
+
+  test("annotated parameters are checked", async () => {
+    const { near, contractId } = fixture
+    expect(await near.view(contractId, "add", { a: 2, b: 3 })).toBe(5)
+    await expect(near.view(contractId, "add", { a: "2", b: 3 })).rejects.toThrow(
+      "a must be an integer"
+    )
+  })
+})
+
+describe("json module", () => {
+  test("loads and dumps round-trip", async () => {
+    const { near, contractId } = fixture
+    await near.call(contractId, "set_config", { raw: '{"b": 1, "a": [true, null]}' })
+    const result = await near.view(contractId, "get_config")
+    expect(result).toBe('{"a":[true,null],"b":1}')
+  })
+})
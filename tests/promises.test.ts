@@ -0,0 +1,37 @@
+import { beforeAll, afterAll, test, expect, describe } from "bun:test"
+import { deployFixture, decodeResult, type Fixture } from "./helpers"
+
+let fixture: Fixture
+
+beforeAll(async () => {
+  fixture = await deployFixture("promises")
+}, 120_000)
+
+afterAll(async () => {
+  if (fixture) await fixture.sandbox.stop()
+})
+
+describe("Promise", () => {
+  test("a returned promise's result is the method's", async () => {
+    const { near, contractId } = fixture
+    const o = await near.call(contractId, "ping", {})
+    expect(JSON.parse(decodeResult(o))).toBe("hi!")
+  })
+
+  test("then passes the result to a @callback", async () => {
+    const { near, contractId } = fixture
+    const o = await near.call(contractId, "ping_then", { suffix: "b" })
+    expect(JSON.parse(decodeResult(o))).toBe("a!b")
+  })
+
+  test("a failed promise reaches a callback that accepts PromiseError", async () => {
+    const { near, contractId } = fixture
+    const o = await near.call(contractId, "ping_failing", {})
+    expect(JSON.parse(decodeResult(o))).toBe("failed?")
+  })
+
+  test("a callback called without a promise fails", async () => {
+    const { near, contractId } = fixture
+    await expect(near.call(contractId, "on_pong", { suffix: "x" })).rejects.toThrow()
+  })
+})
@@ -0,0 +1,26 @@
+import { beforeAll, afterAll, test, expect, describe } from "bun:test"
+import { deployFixture, type Fixture } from "./helpers"
+
+let fixture: Fixture
+
+beforeAll(async () => {
+  fixture = await deployFixture("split_methods", "--split-methods")
+}, 120_000)
+
+afterAll(async () => {
+  if (fixture) await fixture.sandbox.stop()
+})
+
+describe("--split-methods", () => {
+  test("each method dispatches from its own blob", async () => {
+    const { near, contractId } = fixture
+    expect(await near.view(contractId, "hello", { name: "split" })).toBe("Hello, split!")
+    await near.call(contractId, "remember", { name: "blob" })
+    expect(await near.view(contractId, "greet_remembered")).toBe("Hello, blob!")
+  })
+
+  test("a method calling another exported method carries it along", async () => {
+    const { near, contractId } = fixture
+    expect(await near.view(contractId, "shout", { name: "split" })).toBe("HELLO, SPLIT!")
+  })
+})
@@ -0,0 +1,45 @@
+import { beforeAll, afterAll, test, expect, describe } from "bun:test"
+import { deployFixture, type Fixture } from "./helpers"
+
+let fixture: Fixture
+
+beforeAll(async () => {
+  fixture = await deployFixture("u128")
+}, 120_000)
+
+afterAll(async () => {
+  if (fixture) await fixture.sandbox.stop()
+})
+
+const MAX_U128 = (1n << 128n) - 1n
+
+describe("U128", () => {
+  test("amounts beyond 2^53 keep every digit", async () => {
+    const { near, contractId } = fixture
+    const amount = (MAX_U128 / 2n).toString()
+    const result = await near.view(contractId, "double", { amount })
+    expect(result).toBe((MAX_U128 - 1n).toString())
+  })
+
+  test("lists and optional amounts are converted", async () => {
+    const { near, contractId } = fixture
+    const result = await near.view(contractId, "total", {
+      amounts: ["1000000000000000000000000", "2"],
+      extra: "3",
+    })
+    expect(result).toBe("1000000000000000000000005")
+  })
+
+  test("a number instead of a string is rejected", async () => {
+    const { near, contractId } = fixture
+    await expect(near.view(contractId, "double", { amount: 5 })).rejects.toThrow(
+      "amount must be a decimal string"
+    )
+  })
+
+  test("a result that doesn't fit in u128 fails the call", async () => {
+    const { near, contractId } = fixture
+    const amount = (MAX_U128 / 2n + 1n).toString()
+    await expect(near.view(contractId, "double", { amount })).rejects.toThrow()
+  })
+})